@@ -1,62 +1,508 @@
-use log::error;
-use std::{sync::Arc, time::Instant};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use web_time::Instant;
 
 use winit::{
     application::ApplicationHandler,
+    dpi::PhysicalSize,
     event::{DeviceEvent, KeyEvent, WindowEvent},
-    event_loop::ActiveEventLoop,
+    event_loop::{ActiveEventLoop, EventLoop},
     keyboard::{KeyCode, PhysicalKey},
-    window::WindowAttributes,
+    window::{CursorGrabMode, Fullscreen, WindowAttributes, WindowId},
 };
 
+use crate::game::benchmark::Benchmark;
+use crate::game::camera_settings::CameraSettings;
+use crate::game::game_state::{GameState, GameStateStack, StateTransition};
+use crate::game::save_game::SaveGame;
+use crate::options::Options;
 use crate::renderer::Renderer;
+use crate::renderer::debug_view::DebugView;
+use crate::renderer::graphics_settings::GraphicsSettings;
+use crate::settings::Settings;
 
-#[derive(Default)]
 pub struct AppState {
     renderer: Option<Renderer>,
     prev_frame_time: Option<Instant>,
+    states: GameStateStack,
+    settings: Settings,
+    options: Options,
+    // Extra windows looking into the main renderer, e.g. the shadow map faces.
+    debug_views: HashMap<WindowId, DebugView>,
+    // Set when started with --benchmark, which replaces playing.
+    benchmark: Option<Benchmark>,
+    // The browser can't block on setup, so there the renderer arrives as a user event.
+    #[cfg(target_arch = "wasm32")]
+    proxy: winit::event_loop::EventLoopProxy<Renderer>,
 }
 
-impl ApplicationHandler for AppState {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window = Arc::new(
-            event_loop
-                .create_window(WindowAttributes::default().with_title("Mood"))
-                .unwrap(),
-        );
-
-        self.renderer = match pollster::block_on(Renderer::new(
-            window.clone(),
-            String::from("client/src/model/maps/map_1.json"),
-        )) {
-            Ok(r) => Some(r),
-            Err(e) => {
-                error!("{e}");
-                std::process::exit(1);
+impl AppState {
+    const SENSITIVITY_STEP: f32 = 0.05;
+    const MAP_FILE: &str = "client/src/model/maps/map_1.json";
+
+    #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+    pub fn new(event_loop: &EventLoop<Renderer>, options: Options) -> Self {
+        let benchmark = options.benchmark.map(Benchmark::new);
+        if let Some(address) = options.connect {
+            warn!("Joining {address} isn't supported yet, playing offline");
+        }
+        // Benchmarks run on the defaults so they compare across machines and configs.
+        let settings = if benchmark.is_some() {
+            Settings {
+                resolution: Some(Benchmark::RESOLUTION),
+                ..Settings::default()
             }
+        } else {
+            Settings::load()
         };
+        Self {
+            renderer: None,
+            prev_frame_time: None,
+            states: GameStateStack::default(),
+            settings,
+            options,
+            debug_views: HashMap::new(),
+            benchmark,
+            #[cfg(target_arch = "wasm32")]
+            proxy: event_loop.create_proxy(),
+        }
+    }
+
+    fn window_attributes(title: &str) -> WindowAttributes {
+        let attributes = WindowAttributes::default().with_title(title);
+        #[cfg(target_arch = "wasm32")]
+        let attributes = {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            attributes.with_append(true)
+        };
+        attributes
+    }
+
+    fn start(&mut self, mut renderer: Renderer) {
         self.prev_frame_time = Some(Instant::now());
+        Self::enter_state(&mut renderer, self.states.current());
+        self.renderer = Some(renderer);
+    }
+
+    /// Returns false when the transition asked the application to quit.
+    fn apply_transition(
+        states: &mut GameStateStack,
+        renderer: &mut Renderer,
+        transition: StateTransition,
+    ) -> bool {
+        if !states.apply(transition) {
+            return false;
+        }
+        Self::enter_state(renderer, states.current());
+        true
+    }
+
+    /// Sets up the window and input for the state now on top of the stack.
+    fn enter_state(renderer: &mut Renderer, state: GameState) {
+        let window = renderer.get_window().clone();
+        window.set_title(state.title());
+        renderer.get_mut_hud().set_menu(state.menu_lines());
+        // Hidden windows in headless runs leave the cursor alone.
+        if state.is_interactive() && window.is_visible() != Some(false) {
+            if let Err(e) = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+            {
+                warn!("Unable to grab cursor {e}");
+            }
+            window.set_cursor_visible(false);
+        } else {
+            renderer.get_mut_player_controller().release_all();
+            if let Err(e) = window.set_cursor_grab(CursorGrabMode::None) {
+                warn!("Unable to release cursor {e}");
+            }
+            window.set_cursor_visible(true);
+        }
         window.request_redraw();
     }
 
+    /// Opens the shadow map window, or closes every debug window if any are open.
+    fn toggle_debug_view(
+        debug_views: &mut HashMap<WindowId, DebugView>,
+        renderer: &Renderer,
+        event_loop: &ActiveEventLoop,
+    ) {
+        if !debug_views.is_empty() {
+            debug_views.clear();
+            return;
+        }
+        let attributes = Self::window_attributes("Mood shadow maps")
+            .with_inner_size(PhysicalSize::new(768, 512));
+        let view = event_loop
+            .create_window(attributes)
+            .map_err(|e| e.to_string())
+            .and_then(|window| renderer.open_debug_view(Arc::new(window)));
+        match view {
+            Ok(view) => {
+                view.window().request_redraw();
+                debug_views.insert(view.window().id(), view);
+            }
+            Err(e) => error!("Unable to open the debug view {e}"),
+        }
+    }
+
+    /// Events for a debug window, which only ever draws and picks which light it shows.
+    fn debug_view_event(
+        debug_views: &mut HashMap<WindowId, DebugView>,
+        renderer: &Renderer,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        let Some(view) = debug_views.get_mut(&window_id) else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => {
+                debug_views.remove(&window_id);
+            }
+            WindowEvent::Resized(size) => {
+                renderer.resize_debug_view(view, size.width, size.height);
+            }
+            WindowEvent::RedrawRequested => match renderer.render_debug_view(view) {
+                Ok(_) => {}
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                    let size = view.window().inner_size();
+                    renderer.resize_debug_view(view, size.width, size.height);
+                }
+                Err(e) => error!("Unable to render the debug view {e}"),
+            },
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state,
+                        ..
+                    },
+                ..
+            } if state.is_pressed() => match code {
+                KeyCode::ArrowLeft => view.select_light(-1),
+                KeyCode::ArrowRight => view.select_light(1),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Writes back the settings, including anything changed while playing.
+    fn save_settings(&mut self) {
+        if let Some(renderer) = &self.renderer {
+            renderer.capture_settings(&mut self.settings);
+            let size = renderer.get_window().inner_size();
+            self.settings.resolution = Some([size.width, size.height]);
+        }
+        match self.settings.save() {
+            Ok(()) => info!("Saved settings to {}", Settings::path().display()),
+            Err(e) => error!("Unable to save settings {e}"),
+        }
+    }
+
+    fn write_benchmark(benchmark: &Benchmark, renderer: &Renderer) {
+        match benchmark.write_report(renderer.gpu_name()) {
+            Ok(report) => {
+                // Printed rather than logged so scripts running benchmarks can read it, the
+                // native log filter hides anything below errors by default.
+                println!("{}", report.summary());
+                println!("Wrote {} and {}", Benchmark::JSON_FILE, Benchmark::CSV_FILE);
+            }
+            Err(e) => error!("Unable to write the benchmark report {e}"),
+        }
+    }
+
+    fn quick_save(renderer: &mut Renderer) {
+        match renderer.save_game(SaveGame::QUICKSAVE) {
+            Ok(()) => {
+                info!("Saved to {}", SaveGame::QUICKSAVE);
+                renderer.get_mut_hud().show_message("Game saved");
+            }
+            Err(e) => error!("Unable to save {e}"),
+        }
+    }
+
+    fn quick_load(renderer: &mut Renderer) {
+        match renderer.load_game(SaveGame::QUICKSAVE) {
+            Ok(()) => {
+                info!("Loaded {}", SaveGame::QUICKSAVE);
+                renderer.get_mut_hud().show_message("Game loaded");
+            }
+            Err(e) => {
+                error!("Unable to load {e}");
+                renderer
+                    .get_mut_hud()
+                    .show_message("Unable to load the game");
+            }
+        }
+    }
+
+    /// Typing into the open console, which swallows every other key.
+    fn handle_console_key(renderer: &mut Renderer, code: KeyCode, text: Option<&str>) {
+        match code {
+            KeyCode::Escape => renderer.get_mut_console().close(),
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                let line = renderer.get_mut_console().submit();
+                renderer.execute_console_line(&line);
+            }
+            KeyCode::Backspace => renderer.get_mut_console().backspace(),
+            _ => {
+                if let Some(text) = text {
+                    renderer.get_mut_console().push_text(text);
+                }
+            }
+        }
+    }
+
+    fn handle_settings_key(renderer: &mut Renderer, code: KeyCode) {
+        if code == KeyCode::KeyO {
+            let quality = renderer.cycle_ssao_quality();
+            info!("SSAO {quality:?}");
+            return;
+        }
+        if code == KeyCode::KeyR {
+            let resolution = renderer.cycle_render_resolution();
+            info!("Render resolution {resolution:?}");
+            return;
+        }
+        if code == KeyCode::KeyF {
+            let filter = renderer.cycle_upscale_filter();
+            info!("Upscale filter {filter:?}");
+            return;
+        }
+        if code == KeyCode::KeyD {
+            let enabled = renderer.toggle_dynamic_resolution();
+            info!("Dynamic resolution {enabled}");
+            return;
+        }
+        if code == KeyCode::KeyP {
+            let enabled = renderer.toggle_depth_prepass();
+            info!("Depth prepass {enabled}");
+            return;
+        }
+        let scale_delta = match code {
+            KeyCode::Minus => Some(-GraphicsSettings::RENDER_SCALE_STEP),
+            KeyCode::Equal => Some(GraphicsSettings::RENDER_SCALE_STEP),
+            _ => None,
+        };
+        if let Some(scale_delta) = scale_delta {
+            let render_scale = renderer.adjust_render_scale(scale_delta);
+            info!("Render scale {render_scale:.1}");
+            return;
+        }
+        let camera_settings = &mut renderer.get_mut_player().camera_settings;
+        match code {
+            KeyCode::KeyI => {
+                camera_settings.invert_y = !camera_settings.invert_y;
+                info!("Invert Y {}", camera_settings.invert_y);
+                return;
+            }
+            KeyCode::BracketLeft | KeyCode::BracketRight => {
+                let step = match code {
+                    KeyCode::BracketLeft => -CameraSettings::FOV_STEP,
+                    _ => CameraSettings::FOV_STEP,
+                };
+                let fov = camera_settings.set_fov(camera_settings.fov + step);
+                info!("FOV {fov:.0}");
+                return;
+            }
+            KeyCode::Comma | KeyCode::Period => {
+                let step = match code {
+                    KeyCode::Comma => -CameraSettings::SMOOTHING_STEP,
+                    _ => CameraSettings::SMOOTHING_STEP,
+                };
+                let smoothing = camera_settings.set_smoothing(camera_settings.smoothing + step);
+                info!("Mouse smoothing {smoothing:.1}");
+                return;
+            }
+            _ => {}
+        }
+        let delta = match code {
+            KeyCode::ArrowUp => Self::SENSITIVITY_STEP,
+            KeyCode::ArrowDown => -Self::SENSITIVITY_STEP,
+            _ => return,
+        };
+        let sensitivity = renderer.get_mut_player().adjust_sensitivity(delta);
+        info!("Mouse sensitivity {sensitivity:.2}");
+    }
+
+    /// Swaps in a renderer on a new device once the old one is lost, keeping the session.
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn recover_device(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(renderer) = self.renderer.take() else {
+            return;
+        };
+        warn!("Recreating the renderer after losing the GPU device");
+        // Their surfaces belong to the lost device.
+        self.debug_views.clear();
+        #[cfg(not(target_arch = "wasm32"))]
+        match pollster::block_on(renderer.recover()) {
+            Ok(renderer) => self.start(renderer),
+            Err(e) => {
+                error!("Unable to recover the GPU device {e}");
+                event_loop.exit();
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let proxy = self.proxy.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match renderer.recover().await {
+                    Ok(renderer) => {
+                        if proxy.send_event(renderer).is_err() {
+                            error!("Event loop closed before the renderer was recovered");
+                        }
+                    }
+                    Err(e) => error!("Unable to recover the GPU device {e}"),
+                }
+            });
+        }
+    }
+}
+
+impl ApplicationHandler<Renderer> for AppState {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let mut attributes = Self::window_attributes("Mood")
+            .with_visible(!self.options.headless)
+            .with_fullscreen(
+                self.options
+                    .fullscreen
+                    .then_some(Fullscreen::Borderless(None)),
+            );
+        if let Some([width, height]) = self.options.size.or(self.settings.resolution) {
+            attributes = attributes.with_inner_size(PhysicalSize::new(width, height));
+        }
+        let window = Arc::new(event_loop.create_window(attributes).unwrap());
+        // The benchmark's flight path only fits its own map.
+        let map_file = if self.benchmark.is_some() {
+            Benchmark::MAP_FILE.to_string()
+        } else {
+            self.options
+                .map
+                .clone()
+                .unwrap_or_else(|| Self::MAP_FILE.to_string())
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        match pollster::block_on(Renderer::new(window, map_file, &self.settings)) {
+            Ok(renderer) => self.start(renderer),
+            Err(e) => {
+                error!("{e}");
+                event_loop.exit();
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let proxy = self.proxy.clone();
+            let settings = self.settings.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match Renderer::new(window, map_file, &settings).await {
+                    Ok(renderer) => {
+                        if proxy.send_event(renderer).is_err() {
+                            error!("Event loop closed before the renderer was ready");
+                        }
+                    }
+                    Err(e) => error!("{e}"),
+                }
+            });
+        }
+    }
+
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, renderer: Renderer) {
+        self.start(renderer);
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        match (&self.benchmark, &self.renderer) {
+            (Some(benchmark), Some(renderer)) => Self::write_benchmark(benchmark, renderer),
+            (Some(_), None) => {}
+            (None, _) => self.save_settings(),
+        }
+    }
+
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: WindowId,
         event: winit::event::WindowEvent,
     ) {
         let Some(renderer) = &mut self.renderer else {
             return;
         };
+        if window_id != renderer.get_window().id() {
+            Self::debug_view_event(&mut self.debug_views, renderer, window_id, event);
+            return;
+        }
         match event {
             WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
                 event_loop.exit();
             }
+            WindowEvent::Resized(size) => {
+                renderer.resize(size.width, size.height);
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                renderer.set_scale_factor(scale_factor);
+            }
+            WindowEvent::Focused(false) if self.states.current().is_interactive() => {
+                Self::apply_transition(
+                    &mut self.states,
+                    renderer,
+                    StateTransition::Push(GameState::Paused),
+                );
+            }
             WindowEvent::RedrawRequested => {
-                renderer.update(self.prev_frame_time.unwrap_or_else(Instant::now).elapsed());
+                let assets_changed = renderer.poll_assets();
+                if self.states.current() == GameState::Loading {
+                    if !renderer.is_loading() {
+                        let next = if self.benchmark.is_some() {
+                            GameState::InGame
+                        } else {
+                            GameState::MainMenu
+                        };
+                        Self::apply_transition(
+                            &mut self.states,
+                            renderer,
+                            StateTransition::Replace(next),
+                        );
+                    } else if assets_changed {
+                        let (done, total) = renderer.loading_progress();
+                        renderer
+                            .get_window()
+                            .set_title(&format!("{} {done}/{total}", GameState::Loading.title()));
+                    }
+                }
+                let state = self.states.current();
+                // The time step is measured every frame but thrown away while frozen, so a
+                // paused game resumes where it stopped instead of catching up.
+                let dt = self.prev_frame_time.unwrap_or_else(Instant::now).elapsed();
+                if let Some(benchmark) = &mut self.benchmark
+                    && state != GameState::Loading
+                {
+                    benchmark.record(dt, renderer.take_gpu_frame_timings());
+                    match benchmark.advance(dt) {
+                        Some((position, target)) => renderer.fly_camera(position, target),
+                        None => {
+                            event_loop.exit();
+                            return;
+                        }
+                    }
+                } else if state.is_interactive() {
+                    renderer.update(dt);
+                }
                 self.prev_frame_time = Some(Instant::now());
-                match renderer.render() {
+                // GPU timings are shown in the title while the debug view is held.
+                if let Some(report) = renderer.take_gpu_report() {
+                    let title = if renderer.get_mut_player_controller().debug_enabled {
+                        format!("{} | GPU {report}", state.title())
+                    } else {
+                        state.title().to_string()
+                    };
+                    renderer.get_window().set_title(&title);
+                }
+                match renderer.render(state.overlay_color()) {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
                         let size = renderer.get_window().inner_size();
@@ -66,24 +512,99 @@ impl ApplicationHandler for AppState {
                         log::error!("Unable to render {e}");
                     }
                 }
+                for view in self.debug_views.values() {
+                    view.window().request_redraw();
+                }
+                if renderer.is_device_lost() {
+                    self.recover_device(event_loop);
+                }
             }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         physical_key: PhysicalKey::Code(code),
                         state,
+                        repeat,
+                        text,
                         ..
                     },
                 ..
             } => {
+                // Escape cuts a benchmark short, everything else is ignored.
+                if self.benchmark.is_some() {
+                    if code == KeyCode::Escape && state.is_pressed() {
+                        event_loop.exit();
+                    }
+                    return;
+                }
+                let current = self.states.current();
+                if code == KeyCode::Backquote && state.is_pressed() && current != GameState::Loading
+                {
+                    renderer.get_mut_console().toggle();
+                    renderer.get_mut_player_controller().release_all();
+                    return;
+                }
+                if renderer.get_mut_console().is_open() {
+                    if state.is_pressed() {
+                        Self::handle_console_key(renderer, code, text.as_deref());
+                    }
+                    return;
+                }
+                if state.is_pressed()
+                    && !repeat
+                    && let Some(transition) = current.handle_key(code)
+                {
+                    if current == GameState::MainMenu && code == KeyCode::KeyL {
+                        Self::quick_load(renderer);
+                    }
+                    if !Self::apply_transition(&mut self.states, renderer, transition) {
+                        event_loop.exit();
+                    }
+                    return;
+                }
                 // Special key for reloading the renderer
                 if code == KeyCode::KeyB && state.is_pressed() {
                     renderer.rerender();
                     renderer.get_window().as_ref().request_redraw();
-                } else if renderer
+                    return;
+                }
+                match current {
+                    GameState::InGame if state.is_pressed() && code == KeyCode::F5 => {
+                        Self::quick_save(renderer);
+                    }
+                    GameState::InGame if state.is_pressed() && code == KeyCode::F9 => {
+                        Self::quick_load(renderer);
+                    }
+                    GameState::InGame if state.is_pressed() && !repeat && code == KeyCode::Tab => {
+                        renderer.get_mut_automap().toggle();
+                    }
+                    GameState::InGame if state.is_pressed() && !repeat && code == KeyCode::F3 => {
+                        Self::toggle_debug_view(&mut self.debug_views, renderer, event_loop);
+                    }
+                    GameState::InGame => {
+                        let handled = renderer.get_mut_player_controller().handle_key_held(
+                            code,
+                            state,
+                            &self.settings.keys,
+                        );
+                        if handled {
+                            renderer.get_window().as_ref().request_redraw();
+                        }
+                    }
+                    GameState::Settings if state.is_pressed() => {
+                        Self::handle_settings_key(renderer, code);
+                    }
+                    _ => {}
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. }
+                if self.states.current().is_interactive()
+                    && !renderer.get_mut_console().is_open() =>
+            {
+                let handled = renderer
                     .get_mut_player_controller()
-                    .handle_key_held(code, state, event_loop)
-                {
+                    .handle_mouse_button(button, state);
+                if handled {
                     renderer.get_window().as_ref().request_redraw();
                 }
             }
@@ -101,7 +622,10 @@ impl ApplicationHandler for AppState {
             return;
         };
         match event {
-            DeviceEvent::MouseMotion { delta } => {
+            DeviceEvent::MouseMotion { delta }
+                if self.states.current().is_interactive()
+                    && !renderer.get_mut_console().is_open() =>
+            {
                 renderer.get_mut_player_controller().handle_mouse(delta);
             }
             _ => {}
@@ -2,6 +2,8 @@ use nalgebra::{Matrix4, Point3};
 use wgpu::{BindGroup, BindGroupLayout, Buffer, Device};
 
 use super::Camera;
+use super::fog::{Fog, FogMode};
+use super::shadow_map_uniform::ShadowMapUniform;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -11,6 +13,12 @@ pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
     inv_proj: [[f32; 4]; 4],
     inv_view: [[f32; 4]; 4],
+    fog_color: [f32; 3],
+    fog_density: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_mode: u32,
+    _padding: u32,
 }
 
 impl CameraUniform {
@@ -21,9 +29,23 @@ impl CameraUniform {
             view: Matrix4::identity().into(),
             inv_proj: Matrix4::identity().into(),
             inv_view: Matrix4::identity().into(),
+            fog_color: [0.0; 3],
+            fog_density: 0.0,
+            fog_start: 0.0,
+            fog_end: 0.0,
+            fog_mode: FogMode::Off.to_raw(),
+            _padding: 0,
         }
     }
 
+    pub fn set_fog(&mut self, fog: &Fog) {
+        self.fog_color = fog.color;
+        self.fog_density = fog.density;
+        self.fog_start = fog.start;
+        self.fog_end = fog.end;
+        self.fog_mode = fog.mode.to_raw();
+    }
+
     pub fn update_cam(&mut self, camera: &Camera) {
         self.view_pos = camera.position.to_homogeneous().into();
         let view = camera.get_view_mat();
@@ -34,18 +56,42 @@ impl CameraUniform {
         self.inv_view = view.try_inverse().unwrap().into();
     }
 
+    /// Views one face of a cube at `eye` the way shadow map faces are drawn, keeping the fog.
+    pub fn update_cube_face(&mut self, eye: Point3<f32>, face_index: u32) {
+        self.view_pos = eye.to_homogeneous().into();
+        let view = ShadowMapUniform::get_view_for_face(eye, face_index);
+        let proj = ShadowMapUniform::get_proj_for_face();
+        self.view_proj = (proj * view).into();
+        self.view = view.into();
+        self.inv_proj = proj.try_inverse().unwrap().into();
+        self.inv_view = view.try_inverse().unwrap().into();
+    }
+
     pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Joint matrices of every skinned instance.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+            ],
             label: Some("camera_bind_group_layout"),
         })
     }
@@ -54,13 +100,20 @@ impl CameraUniform {
         device: &Device,
         camera_bind_group_layout: &BindGroupLayout,
         camera_buffer: &Buffer,
+        joint_buffer: &Buffer,
     ) -> BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: joint_buffer.as_entire_binding(),
+                },
+            ],
             label: Some("camera_bind_group"),
         })
     }
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+use super::Camera;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FogMode {
+    #[default]
+    Off,
+    // Ramps from nothing at `start` to fully fogged at `end`.
+    Linear,
+    // Thickens with distance by `density` and never fully covers anything.
+    Exponential,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub color: [f32; 3],
+    pub density: f32,
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            mode: FogMode::Off,
+            color: [0.5, 0.55, 0.6],
+            density: 0.02,
+            start: Camera::FAR_PLANE / 2.0,
+            end: Camera::FAR_PLANE,
+        }
+    }
+}
+
+impl FogMode {
+    /// Matches the `FOG_*` constants in the shaders.
+    pub fn to_raw(self) -> u32 {
+        match self {
+            Self::Off => 0,
+            Self::Linear => 1,
+            Self::Exponential => 2,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+use crate::model::bounds::Aabb;
+
+#[derive(Debug, Clone, Copy)]
+struct Plane {
+    normal: Vector3<f32>,
+    distance: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    planes: [Plane; 6],
+}
+
+impl Plane {
+    fn from_row(row: Vector4<f32>) -> Self {
+        let normal = Vector3::new(row.x, row.y, row.z);
+        let length = normal.norm();
+        Self {
+            normal: normal / length,
+            distance: row.w / length,
+        }
+    }
+
+    fn signed_distance(&self, point: &Point3<f32>) -> f32 {
+        self.normal.dot(&point.coords) + self.distance
+    }
+}
+
+impl Frustum {
+    /// Extracts the clip planes from a view projection matrix. wgpu clips depth to [0, 1], so
+    /// the near plane is the third row on its own.
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i: usize| view_proj.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [
+                Plane::from_row(w + x),
+                Plane::from_row(w - x),
+                Plane::from_row(w + y),
+                Plane::from_row(w - y),
+                Plane::from_row(z),
+                Plane::from_row(w - z),
+            ],
+        }
+    }
+
+    pub fn intersects_sphere(&self, center: &Point3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(center) >= -radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Test the corner furthest along the plane normal.
+            let positive = Point3::new(
+                if plane.normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.signed_distance(&positive) >= 0.0
+        })
+    }
+}
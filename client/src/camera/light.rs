@@ -1,7 +1,167 @@
+use std::str::FromStr;
+
 use nalgebra::Point3;
+use serde::{Deserialize, Serialize};
+
+use super::Camera;
+
+/// Shadow samples averaged per pixel, more taps give softer edges.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(try_from = "u32", into = "u32")]
+pub enum PcfKernel {
+    Single,
+    #[default]
+    Taps4,
+    Taps9,
+    Taps16,
+}
+
+/// How a light's shadows are compared against its shadow map.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ShadowSettings {
+    // Pulls the compared depth towards the light, per unit of distance from it. Grazing
+    // surfaces get four times as much.
+    pub depth_bias: f32,
+    // World units the lookup moves off the surface along its normal.
+    pub normal_bias: f32,
+    pub pcf: PcfKernel,
+}
+
+/// Changes a light's intensity over time, on top of its base intensity.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LightAnimation {
+    // Random dips like a failing bulb or a torch, `amount` is the deepest dip from 0 to 1.
+    // Lights with the same seed flicker together.
+    Flicker { seed: u32, amount: f32 },
+    // Fades out and back in every `period` seconds.
+    Pulse { period: f32 },
+    // Seconds spent on, then off.
+    Strobe { on: f32, off: f32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Light {
     pub id: u32,
     pub position: Point3<f32>,
     pub intensity: f32,
     pub color: [f32; 3],
+    // Saves from before shadow settings existed load with the defaults.
+    #[serde(default)]
+    pub shadow: ShadowSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation: Option<LightAnimation>,
+}
+
+impl LightAnimation {
+    // Random levels a flicker picks per second, blended between.
+    const FLICKER_RATE: f32 = 12.0;
+
+    /// Share of the base intensity at `time` seconds, from 0 to 1.
+    pub fn factor(&self, time: f32) -> f32 {
+        match *self {
+            Self::Flicker { seed, amount } => {
+                let t = time * Self::FLICKER_RATE;
+                let step = t.floor();
+                let blend = t - step;
+                let blend = blend * blend * (3.0 - 2.0 * blend);
+                let noise = Self::noise(seed, step as u32) * (1.0 - blend)
+                    + Self::noise(seed, step as u32 + 1) * blend;
+                1.0 - amount.clamp(0.0, 1.0) * noise
+            }
+            Self::Pulse { period } => {
+                let phase = time / period.max(f32::EPSILON) * std::f32::consts::TAU;
+                0.5 + 0.5 * phase.cos()
+            }
+            Self::Strobe { on, off } => {
+                let cycle = (on + off).max(f32::EPSILON);
+                if time.rem_euclid(cycle) < on {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Repeatable value from 0 to 1 for each `step` of a `seed`.
+    fn noise(seed: u32, step: u32) -> f32 {
+        let mut x = seed.wrapping_mul(0x9E37_79B9) ^ step.wrapping_mul(0x85EB_CA6B);
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x7FEB_352D);
+        x ^= x >> 15;
+        x = x.wrapping_mul(0x846C_A68B);
+        x ^= x >> 16;
+        x as f32 / u32::MAX as f32
+    }
+}
+
+impl PcfKernel {
+    pub fn taps(self) -> u32 {
+        match self {
+            Self::Single => 1,
+            Self::Taps4 => 4,
+            Self::Taps9 => 9,
+            Self::Taps16 => 16,
+        }
+    }
+}
+
+impl TryFrom<u32> for PcfKernel {
+    type Error = String;
+
+    fn try_from(taps: u32) -> Result<Self, Self::Error> {
+        match taps {
+            1 => Ok(Self::Single),
+            4 => Ok(Self::Taps4),
+            9 => Ok(Self::Taps9),
+            16 => Ok(Self::Taps16),
+            _ => Err(format!("{taps} PCF taps, expected 1, 4, 9 or 16")),
+        }
+    }
+}
+
+impl From<PcfKernel> for u32 {
+    fn from(kernel: PcfKernel) -> Self {
+        kernel.taps()
+    }
+}
+
+impl FromStr for PcfKernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let taps: u32 = s.parse().map_err(|_| format!("{s} isn't a tap count"))?;
+        Self::try_from(taps)
+    }
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            depth_bias: 0.001,
+            normal_bias: 0.01,
+            pcf: PcfKernel::default(),
+        }
+    }
+}
+
+impl Light {
+    // Contribution below which a light is treated as having no effect.
+    const CUTOFF: f32 = 0.01;
+
+    /// Intensity at `time` seconds, the base intensity when not animated.
+    pub fn intensity_at(&self, time: f32) -> f32 {
+        self.animation.map_or(self.intensity, |animation| {
+            self.intensity * animation.factor(time)
+        })
+    }
+
+    /// Distance at which inverse square falloff drops below the cutoff.
+    pub fn radius(&self) -> f32 {
+        (self.intensity.max(0.0) / Self::CUTOFF)
+            .sqrt()
+            .min(Camera::FAR_PLANE)
+    }
 }
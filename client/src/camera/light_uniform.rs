@@ -1,18 +1,42 @@
 use log::info;
 use nalgebra::Point3;
-use wgpu::{BindGroup, BindGroupLayout, Buffer, Device};
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device, TextureView};
+
+use crate::model::cube_texture::Ibl;
+use crate::model::texture_array::TextureArray;
 
 use super::light::Light;
 
-const MAX_LIGHTS: usize = 32;
+pub const MAX_LIGHTS: usize = 32;
+
+/// What the point light bind group binds besides the lights themselves, made by the passes
+/// that own them.
+pub struct LightResources<'a> {
+    pub lights: &'a Buffer,
+    // Cluster grid and the lights binned into each cluster by the light culler.
+    pub clusters: &'a Buffer,
+    pub light_grid: &'a Buffer,
+    pub light_indices: &'a Buffer,
+    pub ambient_occlusion: &'a TextureView,
+    pub ibl: &'a Ibl,
+    // Reflection probe cube faces and where each probe sits.
+    pub probe_faces: &'a TextureView,
+    pub probes: &'a Buffer,
+    // The map materials' textures, which they sample by layer.
+    pub level_textures: &'a TextureArray,
+}
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct LightUniform {
     pub position: [f32; 3],
-    _padding: f32,
+    pub radius: f32,
     pub color: [f32; 3],
     pub intensity: f32,
+    pub depth_bias: f32,
+    pub normal_bias: f32,
+    pub pcf_taps: u32,
+    pub _padding: f32,
 }
 
 #[repr(C)]
@@ -21,10 +45,15 @@ pub struct LightUniformArray {
     pub lights: [LightUniform; MAX_LIGHTS],
     pub count: u32,
     pub _padding: [f32; 3],
+    // Light reaching everything, scaled by ambient occlusion.
+    pub ambient: [f32; 3],
+    // Strength of the image based light from the skybox.
+    pub environment: f32,
 }
 
 impl LightUniformArray {
-    pub fn new(lights: &[Light]) -> Self {
+    /// Lights as they are `time` seconds into their animations.
+    pub fn new(lights: &[Light], ambient: [f32; 3], environment: f32, time: f32) -> Self {
         if lights.len() > MAX_LIGHTS {
             info!("More than {MAX_LIGHTS} lights");
             panic!();
@@ -32,28 +61,140 @@ impl LightUniformArray {
         let mut light_array = [LightUniform::new(Point3::origin(), 0.0); MAX_LIGHTS];
         for i in 0..lights.len() {
             light_array[i].position = lights[i].position.into();
-            light_array[i].intensity = lights[i].intensity;
+            light_array[i].intensity = lights[i].intensity_at(time);
+            light_array[i].radius = lights[i].radius();
             light_array[i].color = lights[i].color;
+            light_array[i].depth_bias = lights[i].shadow.depth_bias;
+            light_array[i].normal_bias = lights[i].shadow.normal_bias;
+            light_array[i].pcf_taps = lights[i].shadow.pcf.taps();
         }
         Self {
             count: lights.len() as u32,
             _padding: [0.0, 0.0, 0.0],
             lights: light_array,
+            ambient,
+            environment,
         }
     }
 
     pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // Every reflection probe face as one array, see ReflectionProbes.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Every map material texture as one array, see TextureArray.
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 11,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
             label: Some("point_light_bind_group_layout"),
         })
     }
@@ -61,14 +202,60 @@ impl LightUniformArray {
     pub fn create_bind_group(
         device: &Device,
         point_light_bind_group_layout: &BindGroupLayout,
-        light_buffer: &Buffer,
+        resources: &LightResources,
     ) -> BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: point_light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: resources.lights.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: resources.clusters.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: resources.light_grid.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: resources.light_indices.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(resources.ambient_occlusion),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&resources.ibl.irradiance.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&resources.ibl.prefiltered.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::Sampler(&resources.ibl.prefiltered.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(resources.probe_faces),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: resources.probes.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: wgpu::BindingResource::TextureView(&resources.level_textures.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: wgpu::BindingResource::Sampler(&resources.level_textures.sampler),
+                },
+            ],
             label: Some("point_light_bind_group"),
         })
     }
@@ -78,9 +265,13 @@ impl LightUniform {
     pub fn new(position: Point3<f32>, intensity: f32) -> Self {
         Self {
             position: position.into(),
-            _padding: 0.0,
+            radius: 0.0,
             color: [0.0, 0.0, 0.0],
             intensity,
+            depth_bias: 0.0,
+            normal_bias: 0.0,
+            pcf_taps: 1,
+            _padding: 0.0,
         }
     }
 }
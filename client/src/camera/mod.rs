@@ -1,10 +1,15 @@
 pub mod camera_uniform;
+pub mod fog;
+pub mod frustum;
 pub mod light;
 pub mod light_uniform;
 pub mod shadow_map_uniform;
+pub mod view_model_uniform;
 
 use nalgebra::{Matrix4, Perspective3, Point3, Vector3};
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Camera {
     pub position: Point3<f32>,
     pub target: Point3<f32>,
@@ -16,6 +21,10 @@ pub struct Camera {
 }
 
 impl Camera {
+    // Clip planes of the player's view, which fog, light ranges and shadows are fit to.
+    pub const NEAR_PLANE: f32 = 0.01;
+    pub const FAR_PLANE: f32 = 200.0;
+
     pub fn get_proj_mat(&self) -> Matrix4<f32> {
         Perspective3::new(self.aspect, self.fovy, self.near, self.far).to_homogeneous()
     }
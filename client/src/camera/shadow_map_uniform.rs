@@ -1,7 +1,7 @@
 use nalgebra::{Matrix4, Perspective3, Point3, Vector3};
-use wgpu::{BindGroup, BindGroupLayout, Device};
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device, Sampler, TextureView};
 
-use crate::{model::cube_texture::CubeTexture, renderer::Renderer};
+use super::Camera;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -13,7 +13,20 @@ pub struct ShadowMapUniform {
 
 impl ShadowMapUniform {
     pub fn get_uniform_map_for_face(light_pos: Point3<f32>, face_index: u32) -> Self {
-        let eye = light_pos;
+        Self {
+            view_proj: Self::get_view_proj_for_face(light_pos, face_index).into(),
+            position: light_pos.into(),
+            _padding: 0.0,
+        }
+    }
+
+    pub fn get_view_proj_for_face(light_pos: Point3<f32>, face_index: u32) -> Matrix4<f32> {
+        Self::get_proj_for_face() * Self::get_view_for_face(light_pos, face_index)
+    }
+
+    /// Looks out of face `face_index` of a cube centered on `eye`. Reflection probes are
+    /// rendered with the same views so both are read with the same face lookup in shader.wgsl.
+    pub fn get_view_for_face(eye: Point3<f32>, face_index: u32) -> Matrix4<f32> {
         let (target, up): (Point3<f32>, Vector3<f32>) = match face_index {
             0 => (eye + Vector3::x(), -Vector3::y()), // +X
             1 => (eye - Vector3::x(), -Vector3::y()), // -X
@@ -24,20 +37,18 @@ impl ShadowMapUniform {
             _ => panic!("Invalid cube face index"),
         };
 
-        let proj = Perspective3::new(
+        Matrix4::look_at_rh(&eye, &target, &up)
+    }
+
+    /// A square 90 degree frustum, exactly covering one cube face.
+    pub fn get_proj_for_face() -> Matrix4<f32> {
+        Perspective3::new(
             1.0,
             std::f32::consts::FRAC_PI_2,
-            Renderer::NEAR_PLANE,
-            Renderer::FAR_PLANE,
-        );
-
-        let view = Matrix4::look_at_rh(&eye, &target, &up);
-
-        Self {
-            view_proj: (proj.to_homogeneous() * view).into(),
-            position: light_pos.into(),
-            _padding: 0.0,
-        }
+            Camera::NEAR_PLANE,
+            Camera::FAR_PLANE,
+        )
+        .to_homogeneous()
     }
 
     pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
@@ -56,6 +67,7 @@ impl ShadowMapUniform {
         })
     }
 
+    /// The shadow atlas along with where each light's faces sit in it.
     pub fn create_shadow_texture_layout(device: &Device) -> BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
@@ -64,7 +76,7 @@ impl ShadowMapUniform {
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::CubeArray,
+                        view_dimension: wgpu::TextureViewDimension::D2,
                         sample_type: wgpu::TextureSampleType::Depth,
                     },
                     count: None,
@@ -75,13 +87,25 @@ impl ShadowMapUniform {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
             label: Some("shadow_bind_group_layout"),
         })
     }
     pub fn create_shadow_texture_bind_group(
         device: &Device,
-        shadow_texture: &CubeTexture,
+        atlas_view: &TextureView,
+        atlas_sampler: &Sampler,
+        face_buffer: &Buffer,
         shadow_texture_bind_group_layout: &BindGroupLayout,
     ) -> BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -89,11 +113,15 @@ impl ShadowMapUniform {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&shadow_texture.view),
+                    resource: wgpu::BindingResource::TextureView(atlas_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&shadow_texture.sampler),
+                    resource: wgpu::BindingResource::Sampler(atlas_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: face_buffer.as_entire_binding(),
                 },
             ],
             label: Some("shadow_bind_group"),
@@ -0,0 +1,55 @@
+use nalgebra::{Matrix4, Perspective3};
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ViewModelUniform {
+    view_proj: [[f32; 4]; 4],
+    model: [[f32; 4]; 4],
+}
+
+impl ViewModelUniform {
+    // The weapon gets its own projection so it never clips into level geometry.
+    const FOVY: f32 = 1.0;
+    const NEAR_PLANE: f32 = 0.01;
+    const FAR_PLANE: f32 = 10.0;
+
+    pub fn new(aspect: f32, model: Matrix4<f32>) -> Self {
+        let proj = Perspective3::new(aspect, Self::FOVY, Self::NEAR_PLANE, Self::FAR_PLANE);
+        Self {
+            view_proj: (proj.to_homogeneous() * model).into(),
+            model: model.into(),
+        }
+    }
+
+    pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("view_model_bind_group_layout"),
+        })
+    }
+
+    pub fn create_bind_group(
+        device: &Device,
+        view_model_bind_group_layout: &BindGroupLayout,
+        view_model_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: view_model_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: view_model_buffer.as_entire_binding(),
+            }],
+            label: Some("view_model_bind_group"),
+        })
+    }
+}
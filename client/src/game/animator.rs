@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use nalgebra::Matrix4;
+
+use crate::model::skeleton::{AnimationClip, Skeleton};
+
+#[derive(Debug, Clone, Copy)]
+struct ClipPlayback {
+    clip: usize,
+    time: f32,
+    looping: bool,
+}
+
+/// Plays animation clips on a skeleton, cross fading from the previous clip when a new one
+/// starts.
+pub struct Animator {
+    skeleton: Arc<Skeleton>,
+    clips: Arc<Vec<AnimationClip>>,
+    current: Option<ClipPlayback>,
+    previous: Option<ClipPlayback>,
+    blend_time: f32,
+    blend_duration: f32,
+}
+
+impl ClipPlayback {
+    fn advance(&mut self, dt: f32, duration: f32) {
+        self.time += dt;
+        if self.looping && duration > 0.0 {
+            self.time %= duration;
+        } else {
+            self.time = self.time.min(duration);
+        }
+    }
+}
+
+impl Animator {
+    pub fn new(skeleton: Arc<Skeleton>, clips: Arc<Vec<AnimationClip>>) -> Self {
+        Self {
+            skeleton,
+            clips,
+            current: None,
+            previous: None,
+            blend_time: 0.0,
+            blend_duration: 0.0,
+        }
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.skeleton.joints.len()
+    }
+
+    /// Starts `name`, blending over `blend` from whatever was playing. Returns false if the
+    /// clip doesn't exist.
+    pub fn play(&mut self, name: &str, looping: bool, blend: Duration) -> bool {
+        let Some(clip) = self.clips.iter().position(|clip| clip.name == name) else {
+            return false;
+        };
+        if self.current.is_some_and(|current| current.clip == clip) {
+            return true;
+        }
+        self.previous = self.current;
+        self.current = Some(ClipPlayback {
+            clip,
+            time: 0.0,
+            looping,
+        });
+        self.blend_time = 0.0;
+        self.blend_duration = blend.as_secs_f32();
+        true
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        for playback in [&mut self.current, &mut self.previous]
+            .into_iter()
+            .flatten()
+        {
+            playback.advance(dt, self.clips[playback.clip].duration);
+        }
+        if self.previous.is_some() {
+            self.blend_time += dt;
+            if self.blend_time >= self.blend_duration {
+                self.previous = None;
+            }
+        }
+    }
+
+    /// Skinning matrices for the current pose, in the skeleton's joint order.
+    pub fn joint_matrices(&self) -> Vec<Matrix4<f32>> {
+        let mut pose = self.skeleton.rest_pose();
+        if let Some(current) = self.current {
+            self.clips[current.clip].sample(current.time, &mut pose);
+        }
+        if let Some(previous) = self.previous {
+            let mut previous_pose = self.skeleton.rest_pose();
+            self.clips[previous.clip].sample(previous.time, &mut previous_pose);
+            let t = (self.blend_time / self.blend_duration).clamp(0.0, 1.0);
+            for (joint, from) in pose.iter_mut().zip(&previous_pose) {
+                *joint = from.interpolate(joint, t);
+            }
+        }
+        self.skeleton.skinning_matrices(&pose)
+    }
+}
@@ -0,0 +1,175 @@
+use nalgebra::{Point2, Point3};
+
+use super::collision_manager::CollisionManager;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    // Blocks the player at their current height.
+    Wall,
+    // Floors, steps and anything else that can be walked over or under.
+    Ledge,
+}
+
+/// One line of the automap in world XZ.
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    pub start: Point2<f32>,
+    pub end: Point2<f32>,
+    pub kind: SegmentKind,
+}
+
+/// Top down map of the level, built from the outlines of the collision boxes. Only the parts
+/// of the level the player has had a look at get drawn.
+pub struct Automap {
+    open: bool,
+    min_x: f32,
+    min_z: f32,
+    cell_size: f32,
+    width: usize,
+    depth: usize,
+    explored: Vec<bool>,
+    // Cell the player was last explored from, nothing new to see until they leave it.
+    last_cell: Option<usize>,
+}
+
+impl Automap {
+    const CELL_SIZE: f32 = 0.25;
+    // Coarser cells are used for levels that would need more than this many.
+    const MAX_CELLS: usize = 256 * 256;
+    // How far around the player the level gets revealed.
+    const REVEAL_RADIUS: f32 = 4.0;
+    // Boxes this far above the player's feet are stepped onto rather than walls.
+    const STEP_HEIGHT: f32 = 0.3;
+
+    pub fn new(collision_manager: &CollisionManager) -> Self {
+        let boxes = &collision_manager.map_boxes;
+        let (mut min_x, mut min_z) = (f32::INFINITY, f32::INFINITY);
+        let (mut max_x, mut max_z) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for map_box in boxes {
+            min_x = min_x.min(map_box.top_left.x);
+            min_z = min_z.min(map_box.top_left.z);
+            max_x = max_x.max(map_box.bottom_right.x);
+            max_z = max_z.max(map_box.bottom_right.z);
+        }
+        if boxes.is_empty() {
+            (min_x, min_z, max_x, max_z) = (0.0, 0.0, 0.0, 0.0);
+        }
+        let area = (max_x - min_x) * (max_z - min_z);
+        let cell_size = Self::CELL_SIZE.max((area / Self::MAX_CELLS as f32).sqrt());
+        let width = ((max_x - min_x) / cell_size).ceil().max(1.0) as usize;
+        let depth = ((max_z - min_z) / cell_size).ceil().max(1.0) as usize;
+        Self {
+            open: false,
+            min_x,
+            min_z,
+            cell_size,
+            width,
+            depth,
+            explored: vec![false; width * depth],
+            last_cell: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    fn cell_at(&self, x: f32, z: f32) -> Option<usize> {
+        let cx = ((x - self.min_x) / self.cell_size).floor();
+        let cz = ((z - self.min_z) / self.cell_size).floor();
+        if cx < 0.0 || cz < 0.0 || cx as usize >= self.width || cz as usize >= self.depth {
+            return None;
+        }
+        Some(cz as usize * self.width + cx as usize)
+    }
+
+    fn is_explored(&self, point: Point2<f32>) -> bool {
+        self.cell_at(point.x, point.y)
+            .is_some_and(|cell| self.explored[cell])
+    }
+
+    /// Marks every cell around `eye` that isn't hidden behind a map box as explored.
+    pub fn explore(&mut self, eye: Point3<f32>, collision_manager: &CollisionManager) {
+        let cell = self.cell_at(eye.x, eye.z);
+        if cell.is_none() || cell == self.last_cell {
+            return;
+        }
+        self.last_cell = cell;
+        let reach = (Self::REVEAL_RADIUS / self.cell_size).ceil() as isize;
+        let cx = ((eye.x - self.min_x) / self.cell_size).floor() as isize;
+        let cz = ((eye.z - self.min_z) / self.cell_size).floor() as isize;
+        for z in (cz - reach).max(0)..=(cz + reach).min(self.depth as isize - 1) {
+            for x in (cx - reach).max(0)..=(cx + reach).min(self.width as isize - 1) {
+                let index = z as usize * self.width + x as usize;
+                if self.explored[index] {
+                    continue;
+                }
+                let target = Point3::new(
+                    self.min_x + (x as f32 + 0.5) * self.cell_size,
+                    eye.y,
+                    self.min_z + (z as f32 + 0.5) * self.cell_size,
+                );
+                let offset = target - eye;
+                let distance = offset.norm();
+                if distance > Self::REVEAL_RADIUS {
+                    continue;
+                }
+                // Cells on the face of a wall count as seen once the view reaches the wall.
+                let visible = offset.try_normalize(0.0).is_none_or(|direction| {
+                    collision_manager
+                        .raycast(eye, direction, distance)
+                        .is_none_or(|hit| (hit.point - target).norm() <= self.cell_size)
+                });
+                self.explored[index] = visible;
+            }
+        }
+    }
+
+    /// Explored outlines of the collision boxes, split into cell sized pieces so the map
+    /// fills in as the player walks around. Boxes reaching between `feet` and `head` and
+    /// too tall to step onto are walls.
+    pub fn segments(
+        &self,
+        collision_manager: &CollisionManager,
+        feet: f32,
+        head: f32,
+    ) -> Vec<Segment> {
+        let mut segments = vec![];
+        for map_box in &collision_manager.map_boxes {
+            let (min, max) = (map_box.top_left, map_box.bottom_right);
+            // top_left holds the smallest x and z but the largest y.
+            let blocking = min.y > feet + Self::STEP_HEIGHT && max.y < head;
+            let kind = if blocking {
+                SegmentKind::Wall
+            } else {
+                SegmentKind::Ledge
+            };
+            let corners = [
+                Point2::new(min.x, min.z),
+                Point2::new(max.x, min.z),
+                Point2::new(max.x, max.z),
+                Point2::new(min.x, max.z),
+            ];
+            for (i, &start) in corners.iter().enumerate() {
+                let end = corners[(i + 1) % corners.len()];
+                let pieces = ((end - start).norm() / self.cell_size).ceil().max(1.0) as usize;
+                for piece in 0..pieces {
+                    let a = start + (end - start) * (piece as f32 / pieces as f32);
+                    let b = start + (end - start) * ((piece + 1) as f32 / pieces as f32);
+                    if self.is_explored(nalgebra::center(&a, &b)) {
+                        segments.push(Segment {
+                            start: a,
+                            end: b,
+                            kind,
+                        });
+                    }
+                }
+            }
+        }
+        segments
+    }
+}
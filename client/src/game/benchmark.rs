@@ -0,0 +1,230 @@
+use std::{error::Error, fmt::Write, time::Duration};
+
+use nalgebra::Point3;
+use serde::Serialize;
+
+use crate::platform;
+
+/// A closed Catmull-Rom loop through the first map, circling the room at eye height so
+/// every light, wall and pickup passes through view.
+struct Flythrough;
+
+/// Flies the camera around a fixed map for a fixed time and keeps every frame's timings,
+/// started with `--benchmark [seconds]`. Gameplay doesn't run, so runs on the same machine
+/// only differ by the build.
+pub struct Benchmark {
+    duration: Duration,
+    elapsed: Duration,
+    frames: Vec<FrameSample>,
+}
+
+struct FrameSample {
+    frame_ms: f64,
+    // GPU time per pass group. Timestamps arrive a couple of frames late and not at all
+    // without timestamp query support.
+    passes: Vec<(String, f64)>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PassSummary {
+    pub name: String,
+    pub gpu_ms: Percentiles,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BenchmarkReport {
+    pub map_file: String,
+    pub gpu: String,
+    pub resolution: [u32; 2],
+    pub seconds: f64,
+    pub frames: usize,
+    pub frame_ms: Percentiles,
+    pub passes: Vec<PassSummary>,
+}
+
+impl Flythrough {
+    const POINTS: [[f32; 3]; 8] = [
+        [0.5, 0.6, 0.5],
+        [3.5, 0.8, 0.3],
+        [6.5, 0.6, 0.5],
+        [6.8, 0.7, 3.5],
+        [6.5, 0.6, 6.5],
+        [3.5, 0.4, 6.8],
+        [0.5, 0.6, 6.5],
+        [0.3, 0.8, 3.5],
+    ];
+    // Seconds for one lap.
+    const LAP: f32 = 20.0;
+    // How far along the loop the camera looks, as a fraction of a lap.
+    const LOOK_AHEAD: f32 = 0.03;
+
+    /// Position on the loop at `t` laps, any value wraps around.
+    fn sample(t: f32) -> Point3<f32> {
+        let count = Self::POINTS.len();
+        let t = t.rem_euclid(1.0) * count as f32;
+        let segment = t.floor() as usize;
+        let s = t.fract();
+        let point = |offset: usize| Point3::from(Self::POINTS[(segment + offset) % count]).coords;
+        let (p0, p1, p2, p3) = (point(count - 1), point(0), point(1), point(2));
+        let coords = 0.5
+            * ((2.0 * p1)
+                + (p2 - p0) * s
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * (s * s)
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * (s * s * s));
+        Point3::from(coords)
+    }
+
+    /// Camera position and look target `elapsed` into the flight.
+    fn pose(elapsed: Duration) -> (Point3<f32>, Point3<f32>) {
+        let t = elapsed.as_secs_f32() / Self::LAP;
+        (Self::sample(t), Self::sample(t + Self::LOOK_AHEAD))
+    }
+}
+
+impl Percentiles {
+    /// Nearest rank percentiles of `values`, all zero when there are none.
+    fn of(values: impl IntoIterator<Item = f64>) -> Self {
+        let mut values: Vec<f64> = values.into_iter().collect();
+        if values.is_empty() {
+            return Self {
+                mean: 0.0,
+                p50: 0.0,
+                p95: 0.0,
+                p99: 0.0,
+                max: 0.0,
+            };
+        }
+        values.sort_by(f64::total_cmp);
+        let rank = |percentile: f64| {
+            let index = (percentile / 100.0 * values.len() as f64).ceil() as usize;
+            values[index.clamp(1, values.len()) - 1]
+        };
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p50: rank(50.0),
+            p95: rank(95.0),
+            p99: rank(99.0),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+impl Benchmark {
+    pub const MAP_FILE: &str = "client/src/model/maps/map_1.json";
+    pub const RESOLUTION: [u32; 2] = [1280, 720];
+    pub const JSON_FILE: &str = "benchmark.json";
+    pub const CSV_FILE: &str = "benchmark.csv";
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            elapsed: Duration::ZERO,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Steps the flight by the last frame time. Returns where the camera goes next, or None
+    /// once the time is up.
+    pub fn advance(&mut self, dt: Duration) -> Option<(Point3<f32>, Point3<f32>)> {
+        self.elapsed += dt;
+        (self.elapsed < self.duration).then(|| Flythrough::pose(self.elapsed))
+    }
+
+    pub fn record(&mut self, dt: Duration, passes: Vec<(String, f64)>) {
+        self.frames.push(FrameSample {
+            frame_ms: dt.as_secs_f64() * 1000.0,
+            passes,
+        });
+    }
+
+    pub fn report(&self, gpu: &str) -> BenchmarkReport {
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in self.frames.iter().flat_map(|frame| &frame.passes) {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        let passes = names
+            .into_iter()
+            .map(|name| PassSummary {
+                name: name.to_string(),
+                gpu_ms: Percentiles::of(self.frames.iter().flat_map(|frame| {
+                    frame
+                        .passes
+                        .iter()
+                        .filter(move |(pass, _)| pass == name)
+                        .map(|(_, ms)| *ms)
+                })),
+            })
+            .collect();
+        BenchmarkReport {
+            map_file: Self::MAP_FILE.to_string(),
+            gpu: gpu.to_string(),
+            resolution: Self::RESOLUTION,
+            seconds: self.elapsed.min(self.duration).as_secs_f64(),
+            frames: self.frames.len(),
+            frame_ms: Percentiles::of(self.frames.iter().map(|frame| frame.frame_ms)),
+            passes,
+        }
+    }
+
+    /// One row per frame, with a column per pass that is empty when it has no timing.
+    fn csv(&self, report: &BenchmarkReport) -> String {
+        let mut csv = String::from("frame,frame_ms");
+        for pass in &report.passes {
+            let _ = write!(csv, ",{}", pass.name);
+        }
+        csv.push('\n');
+        for (index, frame) in self.frames.iter().enumerate() {
+            let _ = write!(csv, "{index},{:.4}", frame.frame_ms);
+            for pass in &report.passes {
+                csv.push(',');
+                if let Some((_, ms)) = frame.passes.iter().find(|(name, _)| *name == pass.name) {
+                    let _ = write!(csv, "{ms:.4}");
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Writes the summary as JSON and every frame as CSV, returning the summary.
+    pub fn write_report(&self, gpu: &str) -> Result<BenchmarkReport, Box<dyn Error>> {
+        let report = self.report(gpu);
+        platform::write(Self::JSON_FILE, &serde_json::to_string_pretty(&report)?)?;
+        platform::write(Self::CSV_FILE, &self.csv(&report))?;
+        Ok(report)
+    }
+}
+
+impl BenchmarkReport {
+    /// A few lines for the terminal.
+    pub fn summary(&self) -> String {
+        let line = |name: &str, ms: &Percentiles| {
+            format!(
+                "{name}: mean {:.2}ms, p50 {:.2}ms, p95 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+                ms.mean, ms.p50, ms.p95, ms.p99, ms.max
+            )
+        };
+        let mut summary = format!(
+            "{} frames in {:.1}s on {}\n{}",
+            self.frames,
+            self.seconds,
+            self.gpu,
+            line("frame", &self.frame_ms)
+        );
+        for pass in &self.passes {
+            summary.push('\n');
+            summary.push_str(&line(&pass.name, &pass.gpu_ms));
+        }
+        summary
+    }
+}
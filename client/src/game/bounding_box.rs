@@ -26,6 +26,48 @@ impl BoundingBox {
             && self.bottom_right.z > other.top_left.z
     }
 
+    /// Distance along `direction` where the ray first enters the box and the normal of the face
+    /// it hits. Rays starting inside the box don't hit it.
+    pub fn ray_intersection(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<(f32, Vector3<f32>)> {
+        let min = Point3::new(self.top_left.x, self.bottom_right.y, self.top_left.z);
+        let max = Point3::new(self.bottom_right.x, self.top_left.y, self.bottom_right.z);
+        let mut t_near = f32::NEG_INFINITY;
+        let mut t_far = f32::INFINITY;
+        let mut normal = Vector3::zeros();
+        for axis in 0..3 {
+            if direction[axis].abs() < Self::EPSILON {
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (min[axis] - origin[axis]) / direction[axis];
+            let t2 = (max[axis] - origin[axis]) / direction[axis];
+            let (t_enter, t_exit) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+            if t_enter > t_near {
+                t_near = t_enter;
+                normal = Vector3::zeros();
+                normal[axis] = -direction[axis].signum();
+            }
+            t_far = t_far.min(t_exit);
+        }
+        (t_near <= t_far && t_near >= 0.0).then_some((t_near, normal))
+    }
+
+    /// True when this box rests on top of `other`, within `tolerance` above it.
+    pub fn is_standing_on(&self, other: &Self, tolerance: f32) -> bool {
+        let gap = self.bottom_right.y - other.top_left.y;
+        (-Self::EPSILON..=tolerance).contains(&gap)
+            && self.top_left.x < other.bottom_right.x
+            && self.bottom_right.x > other.top_left.x
+            && self.top_left.z < other.bottom_right.z
+            && self.bottom_right.z > other.top_left.z
+    }
+
     pub fn move_by(&mut self, delta: Vector3<f32>) {
         self.top_left += delta;
         self.bottom_right += delta;
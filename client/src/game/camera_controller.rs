@@ -0,0 +1,213 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use nalgebra::{Point3, Vector3};
+
+use super::collision_manager::CollisionManager;
+use super::player::Player;
+use super::player_controller::PlayerController;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    // Walks the player around under gravity.
+    #[default]
+    Fps,
+    // Noclip, flies the player through walls.
+    FreeFly,
+    // Circles a fixed point for looking over the level, leaving the player where it is.
+    Orbit,
+}
+
+/// Turns the held inputs into camera and player movement. Swapping controllers changes how
+/// the player gets around without the renderer knowing the difference.
+pub trait CameraController {
+    fn mode(&self) -> CameraMode;
+
+    /// Applies mouse look. Runs once per rendered frame.
+    fn look(&mut self, player: &mut Player, dt: Duration, player_controller: &mut PlayerController);
+
+    /// Advances movement by one fixed simulation tick.
+    fn tick(
+        &mut self,
+        player: &mut Player,
+        dt: Duration,
+        collision_manager: &mut CollisionManager,
+        player_controller: &PlayerController,
+    );
+
+    /// Called when another controller takes over.
+    fn exit(&mut self, _player: &mut Player) {}
+}
+
+pub struct FpsController;
+
+pub struct FreeFlyController;
+
+pub struct OrbitController {
+    pivot: Point3<f32>,
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl CameraMode {
+    /// A controller for this mode, starting from wherever the player is now.
+    pub fn controller(self, player: &Player) -> Box<dyn CameraController> {
+        match self {
+            Self::Fps => Box::new(FpsController),
+            Self::FreeFly => Box::new(FreeFlyController),
+            Self::Orbit => Box::new(OrbitController::new(player)),
+        }
+    }
+}
+
+impl FromStr for CameraMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fps" => Ok(Self::Fps),
+            "fly" | "free_fly" | "noclip" => Ok(Self::FreeFly),
+            "orbit" => Ok(Self::Orbit),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for CameraMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Fps => "fps",
+            Self::FreeFly => "fly",
+            Self::Orbit => "orbit",
+        };
+        f.write_str(name)
+    }
+}
+
+impl CameraController for FpsController {
+    fn mode(&self) -> CameraMode {
+        CameraMode::Fps
+    }
+
+    fn look(
+        &mut self,
+        player: &mut Player,
+        dt: Duration,
+        player_controller: &mut PlayerController,
+    ) {
+        player.look(dt, player_controller);
+    }
+
+    fn tick(
+        &mut self,
+        player: &mut Player,
+        dt: Duration,
+        collision_manager: &mut CollisionManager,
+        player_controller: &PlayerController,
+    ) {
+        player.tick(dt, collision_manager, player_controller);
+    }
+}
+
+impl CameraController for FreeFlyController {
+    fn mode(&self) -> CameraMode {
+        CameraMode::FreeFly
+    }
+
+    fn look(
+        &mut self,
+        player: &mut Player,
+        dt: Duration,
+        player_controller: &mut PlayerController,
+    ) {
+        player.look(dt, player_controller);
+    }
+
+    fn tick(
+        &mut self,
+        player: &mut Player,
+        dt: Duration,
+        _collision_manager: &mut CollisionManager,
+        player_controller: &PlayerController,
+    ) {
+        player.fly(dt, player_controller);
+    }
+}
+
+impl OrbitController {
+    const START_DISTANCE: f32 = 3.0;
+    const MIN_DISTANCE: f32 = 0.5;
+    const MAX_DISTANCE: f32 = 50.0;
+    // Distance covered per second while zooming, as a fraction of the current distance.
+    const ZOOM_SPEED: f32 = 1.5;
+
+    /// Orbits the point the player is looking at from a few units back.
+    fn new(player: &Player) -> Self {
+        let camera = &player.camera;
+        let looking_at = (camera.target - camera.position).normalize();
+        Self {
+            pivot: camera.position + looking_at * Self::START_DISTANCE,
+            distance: Self::START_DISTANCE,
+            yaw: player.yaw(),
+            pitch: player.pitch(),
+        }
+    }
+
+    fn place_camera(&self, player: &mut Player) {
+        let direction = Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        player.camera.position = self.pivot - direction * self.distance;
+        player.camera.target = self.pivot;
+    }
+}
+
+impl CameraController for OrbitController {
+    fn mode(&self) -> CameraMode {
+        CameraMode::Orbit
+    }
+
+    fn look(
+        &mut self,
+        player: &mut Player,
+        dt: Duration,
+        player_controller: &mut PlayerController,
+    ) {
+        if let Some((dx, dy)) = player_controller.delta_mouse_pos.take() {
+            let settings = player.camera_settings;
+            let dy = if settings.invert_y { -dy } else { dy };
+            let sens = settings.sensitivity * dt.as_secs_f32();
+            self.yaw -= dx * sens;
+            let max_pitch = std::f32::consts::FRAC_PI_2 - 0.01;
+            self.pitch = (self.pitch - dy * sens).clamp(-max_pitch, max_pitch);
+        }
+        self.place_camera(player);
+    }
+
+    /// W and S zoom in and out.
+    fn tick(
+        &mut self,
+        player: &mut Player,
+        dt: Duration,
+        _collision_manager: &mut CollisionManager,
+        player_controller: &PlayerController,
+    ) {
+        let zoom = match (
+            player_controller.is_w_pressed,
+            player_controller.is_s_pressed,
+        ) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => return,
+        };
+        let step = self.distance * Self::ZOOM_SPEED * dt.as_secs_f32();
+        self.distance = (self.distance + zoom * step).clamp(Self::MIN_DISTANCE, Self::MAX_DISTANCE);
+        self.place_camera(player);
+    }
+
+    fn exit(&mut self, player: &mut Player) {
+        player.reset_camera();
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// How the view responds to the mouse, and how wide it is.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub sensitivity: f32,
+    pub invert_y: bool,
+    // Vertical field of view in degrees.
+    pub fov: f32,
+    // Field of view while zoomed in, in degrees.
+    pub zoom_fov: f32,
+    // Share of the previous frame's mouse movement carried into this one, 0 turns it off.
+    pub smoothing: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 0.3,
+            invert_y: false,
+            fov: 1.0f32.to_degrees(),
+            zoom_fov: 30.0,
+            smoothing: 0.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub const MIN_FOV: f32 = 20.0;
+    pub const MAX_FOV: f32 = 120.0;
+    pub const FOV_STEP: f32 = 5.0;
+    pub const MAX_SMOOTHING: f32 = 0.9;
+    pub const SMOOTHING_STEP: f32 = 0.1;
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) -> f32 {
+        self.sensitivity = sensitivity.clamp(0.05, 2.0);
+        self.sensitivity
+    }
+
+    pub fn set_fov(&mut self, fov: f32) -> f32 {
+        self.fov = fov.clamp(Self::MIN_FOV, Self::MAX_FOV);
+        self.fov
+    }
+
+    pub fn set_zoom_fov(&mut self, zoom_fov: f32) -> f32 {
+        self.zoom_fov = zoom_fov.clamp(Self::MIN_FOV, Self::MAX_FOV);
+        self.zoom_fov
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: f32) -> f32 {
+        self.smoothing = smoothing.clamp(0.0, Self::MAX_SMOOTHING);
+        self.smoothing
+    }
+}
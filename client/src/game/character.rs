@@ -0,0 +1,48 @@
+use nalgebra::{Matrix3, Point3, Rotation3, Vector3};
+
+use crate::model::model_instance::{Instance, RawInstance};
+
+use super::animator::Animator;
+use super::bounding_box::BoundingBox;
+use super::enemy::Enemy;
+
+/// Animated model placed in the map, e.g. an enemy.
+pub struct Character {
+    pub joint_offset: u32,
+    pub animator: Animator,
+    // Index of the character's model in the map's models.
+    pub model: usize,
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    // Characters without one just stand and play their animation.
+    pub enemy: Option<Enemy>,
+}
+
+impl Character {
+    /// Box that shots and explosions test against, standing on the character's position.
+    pub fn hitbox(&self) -> BoundingBox {
+        BoundingBox {
+            top_left: Point3::new(
+                self.position.x - Enemy::RADIUS,
+                self.position.y + Enemy::HEIGHT,
+                self.position.z - Enemy::RADIUS,
+            ),
+            bottom_right: Point3::new(
+                self.position.x + Enemy::RADIUS,
+                self.position.y,
+                self.position.z + Enemy::RADIUS,
+            ),
+            collide_on_top: false,
+        }
+    }
+
+    pub fn instance(&self) -> RawInstance {
+        let rotation: Matrix3<f32> =
+            *Rotation3::from_axis_angle(&Vector3::y_axis(), self.yaw).matrix();
+        Instance {
+            position: self.position.coords,
+            rotation,
+        }
+        .to_skinned_raw(self.joint_offset)
+    }
+}
@@ -1,13 +1,22 @@
-use nalgebra::Vector3;
+use nalgebra::{Point3, Vector3};
 
 use super::bounding_box::BoundingBox;
 
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub point: Point3<f32>,
+    pub normal: Vector3<f32>,
+}
+
 #[derive(Debug)]
 pub struct CollisionManager {
     pub map_boxes: Vec<BoundingBox>,
 }
 
 impl CollisionManager {
+    // How far above a moving box the player may float and still be carried by it.
+    const RIDE_TOLERANCE: f32 = 0.02;
+
     /// returns the movement vector after collision calcuations.
     /// Also shifts the player's bounding box to that location.
     pub fn move_player(
@@ -34,4 +43,43 @@ impl CollisionManager {
             new_velocity
         }
     }
+
+    /// Moves a map box by `delta`. Returns true when `rider` stands on it or got pushed by
+    /// it, so the rider should move along.
+    pub fn move_box(&mut self, index: usize, delta: Vector3<f32>, rider: &BoundingBox) -> bool {
+        let Some(map_box) = self.map_boxes.get_mut(index) else {
+            return false;
+        };
+        let riding = rider.is_standing_on(map_box, Self::RIDE_TOLERANCE);
+        map_box.move_by(delta);
+        riding || rider.is_colliding_with(map_box)
+    }
+
+    /// True when no map box is in the way between `from` and `to`.
+    pub fn line_of_sight(&self, from: Point3<f32>, to: Point3<f32>) -> bool {
+        let offset = to - from;
+        let distance = offset.norm();
+        let Some(direction) = offset.try_normalize(0.0) else {
+            return true;
+        };
+        self.raycast(from, direction, distance).is_none()
+    }
+
+    /// Nearest map box hit by a ray within `max_distance`. `direction` must be normalized.
+    pub fn raycast(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        self.map_boxes
+            .iter()
+            .filter_map(|map_box| map_box.ray_intersection(origin, direction))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(distance, normal)| RayHit {
+                point: origin + direction * distance,
+                normal,
+            })
+    }
 }
@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+/// A console line split on whitespace, the first word names the command or cvar.
+pub struct ConsoleCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// Drop down console state: the line being typed and the scrollback.
+#[derive(Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    output: VecDeque<String>,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace().map(str::to_string);
+        let name = words.next()?.to_ascii_lowercase();
+        Some(Self {
+            name,
+            args: words.collect(),
+        })
+    }
+
+    /// Parses argument `index`, naming the command in the error.
+    pub fn arg<T: FromStr>(&self, index: usize) -> Result<T, String> {
+        let arg = self
+            .args
+            .get(index)
+            .ok_or_else(|| format!("{} is missing argument {}", self.name, index + 1))?;
+        arg.parse()
+            .map_err(|_| format!("{} can't use {arg} as argument {}", self.name, index + 1))
+    }
+
+    /// Like `arg` but falls back to `default` when the argument wasn't given.
+    pub fn arg_or<T: FromStr>(&self, index: usize, default: T) -> Result<T, String> {
+        if index < self.args.len() {
+            self.arg(index)
+        } else {
+            Ok(default)
+        }
+    }
+}
+
+impl Console {
+    const MAX_OUTPUT: usize = 64;
+    pub const PROMPT: &str = "> ";
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn push_text(&mut self, text: &str) {
+        self.input
+            .extend(text.chars().filter(|c| !c.is_control() && *c != '`'));
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Takes the typed line and echoes it into the scrollback.
+    pub fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        self.print(format!("{}{line}", Self::PROMPT));
+        line
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        if self.output.len() == Self::MAX_OUTPUT {
+            self.output.pop_front();
+        }
+        self.output.push_back(line.into());
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    /// Scrollback from newest to oldest.
+    pub fn output(&self) -> impl Iterator<Item = &String> {
+        self.output.iter().rev()
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::rng::RngStream;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecalKind {
+    BulletHole,
+    Scorch,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DecalSettings {
+    // Width of the projected square in world units.
+    pub size: f32,
+    pub lifetime: f32,
+    // Seconds at the end of the lifetime spent fading out.
+    pub fade_time: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Decal {
+    pub position: Point3<f32>,
+    pub normal: Vector3<f32>,
+    pub kind: DecalKind,
+    // Rotation around the normal, so repeated decals don't look stamped.
+    pub angle: f32,
+    pub age: f32,
+    // Placed by the map, these never fade and are only evicted when nothing else can be.
+    pub permanent: bool,
+}
+
+/// Ring buffer of decals projected onto the level, the oldest decal is dropped when full.
+#[derive(Default)]
+pub struct DecalSystem {
+    decals: VecDeque<Decal>,
+}
+
+impl DecalKind {
+    pub fn settings(&self) -> DecalSettings {
+        match self {
+            Self::BulletHole => DecalSettings {
+                size: 0.08,
+                lifetime: 20.0,
+                fade_time: 2.0,
+            },
+            Self::Scorch => DecalSettings {
+                size: 0.8,
+                lifetime: 40.0,
+                fade_time: 5.0,
+            },
+        }
+    }
+
+    /// Column of the decal in the decal atlas.
+    pub fn atlas_index(&self) -> u32 {
+        match self {
+            Self::BulletHole => 0,
+            Self::Scorch => 1,
+        }
+    }
+}
+
+impl Decal {
+    pub fn new(
+        position: Point3<f32>,
+        normal: Vector3<f32>,
+        kind: DecalKind,
+        angle: f32,
+        permanent: bool,
+    ) -> Self {
+        Self {
+            position,
+            normal,
+            kind,
+            angle,
+            age: 0.0,
+            permanent,
+        }
+    }
+
+    pub fn opacity(&self) -> f32 {
+        if self.permanent {
+            return 1.0;
+        }
+        let settings = self.kind.settings();
+        let remaining = settings.lifetime - self.age;
+        (remaining / settings.fade_time).clamp(0.0, 1.0)
+    }
+}
+
+impl DecalSystem {
+    pub const MAX_DECALS: usize = 256;
+
+    /// Adds a decal that fades out, turned to a random angle so repeated hits don't line up.
+    pub fn spawn(
+        &mut self,
+        position: Point3<f32>,
+        normal: Vector3<f32>,
+        kind: DecalKind,
+        rng: &mut RngStream,
+    ) {
+        self.add(Decal::new(position, normal, kind, rng.angle(), false));
+    }
+
+    pub fn add(&mut self, decal: Decal) {
+        if self.decals.len() >= Self::MAX_DECALS {
+            match self.decals.iter().position(|decal| !decal.permanent) {
+                Some(oldest) => {
+                    self.decals.remove(oldest);
+                }
+                None => {
+                    self.decals.pop_front();
+                }
+            }
+        }
+        self.decals.push_back(decal);
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        for decal in &mut self.decals {
+            decal.age += dt;
+        }
+        self.decals
+            .retain(|decal| decal.permanent || decal.age < decal.kind.settings().lifetime);
+    }
+
+    pub fn decals(&self) -> impl Iterator<Item = &Decal> {
+        self.decals.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+    }
+}
@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::collision_manager::CollisionManager;
+use super::health::{Damage, Health};
+use super::navigation::NavGrid;
+use super::rng::RngStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnemyState {
+    Idle,
+    Chase,
+    Attack,
+    Dead,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EnemySettings {
+    pub health: u32,
+    pub speed: f32,
+    pub sight_range: f32,
+    pub attack_range: f32,
+    pub attack_damage: u32,
+    // Seconds between attacks.
+    pub attack_interval: f32,
+    // Height of the eyes above the feet, where line of sight is tested from.
+    pub eye_height: f32,
+    // Clips played in each state, left alone when missing.
+    pub idle_animation: Option<String>,
+    pub chase_animation: Option<String>,
+    pub attack_animation: Option<String>,
+    pub death_animation: Option<String>,
+}
+
+/// The level as enemies see it, shared by every enemy's update.
+pub struct EnemyWorld<'a> {
+    pub collision_manager: &'a CollisionManager,
+    pub nav_grid: &'a NavGrid,
+}
+
+/// Brain of an enemy character. Idles until it sees the player, chases them along the
+/// navigation grid and attacks once in range.
+pub struct Enemy {
+    pub settings: EnemySettings,
+    state: EnemyState,
+    health: Health,
+    path: Vec<Point3<f32>>,
+    repath_timer: f32,
+    attack_timer: f32,
+    // Where the player was last seen, chased to even after losing sight of them.
+    last_seen: Option<Point3<f32>>,
+}
+
+impl Default for EnemySettings {
+    fn default() -> Self {
+        Self {
+            health: 30,
+            speed: 1.2,
+            sight_range: 8.0,
+            attack_range: 0.6,
+            attack_damage: 10,
+            attack_interval: 1.0,
+            eye_height: 0.4,
+            idle_animation: None,
+            chase_animation: None,
+            attack_animation: None,
+            death_animation: None,
+        }
+    }
+}
+
+impl Enemy {
+    pub const RADIUS: f32 = 0.1;
+    pub const HEIGHT: f32 = 0.5;
+    const REPATH_INTERVAL: f32 = 0.5;
+    // Attacks keep going until the player is this much further than the attack range.
+    const ATTACK_HYSTERESIS: f32 = 1.2;
+    // Share of the attack interval each attack comes early or late by, so enemies next to
+    // each other don't hit in lockstep.
+    const ATTACK_JITTER: f32 = 0.2;
+
+    pub fn new(settings: EnemySettings) -> Self {
+        Self {
+            health: Health::new(settings.health),
+            settings,
+            state: EnemyState::Idle,
+            path: vec![],
+            repath_timer: 0.0,
+            attack_timer: 0.0,
+            last_seen: None,
+        }
+    }
+
+    pub fn state(&self) -> EnemyState {
+        self.state
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.state == EnemyState::Dead
+    }
+
+    /// Takes a hit, which also gives away where the attacker is. Returns true when this
+    /// killed the enemy.
+    pub fn damage(&mut self, damage: Damage) -> bool {
+        if self.is_dead() {
+            return false;
+        }
+        self.health.damage(damage);
+        if self.health.is_dead() {
+            self.state = EnemyState::Dead;
+            self.path.clear();
+            return true;
+        }
+        self.last_seen = Some(damage.source);
+        if self.state == EnemyState::Idle {
+            self.state = EnemyState::Chase;
+            self.repath_timer = 0.0;
+        }
+        false
+    }
+
+    pub fn animation(&self) -> Option<&str> {
+        match self.state {
+            EnemyState::Idle => self.settings.idle_animation.as_deref(),
+            EnemyState::Chase => self.settings.chase_animation.as_deref(),
+            EnemyState::Attack => self.settings.attack_animation.as_deref(),
+            EnemyState::Dead => self.settings.death_animation.as_deref(),
+        }
+    }
+
+    /// Thinks and moves for one frame, where `target` is the player's eye position.
+    /// Returns the damage dealt to the player.
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        position: &mut Point3<f32>,
+        yaw: &mut f32,
+        target: Point3<f32>,
+        world: &EnemyWorld,
+        rng: &mut RngStream,
+    ) -> u32 {
+        if self.is_dead() {
+            return 0;
+        }
+        let dt = dt.as_secs_f32();
+        let eye = *position + Vector3::new(0.0, self.settings.eye_height, 0.0);
+        let distance = (target - eye).norm();
+        let sees_target = distance <= self.settings.sight_range
+            && world.collision_manager.line_of_sight(eye, target);
+        if sees_target {
+            self.last_seen = Some(target);
+        }
+
+        self.state = match self.state {
+            EnemyState::Idle if sees_target => EnemyState::Chase,
+            EnemyState::Chase if sees_target && distance <= self.settings.attack_range => {
+                self.attack_timer = 0.0;
+                EnemyState::Attack
+            }
+            EnemyState::Chase if self.last_seen.is_none() => EnemyState::Idle,
+            EnemyState::Attack
+                if !sees_target
+                    || distance > self.settings.attack_range * Self::ATTACK_HYSTERESIS =>
+            {
+                self.repath_timer = 0.0;
+                EnemyState::Chase
+            }
+            state => state,
+        };
+
+        match self.state {
+            EnemyState::Idle | EnemyState::Dead => 0,
+            EnemyState::Chase => {
+                self.chase(dt, position, yaw, world.nav_grid);
+                0
+            }
+            EnemyState::Attack => {
+                Self::face(yaw, target - *position);
+                self.attack_timer -= dt;
+                if self.attack_timer > 0.0 {
+                    return 0;
+                }
+                self.attack_timer = self.settings.attack_interval
+                    * rng.range(1.0 - Self::ATTACK_JITTER, 1.0 + Self::ATTACK_JITTER);
+                self.settings.attack_damage
+            }
+        }
+    }
+
+    fn chase(&mut self, dt: f32, position: &mut Point3<f32>, yaw: &mut f32, nav_grid: &NavGrid) {
+        let Some(goal) = self.last_seen else {
+            return;
+        };
+        self.repath_timer -= dt;
+        if self.repath_timer <= 0.0 {
+            self.repath_timer = Self::REPATH_INTERVAL;
+            self.path = nav_grid.find_path(*position, goal).unwrap_or_default();
+            if self.path.is_empty() {
+                // Nowhere to go, give up until the player shows up again.
+                self.last_seen = None;
+                return;
+            }
+        }
+
+        let mut step = self.settings.speed * dt;
+        while let Some(&waypoint) = self.path.first() {
+            let offset = Vector3::new(waypoint.x - position.x, 0.0, waypoint.z - position.z);
+            let remaining = offset.norm();
+            if remaining <= step {
+                position.x = waypoint.x;
+                position.z = waypoint.z;
+                step -= remaining;
+                self.path.remove(0);
+                continue;
+            }
+            Self::face(yaw, offset);
+            *position += offset / remaining * step;
+            break;
+        }
+        if self.path.is_empty() {
+            self.last_seen = None;
+        }
+    }
+
+    fn face(yaw: &mut f32, direction: Vector3<f32>) {
+        if direction.x.abs() > f32::EPSILON || direction.z.abs() > f32::EPSILON {
+            *yaw = direction.x.atan2(direction.z);
+        }
+    }
+}
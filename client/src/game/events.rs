@@ -0,0 +1,91 @@
+use nalgebra::{Point3, Vector3};
+
+use super::pickups::PickupKind;
+
+/// Something that happened in the game, published by the system it happened in so the ones
+/// reacting to it, like the HUD, particles and level scripts, don't need to know about it.
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    WeaponFired {
+        weapon: &'static str,
+        muzzle: Point3<f32>,
+        direction: Vector3<f32>,
+    },
+    WeaponSelected {
+        weapon: &'static str,
+    },
+    // A shot landed, on an enemy when `enemy` is set or on the level otherwise.
+    Hit {
+        point: Point3<f32>,
+        normal: Vector3<f32>,
+        enemy: bool,
+    },
+    Explosion {
+        point: Point3<f32>,
+        normal: Vector3<f32>,
+    },
+    // Movers waiting on `trigger` started moving, e.g. a door opened.
+    MoverActivated {
+        trigger: String,
+    },
+    MoverLocked {
+        key: String,
+    },
+    EnemyDied {
+        position: Point3<f32>,
+    },
+    PlayerHurt {
+        amount: u32,
+        source: Point3<f32>,
+    },
+    PlayerDied,
+    PickupCollected {
+        kind: PickupKind,
+    },
+}
+
+/// Reacts to published events.
+pub trait EventSubscriber {
+    fn on_event(&mut self, event: &GameEvent);
+}
+
+/// Events published during a frame, handed to subscribers in order once gameplay has run.
+/// Publishing only queues, so gameplay code never needs the subscribers at hand.
+#[derive(Default)]
+pub struct EventBus {
+    events: Vec<GameEvent>,
+}
+
+impl GameEvent {
+    /// Name level scripts see it by.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::WeaponFired { .. } => "weapon_fired",
+            Self::WeaponSelected { .. } => "weapon_selected",
+            Self::Hit { .. } => "hit",
+            Self::Explosion { .. } => "explosion",
+            Self::MoverActivated { .. } => "mover_activated",
+            Self::MoverLocked { .. } => "mover_locked",
+            Self::EnemyDied { .. } => "enemy_died",
+            Self::PlayerHurt { .. } => "player_hurt",
+            Self::PlayerDied => "player_died",
+            Self::PickupCollected { .. } => "pickup_collected",
+        }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+
+    /// Everything published since the last call, oldest first.
+    pub fn take(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Drops events nobody has seen yet, e.g. ones from a level that was just replaced.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
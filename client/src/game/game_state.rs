@@ -0,0 +1,130 @@
+use winit::keyboard::KeyCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    Loading,
+    MainMenu,
+    InGame,
+    Paused,
+    Settings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateTransition {
+    Push(GameState),
+    Pop,
+    Replace(GameState),
+    Quit,
+}
+
+/// Stack of application states. Only the top state receives input, states underneath it keep
+/// being drawn but are frozen.
+pub struct GameStateStack {
+    states: Vec<GameState>,
+}
+
+impl GameState {
+    /// Called for every key press while this state is on top of the stack.
+    pub fn handle_key(&self, key: KeyCode) -> Option<StateTransition> {
+        match (self, key) {
+            (Self::Loading, KeyCode::Escape) => Some(StateTransition::Quit),
+            (Self::MainMenu, KeyCode::Enter | KeyCode::Space | KeyCode::KeyL) => {
+                Some(StateTransition::Replace(Self::InGame))
+            }
+            (Self::MainMenu, KeyCode::Escape) => Some(StateTransition::Quit),
+            (Self::InGame, KeyCode::Escape) => Some(StateTransition::Push(Self::Paused)),
+            (Self::Paused, KeyCode::Escape | KeyCode::Enter) => Some(StateTransition::Pop),
+            (Self::Paused, KeyCode::KeyS) => Some(StateTransition::Push(Self::Settings)),
+            (Self::Paused, KeyCode::KeyQ) => Some(StateTransition::Quit),
+            (Self::Settings, KeyCode::Escape) => Some(StateTransition::Pop),
+            _ => None,
+        }
+    }
+
+    /// Whether the world simulation and camera input run in this state.
+    pub fn is_interactive(&self) -> bool {
+        *self == Self::InGame
+    }
+
+    /// Colour blended over the scene while this state is on top.
+    pub fn overlay_color(&self) -> Option<[f32; 4]> {
+        match self {
+            Self::Loading => Some([0.0, 0.0, 0.0, 1.0]),
+            Self::MainMenu => Some([0.0, 0.0, 0.0, 0.85]),
+            Self::InGame => None,
+            Self::Paused => Some([0.0, 0.0, 0.0, 0.5]),
+            Self::Settings => Some([0.02, 0.02, 0.1, 0.7]),
+        }
+    }
+
+    /// Lines drawn over the scene while this state is on top, the first one as a title.
+    pub fn menu_lines(&self) -> &'static [&'static str] {
+        match self {
+            Self::Loading => &["Loading"],
+            Self::MainMenu => &["Mood", "Enter: start", "L: load", "Esc: quit"],
+            Self::InGame => &[],
+            Self::Paused => &["Paused", "Esc: resume", "S: settings", "Q: quit"],
+            Self::Settings => &[
+                "Settings",
+                "Up/Down: sensitivity",
+                "I: invert Y",
+                "[/]: FOV",
+                ",/.: mouse smoothing",
+                "O: SSAO",
+                "R: resolution",
+                "-/=: render scale",
+                "F: upscale filter",
+                "D: dynamic resolution",
+                "P: depth prepass",
+                "Esc: back",
+            ],
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Loading => "Mood - Loading",
+            Self::MainMenu => "Mood - Press Enter to start, L to load",
+            Self::InGame => {
+                "Mood (1/2: weapons, Tab: automap, F5: quicksave, F9: quickload, `: console)"
+            }
+            Self::Paused => "Mood - Paused (Esc: resume, S: settings, Q: quit)",
+            Self::Settings => {
+                "Mood - Settings (Up/Down: sensitivity, I: invert Y, [/]: FOV, ,/.: mouse smoothing, O: SSAO, R: resolution, -/=: render scale, F: upscale filter, D: dynamic resolution, P: depth prepass, Esc: back)"
+            }
+        }
+    }
+}
+
+impl Default for GameStateStack {
+    fn default() -> Self {
+        Self {
+            states: vec![GameState::Loading],
+        }
+    }
+}
+
+impl GameStateStack {
+    pub fn current(&self) -> GameState {
+        *self.states.last().unwrap_or(&GameState::MainMenu)
+    }
+
+    /// Applies `transition` and returns false when the application should quit.
+    pub fn apply(&mut self, transition: StateTransition) -> bool {
+        match transition {
+            StateTransition::Push(state) => self.states.push(state),
+            StateTransition::Pop => {
+                // The bottom state is never popped.
+                if self.states.len() > 1 {
+                    self.states.pop();
+                }
+            }
+            StateTransition::Replace(state) => {
+                self.states.pop();
+                self.states.push(state);
+            }
+            StateTransition::Quit => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,52 @@
+use nalgebra::Point3;
+
+/// Hit points of anything that can be hurt.
+#[derive(Debug, Clone, Copy)]
+pub struct Health {
+    current: u32,
+    max: u32,
+}
+
+/// One hit, from a shot, an explosion or an enemy attack.
+#[derive(Debug, Clone, Copy)]
+pub struct Damage {
+    pub amount: u32,
+    // Where the attacker was, so whoever got hurt knows where to look.
+    pub source: Point3<f32>,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn current(&self) -> u32 {
+        self.current
+    }
+
+    pub fn set(&mut self, health: u32) {
+        self.current = health.min(self.max);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+
+    /// Returns how much health was actually lost.
+    pub fn damage(&mut self, damage: Damage) -> u32 {
+        let taken = damage.amount.min(self.current);
+        self.current -= taken;
+        taken
+    }
+
+    /// Returns how much health was actually restored, nothing when already full.
+    pub fn heal(&mut self, amount: u32) -> u32 {
+        let healed = amount.min(self.max - self.current);
+        self.current += healed;
+        healed
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.max;
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::events::{EventSubscriber, GameEvent};
+
+pub struct HudMessage {
+    pub text: String,
+    remaining: f32,
+}
+
+/// Everything shown on the 2D HUD layer. Gameplay code writes to it, the renderer lays it
+/// out every frame.
+pub struct Hud {
+    health: u32,
+    // Loaded and reserve rounds, hidden while no weapon reports ammo.
+    ammo: Option<(u32, u32)>,
+    messages: VecDeque<HudMessage>,
+    flash: Option<ScreenFlash>,
+    // Title and key hints of the open menu, centered over everything else.
+    menu: &'static [&'static str],
+    pub crosshair_visible: bool,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self {
+            health: Self::DEFAULT_HEALTH,
+            ammo: None,
+            messages: VecDeque::new(),
+            flash: None,
+            menu: &[],
+            crosshair_visible: true,
+        }
+    }
+}
+
+/// Colour blended over the whole screen that fades out, e.g. when getting hurt.
+#[derive(Debug, Clone, Copy)]
+struct ScreenFlash {
+    color: [f32; 4],
+    remaining: f32,
+}
+
+impl HudMessage {
+    const FADE_TIME: f32 = 0.5;
+
+    pub fn opacity(&self) -> f32 {
+        (self.remaining / Self::FADE_TIME).clamp(0.0, 1.0)
+    }
+}
+
+impl Hud {
+    const DEFAULT_HEALTH: u32 = 100;
+    const MESSAGE_TIME: f32 = 3.0;
+    const FLASH_TIME: f32 = 0.3;
+    const MAX_MESSAGES: usize = 4;
+    const HURT_FLASH: [f32; 4] = [0.8, 0.0, 0.0, 0.4];
+    const PICKUP_FLASH: [f32; 4] = [1.0, 0.9, 0.4, 0.25];
+
+    pub fn set_health(&mut self, health: u32) {
+        self.health = health;
+    }
+
+    pub fn health(&self) -> u32 {
+        self.health
+    }
+
+    pub fn set_ammo(&mut self, loaded: u32, reserve: u32) {
+        self.ammo = Some((loaded, reserve));
+    }
+
+    pub fn ammo(&self) -> Option<(u32, u32)> {
+        self.ammo
+    }
+
+    /// Flashes `color` over the screen, its alpha fading to nothing.
+    pub fn flash(&mut self, color: [f32; 4]) {
+        self.flash = Some(ScreenFlash {
+            color,
+            remaining: Self::FLASH_TIME,
+        });
+    }
+
+    pub fn flash_color(&self) -> Option<[f32; 4]> {
+        let flash = self.flash?;
+        let [r, g, b, a] = flash.color;
+        Some([r, g, b, a * flash.remaining / Self::FLASH_TIME])
+    }
+
+    /// Shows a pickup or status message for a few seconds, pushing out the oldest when full.
+    pub fn show_message(&mut self, text: impl Into<String>) {
+        if self.messages.len() == Self::MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(HudMessage {
+            text: text.into(),
+            remaining: Self::MESSAGE_TIME,
+        });
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = &HudMessage> {
+        self.messages.iter()
+    }
+
+    /// Shows `lines` in the middle of the screen, the first one as a title. Empty closes it.
+    pub fn set_menu(&mut self, lines: &'static [&'static str]) {
+        self.menu = lines;
+    }
+
+    pub fn menu(&self) -> &'static [&'static str] {
+        self.menu
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        for message in &mut self.messages {
+            message.remaining -= dt;
+        }
+        self.messages.retain(|message| message.remaining > 0.0);
+        if let Some(flash) = &mut self.flash {
+            flash.remaining -= dt;
+            if flash.remaining <= 0.0 {
+                self.flash = None;
+            }
+        }
+    }
+}
+
+impl EventSubscriber for Hud {
+    fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::WeaponSelected { weapon } => self.show_message(*weapon),
+            GameEvent::MoverLocked { key } => self.show_message(format!("Needs the {key} key")),
+            GameEvent::PlayerHurt { .. } => self.flash(Self::HURT_FLASH),
+            GameEvent::PlayerDied => self.show_message("You died"),
+            GameEvent::PickupCollected { kind } => {
+                self.show_message(kind.description());
+                self.flash(Self::PICKUP_FLASH);
+            }
+            _ => {}
+        }
+    }
+}
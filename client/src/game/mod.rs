@@ -1,4 +1,30 @@
+pub mod animator;
+pub mod automap;
+pub mod benchmark;
 pub mod bounding_box;
+pub mod camera_controller;
+pub mod camera_settings;
+pub mod character;
 pub mod collision_manager;
+pub mod console;
+pub mod decals;
+pub mod enemy;
+pub mod events;
+pub mod game_state;
+pub mod health;
+pub mod hud;
+pub mod movers;
+pub mod navigation;
+pub mod particles;
+pub mod physics;
+pub mod pickups;
 pub mod player;
 pub mod player_controller;
+pub mod replay;
+pub mod rng;
+pub mod save_game;
+pub mod script_host;
+pub mod transform;
+pub mod triggers;
+pub mod view_model;
+pub mod weapons;
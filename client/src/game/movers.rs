@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use nalgebra::{Isometry3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::transform::Transform;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    #[default]
+    EaseInOut,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    // Translation from where the geometry was placed in the map.
+    pub offset: Vector3<f32>,
+    // Seconds it takes to get here from the previous keyframe.
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MoverState {
+    AtStart,
+    Opening,
+    AtEnd,
+    Closing,
+}
+
+/// Moves a model and its collision boxes through a list of keyframes, e.g. a door or an
+/// elevator. Movers with a trigger wait for it, the rest go back and forth on their own.
+pub struct Mover {
+    // Index into the map's models.
+    pub model: Option<usize>,
+    // Indices of the collision boxes carried along.
+    pub bounding_boxes: Vec<usize>,
+    pub trigger: Option<String>,
+    // Key the player needs for the trigger to work.
+    pub key: Option<String>,
+    keyframes: Vec<Keyframe>,
+    easing: Easing,
+    // Seconds spent at either end before heading back, never returns when None.
+    wait: Option<f32>,
+    state: MoverState,
+    // Seconds along the keyframes, or in the current wait at either end.
+    time: f32,
+    // Translation from where the map placed it, stepped by the fixed tick.
+    transform: Transform,
+    // Translation the model was last drawn at, which trails the transform between ticks.
+    drawn_offset: Vector3<f32>,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+impl Mover {
+    pub fn new(
+        keyframes: Vec<Keyframe>,
+        easing: Easing,
+        trigger: Option<String>,
+        wait: Option<f32>,
+    ) -> Self {
+        let offset = keyframes.first().map_or(Vector3::zeros(), |key| key.offset);
+        // Without a trigger there's nothing to start the mover, so it starts by itself.
+        let state = match trigger {
+            Some(_) => MoverState::AtStart,
+            None => MoverState::Opening,
+        };
+        Self {
+            model: None,
+            bounding_boxes: vec![],
+            trigger,
+            key: None,
+            keyframes,
+            easing,
+            wait,
+            state,
+            time: 0.0,
+            transform: Transform::new(Isometry3::translation(offset.x, offset.y, offset.z)),
+            drawn_offset: offset,
+        }
+    }
+
+    /// Starts moving towards the last keyframe, unless already on the way there.
+    pub fn activate(&mut self) {
+        match self.state {
+            MoverState::AtStart => {
+                self.state = MoverState::Opening;
+                self.time = 0.0;
+            }
+            // Reverse mid way, like a door that opens again when walked into.
+            MoverState::Closing => self.state = MoverState::Opening,
+            MoverState::Opening | MoverState::AtEnd => {}
+        }
+    }
+
+    fn total_duration(&self) -> f32 {
+        self.keyframes.iter().skip(1).map(|key| key.duration).sum()
+    }
+
+    /// Offset at `time` seconds along the keyframes.
+    fn offset_at(&self, time: f32) -> Vector3<f32> {
+        let Some(first) = self.keyframes.first() else {
+            return Vector3::zeros();
+        };
+        let mut start = 0.0;
+        let mut offset = first.offset;
+        for pair in self.keyframes.windows(2) {
+            let end = start + pair[1].duration;
+            if time < end {
+                let t = ((time - start) / pair[1].duration.max(f32::EPSILON)).clamp(0.0, 1.0);
+                return pair[0].offset.lerp(&pair[1].offset, self.easing.apply(t));
+            }
+            start = end;
+            offset = pair[1].offset;
+        }
+        offset
+    }
+
+    /// How far the mover's model is drawn from where the map placed it.
+    pub fn travelled(&self) -> Vector3<f32> {
+        self.drawn_offset - self.offset_at(0.0)
+    }
+
+    pub fn begin_tick(&mut self) {
+        self.transform.begin_tick();
+    }
+
+    /// How far to move the model to draw it `alpha` of the way into the next tick, if it moved
+    /// since it was last drawn.
+    pub fn draw_delta(&mut self, alpha: f32) -> Option<Vector3<f32>> {
+        if !self.transform.take_dirty() {
+            return None;
+        }
+        let offset = self.transform.interpolated(alpha).translation.vector;
+        let delta = offset - self.drawn_offset;
+        self.drawn_offset = offset;
+        (delta != Vector3::zeros()).then_some(delta)
+    }
+
+    /// Advances the mover and returns how far it moved, if at all.
+    pub fn update(&mut self, dt: Duration) -> Option<Vector3<f32>> {
+        let dt = dt.as_secs_f32();
+        let total = self.total_duration();
+        match self.state {
+            MoverState::AtStart | MoverState::AtEnd => {
+                let wait = self.wait?;
+                // Triggered movers stay put at the start until activated again.
+                if self.state == MoverState::AtStart && self.trigger.is_some() {
+                    return None;
+                }
+                self.time += dt;
+                if self.time >= wait {
+                    (self.state, self.time) = match self.state {
+                        MoverState::AtStart => (MoverState::Opening, 0.0),
+                        _ => (MoverState::Closing, total),
+                    };
+                }
+                return None;
+            }
+            MoverState::Opening => {
+                self.time += dt;
+                if self.time >= total {
+                    (self.state, self.time) = (MoverState::AtEnd, 0.0);
+                }
+            }
+            MoverState::Closing => {
+                self.time -= dt;
+                if self.time <= 0.0 {
+                    (self.state, self.time) = (MoverState::AtStart, 0.0);
+                }
+            }
+        }
+        let time = match self.state {
+            MoverState::AtEnd => total,
+            MoverState::AtStart => 0.0,
+            _ => self.time,
+        };
+        let delta = self.offset_at(time) - self.transform.position().coords;
+        self.transform.translate(delta);
+        (delta != Vector3::zeros()).then_some(delta)
+    }
+}
@@ -0,0 +1,223 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use nalgebra::Point3;
+
+use super::collision_manager::CollisionManager;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OpenCell {
+    cell: usize,
+    // Cost so far plus the heuristic to the goal.
+    estimate: f32,
+}
+
+impl Eq for OpenCell {}
+
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the heap pops the cheapest cell first.
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Walkability grid over the XZ extent of the level for a single floor height. A cell is
+/// blocked when a map box, grown by the agent radius, reaches into the space an agent
+/// standing on the floor takes up.
+pub struct NavGrid {
+    min_x: f32,
+    min_z: f32,
+    floor: f32,
+    cell_size: f32,
+    width: usize,
+    depth: usize,
+    blocked: Vec<bool>,
+}
+
+impl NavGrid {
+    const CELL_SIZE: f32 = 0.125;
+    // Coarser cells are used for levels that would need more than this many.
+    const MAX_CELLS: usize = 512 * 512;
+    // Boxes this far above the floor are stepped onto rather than blocking.
+    const STEP_HEIGHT: f32 = 0.3;
+    // Keeps a search across an unreachable level from visiting every cell.
+    const MAX_EXPANSIONS: usize = 20_000;
+
+    pub fn build(
+        collision_manager: &CollisionManager,
+        floor: f32,
+        agent_radius: f32,
+        agent_height: f32,
+    ) -> Self {
+        let boxes = &collision_manager.map_boxes;
+        let (mut min_x, mut min_z) = (f32::INFINITY, f32::INFINITY);
+        let (mut max_x, mut max_z) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for map_box in boxes {
+            min_x = min_x.min(map_box.top_left.x);
+            min_z = min_z.min(map_box.top_left.z);
+            max_x = max_x.max(map_box.bottom_right.x);
+            max_z = max_z.max(map_box.bottom_right.z);
+        }
+        if boxes.is_empty() {
+            (min_x, min_z, max_x, max_z) = (0.0, 0.0, 0.0, 0.0);
+        }
+        let area = (max_x - min_x) * (max_z - min_z);
+        let cell_size = Self::CELL_SIZE.max((area / Self::MAX_CELLS as f32).sqrt());
+        let width = ((max_x - min_x) / cell_size).ceil().max(1.0) as usize;
+        let depth = ((max_z - min_z) / cell_size).ceil().max(1.0) as usize;
+        let mut grid = Self {
+            min_x,
+            min_z,
+            floor,
+            cell_size,
+            width,
+            depth,
+            blocked: vec![false; width * depth],
+        };
+
+        let low = floor + Self::STEP_HEIGHT;
+        let high = floor + agent_height;
+        for map_box in boxes {
+            if map_box.top_left.y <= low || map_box.bottom_right.y >= high {
+                continue;
+            }
+            // Every cell whose center lies inside the grown footprint.
+            let x0 = grid.axis_cell(map_box.top_left.x - agent_radius - min_x, width);
+            let x1 = grid.axis_cell(map_box.bottom_right.x + agent_radius - min_x, width);
+            let z0 = grid.axis_cell(map_box.top_left.z - agent_radius - min_z, depth);
+            let z1 = grid.axis_cell(map_box.bottom_right.z + agent_radius - min_z, depth);
+            for z in z0..z1 {
+                for x in x0..x1 {
+                    grid.blocked[z * width + x] = true;
+                }
+            }
+        }
+        grid
+    }
+
+    /// First cell along an axis whose center is past `distance`.
+    fn axis_cell(&self, distance: f32, count: usize) -> usize {
+        ((distance / self.cell_size - 0.5).ceil().max(0.0) as usize).min(count)
+    }
+
+    fn cell_at(&self, position: Point3<f32>) -> Option<usize> {
+        let x = ((position.x - self.min_x) / self.cell_size).floor();
+        let z = ((position.z - self.min_z) / self.cell_size).floor();
+        let inside = x >= 0.0 && z >= 0.0 && (x as usize) < self.width && (z as usize) < self.depth;
+        inside.then(|| z as usize * self.width + x as usize)
+    }
+
+    fn cell_center(&self, cell: usize) -> Point3<f32> {
+        let (x, z) = (cell % self.width, cell / self.width);
+        Point3::new(
+            self.min_x + (x as f32 + 0.5) * self.cell_size,
+            self.floor,
+            self.min_z + (z as f32 + 0.5) * self.cell_size,
+        )
+    }
+
+    fn is_walkable(&self, x: isize, z: isize) -> bool {
+        x >= 0
+            && z >= 0
+            && (x as usize) < self.width
+            && (z as usize) < self.depth
+            && !self.blocked[z as usize * self.width + x as usize]
+    }
+
+    /// Walkable neighbours of `cell` and the cost of stepping to them. Diagonals may not cut
+    /// past a blocked corner.
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let (x, z) = ((cell % self.width) as isize, (cell / self.width) as isize);
+        (-1..=1)
+            .flat_map(move |dz| (-1..=1).map(move |dx| (dx, dz)))
+            .filter(move |&(dx, dz)| {
+                (dx, dz) != (0, 0)
+                    && self.is_walkable(x + dx, z + dz)
+                    && self.is_walkable(x + dx, z)
+                    && self.is_walkable(x, z + dz)
+            })
+            .map(move |(dx, dz)| {
+                let cost = if dx != 0 && dz != 0 {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                ((z + dz) as usize * self.width + (x + dx) as usize, cost)
+            })
+    }
+
+    fn heuristic(&self, from: usize, to: usize) -> f32 {
+        let dx = (from % self.width).abs_diff(to % self.width) as f32;
+        let dz = (from / self.width).abs_diff(to / self.width) as f32;
+        // Octile distance, exact for an open grid with diagonal steps.
+        dx.max(dz) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dz)
+    }
+
+    /// A* from `start` to `goal`. Returns the cell centers to walk through, ending at `goal`,
+    /// or none when the goal can't be reached.
+    pub fn find_path(&self, start: Point3<f32>, goal: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        let start_cell = self.cell_at(start)?;
+        let goal_cell = self.cell_at(goal)?;
+        if self.blocked[goal_cell] {
+            return None;
+        }
+        let mut cost = vec![f32::INFINITY; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        cost[start_cell] = 0.0;
+        open.push(OpenCell {
+            cell: start_cell,
+            estimate: self.heuristic(start_cell, goal_cell),
+        });
+
+        let mut expansions = 0;
+        while let Some(OpenCell { cell, estimate }) = open.pop() {
+            if cell == goal_cell {
+                return Some(self.reconstruct(&came_from, start_cell, goal_cell, goal));
+            }
+            // Stale entry, the cell was reached more cheaply since it was pushed.
+            if estimate > cost[cell] + self.heuristic(cell, goal_cell) {
+                continue;
+            }
+            expansions += 1;
+            if expansions > Self::MAX_EXPANSIONS {
+                return None;
+            }
+            for (neighbour, step) in self.neighbours(cell) {
+                let next_cost = cost[cell] + step;
+                if next_cost < cost[neighbour] {
+                    cost[neighbour] = next_cost;
+                    came_from[neighbour] = cell;
+                    open.push(OpenCell {
+                        cell: neighbour,
+                        estimate: next_cost + self.heuristic(neighbour, goal_cell),
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    fn reconstruct(
+        &self,
+        came_from: &[usize],
+        start_cell: usize,
+        goal_cell: usize,
+        goal: Point3<f32>,
+    ) -> Vec<Point3<f32>> {
+        let mut path = vec![Point3::new(goal.x, self.floor, goal.z)];
+        let mut cell = came_from[goal_cell];
+        while cell != usize::MAX && cell != start_cell {
+            path.push(self.cell_center(cell));
+            cell = came_from[cell];
+        }
+        path.reverse();
+        path
+    }
+}
@@ -0,0 +1,280 @@
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::events::{EventSubscriber, GameEvent};
+use super::rng::RngStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Additive,
+    Alpha,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticlePreset {
+    MuzzleFlash,
+    Blood,
+    Smoke,
+    Explosion,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EmitterSettings {
+    pub color_start: [f32; 4],
+    pub color_end: [f32; 4],
+    pub size_start: f32,
+    pub size_end: f32,
+    pub speed: f32,
+    // Cone half angle in radians around the emit direction.
+    pub spread: f32,
+    pub lifetime: f32,
+    pub gravity: f32,
+    pub blend_mode: BlendMode,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EmitterKind {
+    Burst { count: u32 },
+    Continuous { rate: f32 },
+}
+
+pub struct Emitter {
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub kind: EmitterKind,
+    pub settings: EmitterSettings,
+    spawn_accumulator: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    pub age: f32,
+    pub settings: EmitterSettings,
+}
+
+#[derive(Default)]
+pub struct ParticleSystem {
+    emitters: Vec<Emitter>,
+    particles: Vec<Particle>,
+}
+
+impl ParticlePreset {
+    pub fn settings(&self) -> EmitterSettings {
+        match self {
+            Self::MuzzleFlash => EmitterSettings {
+                color_start: [1.0, 0.8, 0.4, 1.0],
+                color_end: [1.0, 0.3, 0.0, 0.0],
+                size_start: 0.06,
+                size_end: 0.01,
+                speed: 1.5,
+                spread: 0.4,
+                lifetime: 0.08,
+                gravity: 0.0,
+                blend_mode: BlendMode::Additive,
+            },
+            Self::Blood => EmitterSettings {
+                color_start: [0.5, 0.0, 0.0, 1.0],
+                color_end: [0.3, 0.0, 0.0, 0.0],
+                size_start: 0.03,
+                size_end: 0.02,
+                speed: 1.2,
+                spread: 0.8,
+                lifetime: 0.6,
+                gravity: 6.0,
+                blend_mode: BlendMode::Alpha,
+            },
+            Self::Smoke => EmitterSettings {
+                color_start: [0.4, 0.4, 0.4, 0.5],
+                color_end: [0.6, 0.6, 0.6, 0.0],
+                size_start: 0.1,
+                size_end: 0.5,
+                speed: 0.3,
+                spread: 0.3,
+                lifetime: 3.0,
+                gravity: -0.1,
+                blend_mode: BlendMode::Alpha,
+            },
+            Self::Explosion => EmitterSettings {
+                color_start: [1.0, 0.7, 0.2, 1.0],
+                color_end: [0.6, 0.1, 0.0, 0.0],
+                size_start: 0.15,
+                size_end: 0.4,
+                speed: 2.5,
+                spread: std::f32::consts::PI,
+                lifetime: 0.4,
+                gravity: 0.5,
+                blend_mode: BlendMode::Additive,
+            },
+        }
+    }
+}
+
+impl Emitter {
+    pub fn new(
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        kind: EmitterKind,
+        settings: EmitterSettings,
+    ) -> Self {
+        Self {
+            position,
+            direction,
+            kind,
+            settings,
+            spawn_accumulator: 0.0,
+        }
+    }
+}
+
+impl Particle {
+    pub fn progress(&self) -> f32 {
+        (self.age / self.settings.lifetime).clamp(0.0, 1.0)
+    }
+
+    pub fn size(&self) -> f32 {
+        let t = self.progress();
+        self.settings.size_start + (self.settings.size_end - self.settings.size_start) * t
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        let t = self.progress();
+        let start = self.settings.color_start;
+        let end = self.settings.color_end;
+        std::array::from_fn(|i| start[i] + (end[i] - start[i]) * t)
+    }
+}
+
+impl ParticleSystem {
+    pub const MAX_PARTICLES: usize = 4096;
+
+    pub fn add_emitter(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+
+    /// Adds an emitter that lets out `count` particles of `preset` at once.
+    pub fn add_burst(
+        &mut self,
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        preset: ParticlePreset,
+        count: u32,
+    ) {
+        self.add_emitter(Emitter::new(
+            position,
+            direction,
+            EmitterKind::Burst { count },
+            preset.settings(),
+        ));
+    }
+
+    pub fn spawn_burst(
+        &mut self,
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        settings: EmitterSettings,
+        count: u32,
+        rng: &mut RngStream,
+    ) {
+        for _ in 0..count {
+            self.spawn(position, direction, settings, rng);
+        }
+    }
+
+    fn spawn(
+        &mut self,
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        settings: EmitterSettings,
+        rng: &mut RngStream,
+    ) {
+        if self.particles.len() >= Self::MAX_PARTICLES {
+            return;
+        }
+        let velocity = rng.direction_in_cone(direction, settings.spread) * settings.speed;
+        self.particles.push(Particle {
+            position,
+            velocity,
+            age: 0.0,
+            settings,
+        });
+    }
+
+    pub fn update(&mut self, dt: Duration, rng: &mut RngStream) {
+        let dt = dt.as_secs_f32();
+        let mut spawns = vec![];
+        self.emitters.retain_mut(|emitter| match emitter.kind {
+            EmitterKind::Burst { count } => {
+                spawns.push((emitter.position, emitter.direction, emitter.settings, count));
+                false
+            }
+            EmitterKind::Continuous { rate } => {
+                emitter.spawn_accumulator += rate * dt;
+                let count = emitter.spawn_accumulator.floor();
+                emitter.spawn_accumulator -= count;
+                spawns.push((
+                    emitter.position,
+                    emitter.direction,
+                    emitter.settings,
+                    count as u32,
+                ));
+                true
+            }
+        });
+        for (position, direction, settings, count) in spawns {
+            self.spawn_burst(position, direction, settings, count, rng);
+        }
+
+        for particle in &mut self.particles {
+            particle.age += dt;
+            particle.velocity.y -= particle.settings.gravity * dt;
+            particle.position += particle.velocity * dt;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.settings.lifetime);
+    }
+
+    /// Additive particles first, then alpha blended particles sorted back to front.
+    /// Returns the particles and how many of them are additive.
+    pub fn sorted_particles(&self, camera_position: Point3<f32>) -> (Vec<&Particle>, usize) {
+        let (mut additive, mut alpha): (Vec<&Particle>, Vec<&Particle>) = self
+            .particles
+            .iter()
+            .partition(|particle| particle.settings.blend_mode == BlendMode::Additive);
+        alpha.sort_by(|a, b| {
+            let da = (a.position - camera_position).norm_squared();
+            let db = (b.position - camera_position).norm_squared();
+            db.total_cmp(&da)
+        });
+        let additive_count = additive.len();
+        additive.append(&mut alpha);
+        (additive, additive_count)
+    }
+}
+
+impl EventSubscriber for ParticleSystem {
+    fn on_event(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::WeaponFired {
+                muzzle, direction, ..
+            } => self.add_burst(muzzle, direction, ParticlePreset::MuzzleFlash, 12),
+            GameEvent::Hit {
+                point,
+                normal,
+                enemy: true,
+            } => self.add_burst(point, normal, ParticlePreset::Blood, 8),
+            GameEvent::Hit { point, normal, .. } => {
+                self.add_burst(point, normal, ParticlePreset::Smoke, 4)
+            }
+            GameEvent::Explosion { point, normal } => {
+                self.add_burst(point, normal, ParticlePreset::Explosion, 32);
+                self.add_burst(point, normal, ParticlePreset::Smoke, 12);
+            }
+            _ => {}
+        }
+    }
+}
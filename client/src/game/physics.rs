@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use nalgebra::Vector3;
+
+use super::{bounding_box::BoundingBox, collision_manager::CollisionManager};
+
+#[derive(Debug, Clone)]
+pub struct PhysicsBody {
+    pub hitbox: BoundingBox,
+    pub velocity: Vector3<f32>,
+    pub is_on_ground: bool,
+}
+
+pub struct Physics;
+
+impl Physics {
+    pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+    // Never simulate more than this much time in one frame, otherwise a long stall would
+    // make us spend the next frames catching up.
+    pub const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+    const GRAVITY: f32 = 6.0;
+    const STEP_HEIGHT: f32 = 0.3;
+    const EPSILON: f32 = 0.0001;
+
+    /// Integrates one tick for `body`, where `movement` is the desired horizontal velocity.
+    /// Returns how far the body actually moved.
+    pub fn step(
+        body: &mut PhysicsBody,
+        movement: Vector3<f32>,
+        jump_strength: Option<f32>,
+        dt: Duration,
+        collision_manager: &mut CollisionManager,
+    ) -> Vector3<f32> {
+        let dt = dt.as_secs_f32();
+        body.velocity.x = movement.x;
+        body.velocity.z = movement.z;
+        if let Some(jump_strength) = jump_strength
+            && body.is_on_ground
+        {
+            body.velocity.y += jump_strength;
+        }
+        body.velocity.y -= Self::GRAVITY * dt;
+
+        let intended_displacement = body.velocity * dt;
+        let start = body.hitbox.clone();
+        let mut actual_displacement =
+            collision_manager.move_player(&mut body.hitbox, intended_displacement);
+
+        if body.is_on_ground
+            && Self::horizontally_blocked(intended_displacement, actual_displacement)
+        {
+            let mut stepped = start.clone();
+            if let Some(stepped_displacement) =
+                Self::try_step_up(&mut stepped, intended_displacement, collision_manager)
+                && Self::horizontal_norm(stepped_displacement)
+                    > Self::horizontal_norm(actual_displacement) + Self::EPSILON
+            {
+                body.hitbox = stepped;
+                actual_displacement = stepped_displacement;
+            }
+        }
+
+        if intended_displacement.y < 0.0
+            && actual_displacement.y > intended_displacement.y + Self::EPSILON
+        {
+            body.velocity.y = 0.0;
+            body.is_on_ground = true;
+        } else {
+            body.is_on_ground = false;
+        }
+        body.velocity = actual_displacement / dt;
+        actual_displacement
+    }
+
+    fn horizontal_norm(v: Vector3<f32>) -> f32 {
+        Vector3::new(v.x, 0.0, v.z).norm()
+    }
+
+    fn horizontally_blocked(intended: Vector3<f32>, actual: Vector3<f32>) -> bool {
+        Self::horizontal_norm(intended) > Self::EPSILON
+            && Self::horizontal_norm(actual) + Self::EPSILON < Self::horizontal_norm(intended)
+    }
+
+    /// Lifts the box by the step height, moves it horizontally, then drops it back down.
+    /// Returns the total displacement if the box ended up standing on something.
+    fn try_step_up(
+        hitbox: &mut BoundingBox,
+        intended: Vector3<f32>,
+        collision_manager: &mut CollisionManager,
+    ) -> Option<Vector3<f32>> {
+        let up = collision_manager.move_player(hitbox, Vector3::new(0.0, Self::STEP_HEIGHT, 0.0));
+        let across =
+            collision_manager.move_player(hitbox, Vector3::new(intended.x, 0.0, intended.z));
+        let drop = -(up.y + Self::EPSILON) + intended.y.min(0.0);
+        let down = collision_manager.move_player(hitbox, Vector3::new(0.0, drop, 0.0));
+        let landed = down.y > drop + Self::EPSILON;
+        landed.then_some(up + across + down)
+    }
+}
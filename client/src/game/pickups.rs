@@ -0,0 +1,93 @@
+use nalgebra::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::bounding_box::BoundingBox;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PickupKind {
+    Medkit { health: u32 },
+    Ammo { rounds: u32 },
+    // Opens movers locked with the same name.
+    Key { name: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub position: Point3<f32>,
+    // Model showing the pickup in the map's models and its instance there, pickups drawn
+    // alike share one model.
+    pub model: Option<(usize, usize)>,
+}
+
+/// Items lying around the level, waiting for the player to walk over them.
+#[derive(Default)]
+pub struct PickupSystem {
+    pickups: Vec<Pickup>,
+}
+
+impl PickupKind {
+    pub fn description(&self) -> String {
+        match self {
+            Self::Medkit { health } => format!("Medkit +{health}"),
+            Self::Ammo { rounds } => format!("Ammo +{rounds}"),
+            Self::Key { name } => format!("Picked up the {name} key"),
+        }
+    }
+}
+
+impl Pickup {
+    // Half the width of the space in which the player picks the item up.
+    pub const RADIUS: f32 = 0.15;
+
+    pub fn bounds(&self) -> BoundingBox {
+        let extent = Vector3::repeat(Self::RADIUS);
+        BoundingBox {
+            top_left: Point3::new(
+                self.position.x - extent.x,
+                self.position.y + extent.y,
+                self.position.z - extent.z,
+            ),
+            bottom_right: Point3::new(
+                self.position.x + extent.x,
+                self.position.y - extent.y,
+                self.position.z + extent.z,
+            ),
+            collide_on_top: false,
+        }
+    }
+}
+
+impl PickupSystem {
+    pub fn add(&mut self, pickup: Pickup) {
+        self.pickups.push(pickup);
+    }
+
+    pub fn clear(&mut self) {
+        self.pickups.clear();
+    }
+
+    /// Model instances of the pickups still waiting to be collected.
+    pub fn models(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.pickups.iter().filter_map(|pickup| pickup.model)
+    }
+
+    /// Removes and returns the pickups touching `player_box` that `take` accepts. Anything
+    /// the player can't use right now, like a medkit at full health, stays where it is.
+    pub fn collect(
+        &mut self,
+        player_box: &BoundingBox,
+        mut take: impl FnMut(&PickupKind) -> bool,
+    ) -> Vec<Pickup> {
+        let mut collected = vec![];
+        self.pickups.retain(|pickup| {
+            let taken = player_box.is_colliding_with(&pickup.bounds()) && take(&pickup.kind);
+            if taken {
+                collected.push(pickup.clone());
+            }
+            !taken
+        });
+        collected
+    }
+}
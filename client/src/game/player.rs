@@ -1,30 +1,43 @@
 use std::time::Duration;
 
-use nalgebra::{Point3, Vector3};
+use nalgebra::{Isometry3, Point3, Vector3};
 
 use crate::camera::Camera;
 
 use super::{
-    bounding_box::BoundingBox, collision_manager::CollisionManager,
+    bounding_box::BoundingBox,
+    camera_settings::CameraSettings,
+    collision_manager::CollisionManager,
+    health::Health,
+    physics::{Physics, PhysicsBody},
     player_controller::PlayerController,
+    save_game::PlayerState,
+    transform::Transform,
 };
 
 pub struct Player {
-    position: Point3<f32>,
-    velocity: Vector3<f32>,
-    sensitivity: f32,
+    // Where the eyes are, stepped by the fixed tick.
+    transform: Transform,
+    pub camera_settings: CameraSettings,
+    // Mouse movement after smoothing, carried over between frames.
+    smoothed_mouse: (f32, f32),
+    // How far into the zoom the view is, from 0 to 1.
+    zoom: f32,
     speed: f32,
     jump_strength: f32,
-    hitbox: BoundingBox,
-    is_on_ground: bool,
+    body: PhysicsBody,
     pub camera: Camera,
     yaw: f32,
     pitch: f32,
+    pub health: Health,
+    keys: Vec<String>,
 }
 
 impl Player {
-    const GRAVITY: f32 = 0.1;
-    const SLOW_DOWN: f32 = 0.0;
+    pub const MAX_HEALTH: u32 = 100;
+    const NOCLIP_SPEED_MULTIPLIER: f32 = 2.0;
+    // Seconds to zoom all the way in or out.
+    const ZOOM_TIME: f32 = 0.15;
 
     pub fn new(
         sensitivity: f32,
@@ -36,56 +49,190 @@ impl Player {
     ) -> Self {
         let position = camera.position;
         Self {
-            position,
-            velocity: Vector3::zeros(),
-            sensitivity,
+            transform: Transform::new(Isometry3::translation(position.x, position.y, position.z)),
+            camera_settings: CameraSettings {
+                sensitivity,
+                ..Default::default()
+            },
+            smoothed_mouse: (0.0, 0.0),
+            zoom: 0.0,
             speed,
             jump_strength,
-            hitbox: BoundingBox {
-                top_left: Point3::new(
-                    position.x - (hitbox_width / 2.0),
-                    position.y,
-                    position.z - (hitbox_width / 2.0),
-                ),
-                bottom_right: Point3::new(
-                    position.x + (hitbox_width / 2.0),
-                    position.y - hitbox_height,
-                    position.z + (hitbox_width / 2.0),
-                ),
-                collide_on_top: false,
+            body: PhysicsBody {
+                hitbox: BoundingBox {
+                    top_left: Point3::new(
+                        position.x - (hitbox_width / 2.0),
+                        position.y,
+                        position.z - (hitbox_width / 2.0),
+                    ),
+                    bottom_right: Point3::new(
+                        position.x + (hitbox_width / 2.0),
+                        position.y - hitbox_height,
+                        position.z + (hitbox_width / 2.0),
+                    ),
+                    collide_on_top: false,
+                },
+                velocity: Vector3::zeros(),
+                is_on_ground: false,
             },
-            is_on_ground: false,
             camera,
             pitch: 0.0,
             yaw: 0.0,
+            health: Health::new(Self::MAX_HEALTH),
+            keys: vec![],
         }
     }
 
-    pub fn update(
+    pub fn adjust_sensitivity(&mut self, delta: f32) -> f32 {
+        self.set_sensitivity(self.camera_settings.sensitivity + delta)
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) -> f32 {
+        self.camera_settings.set_sensitivity(sensitivity)
+    }
+
+    pub fn sensitivity(&self) -> f32 {
+        self.camera_settings.sensitivity
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn save_state(&self) -> PlayerState {
+        PlayerState {
+            camera: self.camera.clone(),
+            yaw: self.yaw,
+            pitch: self.pitch,
+            velocity: self.body.velocity,
+            sensitivity: self.camera_settings.sensitivity,
+            health: self.health.current(),
+        }
+    }
+
+    pub fn restore_state(&mut self, state: &PlayerState) {
+        let delta = state.camera.position - self.camera.position;
+        self.body.hitbox.move_by(delta);
+        self.body.velocity = state.velocity;
+        self.transform.teleport(state.camera.position);
+        // Keep the current aspect ratio, the window may have changed size since saving.
+        let aspect = self.camera.aspect;
+        self.camera = state.camera.clone();
+        self.camera.aspect = aspect;
+        self.yaw = state.yaw;
+        self.pitch = state.pitch;
+        self.camera_settings.sensitivity = state.sensitivity;
+        self.health.set(state.health);
+    }
+
+    /// Puts the player back at `position` with full health and nothing picked up.
+    pub fn respawn(&mut self, position: Point3<f32>) {
+        let delta = position - self.camera.position;
+        self.carry(delta);
+        self.transform.teleport(position);
+        self.body.velocity = Vector3::zeros();
+        self.health.reset();
+        self.keys.clear();
+    }
+
+    /// Returns false when the player already had the key.
+    pub fn give_key(&mut self, name: &str) -> bool {
+        if self.has_key(name) {
+            return false;
+        }
+        self.keys.push(name.to_string());
+        true
+    }
+
+    pub fn has_key(&self, name: &str) -> bool {
+        self.keys.iter().any(|key| key == name)
+    }
+
+    pub fn hitbox(&self) -> &BoundingBox {
+        &self.body.hitbox
+    }
+
+    /// Moves the player along with whatever it is standing on or being pushed by.
+    pub fn carry(&mut self, delta: Vector3<f32>) {
+        self.body.hitbox.move_by(delta);
+        self.camera.move_camera(delta);
+        self.transform.translate(delta);
+    }
+
+    /// Applies mouse look and eases the field of view towards the zoom. Runs once per
+    /// rendered frame.
+    pub fn look(&mut self, dt: Duration, player_controller: &mut PlayerController) {
+        let settings = self.camera_settings;
+        let step = dt.as_secs_f32() / Self::ZOOM_TIME;
+        self.zoom = if player_controller.is_zoom_pressed {
+            (self.zoom + step).min(1.0)
+        } else {
+            (self.zoom - step).max(0.0)
+        };
+        let t = self.zoom * self.zoom * (3.0 - 2.0 * self.zoom);
+        let fov = settings.fov + (settings.zoom_fov - settings.fov) * t;
+        self.camera.fovy = fov.to_radians();
+
+        let (dx, dy) = player_controller.delta_mouse_pos.take().unwrap_or_default();
+        let dy = if settings.invert_y { -dy } else { dy };
+        let keep = settings.smoothing;
+        self.smoothed_mouse = (
+            self.smoothed_mouse.0 * keep + dx * (1.0 - keep),
+            self.smoothed_mouse.1 * keep + dy * (1.0 - keep),
+        );
+        let (dx, dy) = self.smoothed_mouse;
+        if dx == 0.0 && dy == 0.0 {
+            return;
+        }
+        let sens = settings.sensitivity * dt.as_secs_f32();
+        self.yaw -= dx * sens;
+        self.pitch -= dy * sens;
+        let max_pitch = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.pitch = self.pitch.clamp(-max_pitch, max_pitch);
+        self.camera.rotate_camera(self.pitch, self.yaw);
+    }
+
+    pub fn begin_tick(&mut self) {
+        self.transform.begin_tick();
+    }
+
+    /// The camera moved along with the player's eyes to `alpha` of the way into the next tick,
+    /// for drawing. The orbit camera doesn't move the player, so it is left where it is.
+    pub fn interpolated_camera(&self, alpha: f32) -> Camera {
+        let mut camera = self.camera.clone();
+        let eye = self.transform.interpolated(alpha).translation.vector;
+        camera.move_camera(eye - self.transform.position().coords);
+        camera
+    }
+
+    /// Puts the camera back at the player's eyes, facing where the player looks.
+    pub fn reset_camera(&mut self) {
+        let delta = self.transform.position() - self.camera.position;
+        self.camera.move_camera(delta);
+        self.camera.rotate_camera(self.pitch, self.yaw);
+    }
+
+    /// Where the player looks, and the directions to its left and straight ahead along the
+    /// ground.
+    fn move_axes(&self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        let looking_at = (self.camera.target - self.camera.position).normalize();
+        let left = self.camera.up.cross(&looking_at).normalize();
+        let forward = left.cross(&self.camera.up).normalize();
+        (looking_at, left, forward)
+    }
+
+    /// Walks under gravity for one fixed simulation tick.
+    pub fn tick(
         &mut self,
         dt: Duration,
         collision_manager: &mut CollisionManager,
-        player_controller: &mut PlayerController,
+        player_controller: &PlayerController,
     ) {
-        let sens = self.sensitivity * dt.as_secs_f32();
-        self.velocity.x *= Self::SLOW_DOWN;
-        self.velocity.z *= Self::SLOW_DOWN;
-        if let Some(delta_mouse_pos) = player_controller.delta_mouse_pos {
-            self.yaw -= delta_mouse_pos.0 * sens;
-            self.pitch -= delta_mouse_pos.1 * sens;
-            player_controller.delta_mouse_pos = None;
-            let max_pitch = std::f32::consts::FRAC_PI_2 - 0.01;
-            self.pitch = self.pitch.clamp(-max_pitch, max_pitch);
-            self.camera.rotate_camera(self.pitch, self.yaw);
-        }
-
-        let camera_position = self.camera.position;
-        let camera_target = self.camera.target;
-        let camera_up = self.camera.up;
-
-        let looking_at = (camera_target - camera_position).normalize();
-        let left = camera_up.cross(&looking_at).normalize();
-        let forward = left.cross(&camera_up).normalize();
+        let (_, left, forward) = self.move_axes();
         let mut delta_velocity = Vector3::zeros();
         if player_controller.is_w_pressed {
             delta_velocity += forward;
@@ -99,27 +246,52 @@ impl Player {
         if player_controller.is_d_pressed {
             delta_velocity -= left;
         }
-        if player_controller.is_space_pressed && self.is_on_ground {
-            self.velocity.y += self.jump_strength;
-        }
-        self.velocity.y -= Self::GRAVITY;
         let mut movement_velocity = Vector3::zeros();
         if let Some(normalized_delta_velocity) = delta_velocity.try_normalize(0.0) {
             movement_velocity = normalized_delta_velocity * self.speed;
         }
-        self.velocity.x += movement_velocity.x;
-        self.velocity.z += movement_velocity.z;
-        let intended_displacement = self.velocity * dt.as_secs_f32();
-        let actual_displacement =
-            collision_manager.move_player(&mut self.hitbox, intended_displacement);
-        if actual_displacement.y == 0.0 && intended_displacement.y < 0.0 {
-            self.velocity.y = 0.0;
-            self.is_on_ground = true;
-        } else {
-            self.is_on_ground = false;
-        }
-        self.velocity = actual_displacement / dt.as_secs_f32();
+        let jump = player_controller
+            .is_space_pressed
+            .then_some(self.jump_strength);
+        let actual_displacement = Physics::step(
+            &mut self.body,
+            movement_velocity,
+            jump,
+            dt,
+            collision_manager,
+        );
         self.camera.move_camera(actual_displacement);
-        self.position += actual_displacement;
+        self.transform.translate(actual_displacement);
+    }
+
+    /// Flies through geometry along the view direction for one fixed simulation tick,
+    /// ignoring gravity.
+    pub fn fly(&mut self, dt: Duration, player_controller: &PlayerController) {
+        let (looking_at, left, _) = self.move_axes();
+        let mut direction = Vector3::zeros();
+        if player_controller.is_w_pressed {
+            direction += looking_at;
+        }
+        if player_controller.is_s_pressed {
+            direction -= looking_at;
+        }
+        if player_controller.is_a_pressed {
+            direction += left;
+        }
+        if player_controller.is_d_pressed {
+            direction -= left;
+        }
+        if player_controller.is_space_pressed {
+            direction += Vector3::y();
+        }
+        let Some(direction) = direction.try_normalize(0.0) else {
+            return;
+        };
+        let displacement =
+            direction * self.speed * Self::NOCLIP_SPEED_MULTIPLIER * dt.as_secs_f32();
+        self.body.hitbox.move_by(displacement);
+        self.body.velocity = Vector3::zeros();
+        self.camera.move_camera(displacement);
+        self.transform.translate(displacement);
     }
 }
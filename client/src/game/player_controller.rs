@@ -1,4 +1,9 @@
-use winit::{event::ElementState, event_loop::ActiveEventLoop, keyboard::KeyCode};
+use winit::{
+    event::{ElementState, MouseButton},
+    keyboard::KeyCode,
+};
+
+use crate::settings::KeyBindings;
 
 #[derive(Default)]
 pub struct PlayerController {
@@ -7,7 +12,12 @@ pub struct PlayerController {
     pub is_a_pressed: bool,
     pub is_d_pressed: bool,
     pub is_space_pressed: bool,
+    pub is_fire_pressed: bool,
+    pub is_reload_pressed: bool,
+    pub is_zoom_pressed: bool,
     pub debug_enabled: bool,
+    // Weapon slot picked with the number keys, taken by the renderer.
+    pub weapon_slot: Option<usize>,
     pub delta_mouse_pos: Option<(f32, f32)>,
 }
 
@@ -16,41 +26,47 @@ impl PlayerController {
         &mut self,
         key: KeyCode,
         state: ElementState,
-        event_loop: &ActiveEventLoop,
+        keys: &KeyBindings,
     ) -> bool {
-        match key {
-            KeyCode::Escape => {
-                event_loop.exit();
-                false
-            }
-            KeyCode::KeyW => {
-                self.is_w_pressed = state.is_pressed();
-                true
-            }
-            KeyCode::KeyS => {
-                self.is_s_pressed = state.is_pressed();
-                true
-            }
-            KeyCode::KeyD => {
-                self.is_d_pressed = state.is_pressed();
-                true
+        let held = match key {
+            _ if key == keys.forward => &mut self.is_w_pressed,
+            _ if key == keys.back => &mut self.is_s_pressed,
+            _ if key == keys.right => &mut self.is_d_pressed,
+            _ if key == keys.left => &mut self.is_a_pressed,
+            _ if key == keys.debug => &mut self.debug_enabled,
+            _ if key == keys.jump => &mut self.is_space_pressed,
+            _ if key == keys.reload => &mut self.is_reload_pressed,
+            KeyCode::Digit1 | KeyCode::Digit2 => {
+                if state.is_pressed() {
+                    self.weapon_slot = Some(if key == KeyCode::Digit1 { 0 } else { 1 });
+                }
+                return true;
             }
-            KeyCode::KeyA => {
-                self.is_a_pressed = state.is_pressed();
-                true
-            }
-            KeyCode::KeyG => {
-                self.debug_enabled = state.is_pressed();
+            _ => return false,
+        };
+        *held = state.is_pressed();
+        true
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, state: ElementState) -> bool {
+        match button {
+            MouseButton::Left => {
+                self.is_fire_pressed = state.is_pressed();
                 true
             }
-            KeyCode::Space => {
-                self.is_space_pressed = state.is_pressed();
+            MouseButton::Right => {
+                self.is_zoom_pressed = state.is_pressed();
                 true
             }
             _ => false,
         }
     }
 
+    /// Forgets every held input, so nothing stays pressed while input goes elsewhere.
+    pub fn release_all(&mut self) {
+        *self = Self::default();
+    }
+
     pub fn handle_mouse(&mut self, delta: (f64, f64)) {
         let dx = delta.0 as f32;
         let dy = delta.1 as f32;
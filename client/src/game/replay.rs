@@ -0,0 +1,135 @@
+use std::{error::Error, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::platform;
+
+use super::player_controller::PlayerController;
+use super::save_game::PlayerState;
+
+/// Inputs of one frame, compact since a demo holds thousands of them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct InputFrame {
+    // Seconds the frame lasted. The fixed ticks follow from these.
+    pub dt: f32,
+    // Held buttons, one bit each in the order `InputFrame::buttons` lists them.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub buttons: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mouse: Option<(f32, f32)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weapon_slot: Option<usize>,
+}
+
+/// A recorded play session, from the moment the level was restarted. Inputs are recorded once
+/// per rendered frame rather than per fixed tick. Playing it back feeds the same inputs and
+/// frame times through the simulation, which runs the same ticks from them, so it ends up
+/// where it did when recorded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub map_file: String,
+    // Where the player started, facing and sensitivity included.
+    pub player: PlayerState,
+    // Seed for gameplay randomness, so random events repeat on playback.
+    pub seed: u64,
+    pub frames: Vec<InputFrame>,
+}
+
+/// What happens to the inputs each frame.
+#[derive(Default)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    Recording(Replay),
+    Playing {
+        replay: Replay,
+        frame: usize,
+    },
+}
+
+fn is_zero(buttons: &u8) -> bool {
+    *buttons == 0
+}
+
+impl InputFrame {
+    const BUTTONS: usize = 8;
+
+    fn buttons(player_controller: &PlayerController) -> [bool; Self::BUTTONS] {
+        [
+            player_controller.is_w_pressed,
+            player_controller.is_s_pressed,
+            player_controller.is_a_pressed,
+            player_controller.is_d_pressed,
+            player_controller.is_space_pressed,
+            player_controller.is_fire_pressed,
+            player_controller.is_reload_pressed,
+            player_controller.is_zoom_pressed,
+        ]
+    }
+
+    /// Reads the frame's inputs before anything consumes them.
+    pub fn capture(dt: Duration, player_controller: &PlayerController) -> Self {
+        let buttons = Self::buttons(player_controller)
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &held)| bits | ((held as u8) << i));
+        Self {
+            dt: dt.as_secs_f32(),
+            buttons,
+            mouse: player_controller.delta_mouse_pos,
+            weapon_slot: player_controller.weapon_slot,
+        }
+    }
+
+    /// Overwrites every input the simulation reads with the recorded ones.
+    pub fn apply(&self, player_controller: &mut PlayerController) {
+        let held = |i: usize| self.buttons & (1 << i) != 0;
+        player_controller.is_w_pressed = held(0);
+        player_controller.is_s_pressed = held(1);
+        player_controller.is_a_pressed = held(2);
+        player_controller.is_d_pressed = held(3);
+        player_controller.is_space_pressed = held(4);
+        player_controller.is_fire_pressed = held(5);
+        player_controller.is_reload_pressed = held(6);
+        player_controller.is_zoom_pressed = held(7);
+        player_controller.delta_mouse_pos = self.mouse;
+        player_controller.weapon_slot = self.weapon_slot;
+    }
+
+    /// Frames loaded through `Replay::from_file` always have one.
+    pub fn duration(&self) -> Duration {
+        Duration::try_from_secs_f32(self.dt).unwrap_or_default()
+    }
+}
+
+impl Replay {
+    pub const DEFAULT_FILE: &str = "replays/demo.json";
+
+    pub fn new(map_file: String, player: PlayerState, seed: u64) -> Self {
+        Self {
+            map_file,
+            player,
+            seed,
+            frames: vec![],
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(InputFrame::duration).sum()
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let json = platform::read_to_string(path)?;
+        let replay: Self = serde_json::from_str(&json)?;
+        for (i, frame) in replay.frames.iter().enumerate() {
+            Duration::try_from_secs_f32(frame.dt)
+                .map_err(|e| format!("Frame {i} has a bad frame time {}: {e}", frame.dt))?;
+        }
+        Ok(replay)
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        platform::write(path, &serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use nalgebra::Vector3;
+
+/// Gameplay systems drawing random numbers, each from its own stream so one system drawing
+/// more or less often doesn't shift what the others get.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngSystem {
+    Weapons,
+    Enemies,
+    Particles,
+    Decals,
+}
+
+/// One system's sequence of random numbers. SplitMix64 is spelled out here rather than taken
+/// from `rand`, whose generators may change between versions, so any build seeded the same way
+/// draws the same numbers.
+#[derive(Debug, Clone)]
+pub struct RngStream {
+    state: u64,
+}
+
+/// Seeded randomness for everything the simulation does, so replays and networked games
+/// repeat exactly when started from the same seed. Cosmetic randomness that never feeds back
+/// into gameplay, like shader noise, can keep using `rand`.
+#[derive(Debug, Clone)]
+pub struct GameRng {
+    seed: u64,
+    streams: [RngStream; 4],
+}
+
+impl RngSystem {
+    const ALL: [Self; 4] = [Self::Weapons, Self::Enemies, Self::Particles, Self::Decals];
+}
+
+impl RngStream {
+    const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    /// Stream `index` of `seed`, far enough apart from the other streams to never overlap.
+    pub fn new(seed: u64, index: u64) -> Self {
+        let mut stream = Self {
+            state: seed ^ (index + 1).wrapping_mul(0xD1B5_4A32_D192_ED03),
+        };
+        stream.state = stream.next_u64();
+        stream
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(Self::GOLDEN_GAMMA);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`, from the top 24 bits so every value is exact in an f32.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Uniform in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    pub fn angle(&mut self) -> f32 {
+        self.next_f32() * std::f32::consts::TAU
+    }
+
+    /// A unit vector within `spread` radians of `direction`, spread evenly over the cap.
+    pub fn direction_in_cone(&mut self, direction: Vector3<f32>, spread: f32) -> Vector3<f32> {
+        let axis = direction.try_normalize(0.0).unwrap_or(Vector3::y());
+        let helper = if axis.x.abs() < 0.9 {
+            Vector3::x()
+        } else {
+            Vector3::z()
+        };
+        let tangent = axis.cross(&helper).normalize();
+        let bitangent = axis.cross(&tangent);
+        let angle = self.angle();
+        let cos_theta = 1.0 - self.next_f32() * (1.0 - spread.cos());
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        axis * cos_theta + (tangent * angle.cos() + bitangent * angle.sin()) * sin_theta
+    }
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: RngSystem::ALL.map(|system| RngStream::new(seed, system as u64)),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stream(&mut self, system: RngSystem) -> &mut RngStream {
+        &mut self.streams[system as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Replays and demos recorded by earlier builds only play back while these hold.
+    #[test]
+    fn streams_draw_fixed_values() {
+        let mut stream = RngStream::new(0, 0);
+        assert_eq!(stream.next_u64(), 0x98BC_9B3A_9F64_DA94);
+        assert_eq!(stream.next_f32(), 8_297_832.0 / 16_777_216.0);
+
+        let mut stream = RngStream::new(1234, RngSystem::Particles as u64);
+        assert_eq!(stream.next_u64(), 0x6F01_FDF6_BD84_6086);
+        assert_eq!(stream.next_f32(), 14_810_090.0 / 16_777_216.0);
+    }
+
+    // The reference SplitMix64 sequence starting from a zero state.
+    #[test]
+    fn matches_splitmix64() {
+        let mut stream = RngStream { state: 0 };
+        assert_eq!(stream.next_u64(), 0xE220_A839_7B1D_CDAF);
+    }
+}
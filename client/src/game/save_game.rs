@@ -0,0 +1,49 @@
+use std::{error::Error, path::Path};
+
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{Camera, light::Light};
+use crate::platform;
+
+use super::player::Player;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerState {
+    pub camera: Camera,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub velocity: Vector3<f32>,
+    pub sensitivity: f32,
+    // Saves from before health existed load with full health.
+    #[serde(default = "PlayerState::default_health")]
+    pub health: u32,
+}
+
+impl PlayerState {
+    fn default_health() -> u32 {
+        Player::MAX_HEALTH
+    }
+}
+
+/// Runtime state written to disk so a play session can be resumed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveGame {
+    pub map_file: String,
+    pub player: PlayerState,
+    pub lights: Vec<Light>,
+}
+
+impl SaveGame {
+    pub const QUICKSAVE: &str = "saves/quicksave.json";
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let json = platform::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        platform::write(path, &serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
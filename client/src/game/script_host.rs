@@ -0,0 +1,319 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+use std::time::Duration;
+
+use log::{error, info};
+use nalgebra::{Point3, Vector3};
+use rhai::{AST, Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+
+use crate::platform;
+
+use super::events::GameEvent;
+use super::particles::ParticlePreset;
+
+/// What a script asked the game to do, applied by the renderer once the hook returns.
+#[derive(Debug, Clone)]
+pub enum ScriptCommand {
+    SpawnLight {
+        position: Point3<f32>,
+        intensity: f32,
+    },
+    MoveLight {
+        id: u32,
+        position: Point3<f32>,
+    },
+    SetLightIntensity {
+        id: u32,
+        intensity: f32,
+    },
+    SpawnParticles {
+        preset: ParticlePreset,
+        position: Point3<f32>,
+    },
+    PlaySound(String),
+    ShowMessage(String),
+}
+
+/// Entry points a level script may define, each is optional.
+pub enum ScriptHook<'a> {
+    // fn on_start()
+    Start,
+    // fn on_update(dt)
+    Update(Duration),
+    // fn on_trigger(name)
+    Trigger(&'a str),
+    // fn on_event(event)
+    Event(&'a GameEvent),
+}
+
+#[derive(Default)]
+struct ScriptBridge {
+    commands: Vec<ScriptCommand>,
+    player_position: [f32; 3],
+    // Lights including the ones spawned during this hook, so spawn_light can return ids.
+    light_count: usize,
+    time: f32,
+}
+
+/// A level's Rhai script. Bindings only queue commands, so scripts never hold on to game
+/// state and a failing hook just logs its error.
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    bridge: Rc<RefCell<ScriptBridge>>,
+    path: String,
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+impl ScriptHook<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Start => "on_start",
+            Self::Update(_) => "on_update",
+            Self::Trigger(_) => "on_trigger",
+            Self::Event(_) => "on_event",
+        }
+    }
+}
+
+impl ScriptHost {
+    // Stops runaway loops from freezing the game.
+    const MAX_OPERATIONS: u64 = 1_000_000;
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let bridge = Rc::new(RefCell::new(ScriptBridge::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(Self::MAX_OPERATIONS);
+        let script_path = path.to_string();
+        engine.on_print(move |text| info!("{script_path}: {text}"));
+        Self::register_bindings(&mut engine, &bridge);
+
+        let ast = engine.compile(platform::read_to_string(path)?)?;
+        let mut scope = Scope::new();
+        // Top level statements run once, for script globals.
+        engine.run_ast_with_scope(&mut scope, &ast)?;
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            bridge,
+            path: path.to_string(),
+        })
+    }
+
+    fn register_bindings(engine: &mut Engine, bridge: &Rc<RefCell<ScriptBridge>>) {
+        let b = bridge.clone();
+        engine.register_fn(
+            "spawn_light",
+            move |x: Dynamic, y: Dynamic, z: Dynamic, intensity: Dynamic| -> ScriptResult<i64> {
+                let mut bridge = b.borrow_mut();
+                bridge.commands.push(ScriptCommand::SpawnLight {
+                    position: Self::point(x, y, z)?,
+                    intensity: Self::number(intensity)?,
+                });
+                bridge.light_count += 1;
+                Ok(bridge.light_count as i64 - 1)
+            },
+        );
+        let b = bridge.clone();
+        engine.register_fn(
+            "move_light",
+            move |id: i64, x: Dynamic, y: Dynamic, z: Dynamic| -> ScriptResult<()> {
+                b.borrow_mut().commands.push(ScriptCommand::MoveLight {
+                    id: Self::id(id)?,
+                    position: Self::point(x, y, z)?,
+                });
+                Ok(())
+            },
+        );
+        let b = bridge.clone();
+        engine.register_fn(
+            "set_light_intensity",
+            move |id: i64, intensity: Dynamic| -> ScriptResult<()> {
+                b.borrow_mut()
+                    .commands
+                    .push(ScriptCommand::SetLightIntensity {
+                        id: Self::id(id)?,
+                        intensity: Self::number(intensity)?,
+                    });
+                Ok(())
+            },
+        );
+        let b = bridge.clone();
+        engine.register_fn(
+            "spawn_particles",
+            move |preset: &str, x: Dynamic, y: Dynamic, z: Dynamic| -> ScriptResult<()> {
+                // Same names as emitter presets in the map file.
+                let preset: ParticlePreset =
+                    serde_json::from_value(serde_json::Value::String(preset.to_string()))
+                        .map_err(|_| format!("Unknown particle preset {preset}"))?;
+                b.borrow_mut().commands.push(ScriptCommand::SpawnParticles {
+                    preset,
+                    position: Self::point(x, y, z)?,
+                });
+                Ok(())
+            },
+        );
+        let b = bridge.clone();
+        engine.register_fn("play_sound", move |name: &str| {
+            b.borrow_mut()
+                .commands
+                .push(ScriptCommand::PlaySound(name.to_string()));
+        });
+        let b = bridge.clone();
+        engine.register_fn("show_message", move |text: &str| {
+            b.borrow_mut()
+                .commands
+                .push(ScriptCommand::ShowMessage(text.to_string()));
+        });
+        let b = bridge.clone();
+        engine.register_fn("player_position", move || -> Array {
+            b.borrow()
+                .player_position
+                .iter()
+                .map(|v| Dynamic::from_float(*v as f64))
+                .collect()
+        });
+        let b = bridge.clone();
+        engine.register_fn("time", move || b.borrow().time as f64);
+    }
+
+    /// Accepts both integer and float script values.
+    fn number(value: Dynamic) -> ScriptResult<f32> {
+        if let Ok(value) = value.as_float() {
+            return Ok(value as f32);
+        }
+        value
+            .as_int()
+            .map(|value| value as f32)
+            .map_err(|type_name| format!("Expected a number, got {type_name}").into())
+    }
+
+    fn point(x: Dynamic, y: Dynamic, z: Dynamic) -> ScriptResult<Point3<f32>> {
+        Ok(Point3::new(
+            Self::number(x)?,
+            Self::number(y)?,
+            Self::number(z)?,
+        ))
+    }
+
+    fn id(id: i64) -> ScriptResult<u32> {
+        u32::try_from(id).map_err(|_| format!("Invalid light id {id}").into())
+    }
+
+    fn vector(vector: Vector3<f32>) -> Dynamic {
+        Dynamic::from_array(
+            vector
+                .iter()
+                .map(|v| Dynamic::from_float(*v as f64))
+                .collect(),
+        )
+    }
+
+    /// An event as scripts see it, a map with its name and fields. Points and directions are
+    /// [x, y, z] arrays.
+    fn event_map(event: &GameEvent) -> Map {
+        let mut fields: Vec<(&str, Dynamic)> = vec![("name", event.name().into())];
+        match event {
+            GameEvent::WeaponFired {
+                weapon,
+                muzzle,
+                direction,
+            } => fields.extend([
+                ("weapon", (*weapon).into()),
+                ("position", Self::vector(muzzle.coords)),
+                ("direction", Self::vector(*direction)),
+            ]),
+            GameEvent::WeaponSelected { weapon } => fields.push(("weapon", (*weapon).into())),
+            GameEvent::Hit {
+                point,
+                normal,
+                enemy,
+            } => fields.extend([
+                ("position", Self::vector(point.coords)),
+                ("normal", Self::vector(*normal)),
+                ("enemy", Dynamic::from_bool(*enemy)),
+            ]),
+            GameEvent::Explosion { point, normal } => fields.extend([
+                ("position", Self::vector(point.coords)),
+                ("normal", Self::vector(*normal)),
+            ]),
+            GameEvent::MoverActivated { trigger } => {
+                fields.push(("trigger", trigger.as_str().into()))
+            }
+            GameEvent::MoverLocked { key } => fields.push(("key", key.as_str().into())),
+            GameEvent::EnemyDied { position } => {
+                fields.push(("position", Self::vector(position.coords)))
+            }
+            GameEvent::PlayerHurt { amount, source } => fields.extend([
+                ("amount", Dynamic::from_int(*amount as i64)),
+                ("position", Self::vector(source.coords)),
+            ]),
+            GameEvent::PlayerDied => {}
+            GameEvent::PickupCollected { kind } => fields.push(("item", kind.description().into())),
+        }
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.into(), value))
+            .collect()
+    }
+
+    /// Runs `hook` if the script defines it and returns the commands it queued.
+    pub fn call(
+        &mut self,
+        hook: ScriptHook,
+        player_position: Point3<f32>,
+        light_count: usize,
+    ) -> Vec<ScriptCommand> {
+        {
+            let mut bridge = self.bridge.borrow_mut();
+            bridge.player_position = player_position.into();
+            bridge.light_count = light_count;
+            if let ScriptHook::Update(dt) = hook {
+                bridge.time += dt.as_secs_f32();
+            }
+        }
+        let name = hook.name();
+        let arity = match hook {
+            ScriptHook::Start => 0,
+            ScriptHook::Update(_) | ScriptHook::Trigger(_) | ScriptHook::Event(_) => 1,
+        };
+        let defined = self
+            .ast
+            .iter_functions()
+            .any(|function| function.name == name && function.params.len() == arity);
+        if defined {
+            let result = match hook {
+                ScriptHook::Start => {
+                    self.engine
+                        .call_fn::<Dynamic>(&mut self.scope, &self.ast, name, ())
+                }
+                ScriptHook::Update(dt) => self.engine.call_fn::<Dynamic>(
+                    &mut self.scope,
+                    &self.ast,
+                    name,
+                    (dt.as_secs_f64(),),
+                ),
+                ScriptHook::Trigger(trigger) => self.engine.call_fn::<Dynamic>(
+                    &mut self.scope,
+                    &self.ast,
+                    name,
+                    (trigger.to_string(),),
+                ),
+                ScriptHook::Event(event) => self.engine.call_fn::<Dynamic>(
+                    &mut self.scope,
+                    &self.ast,
+                    name,
+                    (Self::event_map(event),),
+                ),
+            };
+            if let Err(e) = result {
+                error!("{} {name} failed {e}", self.path);
+            }
+        }
+        std::mem::take(&mut self.bridge.borrow_mut().commands)
+    }
+}
@@ -0,0 +1,62 @@
+use nalgebra::{Isometry3, Point3, Translation3, Vector3};
+
+/// Where something moved by the fixed simulation tick stands after the last tick and the one
+/// before it. Frames land in between ticks, so drawing the blend of the two by how far along
+/// the next tick is moves things smoothly instead of in tick sized steps.
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    previous: Isometry3<f32>,
+    current: Isometry3<f32>,
+    // Set whenever it moves, cleared once it has been drawn at rest.
+    dirty: bool,
+}
+
+impl Transform {
+    pub fn new(isometry: Isometry3<f32>) -> Self {
+        Self {
+            previous: isometry,
+            current: isometry,
+            dirty: true,
+        }
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        self.current.translation.vector.into()
+    }
+
+    /// Called before each tick, so what the tick does is what frames blend across.
+    pub fn begin_tick(&mut self) {
+        self.previous = self.current;
+    }
+
+    pub fn translate(&mut self, delta: Vector3<f32>) {
+        if delta == Vector3::zeros() {
+            return;
+        }
+        self.current.translation.vector += delta;
+        self.dirty = true;
+    }
+
+    /// Jumps to `position` without blending, for respawns and loading saves.
+    pub fn teleport(&mut self, position: Point3<f32>) {
+        self.current.translation = Translation3::from(position.coords);
+        self.previous = self.current;
+        self.dirty = true;
+    }
+
+    /// The blend `alpha` of the way from the previous tick to the current one.
+    pub fn interpolated(&self, alpha: f32) -> Isometry3<f32> {
+        self.previous
+            .lerp_slerp(&self.current, alpha.clamp(0.0, 1.0))
+    }
+
+    /// True when it has to be drawn again: every frame while moving between ticks, and once
+    /// more after it settles.
+    pub fn take_dirty(&mut self) -> bool {
+        let dirty = self.dirty;
+        if self.previous == self.current {
+            self.dirty = false;
+        }
+        dirty
+    }
+}
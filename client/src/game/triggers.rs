@@ -0,0 +1,53 @@
+use super::bounding_box::BoundingBox;
+
+#[derive(Debug, Clone)]
+pub struct TriggerVolume {
+    pub name: String,
+    pub bounds: BoundingBox,
+    // Only fires the first time the player walks in.
+    pub once: bool,
+    inside: bool,
+    fired: bool,
+}
+
+/// Axis aligned volumes that fire their name when the player enters them.
+#[derive(Default)]
+pub struct TriggerSystem {
+    volumes: Vec<TriggerVolume>,
+}
+
+impl TriggerVolume {
+    pub fn new(name: String, bounds: BoundingBox, once: bool) -> Self {
+        Self {
+            name,
+            bounds,
+            once,
+            inside: false,
+            fired: false,
+        }
+    }
+}
+
+impl TriggerSystem {
+    pub fn add(&mut self, volume: TriggerVolume) {
+        self.volumes.push(volume);
+    }
+
+    pub fn clear(&mut self) {
+        self.volumes.clear();
+    }
+
+    /// Names of the volumes the player entered since the last update.
+    pub fn update(&mut self, player_box: &BoundingBox) -> Vec<String> {
+        let mut entered = vec![];
+        for volume in &mut self.volumes {
+            let inside = player_box.is_colliding_with(&volume.bounds);
+            if inside && !volume.inside && !(volume.once && volume.fired) {
+                volume.fired = true;
+                entered.push(volume.name.clone());
+            }
+            volume.inside = inside;
+        }
+        entered
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use nalgebra::{Matrix4, Vector3};
+
+use super::player_controller::PlayerController;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewModelState {
+    Idle,
+    Fire,
+    Reload,
+}
+
+/// Animation state machine for the first person weapon.
+pub struct ViewModel {
+    state: ViewModelState,
+    time_in_state: f32,
+    total_time: f32,
+    ammo: u32,
+    // Rounds carried on top of the magazine, reloads draw from these.
+    reserve_ammo: u32,
+}
+
+impl Default for ViewModel {
+    fn default() -> Self {
+        Self {
+            state: ViewModelState::Idle,
+            time_in_state: 0.0,
+            total_time: 0.0,
+            ammo: Self::MAGAZINE_SIZE,
+            reserve_ammo: Self::STARTING_RESERVE,
+        }
+    }
+}
+
+impl ViewModel {
+    pub const MAGAZINE_SIZE: u32 = 12;
+    const STARTING_RESERVE: u32 = 36;
+    const MAX_RESERVE: u32 = 99;
+    const FIRE_DURATION: f32 = 0.15;
+    const RELOAD_DURATION: f32 = 1.0;
+    const RESTING_OFFSET: Vector3<f32> = Vector3::new(0.18, -0.16, -0.35);
+    const BOB_SPEED: f32 = 2.0;
+    const BOB_AMOUNT: f32 = 0.004;
+    const RECOIL_DISTANCE: f32 = 0.06;
+    const RECOIL_PITCH: f32 = 0.25;
+    const RELOAD_DIP: f32 = 0.12;
+    const RELOAD_ROLL: f32 = 0.6;
+
+    /// Returns true when a shot was fired this frame.
+    pub fn update(&mut self, dt: Duration, player_controller: &PlayerController) -> bool {
+        let dt = dt.as_secs_f32();
+        self.time_in_state += dt;
+        self.total_time += dt;
+
+        let next_state = match self.state {
+            ViewModelState::Idle
+                if player_controller.is_reload_pressed
+                    && self.ammo < Self::MAGAZINE_SIZE
+                    && self.reserve_ammo > 0 =>
+            {
+                Some(ViewModelState::Reload)
+            }
+            ViewModelState::Idle if player_controller.is_fire_pressed && self.ammo > 0 => {
+                Some(ViewModelState::Fire)
+            }
+            ViewModelState::Fire if self.time_in_state >= Self::FIRE_DURATION => {
+                Some(ViewModelState::Idle)
+            }
+            ViewModelState::Reload if self.time_in_state >= Self::RELOAD_DURATION => {
+                let reloaded = (Self::MAGAZINE_SIZE - self.ammo).min(self.reserve_ammo);
+                self.ammo += reloaded;
+                self.reserve_ammo -= reloaded;
+                Some(ViewModelState::Idle)
+            }
+            _ => None,
+        };
+        if let Some(next_state) = next_state {
+            self.state = next_state;
+            self.time_in_state = 0.0;
+        }
+        if next_state == Some(ViewModelState::Fire) {
+            self.ammo -= 1;
+        }
+        next_state == Some(ViewModelState::Fire)
+    }
+
+    /// Rounds left in the magazine.
+    pub fn ammo(&self) -> u32 {
+        self.ammo
+    }
+
+    pub fn reserve_ammo(&self) -> u32 {
+        self.reserve_ammo
+    }
+
+    /// Returns how many rounds fit into the reserve, nothing when it is full.
+    pub fn add_ammo(&mut self, rounds: u32) -> u32 {
+        let added = rounds.min(Self::MAX_RESERVE - self.reserve_ammo);
+        self.reserve_ammo += added;
+        added
+    }
+
+    /// Model matrix of the weapon in view space.
+    pub fn transform(&self) -> Matrix4<f32> {
+        let bob = (self.total_time * Self::BOB_SPEED).sin() * Self::BOB_AMOUNT;
+        let mut offset = Self::RESTING_OFFSET + Vector3::new(0.0, bob, 0.0);
+        let mut pitch = 0.0;
+        let mut roll = 0.0;
+        match self.state {
+            ViewModelState::Idle => {}
+            ViewModelState::Fire => {
+                // Snap back on the shot then ease forward again.
+                let t = 1.0 - (self.time_in_state / Self::FIRE_DURATION).min(1.0);
+                let kick = t * t;
+                offset.z += kick * Self::RECOIL_DISTANCE;
+                pitch = kick * Self::RECOIL_PITCH;
+            }
+            ViewModelState::Reload => {
+                let t = (self.time_in_state / Self::RELOAD_DURATION).min(1.0);
+                let dip = (t * std::f32::consts::PI).sin();
+                offset.y -= dip * Self::RELOAD_DIP;
+                roll = dip * Self::RELOAD_ROLL;
+            }
+        }
+        Matrix4::new_translation(&offset) * Matrix4::from_euler_angles(pitch, 0.0, roll)
+    }
+}
@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector3};
+
+use super::bounding_box::BoundingBox;
+use super::collision_manager::CollisionManager;
+use super::rng::RngStream;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ProjectileSettings {
+    pub speed: f32,
+    // Dealt to whatever the projectile hits, on top of the splash.
+    pub direct_damage: u32,
+    pub splash_radius: f32,
+    // Dealt at the center of the explosion, falling off to nothing at the radius.
+    pub splash_damage: u32,
+    // Seconds before a projectile that hit nothing explodes anyway.
+    pub lifetime: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WeaponKind {
+    // Shots land anywhere within `spread` radians of where the player aims.
+    Hitscan {
+        range: f32,
+        damage: u32,
+        spread: f32,
+    },
+    Projectile(ProjectileSettings),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Weapon {
+    pub name: &'static str,
+    pub kind: WeaponKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Projectile {
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    pub settings: ProjectileSettings,
+    age: f32,
+}
+
+/// Where a shot or projectile stopped. `target` is the id of the entity it hit, none when it
+/// hit the level.
+#[derive(Debug, Clone, Copy)]
+pub struct Impact {
+    pub point: Point3<f32>,
+    pub normal: Vector3<f32>,
+    pub target: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Explosion {
+    // Missing when the projectile ran out of time in mid air.
+    pub impact: Option<Impact>,
+    pub point: Point3<f32>,
+    pub settings: ProjectileSettings,
+}
+
+/// The player's arsenal and every projectile in flight. Entities that can be shot are
+/// passed in as `(id, hitbox)` pairs.
+#[derive(Default)]
+pub struct WeaponSystem {
+    current: usize,
+    projectiles: Vec<Projectile>,
+}
+
+impl Explosion {
+    /// Splash damage at `point`, falling off linearly from the center.
+    pub fn splash_damage_at(&self, point: Point3<f32>) -> u32 {
+        let distance = (point - self.point).norm();
+        let falloff = 1.0 - distance / self.settings.splash_radius.max(f32::EPSILON);
+        (self.settings.splash_damage as f32 * falloff.max(0.0)).round() as u32
+    }
+}
+
+impl WeaponSystem {
+    pub const WEAPONS: [Weapon; 2] = [
+        Weapon {
+            name: "Pistol",
+            kind: WeaponKind::Hitscan {
+                range: 100.0,
+                damage: 10,
+                spread: 0.015,
+            },
+        },
+        Weapon {
+            name: "Rocket Launcher",
+            kind: WeaponKind::Projectile(ProjectileSettings {
+                speed: 6.0,
+                direct_damage: 40,
+                splash_radius: 1.0,
+                splash_damage: 30,
+                lifetime: 5.0,
+            }),
+        },
+    ];
+
+    pub fn current(&self) -> &Weapon {
+        &Self::WEAPONS[self.current]
+    }
+
+    /// Switches to the weapon in `slot`, returning it unless the slot is empty.
+    pub fn select(&mut self, slot: usize) -> Option<&Weapon> {
+        if slot >= Self::WEAPONS.len() {
+            return None;
+        }
+        self.current = slot;
+        Some(self.current())
+    }
+
+    pub fn projectiles(&self) -> &[Projectile] {
+        &self.projectiles
+    }
+
+    pub fn clear(&mut self) {
+        self.projectiles.clear();
+    }
+
+    /// Fires the current weapon. Hitscan weapons return what they hit right away, projectile
+    /// weapons launch a projectile and report when it explodes in `update`.
+    pub fn fire(
+        &mut self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        collision_manager: &CollisionManager,
+        targets: &[(usize, BoundingBox)],
+        rng: &mut RngStream,
+    ) -> Option<Impact> {
+        match self.current().kind {
+            WeaponKind::Hitscan { range, spread, .. } => {
+                let direction = rng.direction_in_cone(direction, spread);
+                Self::trace(origin, direction, range, collision_manager, targets)
+            }
+            WeaponKind::Projectile(settings) => {
+                self.projectiles.push(Projectile {
+                    position: origin,
+                    velocity: direction * settings.speed,
+                    settings,
+                    age: 0.0,
+                });
+                None
+            }
+        }
+    }
+
+    /// Nearest level or entity hit by a ray within `range`. `direction` must be normalized.
+    pub fn trace(
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        range: f32,
+        collision_manager: &CollisionManager,
+        targets: &[(usize, BoundingBox)],
+    ) -> Option<Impact> {
+        let level = collision_manager
+            .raycast(origin, direction, range)
+            .map(|hit| ((hit.point - origin).norm(), hit.normal, None));
+        let entity = targets
+            .iter()
+            .filter_map(|(id, hitbox)| {
+                hitbox
+                    .ray_intersection(origin, direction)
+                    .map(|(distance, normal)| (distance, normal, Some(*id)))
+            })
+            .filter(|(distance, _, _)| *distance <= range)
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        [level, entity]
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(distance, normal, target)| Impact {
+                point: origin + direction * distance,
+                normal,
+                target,
+            })
+    }
+
+    /// Moves every projectile and returns the ones that exploded this frame.
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        collision_manager: &CollisionManager,
+        targets: &[(usize, BoundingBox)],
+    ) -> Vec<Explosion> {
+        let dt = dt.as_secs_f32();
+        let mut explosions = vec![];
+        self.projectiles.retain_mut(|projectile| {
+            projectile.age += dt;
+            let step = projectile.velocity * dt;
+            let impact = step.try_normalize(0.0).and_then(|direction| {
+                Self::trace(
+                    projectile.position,
+                    direction,
+                    step.norm(),
+                    collision_manager,
+                    targets,
+                )
+            });
+            if impact.is_none() && projectile.age < projectile.settings.lifetime {
+                projectile.position += step;
+                return true;
+            }
+            explosions.push(Explosion {
+                impact,
+                point: impact.map_or(projectile.position, |impact| impact.point),
+                settings: projectile.settings,
+            });
+            false
+        });
+        explosions
+    }
+}
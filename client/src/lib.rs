@@ -2,22 +2,52 @@ mod application;
 mod camera;
 mod game;
 mod model;
+mod options;
+mod platform;
 mod renderer;
+mod settings;
 
 use application::AppState;
+pub use options::Options;
 use winit::error::EventLoopError;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 pub struct Game;
 
 impl Game {
-    pub fn run() -> Result<(), EventLoopError> {
-        env_logger::init();
-        let event_loop = EventLoop::new().unwrap();
+    pub fn run(options: Options) -> Result<(), EventLoopError> {
+        // The browser console is only open when someone is looking at it.
+        let default_level = if cfg!(target_arch = "wasm32") {
+            log::LevelFilter::Info
+        } else {
+            log::LevelFilter::Error
+        };
+        platform::init_logging(options.log_level.unwrap_or(default_level));
+        let event_loop = EventLoop::with_user_event().build().unwrap();
         event_loop.set_control_flow(ControlFlow::Poll);
         event_loop.set_control_flow(ControlFlow::Wait);
-        let mut app = AppState::default();
+        let app = AppState::new(&event_loop, options);
 
-        event_loop.run_app(&mut app)
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut app = app;
+            event_loop.run_app(&mut app)
+        }
+        // Hands the loop to the browser and returns, blocking here would freeze the page.
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::EventLoopExtWebSys;
+            event_loop.spawn_app(app);
+            Ok(())
+        }
+    }
+}
+
+/// Entry point of the web build, run once the module is loaded.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn start() {
+    if let Err(e) = Game::run(Options::default()) {
+        log::error!("{e}");
     }
 }
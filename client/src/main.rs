@@ -1,6 +1,7 @@
-use client::Game;
+use clap::Parser;
+use client::{Game, Options};
 use winit::error::EventLoopError;
 
 fn main() -> Result<(), EventLoopError> {
-    Game::run()
+    Game::run(Options::parse())
 }
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureKind {
+    D2,
+    Cube,
+    // Every file is one layer of a 2D array, see TextureArray.
+    Array,
+}
+
+/// Identifies a texture by the images it is built from and how they are used.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssetKey {
+    paths: Vec<PathBuf>,
+    kind: TextureKind,
+}
+
+struct CacheEntry<H> {
+    handle: H,
+    ref_count: u32,
+}
+
+/// Reference counted map from asset keys to handles so repeated requests share one load.
+/// Entries stay cached at zero references until they are evicted.
+pub struct AssetCache<H> {
+    entries: HashMap<AssetKey, CacheEntry<H>>,
+}
+
+impl AssetKey {
+    pub fn new(files: &[String], kind: TextureKind) -> Self {
+        // Missing files keep their path as is and fail once they are actually loaded.
+        let paths = files
+            .iter()
+            .map(|file| fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file)))
+            .collect();
+        Self { paths, kind }
+    }
+}
+
+impl<H> Default for AssetCache<H> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<H: Copy + PartialEq> AssetCache<H> {
+    /// Takes a reference to the cached handle for `key`, if there is one.
+    pub fn acquire(&mut self, key: &AssetKey) -> Option<H> {
+        let entry = self.entries.get_mut(key)?;
+        entry.ref_count += 1;
+        Some(entry.handle)
+    }
+
+    /// Caches a newly loaded handle holding one reference.
+    pub fn insert(&mut self, key: AssetKey, handle: H) {
+        self.entries.insert(
+            key,
+            CacheEntry {
+                handle,
+                ref_count: 1,
+            },
+        );
+    }
+
+    pub fn release(&mut self, handle: H) {
+        if let Some(entry) = self
+            .entries
+            .values_mut()
+            .find(|entry| entry.handle == handle)
+        {
+            entry.ref_count = entry.ref_count.saturating_sub(1);
+        }
+    }
+
+    /// Drops every entry nobody references and returns their handles so the owner can free
+    /// whatever was loaded for them.
+    pub fn evict_unused(&mut self) -> Vec<H> {
+        let mut evicted = vec![];
+        self.entries.retain(|_, entry| {
+            if entry.ref_count == 0 {
+                evicted.push(entry.handle);
+            }
+            entry.ref_count > 0
+        });
+        evicted
+    }
+
+    /// Forgets `key` regardless of its references, the next request loads it again.
+    pub fn evict(&mut self, key: &AssetKey) -> Option<H> {
+        self.entries.remove(key).map(|entry| entry.handle)
+    }
+
+    /// Forgets the entry for `handle` regardless of its references, e.g. after its load
+    /// failed, so the next request loads it again.
+    pub fn evict_handle(&mut self, handle: H) {
+        self.entries.retain(|_, entry| entry.handle != handle);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(file: &str) -> AssetKey {
+        AssetKey::new(&[file.to_string()], TextureKind::D2)
+    }
+
+    #[test]
+    fn repeated_requests_share_handle() {
+        let mut cache = AssetCache::default();
+        assert_eq!(cache.acquire(&key("missing.png")), None);
+        cache.insert(key("missing.png"), 7);
+        assert_eq!(cache.acquire(&key("missing.png")), Some(7));
+        assert_eq!(cache.acquire(&key("other.png")), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn evicts_once_released() {
+        let mut cache = AssetCache::default();
+        cache.insert(key("missing.png"), 7);
+        cache.acquire(&key("missing.png"));
+        cache.release(7);
+        assert!(cache.evict_unused().is_empty());
+        cache.release(7);
+        assert_eq!(cache.evict_unused(), vec![7]);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn failed_load_is_requested_again() {
+        let mut cache = AssetCache::default();
+        cache.insert(key("missing.png"), 7);
+        cache.evict_handle(7);
+        assert_eq!(cache.acquire(&key("missing.png")), None);
+    }
+
+    #[test]
+    fn releasing_unknown_handle_does_nothing() {
+        let mut cache = AssetCache::default();
+        cache.insert(key("missing.png"), 7);
+        cache.release(8);
+        assert!(cache.evict_unused().is_empty());
+        assert_eq!(cache.acquire(&key("missing.png")), Some(7));
+    }
+}
@@ -0,0 +1,219 @@
+use image::RgbaImage;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use super::asset_cache::{AssetCache, AssetKey, TextureKind};
+use super::map_loader::MapChunk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureHandle(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkHandle(u32);
+
+pub struct LoadedAsset {
+    pub handle: TextureHandle,
+    pub kind: TextureKind,
+    // The first file, to name the texture after in GPU captures.
+    pub label: String,
+    pub images: Result<Vec<RgbaImage>, String>,
+}
+
+pub struct LoadedChunkFile {
+    pub handle: ChunkHandle,
+    pub chunk: Result<MapChunk, String>,
+}
+
+/// Reads and decodes images on the rayon pool so file I/O never blocks the render thread. The
+/// web build has no threads and fetches them asynchronously instead.
+/// Handles are returned immediately and resolve through `poll`, uploading is up to the caller.
+/// Requests for an already cached texture return the existing handle without loading again.
+/// Map chunk files are read and parsed the same way, building them is up to the caller too.
+pub struct AssetLoader {
+    next_handle: u32,
+    cache: AssetCache<TextureHandle>,
+    sender: Sender<LoadedAsset>,
+    receiver: Receiver<LoadedAsset>,
+    pending: HashSet<TextureHandle>,
+    pending_critical: HashSet<TextureHandle>,
+    requested: usize,
+    next_chunk: u32,
+    chunk_sender: Sender<LoadedChunkFile>,
+    chunk_receiver: Receiver<LoadedChunkFile>,
+    pending_critical_chunks: HashSet<ChunkHandle>,
+}
+
+impl Default for AssetLoader {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let (chunk_sender, chunk_receiver) = mpsc::channel();
+        Self {
+            next_handle: 0,
+            cache: AssetCache::default(),
+            sender,
+            receiver,
+            pending: HashSet::new(),
+            pending_critical: HashSet::new(),
+            requested: 0,
+            next_chunk: 0,
+            chunk_sender,
+            chunk_receiver,
+            pending_critical_chunks: HashSet::new(),
+        }
+    }
+}
+
+impl AssetLoader {
+    /// Decodes `files` as one texture, e.g. the six faces of a cube map. Critical textures keep
+    /// the loading screen up until they resolve. Every call holds a reference until `release`.
+    pub fn load_images(
+        &mut self,
+        files: Vec<String>,
+        kind: TextureKind,
+        critical: bool,
+    ) -> TextureHandle {
+        let key = AssetKey::new(&files, kind);
+        if let Some(handle) = self.cache.acquire(&key) {
+            if critical && self.pending.contains(&handle) {
+                self.pending_critical.insert(handle);
+            }
+            return handle;
+        }
+
+        let handle = TextureHandle(self.next_handle);
+        self.next_handle += 1;
+        self.requested += 1;
+        self.cache.insert(key, handle);
+        self.pending.insert(handle);
+        if critical {
+            self.pending_critical.insert(handle);
+        }
+
+        let sender = self.sender.clone();
+        #[cfg(not(target_arch = "wasm32"))]
+        rayon::spawn(move || {
+            let images = files
+                .par_iter()
+                .map(|file| {
+                    image::open(file)
+                        .map(|image| image.to_rgba8())
+                        .map_err(|e| format!("{file}: {e}"))
+                })
+                .collect();
+            // Nobody is waiting anymore if the loader was dropped.
+            let _ = sender.send(LoadedAsset {
+                handle,
+                kind,
+                label: files.first().cloned().unwrap_or_default(),
+                images,
+            });
+        });
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let images = Self::fetch_images(&files).await;
+            let _ = sender.send(LoadedAsset {
+                handle,
+                kind,
+                label: files.first().cloned().unwrap_or_default(),
+                images,
+            });
+        });
+        handle
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn fetch_images(files: &[String]) -> Result<Vec<RgbaImage>, String> {
+        let mut images = Vec::with_capacity(files.len());
+        for file in files {
+            let bytes = crate::platform::fetch(file)
+                .await
+                .map_err(|e| format!("{file}: {e}"))?;
+            let image = image::load_from_memory(&bytes).map_err(|e| format!("{file}: {e}"))?;
+            images.push(image.to_rgba8());
+        }
+        Ok(images)
+    }
+
+    pub fn load_image(&mut self, file: &str, critical: bool) -> TextureHandle {
+        self.load_images(vec![file.to_string()], TextureKind::D2, critical)
+    }
+
+    /// Reads and parses a map chunk file. Critical chunks keep the loading screen up until
+    /// they arrive.
+    pub fn load_chunk(&mut self, file: &str, critical: bool) -> ChunkHandle {
+        let handle = ChunkHandle(self.next_chunk);
+        self.next_chunk += 1;
+        if critical {
+            self.pending_critical_chunks.insert(handle);
+        }
+        let sender = self.chunk_sender.clone();
+        let file = file.to_string();
+        #[cfg(not(target_arch = "wasm32"))]
+        rayon::spawn(move || {
+            let chunk = crate::platform::read_to_string(&file)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+                .map_err(|e| format!("{file}: {e}"));
+            let _ = sender.send(LoadedChunkFile { handle, chunk });
+        });
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let chunk = crate::platform::fetch(&file)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+                .map_err(|e| format!("{file}: {e}"));
+            let _ = sender.send(LoadedChunkFile { handle, chunk });
+        });
+        handle
+    }
+
+    pub fn release(&mut self, handle: TextureHandle) {
+        self.cache.release(handle);
+    }
+
+    /// Forgets every texture without references, returning the handles to free. Ones still
+    /// loading are dropped when they arrive.
+    pub fn evict_unused(&mut self) -> Vec<TextureHandle> {
+        let evicted = self.cache.evict_unused();
+        for handle in &evicted {
+            self.pending.remove(handle);
+            self.pending_critical.remove(handle);
+        }
+        evicted
+    }
+
+    /// Everything that finished decoding since the last call, leaving out textures evicted
+    /// while they loaded. Failed loads are forgotten so the next request tries again.
+    pub fn poll(&mut self) -> Vec<LoadedAsset> {
+        let mut loaded: Vec<LoadedAsset> = self.receiver.try_iter().collect();
+        loaded.retain(|asset| self.pending.remove(&asset.handle));
+        for asset in &loaded {
+            self.pending_critical.remove(&asset.handle);
+            if asset.images.is_err() {
+                self.cache.evict_handle(asset.handle);
+            }
+        }
+        loaded
+    }
+
+    /// Chunk files that finished parsing since the last call.
+    pub fn poll_chunks(&mut self) -> Vec<LoadedChunkFile> {
+        let loaded: Vec<LoadedChunkFile> = self.chunk_receiver.try_iter().collect();
+        for chunk in &loaded {
+            self.pending_critical_chunks.remove(&chunk.handle);
+        }
+        loaded
+    }
+
+    pub fn is_loading_critical(&self) -> bool {
+        !self.pending_critical.is_empty() || !self.pending_critical_chunks.is_empty()
+    }
+
+    /// Finished and total number of requested assets.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.requested - self.pending.len(), self.requested)
+    }
+}
@@ -0,0 +1,93 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use super::vertex::Vertex;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        Self::from_points(vertices.iter().map(|vertex| Point3::from(vertex.position)))
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Point3<f32>>) -> Self {
+        let mut points = points.into_iter();
+        let first = points.next().unwrap_or_else(Point3::origin);
+        points.fold(Self::point(first), |bounds, point| {
+            bounds.union(&Self::point(point))
+        })
+    }
+
+    fn point(point: Point3<f32>) -> Self {
+        Self {
+            min: point,
+            max: point,
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3::new(a.x, a.y, a.z),
+            Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z),
+            Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z),
+            Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z),
+            Point3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Self {
+        Self::from_points(
+            self.corners()
+                .iter()
+                .map(|corner| transform.transform_point(corner)),
+        )
+    }
+
+    /// Grows the box by `margin` on every side.
+    pub fn expanded(&self, margin: f32) -> Self {
+        let margin = Vector3::repeat(margin);
+        Self {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    pub fn radius(&self) -> f32 {
+        (self.max - self.min).norm() / 2.0
+    }
+
+    /// Distance from `point` to the nearest point of the box, 0 inside it.
+    pub fn distance_to(&self, point: Point3<f32>) -> f32 {
+        (point.coords - point.coords.sup(&self.min.coords).inf(&self.max.coords)).norm()
+    }
+
+    /// Share of the screen's height the box's bounding sphere covers from `eye`, where
+    /// `focal_length` is 1 over the tangent of half the vertical field of view. Infinite with
+    /// the eye inside the sphere.
+    pub fn screen_size(&self, eye: Point3<f32>, focal_length: f32) -> f32 {
+        let radius = self.radius();
+        let distance = nalgebra::distance(&self.center(), &eye);
+        if distance <= radius {
+            return f32::INFINITY;
+        }
+        radius * focal_length / distance
+    }
+}
@@ -1,13 +1,44 @@
-use image::RgbaImage;
-use rayon::prelude::*;
-use wgpu::{BindGroup, BindGroupLayout, Device, Extent3d, Queue, TextureFormat, TextureView};
+use image::{Rgba, RgbaImage};
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Device, Extent3d, Queue, TextureFormat};
 
 pub struct CubeTextureBuilder;
 
+/// One side of a cube map. Discriminants are the array layer wgpu samples each side from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX = 0,
+    NegativeX = 1,
+    PositiveY = 2,
+    NegativeY = 3,
+    PositiveZ = 4,
+    NegativeZ = 5,
+}
+
+/// One or more cube maps stored back to back as six array layers each, in any format. `view`
+/// samples the first cube, the accessors hand out views of a single cube, face or mip level
+/// for rendering into, e.g. one reflection probe of many.
 pub struct CubeTexture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    size: u32,
+    cubes: u32,
+}
+
+/// Image based lighting derived from an environment cube map. `irradiance` holds the diffuse
+/// light arriving from every direction, `prefiltered` the reflections blurred for rougher
+/// surfaces further down its mip chain.
+pub struct Ibl {
+    pub irradiance: CubeTexture,
+    pub prefiltered: CubeTexture,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct IblParams {
+    mode: u32,
+    roughness: f32,
 }
 
 impl CubeTextureBuilder {
@@ -57,82 +88,337 @@ impl CubeTextureBuilder {
     }
 }
 
+impl CubeFace {
+    pub const ALL: [Self; 6] = [
+        Self::PositiveX,
+        Self::NegativeX,
+        Self::PositiveY,
+        Self::NegativeY,
+        Self::PositiveZ,
+        Self::NegativeZ,
+    ];
+}
+
+impl Ibl {
+    pub const IRRADIANCE_SIZE: u32 = 32;
+    pub const PREFILTERED_SIZE: u32 = 128;
+    // Roughness goes from 0 at the top mip to 1 at the last.
+    pub const PREFILTERED_MIPS: u32 = 5;
+    const FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+    const WORKGROUP_SIZE: u32 = 8;
+    const IRRADIANCE_MODE: u32 = 0;
+    const PREFILTER_MODE: u32 = 1;
+
+    fn create_target(device: &Device, size: u32, mips: u32, label: &str) -> CubeTexture {
+        CubeTexture::new(
+            device,
+            Some(label),
+            size,
+            1,
+            mips,
+            Self::FORMAT,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        )
+    }
+}
+
 impl CubeTexture {
-    pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
-    pub fn new_shadow_map(
+    /// `cubes` cube maps with `size` texel sides at mip 0.
+    pub fn new(
         device: &Device,
-        resolution: u32,
-        num_lights: u32,
         label: Option<&str>,
+        size: u32,
+        cubes: u32,
+        mip_level_count: u32,
+        format: TextureFormat,
+        usage: wgpu::TextureUsages,
     ) -> Self {
+        let cubes = cubes.max(1);
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size: Extent3d {
-                width: resolution,
-                height: resolution,
-                depth_or_array_layers: 6 * num_lights,
+                width: size,
+                height: size,
+                depth_or_array_layers: cubes * 6,
             },
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            usage,
             view_formats: &[],
         });
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            dimension: Some(wgpu::TextureViewDimension::CubeArray),
-            array_layer_count: Some(6 * num_lights),
+            label,
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            array_layer_count: Some(6),
             ..Default::default()
         });
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            compare: Some(wgpu::CompareFunction::LessEqual),
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            // Depth cubes are shadow maps, sampled with textureSampleCompare.
+            compare: format
+                .is_depth_stencil_format()
+                .then_some(wgpu::CompareFunction::LessEqual),
             ..Default::default()
         });
-
         Self {
             texture,
             view,
             sampler,
+            size,
+            cubes,
+        }
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.texture.format()
+    }
+
+    pub fn cubes(&self) -> u32 {
+        self.cubes
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.texture.mip_level_count()
+    }
+
+    /// Side length in texels of every face at `mip`, what a viewport rendering into it spans.
+    pub fn size(&self, mip: u32) -> u32 {
+        (self.size >> mip).max(1)
+    }
+
+    /// Array layer of `face` in cube `cube`.
+    pub fn layer(&self, cube: u32, face: CubeFace) -> Result<u32, String> {
+        if cube >= self.cubes {
+            return Err(format!(
+                "Cube {cube} is out of range, the texture holds {}",
+                self.cubes
+            ));
         }
+        Ok(cube * 6 + face as u32)
+    }
+
+    /// All six faces of cube `cube` for sampling as a cube map.
+    pub fn cube_view(&self, cube: u32) -> Result<wgpu::TextureView, String> {
+        let base_array_layer = self.layer(cube, CubeFace::PositiveX)?;
+        Ok(self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("Cube {cube}")),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            base_array_layer,
+            array_layer_count: Some(6),
+            ..Default::default()
+        }))
     }
 
-    pub fn create_view_from_face(
+    /// A single face at one mip level, for rendering into.
+    pub fn face_view(
         &self,
-        light_index: u32,
-        face_index: u32,
-        label: Option<&str>,
-    ) -> TextureView {
-        self.texture.create_view(&wgpu::TextureViewDescriptor {
-            label,
+        cube: u32,
+        face: CubeFace,
+        mip: u32,
+    ) -> Result<wgpu::TextureView, String> {
+        let base_array_layer = self.layer(cube, face)?;
+        self.check_mip(mip)?;
+        Ok(self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("Cube {cube} {face:?} Mip {mip}")),
             dimension: Some(wgpu::TextureViewDimension::D2),
-            base_array_layer: 6 * light_index + face_index,
+            base_array_layer,
             array_layer_count: Some(1),
+            base_mip_level: mip,
             mip_level_count: Some(1),
-            format: Some(wgpu::TextureFormat::Depth32Float),
             ..Default::default()
-        })
+        }))
+    }
+
+    /// Every layer of every cube at one mip level as a plain array, e.g. for compute shaders
+    /// writing all faces at once.
+    pub fn layers_view(&self, mip: u32) -> Result<wgpu::TextureView, String> {
+        self.check_mip(mip)?;
+        Ok(self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("Cube Layers Mip {mip}")),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            base_mip_level: mip,
+            mip_level_count: Some(1),
+            array_layer_count: Some(self.cubes * 6),
+            ..Default::default()
+        }))
+    }
+
+    fn check_mip(&self, mip: u32) -> Result<(), String> {
+        if mip >= self.mip_level_count() {
+            return Err(format!(
+                "Mip {mip} is out of range, the texture has {}",
+                self.mip_level_count()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Convolves this environment into irradiance and prefiltered specular maps on the GPU.
+    pub fn generate_ibl(&self, device: &Device, queue: &Queue) -> Ibl {
+        let irradiance = Ibl::create_target(device, Ibl::IRRADIANCE_SIZE, 1, "IBL Irradiance");
+        let prefiltered = Ibl::create_target(
+            device,
+            Ibl::PREFILTERED_SIZE,
+            Ibl::PREFILTERED_MIPS,
+            "IBL Prefiltered",
+        );
+        let storage_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                storage_entry(
+                    0,
+                    wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                ),
+                storage_entry(
+                    1,
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                ),
+                storage_entry(
+                    2,
+                    wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: Ibl::FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                    },
+                ),
+                storage_entry(
+                    3,
+                    wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                ),
+            ],
+            label: Some("ibl_bind_group_layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("IBL Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("IBL Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../renderer/shaders/ibl.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("IBL Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        // Sample the source smoothly even though its faces come without mips.
+        let source_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("IBL Source Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut passes = vec![(
+            &irradiance,
+            0,
+            Ibl::IRRADIANCE_SIZE,
+            IblParams {
+                mode: Ibl::IRRADIANCE_MODE,
+                roughness: 0.0,
+            },
+        )];
+        for mip in 0..Ibl::PREFILTERED_MIPS {
+            passes.push((
+                &prefiltered,
+                mip,
+                (Ibl::PREFILTERED_SIZE >> mip).max(1),
+                IblParams {
+                    mode: Ibl::PREFILTER_MODE,
+                    roughness: mip as f32 / (Ibl::PREFILTERED_MIPS - 1) as f32,
+                },
+            ));
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("IBL Encoder"),
+        });
+        for (target, mip, size, params) in passes {
+            let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("IBL Params Buffer"),
+                contents: bytemuck::cast_slice(&[params]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            let output = target
+                .layers_view(mip)
+                .expect("IBL targets have a mip per pass");
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&self.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&source_sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&output),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("ibl_bind_group"),
+            });
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("IBL Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            let groups = size.div_ceil(Ibl::WORKGROUP_SIZE);
+            compute_pass.dispatch_workgroups(groups, groups, 6);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ibl {
+            irradiance,
+            prefiltered,
+        }
     }
 
-    pub fn from_files(
-        files: &[String],
+    /// Six 1x1 faces of `color`, used while the real faces are still loading.
+    pub fn from_color(color: [u8; 4], device: &Device, queue: &Queue, label: Option<&str>) -> Self {
+        let faces = vec![RgbaImage::from_pixel(1, 1, Rgba(color)); 6];
+        Self::from_images(&faces, device, queue, label)
+    }
+
+    pub fn from_images(
+        rgbas: &[RgbaImage],
         device: &Device,
         queue: &Queue,
         label: Option<&str>,
     ) -> Self {
-        assert_eq!(files.len(), 6, "Cube maps must contain exactly 6 textures.");
-        let rgbas: Vec<RgbaImage> = files
-            .par_iter()
-            .map(|filename| -> RgbaImage {
-                let image = image::open(filename).expect("Failed to load image");
-                image.to_rgba8()
-            })
-            .collect();
+        assert_eq!(rgbas.len(), 6, "Cube maps must contain exactly 6 textures.");
         let first_dim = rgbas[0].dimensions();
         for rgba in &rgbas[1..] {
             assert_eq!(
@@ -142,36 +428,32 @@ impl CubeTexture {
             );
         }
         let (w, h) = first_dim;
+        assert_eq!(w, h, "Cubemap faces must be square");
         let size = Extent3d {
             width: w,
             height: h,
             depth_or_array_layers: 1,
         };
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
+        let cube = Self::new(
+            device,
             label,
-            size: Extent3d {
-                width: w,
-                height: h,
-                depth_or_array_layers: 6,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
+            w,
+            1,
+            1,
+            TextureFormat::Rgba8Unorm,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
 
-        for (i, rgba) in rgbas.iter().enumerate() {
+        for (face, rgba) in CubeFace::ALL.into_iter().zip(rgbas) {
             queue.write_texture(
                 wgpu::TexelCopyTextureInfo {
                     aspect: wgpu::TextureAspect::All,
-                    texture: &texture,
+                    texture: &cube.texture,
                     mip_level: 0,
                     origin: wgpu::Origin3d {
                         x: 0,
                         y: 0,
-                        z: i as u32,
+                        z: face as u32,
                     },
                 },
                 rgba,
@@ -183,27 +465,6 @@ impl CubeTexture {
                 size,
             );
         }
-
-        let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label,
-            dimension: Some(wgpu::TextureViewDimension::Cube),
-            array_layer_count: Some(6),
-            ..Default::default()
-        });
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        Self {
-            texture,
-            view,
-            sampler,
-        }
+        cube
     }
 }
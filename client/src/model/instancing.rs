@@ -0,0 +1,38 @@
+use super::model_instance::RawInstance;
+
+/// Collects instances of repeated props by what they look like, so every group of identical
+/// meshes ends up as one model drawn with a single instanced call per mesh.
+pub struct InstanceBatcher<K> {
+    batches: Vec<(K, Vec<RawInstance>)>,
+}
+
+impl<K> Default for InstanceBatcher<K> {
+    fn default() -> Self {
+        Self { batches: vec![] }
+    }
+}
+
+impl<K: PartialEq> InstanceBatcher<K> {
+    /// Adds an instance to the batch for `key`. Returns the batch and the instance's index in
+    /// it, batches are numbered in the order their first instance came in.
+    pub fn add(&mut self, key: K, instance: RawInstance) -> (usize, usize) {
+        let batch = match self
+            .batches
+            .iter()
+            .position(|(batch_key, _)| *batch_key == key)
+        {
+            Some(batch) => batch,
+            None => {
+                self.batches.push((key, vec![]));
+                self.batches.len() - 1
+            }
+        };
+        let instances = &mut self.batches[batch].1;
+        instances.push(instance);
+        (batch, instances.len() - 1)
+    }
+
+    pub fn into_batches(self) -> impl Iterator<Item = (K, Vec<RawInstance>)> {
+        self.batches.into_iter()
+    }
+}
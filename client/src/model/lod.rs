@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+use wgpu::{Buffer, Device};
+
+use super::Mesh;
+use super::bounds::Aabb;
+use super::vertex::Vertex;
+
+/// A coarser version of a mesh, drawn once the mesh covers less than `screen_size` of the
+/// screen's height. Levels made by decimation only swap the indices and keep the mesh's
+/// vertices, imported ones can bring their own.
+pub struct MeshLod {
+    pub vertex_buffer: Option<Buffer>,
+    pub index_buffer: Buffer,
+    pub num_elements: u32,
+    pub screen_size: f32,
+}
+
+impl MeshLod {
+    // How far past a level's screen size the mesh has to get before switching, so a mesh
+    // sitting right on the threshold doesn't pop back and forth.
+    pub const HYSTERESIS: f32 = 0.15;
+    // Meshes with fewer triangles aren't worth decimating.
+    const MIN_TRIANGLES: usize = 64;
+    // Grid cells along the longest side of the mesh, and the screen size each level takes over
+    // at.
+    const AUTO_LEVELS: [(f32, f32); 2] = [(24.0, 0.2), (8.0, 0.08)];
+    // A level has to drop at least this share of the previous level's triangles to be kept.
+    const MIN_REDUCTION: f32 = 0.25;
+
+    pub fn new(
+        name: &str,
+        vertex_buffer: Option<Buffer>,
+        indices: &[u16],
+        screen_size: f32,
+        device: &Device,
+    ) -> Self {
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{name} LOD Index Buffer")),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_elements: indices.len() as u32,
+            screen_size,
+        }
+    }
+
+    /// Screen size for the `level`th imported level when the file doesn't give one, halving
+    /// with each level.
+    pub fn default_screen_size(level: u32) -> f32 {
+        0.4 / 2f32.powi(level as i32)
+    }
+
+    /// Decimated levels for a mesh, coarsest last. Small meshes and meshes that don't
+    /// simplify get none.
+    pub fn generate(
+        name: &str,
+        vertices: &[Vertex],
+        indices: &[u16],
+        device: &Device,
+    ) -> Vec<Self> {
+        let mut levels = vec![];
+        if indices.len() / 3 < Self::MIN_TRIANGLES {
+            return levels;
+        }
+        let bounds = Aabb::from_vertices(vertices);
+        let extent = (bounds.max - bounds.min).max();
+        let mut previous = indices.len();
+        for (cells, screen_size) in Self::AUTO_LEVELS {
+            let decimated = Self::decimate(vertices, indices, extent / cells);
+            if decimated.is_empty()
+                || decimated.len() as f32 > previous as f32 * (1.0 - Self::MIN_REDUCTION)
+            {
+                continue;
+            }
+            previous = decimated.len();
+            levels.push(Self::new(name, None, &decimated, screen_size, device));
+        }
+        levels
+    }
+
+    /// Vertex clustering: every vertex snaps to the first vertex found in its grid cell of
+    /// `cell_size`, and triangles that collapse are dropped. Only the indices change.
+    pub fn decimate(vertices: &[Vertex], indices: &[u16], cell_size: f32) -> Vec<u16> {
+        if cell_size <= 0.0 {
+            return indices.to_vec();
+        }
+        let mut cells: HashMap<[i32; 3], u16> = HashMap::new();
+        let representatives: Vec<u16> = vertices
+            .iter()
+            .enumerate()
+            .map(|(index, vertex)| {
+                let cell = vertex
+                    .position
+                    .map(|coordinate| (coordinate / cell_size).floor() as i32);
+                *cells.entry(cell).or_insert(index as u16)
+            })
+            .collect();
+        indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                triangle
+                    .iter()
+                    .map(|&index| representatives[index as usize])
+            })
+            .filter_map(|mut triangle| {
+                let (a, b, c) = (triangle.next()?, triangle.next()?, triangle.next()?);
+                (a != b && b != c && a != c).then_some([a, b, c])
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+impl Mesh {
+    /// Moves to a coarser or finer level for a mesh covering `screen_size` of the screen's
+    /// height, only once it is clearly past a level's threshold.
+    pub fn select_lod(&mut self, screen_size: f32) {
+        while self.lod < self.lods.len()
+            && screen_size < self.lods[self.lod].screen_size * (1.0 - MeshLod::HYSTERESIS)
+        {
+            self.lod += 1;
+        }
+        while self.lod > 0
+            && screen_size > self.lods[self.lod - 1].screen_size * (1.0 + MeshLod::HYSTERESIS)
+        {
+            self.lod -= 1;
+        }
+    }
+
+    /// Vertex buffer, index buffer and index count of the selected level.
+    pub fn buffers(&self) -> (&Buffer, &Buffer, u32) {
+        match self
+            .lod
+            .checked_sub(1)
+            .and_then(|level| self.lods.get(level))
+        {
+            Some(lod) => (
+                lod.vertex_buffer.as_ref().unwrap_or(&self.vertex_buffer),
+                &lod.index_buffer,
+                lod.num_elements,
+            ),
+            None => (&self.vertex_buffer, &self.index_buffer, self.num_elements),
+        }
+    }
+
+    /// Replaces the levels, finest first whatever order they come in.
+    pub fn set_lods(&mut self, mut lods: Vec<MeshLod>) {
+        lods.sort_by(|a, b| b.screen_size.total_cmp(&a.screen_size));
+        self.lods = lods;
+        self.lod = 0;
+    }
+}
@@ -1,29 +1,95 @@
+use log::error;
 use nalgebra::{Matrix3, Point3, Vector3};
-use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use std::{collections::HashMap, error::Error, fs};
+use std::{collections::HashMap, error::Error, time::Duration};
 use wgpu::util::DeviceExt;
 use wgpu::{BindGroupLayout, Device, Queue};
 
 use crate::{
-    camera::light::Light,
-    game::{bounding_box::BoundingBox, collision_manager::CollisionManager},
+    camera::{
+        fog::Fog,
+        light::{Light, LightAnimation, ShadowSettings},
+    },
+    game::{
+        animator::Animator,
+        bounding_box::BoundingBox,
+        character::Character,
+        collision_manager::CollisionManager,
+        decals::{Decal, DecalKind},
+        enemy::{Enemy, EnemySettings},
+        movers::{Easing, Keyframe, Mover},
+        particles::{Emitter, EmitterKind, ParticlePreset},
+        pickups::{Pickup, PickupKind},
+        triggers::TriggerVolume,
+    },
+    platform,
+    renderer::joint_palette::JointPalette,
 };
 
+use super::asset_cache::TextureKind;
+use super::asset_loader::{AssetLoader, TextureHandle};
+use super::bounds::Aabb;
+use super::instancing::InstanceBatcher;
+use super::lod::MeshLod;
 use super::model_instance::{Instance, RawInstance};
+use super::primitives::Primitives;
+use super::skinned_model::SkinnedModel;
+use super::wad_loader::{WadGeometry, WadLoader};
 use super::{
-    Material, Mesh, Model,
-    texture::TextureBuilder,
+    BlendMode, Material, MaterialTextures, MaterialUniform, Mesh, Model,
+    texture::{Texture, TextureBuilder},
+    texture_array::TextureArrayBuilder,
     vertex::{LineVertex, Vertex},
 };
 
 pub struct Map {
     pub models: Vec<Model>,
+    pub materials: HashMap<String, Material>,
+    // Every material's textures packed as one array, None when the map has no materials.
+    pub level_textures: Option<TextureHandle>,
     pub skybox_textures: Vec<String>,
     pub lights: Vec<Light>,
     pub collision_manager: CollisionManager,
     pub debug_lines: Vec<LineVertex>,
+    pub spawn_point: Option<Point3<f32>>,
+    pub emitters: Vec<Emitter>,
+    pub characters: Vec<Character>,
+    pub decals: Vec<Decal>,
+    pub fog: Fog,
+    pub ambient: [f32; 3],
+    pub environment: f32,
+    pub script: Option<String>,
+    pub triggers: Vec<TriggerVolume>,
+    pub movers: Vec<Mover>,
+    pub pickups: Vec<Pickup>,
+    pub reflection_probes: Vec<Point3<f32>>,
+    pub chunks: Vec<ChunkInfo>,
+}
+
+/// Where a streamed chunk's file is and the space its contents are in.
+pub struct ChunkInfo {
+    pub file: String,
+    pub bounds: Aabb,
+}
+
+/// Materials, lights and models of one part of a large map, kept in their own file and only
+/// loaded while the player is near. Collision stays with the map, so the level is solid
+/// wherever a chunk is missing.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MapChunk {
+    #[serde(default)]
+    materials: Vec<MaterialLoader>,
+    #[serde(default)]
+    lights: Vec<LightLoader>,
+    #[serde(default)]
+    models: Vec<ModelLoader>,
+}
+
+/// A chunk's GPU resources, ready to be added to the level.
+pub struct LoadedChunk {
+    pub models: Vec<Model>,
+    pub materials: HashMap<String, Material>,
+    pub lights: Vec<Light>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -33,6 +99,130 @@ pub struct MapLoader {
     materials: Vec<MaterialLoader>,
     models: Vec<ModelLoader>,
     bounding_boxes: Vec<BoundingBoxLoader>,
+    #[serde(default)]
+    wad: Option<WadLoader>,
+    #[serde(default)]
+    emitters: Vec<EmitterLoader>,
+    #[serde(default)]
+    characters: Vec<CharacterLoader>,
+    #[serde(default)]
+    decals: Vec<DecalLoader>,
+    #[serde(default)]
+    fog: Fog,
+    #[serde(default = "MapLoader::default_ambient")]
+    ambient: [f32; 3],
+    // How strongly the skybox lights the level.
+    #[serde(default = "MapLoader::default_environment")]
+    environment: f32,
+    // Rhai file with the level's gameplay hooks.
+    #[serde(default)]
+    script: Option<String>,
+    #[serde(default)]
+    triggers: Vec<TriggerLoader>,
+    #[serde(default)]
+    movers: Vec<MoverLoader>,
+    #[serde(default)]
+    pickups: Vec<PickupLoader>,
+    // Spots shiny materials see their surroundings from, baked once the level has loaded.
+    #[serde(default)]
+    reflection_probes: Vec<ReflectionProbeLoader>,
+    // Parts of the level streamed in around the player.
+    #[serde(default)]
+    chunks: Vec<ChunkLoader>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ChunkLoader {
+    pub file: String,
+    pub top_left: [f32; 3],
+    pub bottom_right: [f32; 3],
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PickupLoader {
+    pub position: [f32; 3],
+    // Drawn as a small box with this material, invisible without one.
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default = "InstanceLoader::default_tint")]
+    pub tint: [f32; 4],
+    #[serde(flatten)]
+    pub kind: PickupKind,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TriggerLoader {
+    pub name: String,
+    pub top_left: [f32; 3],
+    pub bottom_right: [f32; 3],
+    #[serde(default)]
+    pub once: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct MoverLoader {
+    // Index into `models`, moving every instance of it.
+    #[serde(default)]
+    pub model: Option<usize>,
+    // Indices into `bounding_boxes`.
+    #[serde(default)]
+    pub bounding_boxes: Vec<usize>,
+    pub keyframes: Vec<KeyframeLoader>,
+    #[serde(default)]
+    pub easing: Easing,
+    #[serde(default)]
+    pub trigger: Option<String>,
+    #[serde(default)]
+    pub wait: Option<f32>,
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct KeyframeLoader {
+    pub offset: [f32; 3],
+    #[serde(default)]
+    pub duration: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct DecalLoader {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub kind: DecalKind,
+    // Radians around the normal.
+    #[serde(default)]
+    pub angle: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CharacterLoader {
+    // Path to a skinned glTF model.
+    pub model: String,
+    pub material: String,
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub yaw: f32,
+    #[serde(default)]
+    pub animation: Option<String>,
+    // Makes the character an enemy that hunts the player.
+    #[serde(default)]
+    pub enemy: Option<EnemySettings>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct EmitterLoader {
+    pub position: [f32; 3],
+    #[serde(default = "EmitterLoader::default_direction")]
+    pub direction: [f32; 3],
+    pub preset: ParticlePreset,
+    pub rate: f32,
+}
+
+impl EmitterLoader {
+    fn default_direction() -> [f32; 3] {
+        [0.0, 1.0, 0.0]
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -40,6 +230,15 @@ struct MaterialLoader {
     pub name: String,
     pub texture_map: String,
     pub normal_map: String,
+    #[serde(default)]
+    pub blend: BlendMode,
+    #[serde(default)]
+    pub reflectivity: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ReflectionProbeLoader {
+    pub position: [f32; 3],
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -47,6 +246,10 @@ struct LightLoader {
     pub position: [f32; 3],
     pub color: [f32; 3],
     pub intensity: f32,
+    #[serde(default)]
+    pub shadow: ShadowSettings,
+    #[serde(default)]
+    pub animation: Option<LightAnimation>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -61,6 +264,20 @@ struct MeshLoader {
     pub vertices: Vec<VertexLoader>,
     pub indices: Vec<u16>,
     pub material: String,
+    // Replaces the levels that would be decimated automatically when given.
+    #[serde(default)]
+    pub lods: Vec<LodLoader>,
+}
+
+/// One coarser level of a map mesh, either its own indices into the mesh's vertices or a grid
+/// cell size to decimate it with.
+#[derive(Serialize, Deserialize, Debug)]
+struct LodLoader {
+    pub screen_size: f32,
+    #[serde(default)]
+    pub indices: Option<Vec<u16>>,
+    #[serde(default)]
+    pub cell_size: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -78,6 +295,9 @@ struct InstanceLoader {
     pub height: u32,
     pub depth: u32,
     pub rotation: [[f32; 3]; 3],
+    // Applied to every copy in the grid.
+    #[serde(default = "InstanceLoader::default_tint")]
+    pub tint: [f32; 4],
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -87,31 +307,81 @@ struct BoundingBoxLoader {
     pub collide_on_top: bool,
 }
 
+impl MapChunk {
+    /// Builds the chunk's resources, numbering its lights from `first_light_id`. Textures are
+    /// requested from `asset_loader` and start out as placeholders.
+    pub fn load(
+        &self,
+        first_light_id: u32,
+        critical: bool,
+        device: &Device,
+        queue: &Queue,
+        bind_group_layout: &BindGroupLayout,
+        asset_loader: &mut AssetLoader,
+    ) -> LoadedChunk {
+        LoadedChunk {
+            models: MapLoader::models(&self.models, device),
+            materials: MapLoader::materials(
+                &self.materials,
+                critical,
+                device,
+                queue,
+                bind_group_layout,
+                asset_loader,
+                None,
+            ),
+            lights: MapLoader::lights(&self.lights, first_light_id),
+        }
+    }
+}
+
+impl InstanceLoader {
+    fn default_tint() -> [f32; 4] {
+        RawInstance::NO_TINT
+    }
+}
+
 impl MapLoader {
     const LINE_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+    const TRIGGER_LINE_COLOR: [f32; 3] = [0.0, 1.0, 0.0];
     const MATERIAL_INDEX: u32 = 0;
+    const PLACEHOLDER_DIFFUSE: [u8; 4] = [128, 128, 128, 255];
+    const PLACEHOLDER_NORMAL: [u8; 4] = [128, 128, 255, 255];
+    const SKINNED_BOUNDS_MARGIN: f32 = 0.5;
+    const PICKUP_SIZE: f32 = 0.15;
     pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
-        let json_data = fs::read_to_string(filename)?;
+        let json_data = platform::read_to_string(filename)?;
         let l: Self = serde_json::from_str(&json_data)?;
         Ok(l)
     }
 
-    pub fn load(&self, device: &Device, queue: &Queue, bind_group_layout: &BindGroupLayout) -> Map {
+    fn default_ambient() -> [f32; 3] {
+        [0.08, 0.08, 0.08]
+    }
+
+    fn default_environment() -> f32 {
+        0.2
+    }
+
+    /// Builds the map geometry. Material textures are requested from `asset_loader` and start
+    /// out as placeholders.
+    pub fn load(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        bind_group_layout: &BindGroupLayout,
+        asset_loader: &mut AssetLoader,
+    ) -> Map {
+        let wad_geometry = self.wad.as_ref().and_then(|wad| match wad.load() {
+            Ok(geometry) => Some(geometry),
+            Err(e) => {
+                error!("Failed to import {} from {}: {e}", wad.level, wad.file);
+                None
+            }
+        });
         let skybox_textures = self.skybox.clone();
-        let lights: Vec<Light> = self
-            .lights
-            .iter()
-            .enumerate()
-            .map(|(i, light)| -> Light {
-                Light {
-                    id: i as u32,
-                    position: Point3::new(light.position[0], light.position[1], light.position[2]),
-                    color: light.color,
-                    intensity: light.intensity,
-                }
-            })
-            .collect();
-        let map_boxes: Vec<BoundingBox> = self
+        let lights = Self::lights(&self.lights, 0);
+        let mut map_boxes: Vec<BoundingBox> = self
             .bounding_boxes
             .iter()
             .map(|bounding_box| -> BoundingBox {
@@ -130,33 +400,228 @@ impl MapLoader {
                 }
             })
             .collect();
+        if let Some(wad_geometry) = &wad_geometry {
+            map_boxes.extend(wad_geometry.bounding_boxes.iter().cloned());
+        }
+
+        let triggers: Vec<TriggerVolume> = self
+            .triggers
+            .iter()
+            .map(|trigger| {
+                TriggerVolume::new(
+                    trigger.name.clone(),
+                    BoundingBox {
+                        top_left: Point3::from(trigger.top_left),
+                        bottom_right: Point3::from(trigger.bottom_right),
+                        collide_on_top: false,
+                    },
+                    trigger.once,
+                )
+            })
+            .collect();
 
         let debug_lines: Vec<LineVertex> = map_boxes
             .iter()
             .flat_map(|map_box| Self::bounding_box_to_line_vertices(map_box, Self::LINE_COLOR))
+            .chain(triggers.iter().flat_map(|trigger| {
+                Self::bounding_box_to_line_vertices(&trigger.bounds, Self::TRIGGER_LINE_COLOR)
+            }))
             .collect();
         let collision_manager = CollisionManager { map_boxes };
 
-        let materials: Arc<HashMap<String, Material>> = Arc::new(
-            self.materials
-                .par_iter()
-                .map(|material| -> (String, Material) {
-                    (
-                        String::from(&material.name),
-                        Self::load_texture(
-                            &material.texture_map,
-                            &material.normal_map,
-                            device,
-                            queue,
-                            bind_group_layout,
-                        ),
-                    )
+        let mut texture_array = TextureArrayBuilder::default();
+        let materials = Self::materials(
+            &self.materials,
+            true,
+            device,
+            queue,
+            bind_group_layout,
+            asset_loader,
+            Some(&mut texture_array),
+        );
+        let level_textures = (!texture_array.is_empty()).then(|| {
+            asset_loader.load_images(texture_array.into_files(), TextureKind::Array, true)
+        });
+
+        let mut models = Self::models(&self.models, device);
+        let emitters = self
+            .emitters
+            .iter()
+            .map(|emitter| {
+                Emitter::new(
+                    Point3::from(emitter.position),
+                    Vector3::from(emitter.direction),
+                    EmitterKind::Continuous { rate: emitter.rate },
+                    emitter.preset.settings(),
+                )
+            })
+            .collect();
+        let decals = self
+            .decals
+            .iter()
+            .map(|decal| {
+                Decal::new(
+                    Point3::from(decal.position),
+                    Vector3::from(decal.normal),
+                    decal.kind,
+                    decal.angle,
+                    true,
+                )
+            })
+            .collect();
+        let movers = self
+            .movers
+            .iter()
+            .filter_map(|mover| Self::mover(mover, models.len(), collision_manager.map_boxes.len()))
+            .collect();
+        // Pickups sharing a material are copies of one box, drawn together.
+        let mut pickup_batcher = InstanceBatcher::default();
+        let pickups = self
+            .pickups
+            .iter()
+            .map(|pickup| {
+                let position = Point3::from(pickup.position);
+                let model = pickup
+                    .material
+                    .as_deref()
+                    .filter(|material| {
+                        let exists = materials.contains_key(*material);
+                        if !exists {
+                            error!("Pickup uses unknown material {material}");
+                        }
+                        exists
+                    })
+                    .map(|material| {
+                        let instance = Instance {
+                            position: position.coords,
+                            rotation: Matrix3::identity(),
+                        }
+                        .to_raw()
+                        .with_tint(pickup.tint);
+                        let (batch, instance) = pickup_batcher.add(material, instance);
+                        (models.len() + batch, instance)
+                    });
+                Pickup {
+                    kind: pickup.kind.clone(),
+                    position,
+                    model,
+                }
+            })
+            .collect();
+        for (material, instances) in pickup_batcher.into_batches() {
+            models.push(Self::pickup_model(material, instances, device));
+        }
+        let spawn_point = wad_geometry
+            .as_ref()
+            .and_then(|geometry| geometry.spawn_point);
+        if let Some(wad_geometry) = wad_geometry {
+            models.push(Self::wad_model(wad_geometry, &materials, device));
+        }
+        let mut characters = vec![];
+        let mut joint_offset = 0;
+        for character in &self.characters {
+            match Self::character(character, joint_offset, models.len(), device) {
+                Ok((model, character)) => {
+                    joint_offset += character.animator.joint_count() as u32;
+                    if joint_offset as usize > JointPalette::MAX_JOINTS {
+                        error!("Too many joints, skipping the remaining characters");
+                        break;
+                    }
+                    models.push(model);
+                    characters.push(character);
+                }
+                Err(e) => error!("Failed to import {}: {e}", character.model),
+            }
+        }
+
+        for model in &mut models {
+            model.apply_blend_modes(&materials);
+        }
+
+        Map {
+            skybox_textures,
+            collision_manager,
+            lights,
+            debug_lines,
+            models,
+            materials,
+            level_textures,
+            spawn_point,
+            emitters,
+            characters,
+            decals,
+            fog: self.fog,
+            ambient: self.ambient,
+            environment: self.environment,
+            script: self.script.clone(),
+            triggers,
+            movers,
+            pickups,
+            reflection_probes: self
+                .reflection_probes
+                .iter()
+                .map(|probe| Point3::from(probe.position))
+                .collect(),
+            chunks: self
+                .chunks
+                .iter()
+                .map(|chunk| ChunkInfo {
+                    file: chunk.file.clone(),
+                    bounds: Aabb::from_points([
+                        Point3::from(chunk.top_left),
+                        Point3::from(chunk.bottom_right),
+                    ]),
                 })
                 .collect(),
-        );
+        }
+    }
+
+    fn lights(lights: &[LightLoader], first_id: u32) -> Vec<Light> {
+        lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| Light {
+                id: first_id + i as u32,
+                position: Point3::from(light.position),
+                color: light.color,
+                intensity: light.intensity,
+                shadow: light.shadow,
+                animation: light.animation,
+            })
+            .collect()
+    }
+
+    /// Materials start out with placeholder textures. Critical ones hold up the loading
+    /// screen until their diffuse map is in. With `texture_array` their textures become its
+    /// layers, for the caller to load, rather than being loaded one by one.
+    fn materials(
+        materials: &[MaterialLoader],
+        critical: bool,
+        device: &Device,
+        queue: &Queue,
+        bind_group_layout: &BindGroupLayout,
+        asset_loader: &mut AssetLoader,
+        mut texture_array: Option<&mut TextureArrayBuilder>,
+    ) -> HashMap<String, Material> {
+        materials
+            .iter()
+            .map(|material| {
+                let loaded = Self::load_texture(
+                    material,
+                    critical,
+                    device,
+                    queue,
+                    bind_group_layout,
+                    asset_loader,
+                    texture_array.as_deref_mut(),
+                );
+                (String::from(&material.name), loaded)
+            })
+            .collect()
+    }
 
-        let models: Vec<Model> = self
-            .models
+    fn models(models: &[ModelLoader], device: &Device) -> Vec<Model> {
+        models
             .iter()
             .map(|model| -> Model {
                 let meshes: Vec<Mesh> = model
@@ -173,16 +638,22 @@ impl MapLoader {
                                     normal: vertex.normal,
                                     tangent: [0.0; 3],
                                     bitangent: [0.0; 3],
+                                    joints: [0; 4],
+                                    weights: [0.0; 4],
                                 }
                             })
                             .collect();
-                        Self::gen_mesh(
+                        let mut built = Self::gen_mesh(
                             &mesh.name,
                             &mut vertices,
                             &mesh.indices,
                             &mesh.material,
                             device,
-                        )
+                        );
+                        if !mesh.lods.is_empty() {
+                            built.set_lods(Self::mesh_lods(mesh, &vertices, device));
+                        }
+                        built
                     })
                     .collect();
 
@@ -205,36 +676,144 @@ impl MapLoader {
                                     ),
                                     rotation: Matrix3::from(instance.rotation),
                                 }
-                                .to_raw(),
+                                .to_raw()
+                                .with_tint(instance.tint),
                             )
                         }
                         instances
                     })
                     .collect();
-                let num_instances = instances.len() as u32;
-                let instance_buffer =
-                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some("Index Buffer"),
-                        contents: bytemuck::cast_slice(&instances),
-                        usage: wgpu::BufferUsages::VERTEX,
-                    });
-                Model {
-                    meshes,
-                    materials: materials.clone(),
-                    instances,
-                    instance_buffer,
-                    num_instances,
+                Model::new(meshes, instances, "Instance Buffer", device)
+            })
+            .collect()
+    }
+
+    fn pickup_model(material: &str, instances: Vec<RawInstance>, device: &Device) -> Model {
+        let half = Vector3::repeat(Self::PICKUP_SIZE / 2.0);
+        let (mut vertices, mut indices) = (vec![], vec![]);
+        Primitives::append_cuboid(
+            Point3::from(-half),
+            Point3::from(half),
+            &mut vertices,
+            &mut indices,
+        );
+        let meshes = vec![Self::gen_mesh(
+            "Pickup",
+            &mut vertices,
+            &indices,
+            material,
+            device,
+        )];
+        Model::new(meshes, instances, "Pickup Instance Buffer", device)
+    }
+
+    /// Builds a mover, skipping it when it refers to models or boxes that don't exist.
+    fn mover(mover: &MoverLoader, model_count: usize, box_count: usize) -> Option<Mover> {
+        if let Some(model) = mover.model
+            && model >= model_count
+        {
+            error!("Mover uses unknown model {model}");
+            return None;
+        }
+        if let Some(index) = mover
+            .bounding_boxes
+            .iter()
+            .find(|&&index| index >= box_count)
+        {
+            error!("Mover uses unknown bounding box {index}");
+            return None;
+        }
+        let keyframes = mover
+            .keyframes
+            .iter()
+            .map(|keyframe| Keyframe {
+                offset: Vector3::from(keyframe.offset),
+                duration: keyframe.duration,
+            })
+            .collect();
+        let mut built = Mover::new(keyframes, mover.easing, mover.trigger.clone(), mover.wait);
+        built.model = mover.model;
+        built.bounding_boxes = mover.bounding_boxes.clone();
+        built.key = mover.key.clone();
+        Some(built)
+    }
+
+    fn wad_model(
+        wad_geometry: WadGeometry,
+        materials: &HashMap<String, Material>,
+        device: &Device,
+    ) -> Model {
+        let meshes: Vec<Mesh> = wad_geometry
+            .meshes
+            .into_iter()
+            .filter(|wad_mesh| {
+                let exists = materials.contains_key(&wad_mesh.material);
+                if !exists {
+                    error!("WAD mesh uses unknown material {}", wad_mesh.material);
                 }
+                exists
+            })
+            .map(|mut wad_mesh| {
+                Self::gen_mesh(
+                    &wad_mesh.name,
+                    &mut wad_mesh.vertices,
+                    &wad_mesh.indices,
+                    &wad_mesh.material,
+                    device,
+                )
             })
             .collect();
+        let instances = vec![
+            Instance {
+                position: Vector3::zeros(),
+                rotation: Matrix3::identity(),
+            }
+            .to_raw(),
+        ];
+        Model::new(meshes, instances, "WAD Instance Buffer", device)
+    }
 
-        Map {
-            skybox_textures,
-            collision_manager,
-            lights,
-            debug_lines,
-            models,
+    /// Places a skinned model with joints starting at `joint_offset` in the joint palette.
+    fn character(
+        character: &CharacterLoader,
+        joint_offset: u32,
+        model: usize,
+        device: &Device,
+    ) -> Result<(Model, Character), Box<dyn Error>> {
+        let skinned_model = SkinnedModel::from_gltf(&character.model, &character.material, device)?;
+        let mut animator = Animator::new(skinned_model.skeleton, skinned_model.clips);
+        if let Some(animation) = &character.animation
+            && !animator.play(animation, true, Duration::ZERO)
+        {
+            error!("{} has no animation {animation}", character.model);
         }
+        let placed = Character {
+            joint_offset,
+            animator,
+            model,
+            position: Point3::from(character.position),
+            yaw: character.yaw,
+            enemy: character.enemy.clone().map(Enemy::new),
+        };
+        let mut meshes = skinned_model.meshes;
+        // Culling uses the bind pose, leave room for limbs moving outside of it.
+        for mesh in &mut meshes {
+            mesh.bounds = mesh.bounds.expanded(Self::SKINNED_BOUNDS_MARGIN);
+        }
+        // Enemies are moved around every frame.
+        let model = Model::new(
+            meshes,
+            vec![placed.instance()],
+            "Character Instance Buffer",
+            device,
+        );
+        Ok((
+            Model {
+                skinned: true,
+                ..model
+            },
+            placed,
+        ))
     }
     fn bounding_box_to_line_vertices(bbox: &BoundingBox, color: [f32; 3]) -> Vec<LineVertex> {
         let top_left = bbox.top_left;
@@ -282,35 +861,101 @@ impl MapLoader {
     }
 
     fn load_texture(
-        filename: &str,
-        normal_filename: &str,
+        material: &MaterialLoader,
+        critical: bool,
         device: &Device,
         queue: &Queue,
         bind_group_layout: &BindGroupLayout,
+        asset_loader: &mut AssetLoader,
+        texture_array: Option<&mut TextureArrayBuilder>,
     ) -> Material {
+        let (filename, normal_filename) = (&material.texture_map, &material.normal_map);
+        let reflectivity = material.reflectivity;
         let diffuse_texture =
-            super::texture::Texture::from_file(filename, device, queue, Some(filename));
-        let normal_texture = super::texture::Texture::from_file(
-            normal_filename,
+            Texture::from_color(Self::PLACEHOLDER_DIFFUSE, device, queue, Some(filename));
+        let normal_texture = Texture::from_color(
+            Self::PLACEHOLDER_NORMAL,
             device,
             queue,
             Some(normal_filename),
         );
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{filename} Params Buffer")),
+            contents: bytemuck::cast_slice(&[MaterialUniform::new(reflectivity)]),
+            // Layers are written once the level's texture array is in.
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
         let bind_group = TextureBuilder::create_bind_group(
             device,
             &diffuse_texture,
             &normal_texture,
+            &params_buffer,
             bind_group_layout,
         );
 
+        let textures = match texture_array {
+            Some(texture_array) => MaterialTextures::Layers {
+                diffuse: texture_array.add(filename),
+                normal: texture_array.add(normal_filename),
+            },
+            // A flat normal is a reasonable stand in, so only the diffuse map holds up loading.
+            None => MaterialTextures::Separate {
+                diffuse: asset_loader.load_image(filename, critical),
+                normal: asset_loader.load_image(normal_filename, false),
+            },
+        };
         Material {
             name: String::from(filename),
             diffuse_texture,
             normal_texture,
             bind_group,
+            textures,
+            diffuse_pending: true,
+            normal_pending: true,
+            blend_mode: material.blend,
+            reflectivity,
+            params_buffer,
         }
     }
-    fn gen_mesh(
+
+    /// The levels a map mesh lists. Levels giving neither indices nor a cell size, or
+    /// pointing past the mesh's vertices, are skipped.
+    fn mesh_lods(mesh: &MeshLoader, vertices: &[Vertex], device: &Device) -> Vec<MeshLod> {
+        mesh.lods
+            .iter()
+            .filter_map(|lod| {
+                let indices = match (&lod.indices, lod.cell_size) {
+                    (Some(indices), _) => indices.clone(),
+                    (None, Some(cell_size)) => {
+                        MeshLod::decimate(vertices, &mesh.indices, cell_size)
+                    }
+                    (None, None) => {
+                        error!(
+                            "LOD of mesh {} has neither indices nor a cell size",
+                            mesh.name
+                        );
+                        return None;
+                    }
+                };
+                if indices
+                    .iter()
+                    .any(|&index| index as usize >= vertices.len())
+                {
+                    error!("LOD of mesh {} uses unknown vertices", mesh.name);
+                    return None;
+                }
+                Some(MeshLod::new(
+                    &mesh.name,
+                    None,
+                    &indices,
+                    lod.screen_size,
+                    device,
+                ))
+            })
+            .collect()
+    }
+
+    pub(crate) fn gen_mesh(
         name: &str,
         vertices: &mut [Vertex],
         indices: &[u16],
@@ -367,6 +1012,10 @@ impl MapLoader {
             index_buffer,
             num_elements: num_indices,
             material: String::from(material),
+            bounds: Aabb::from_vertices(vertices),
+            blend_mode: BlendMode::Opaque,
+            lods: MeshLod::generate(name, vertices, indices, device),
+            lod: 0,
         }
     }
 }
@@ -1,15 +1,35 @@
 #![allow(dead_code)]
-use std::{collections::HashMap, sync::Arc};
+use std::collections::HashMap;
 
+use asset_loader::{AssetLoader, TextureHandle};
+use bounds::Aabb;
+use lod::MeshLod;
 use model_instance::RawInstance;
-use wgpu::{Buffer, RenderPass};
+use nalgebra::{Matrix4, Point3, Vector3};
+use serde::{Deserialize, Serialize};
+use texture::{Texture, TextureBuilder};
+use texture_array::TextureArray;
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroupLayout, Buffer, Device, Queue, RenderPass};
 
+use crate::camera::frustum::Frustum;
+
+pub mod asset_cache;
+pub mod asset_loader;
+pub mod bounds;
 pub mod cube_texture;
 pub mod depth_texture;
+pub mod instancing;
+pub mod lod;
 pub mod map_loader;
 pub mod model_instance;
+pub mod primitives;
+pub mod skeleton;
+pub mod skinned_model;
 pub mod texture;
+pub mod texture_array;
 pub mod vertex;
+pub mod wad_loader;
 
 pub struct Mesh {
     pub name: String,
@@ -17,6 +37,41 @@ pub struct Mesh {
     pub index_buffer: Buffer,
     pub num_elements: u32,
     pub material: String,
+    // Local space bounds of the vertices.
+    pub bounds: Aabb,
+    // Copied from the material so depth only passes can tell what to skip.
+    pub blend_mode: BlendMode,
+    // Coarser levels, finest first, and the one drawn: 0 is the full mesh, n is `lods[n - 1]`.
+    pub lods: Vec<MeshLod>,
+    pub lod: usize,
+}
+
+/// How a material combines with what is behind it. Each mode draws with its own pipeline.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum BlendMode {
+    #[default]
+    Opaque,
+    // Cut out wherever the diffuse alpha is below half, like grates and foliage.
+    Masked,
+    // Blended by the diffuse alpha and drawn back to front without writing depth.
+    Translucent,
+}
+
+/// Where a material's textures come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialTextures {
+    // Loaded on their own, like the materials of streamed chunks.
+    Separate {
+        diffuse: TextureHandle,
+        normal: TextureHandle,
+    },
+    // Layers of the level's texture array, which the level holds the reference to.
+    Layers {
+        diffuse: u32,
+        normal: u32,
+    },
 }
 
 pub struct Material {
@@ -24,37 +79,317 @@ pub struct Material {
     pub diffuse_texture: texture::Texture,
     pub normal_texture: texture::Texture,
     pub bind_group: wgpu::BindGroup,
+    pub textures: MaterialTextures,
+    // Set while the material still draws with a placeholder for that texture.
+    pub diffuse_pending: bool,
+    pub normal_pending: bool,
+    pub blend_mode: BlendMode,
+    // Share of the nearest reflection probe mirrored head on, 0 for matte surfaces.
+    pub reflectivity: f32,
+    pub params_buffer: Buffer,
+}
+
+/// The material values shader.wgsl reads besides its textures.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub reflectivity: f32,
+    // Layers of the level's texture array, or NO_LAYER to sample the material's own textures.
+    pub diffuse_layer: u32,
+    pub normal_layer: u32,
+    pub _padding: f32,
 }
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
-    pub materials: Arc<HashMap<String, Material>>,
     pub instances: Vec<RawInstance>,
     pub instance_buffer: Buffer,
     pub num_instances: u32,
+    // World space bounds of each mesh across every instance.
+    pub mesh_bounds: Vec<Aabb>,
+    // Skinned models are posed every frame, so they are left out of cached shadow maps.
+    pub skinned: bool,
+}
+
+impl MaterialUniform {
+    pub const NO_LAYER: u32 = u32::MAX;
+
+    pub fn new(reflectivity: f32) -> Self {
+        Self {
+            reflectivity,
+            diffuse_layer: Self::NO_LAYER,
+            normal_layer: Self::NO_LAYER,
+            _padding: 0.0,
+        }
+    }
+}
+
+impl Material {
+    /// Swaps in any of this material's textures that finished loading, layered ones once
+    /// `level_textures` is in. Returns true when the bind group was rebuilt.
+    pub fn resolve(
+        &mut self,
+        textures: &HashMap<TextureHandle, Texture>,
+        level_textures: Option<&TextureArray>,
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+    ) -> bool {
+        let mut changed = false;
+        match self.textures {
+            MaterialTextures::Separate { diffuse, normal } => {
+                if self.diffuse_pending
+                    && let Some(texture) = textures.get(&diffuse)
+                {
+                    self.diffuse_texture = texture.clone();
+                    self.diffuse_pending = false;
+                    changed = true;
+                }
+                if self.normal_pending
+                    && let Some(texture) = textures.get(&normal)
+                {
+                    self.normal_texture = texture.clone();
+                    self.normal_pending = false;
+                    changed = true;
+                }
+            }
+            MaterialTextures::Layers { diffuse, normal } => {
+                if self.diffuse_pending
+                    && let Some(level_textures) = level_textures
+                {
+                    // The main pass samples the array itself, passes binding only the
+                    // material read the same layers through their own views.
+                    self.diffuse_texture = level_textures.layer(diffuse);
+                    self.normal_texture = level_textures.layer(normal);
+                    self.diffuse_pending = false;
+                    self.normal_pending = false;
+                    let uniform = MaterialUniform {
+                        diffuse_layer: diffuse,
+                        normal_layer: normal,
+                        ..MaterialUniform::new(self.reflectivity)
+                    };
+                    queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[uniform]));
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.bind_group = TextureBuilder::create_bind_group(
+                device,
+                &self.diffuse_texture,
+                &self.normal_texture,
+                &self.params_buffer,
+                layout,
+            );
+        }
+        changed
+    }
+
+    /// Drops the references this material holds on its own textures.
+    pub fn release_textures(&self, asset_loader: &mut AssetLoader) {
+        if let MaterialTextures::Separate { diffuse, normal } = self.textures {
+            asset_loader.release(diffuse);
+            asset_loader.release(normal);
+        }
+    }
 }
 
 impl Model {
-    pub fn draw(&self, render_pass: &mut RenderPass) {
+    /// Draws `meshes` once per instance, each in one draw call. The instance buffer allows
+    /// `COPY_DST` so instances can be moved or hidden later.
+    pub fn new(
+        meshes: Vec<Mesh>,
+        instances: Vec<RawInstance>,
+        label: &str,
+        device: &Device,
+    ) -> Self {
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let mesh_bounds = Self::compute_mesh_bounds(&meshes, &instances);
+        Self {
+            num_instances: instances.len() as u32,
+            meshes,
+            instances,
+            instance_buffer,
+            mesh_bounds,
+            skinned: false,
+        }
+    }
+
+    pub fn compute_mesh_bounds(meshes: &[Mesh], instances: &[RawInstance]) -> Vec<Aabb> {
+        meshes
+            .iter()
+            .map(|mesh| {
+                instances
+                    .iter()
+                    .filter(|instance| !instance.is_hidden())
+                    .map(|instance| mesh.bounds.transformed(&Matrix4::from(instance.model_mat)))
+                    .reduce(|a, b| a.union(&b))
+                    .unwrap_or(mesh.bounds)
+            })
+            .collect()
+    }
+
+    /// Shifts every instance by `delta` and uploads them. The instance buffer needs
+    /// `COPY_DST`.
+    pub fn translate(&mut self, delta: Vector3<f32>, queue: &Queue) {
+        for instance in self
+            .instances
+            .iter_mut()
+            .filter(|instance| !instance.is_hidden())
+        {
+            for (axis, offset) in delta.iter().enumerate() {
+                instance.model_mat[3][axis] += offset;
+            }
+        }
+        for bounds in &mut self.mesh_bounds {
+            bounds.min += delta;
+            bounds.max += delta;
+        }
+        queue.write_buffer(
+            &self.instance_buffer,
+            0,
+            bytemuck::cast_slice(&self.instances),
+        );
+    }
+
+    /// Replaces one instance and uploads it. The instance buffer needs `COPY_DST`.
+    pub fn set_instance(&mut self, index: usize, instance: RawInstance, queue: &Queue) {
+        self.instances[index] = instance;
+        self.mesh_bounds = Self::compute_mesh_bounds(&self.meshes, &self.instances);
+        let offset = (index * std::mem::size_of::<RawInstance>()) as wgpu::BufferAddress;
+        queue.write_buffer(&self.instance_buffer, offset, bytemuck::bytes_of(&instance));
+    }
+
+    /// Copies each mesh's blend mode from its material.
+    pub fn apply_blend_modes(&mut self, materials: &HashMap<String, Material>) {
+        for mesh in &mut self.meshes {
+            if let Some(material) = materials.get(&mesh.material) {
+                mesh.blend_mode = material.blend_mode;
+            }
+        }
+    }
+
+    /// Picks each mesh's level from how much of the screen its bounds cover from `eye`. A
+    /// mesh drawn for a whole grid of instances switches all of them at once.
+    pub fn select_lods(&mut self, eye: Point3<f32>, focal_length: f32) {
+        for (mesh, bounds) in self.meshes.iter_mut().zip(&self.mesh_bounds) {
+            mesh.select_lod(bounds.screen_size(eye, focal_length));
+        }
+    }
+
+    /// Stops drawing one instance, leaving the rest of the batch alone.
+    pub fn hide_instance(&mut self, index: usize, queue: &Queue) {
+        self.set_instance(index, RawInstance::hidden(), queue);
+    }
+
+    fn visible_meshes<'a>(
+        &'a self,
+        frustum: &'a Frustum,
+    ) -> impl Iterator<Item = (&'a Mesh, &'a Aabb)> {
+        self.meshes
+            .iter()
+            .zip(&self.mesh_bounds)
+            .filter(|(_, bounds)| frustum.intersects_aabb(bounds))
+    }
+
+    /// Draws the visible meshes with the given blend mode. The pipeline for that mode has
+    /// to be set already.
+    pub fn draw(
+        &self,
+        render_pass: &mut RenderPass,
+        frustum: &Frustum,
+        materials: &HashMap<String, Material>,
+        blend_mode: BlendMode,
+    ) {
         render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        for mesh in &self.meshes {
-            render_pass.set_bind_group(
-                3,
-                &self.materials.get(&mesh.material).unwrap().bind_group,
-                &[],
-            );
-            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..mesh.num_elements, 0, 0..self.num_instances);
+        for (mesh, _) in self.visible_meshes(frustum) {
+            if mesh.blend_mode == blend_mode {
+                self.draw_mesh(render_pass, mesh, materials);
+            }
+        }
+    }
+
+    /// Visible translucent meshes with their distance from `eye`, for sorting.
+    pub fn translucent_meshes<'a>(
+        &'a self,
+        frustum: &'a Frustum,
+        eye: Point3<f32>,
+    ) -> impl Iterator<Item = (f32, &'a Mesh)> {
+        self.visible_meshes(frustum)
+            .filter(|(mesh, _)| mesh.blend_mode == BlendMode::Translucent)
+            .map(move |(mesh, bounds)| (nalgebra::distance(&bounds.center(), &eye), mesh))
+    }
+
+    /// Draws one of this model's meshes on its own, binding the instance buffer as well.
+    pub fn draw_single(
+        &self,
+        render_pass: &mut RenderPass,
+        mesh: &Mesh,
+        materials: &HashMap<String, Material>,
+    ) {
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        self.draw_mesh(render_pass, mesh, materials);
+    }
+
+    fn draw_mesh(
+        &self,
+        render_pass: &mut RenderPass,
+        mesh: &Mesh,
+        materials: &HashMap<String, Material>,
+    ) {
+        render_pass.push_debug_group(&mesh.name);
+        render_pass.set_bind_group(3, &materials.get(&mesh.material).unwrap().bind_group, &[]);
+        let (vertex_buffer, index_buffer, num_elements) = mesh.buffers();
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..num_elements, 0, 0..self.num_instances);
+        render_pass.pop_debug_group();
+    }
+
+    /// Draws the opaque meshes without materials, for depth only passes. Translucent meshes
+    /// are never drawn there since they would cover what shows through them, and masked ones
+    /// go through `draw_masked_geometry`.
+    pub fn draw_geometry(&self, render_pass: &mut RenderPass, frustum: &Frustum) {
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        for (mesh, _) in self.visible_meshes(frustum) {
+            if mesh.blend_mode == BlendMode::Opaque {
+                self.draw_mesh_geometry(render_pass, mesh);
+            }
         }
     }
 
-    pub fn draw_shadow(&self, render_pass: &mut RenderPass) {
+    /// Draws the masked meshes for depth only passes, binding each one's material at
+    /// `material_group` so the pipeline can cut out the same holes the main pass does.
+    pub fn draw_masked_geometry(
+        &self,
+        render_pass: &mut RenderPass,
+        frustum: &Frustum,
+        materials: &HashMap<String, Material>,
+        material_group: u32,
+    ) {
         render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        for mesh in &self.meshes {
-            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..mesh.num_elements, 0, 0..self.num_instances);
+        for (mesh, _) in self.visible_meshes(frustum) {
+            if mesh.blend_mode != BlendMode::Masked {
+                continue;
+            }
+            let Some(material) = materials.get(&mesh.material) else {
+                continue;
+            };
+            render_pass.set_bind_group(material_group, &material.bind_group, &[]);
+            self.draw_mesh_geometry(render_pass, mesh);
         }
     }
+
+    fn draw_mesh_geometry(&self, render_pass: &mut RenderPass, mesh: &Mesh) {
+        render_pass.push_debug_group(&mesh.name);
+        let (vertex_buffer, index_buffer, num_elements) = mesh.buffers();
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..num_elements, 0, 0..self.num_instances);
+        render_pass.pop_debug_group();
+    }
 }
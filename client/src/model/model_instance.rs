@@ -11,19 +11,46 @@ pub struct Instance {
 pub struct RawInstance {
     pub model_mat: [[f32; 4]; 4],
     pub normal_mat: [[f32; 3]; 3],
+    // First joint matrix of this instance in the joint palette, or `NO_SKIN`.
+    pub joint_offset: u32,
+    // Multiplies the diffuse colour and alpha, so copies of one prop can look different.
+    pub tint: [f32; 4],
 }
 
 impl Instance {
     pub fn to_raw(&self) -> RawInstance {
+        self.to_skinned_raw(RawInstance::NO_SKIN)
+    }
+
+    pub fn to_skinned_raw(&self, joint_offset: u32) -> RawInstance {
         RawInstance {
             model_mat: (Matrix4::new_translation(&self.position) * self.rotation.to_homogeneous())
                 .into(),
             normal_mat: self.rotation.into(),
+            joint_offset,
+            tint: RawInstance::NO_TINT,
         }
     }
 }
 
 impl RawInstance {
+    pub const NO_SKIN: u32 = u32::MAX;
+    pub const NO_TINT: [f32; 4] = [1.0; 4];
+
+    pub fn with_tint(self, tint: [f32; 4]) -> Self {
+        Self { tint, ..self }
+    }
+
+    /// An instance collapsed to a point, which draws nothing while keeping the other
+    /// instances where they are in the buffer.
+    pub fn hidden() -> Self {
+        Self::zeroed()
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.model_mat[3][3] == 0.0
+    }
+
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
         wgpu::VertexBufferLayout {
@@ -65,6 +92,17 @@ impl RawInstance {
                     shader_location: 11,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                // 13 and 14 are the skinning attributes of `Vertex`.
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 26]>() as wgpu::BufferAddress,
+                    shader_location: 15,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -0,0 +1,47 @@
+use nalgebra::{Point3, Vector3};
+
+use super::vertex::Vertex;
+
+pub struct Primitives;
+
+impl Primitives {
+    /// Appends an axis aligned box to `vertices`/`indices` with outward facing normals and one
+    /// texture repeat per face.
+    pub fn append_cuboid(
+        min: Point3<f32>,
+        max: Point3<f32>,
+        vertices: &mut Vec<Vertex>,
+        indices: &mut Vec<u16>,
+    ) {
+        let faces: [(Vector3<f32>, Vector3<f32>, Vector3<f32>); 6] = [
+            (Vector3::x(), -Vector3::z(), Vector3::y()),
+            (-Vector3::x(), Vector3::z(), Vector3::y()),
+            (Vector3::y(), Vector3::x(), -Vector3::z()),
+            (-Vector3::y(), Vector3::x(), Vector3::z()),
+            (Vector3::z(), Vector3::x(), Vector3::y()),
+            (-Vector3::z(), -Vector3::x(), Vector3::y()),
+        ];
+        let center = Point3::from((min.coords + max.coords) / 2.0);
+        let half = (max - min) / 2.0;
+        for (normal, tangent, bitangent) in faces {
+            let base = vertices.len() as u16;
+            let face_center = center + normal.component_mul(&half);
+            let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+            for (u, v) in corners {
+                let position = face_center
+                    + tangent.component_mul(&half) * u
+                    + bitangent.component_mul(&half) * v;
+                vertices.push(Vertex {
+                    position: position.into(),
+                    tex_coords: [(u + 1.0) / 2.0, (1.0 - v) / 2.0],
+                    normal: normal.into(),
+                    tangent: tangent.into(),
+                    bitangent: bitangent.into(),
+                    joints: [0; 4],
+                    weights: [0.0; 4],
+                });
+            }
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+}
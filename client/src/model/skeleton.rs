@@ -0,0 +1,143 @@
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+
+#[derive(Debug, Clone, Copy)]
+pub struct JointTransform {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub inverse_bind: Matrix4<f32>,
+    pub rest: JointTransform,
+}
+
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    // Joint indices ordered so every parent comes before its children.
+    pub order: Vec<usize>,
+}
+
+pub enum ChannelValues {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<UnitQuaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+}
+
+pub struct Channel {
+    pub joint: usize,
+    pub times: Vec<f32>,
+    pub values: ChannelValues,
+    // Holds each key until the next one instead of interpolating.
+    pub step: bool,
+}
+
+pub struct AnimationClip {
+    pub name: String,
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl JointTransform {
+    pub fn to_matrix(self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    pub fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: Self::nlerp(&self.rotation, &other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+
+    /// Normalized lerp along the shorter arc, close enough to slerp between nearby keys and
+    /// stable for opposite ones.
+    fn nlerp(a: &UnitQuaternion<f32>, b: &UnitQuaternion<f32>, t: f32) -> UnitQuaternion<f32> {
+        let mut target = b.into_inner();
+        if a.coords.dot(&target.coords) < 0.0 {
+            target = -target;
+        }
+        UnitQuaternion::new_normalize(a.into_inner().lerp(&target, t))
+    }
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Self {
+        let depth = |mut joint: usize| {
+            let mut depth = 0;
+            while let Some(parent) = joints[joint].parent {
+                joint = parent;
+                depth += 1;
+            }
+            depth
+        };
+        let mut order: Vec<usize> = (0..joints.len()).collect();
+        order.sort_by_key(|&joint| depth(joint));
+        Self { joints, order }
+    }
+
+    pub fn rest_pose(&self) -> Vec<JointTransform> {
+        self.joints.iter().map(|joint| joint.rest).collect()
+    }
+
+    /// Matrices taking bind pose vertices to their posed position, one per joint.
+    pub fn skinning_matrices(&self, pose: &[JointTransform]) -> Vec<Matrix4<f32>> {
+        let mut globals = vec![Matrix4::identity(); self.joints.len()];
+        for &index in &self.order {
+            let local = pose[index].to_matrix();
+            globals[index] = match self.joints[index].parent {
+                Some(parent) => globals[parent] * local,
+                None => local,
+            };
+        }
+        globals
+            .iter()
+            .zip(&self.joints)
+            .map(|(global, joint)| global * joint.inverse_bind)
+            .collect()
+    }
+}
+
+impl Channel {
+    /// Surrounding keyframes of `time` and how far between them it is.
+    fn keys(&self, time: f32) -> (usize, usize, f32) {
+        let next = self.times.partition_point(|&t| t <= time);
+        let last = self.times.len() - 1;
+        let a = next.saturating_sub(1).min(last);
+        let b = next.min(last);
+        let span = self.times[b] - self.times[a];
+        let t = if span > 0.0 && !self.step {
+            ((time - self.times[a]) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (a, b, t)
+    }
+}
+
+impl AnimationClip {
+    /// Overwrites the animated parts of `pose` with the clip at `time`.
+    pub fn sample(&self, time: f32, pose: &mut [JointTransform]) {
+        for channel in &self.channels {
+            if channel.times.is_empty() {
+                continue;
+            }
+            let (a, b, t) = channel.keys(time);
+            let joint = &mut pose[channel.joint];
+            match &channel.values {
+                ChannelValues::Translation(values) => {
+                    joint.translation = values[a].lerp(&values[b], t);
+                }
+                ChannelValues::Rotation(values) => {
+                    joint.rotation = JointTransform::nlerp(&values[a], &values[b], t);
+                }
+                ChannelValues::Scale(values) => joint.scale = values[a].lerp(&values[b], t),
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use gltf::animation::{Interpolation, util::ReadOutputs};
+use log::warn;
+use nalgebra::{Matrix4, Quaternion, UnitQuaternion, Vector3};
+use wgpu::Device;
+
+use super::Mesh;
+use super::lod::MeshLod;
+use super::map_loader::MapLoader;
+use super::skeleton::{AnimationClip, Channel, ChannelValues, Joint, JointTransform, Skeleton};
+use super::vertex::Vertex;
+
+/// Skinned meshes, skeleton and animation clips imported from a glTF file.
+pub struct SkinnedModel {
+    pub meshes: Vec<Mesh>,
+    pub skeleton: Arc<Skeleton>,
+    pub clips: Arc<Vec<AnimationClip>>,
+}
+
+impl SkinnedModel {
+    /// Imports the first skin of `path` along with every mesh and animation, all meshes are
+    /// drawn with `material`.
+    pub fn from_gltf(path: &str, material: &str, device: &Device) -> Result<Self, Box<dyn Error>> {
+        // The web build can't resolve buffers next to the file, so it needs a self contained glb.
+        #[cfg(target_arch = "wasm32")]
+        let (document, buffers, _) = gltf::import_slice(crate::platform::read(path)?)?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let (document, buffers, _) = gltf::import(path)?;
+        let skin = document
+            .skins()
+            .next()
+            .ok_or_else(|| format!("{path} has no skin"))?;
+
+        // glTF joints are nodes, vertices and channels refer to them by their index in the skin.
+        let joint_nodes: Vec<gltf::Node> = skin.joints().collect();
+        let joint_index: HashMap<usize, usize> = joint_nodes
+            .iter()
+            .enumerate()
+            .map(|(joint, node)| (node.index(), joint))
+            .collect();
+        let mut parents = vec![None; joint_nodes.len()];
+        for (joint, node) in joint_nodes.iter().enumerate() {
+            for child in node.children() {
+                if let Some(&child) = joint_index.get(&child.index()) {
+                    parents[child] = Some(joint);
+                }
+            }
+        }
+        let inverse_binds: Vec<Matrix4<f32>> = skin
+            .reader(|buffer| Some(&buffers[buffer.index()]))
+            .read_inverse_bind_matrices()
+            .map(|matrices| matrices.map(Matrix4::from).collect())
+            .unwrap_or_else(|| vec![Matrix4::identity(); joint_nodes.len()]);
+        let joints = joint_nodes
+            .iter()
+            .zip(parents)
+            .zip(inverse_binds)
+            .map(|((node, parent), inverse_bind)| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                Joint {
+                    name: node.name().unwrap_or_default().to_string(),
+                    parent,
+                    inverse_bind,
+                    rest: JointTransform {
+                        translation: Vector3::from(translation),
+                        rotation: Self::rotation(rotation),
+                        scale: Vector3::from(scale),
+                    },
+                }
+            })
+            .collect();
+
+        let mut meshes = vec![];
+        // Meshes named like `body_LOD1` are coarser levels of `body`.
+        let mut levels: HashMap<String, Vec<MeshLod>> = HashMap::new();
+        for mesh in document.meshes() {
+            let (base_name, level) = Self::lod_name(mesh.name().unwrap_or(path));
+            for (i, primitive) in mesh.primitives().enumerate() {
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let positions: Vec<[f32; 3]> = reader
+                    .read_positions()
+                    .ok_or_else(|| format!("{path} mesh is missing positions"))?
+                    .collect();
+                if positions.len() > u16::MAX as usize {
+                    return Err(format!("{path} mesh has more than {} vertices", u16::MAX).into());
+                }
+                let mut normals = reader.read_normals().into_iter().flatten();
+                let mut tex_coords = reader
+                    .read_tex_coords(0)
+                    .map(|coords| coords.into_f32())
+                    .into_iter()
+                    .flatten();
+                let mut vertex_joints = reader
+                    .read_joints(0)
+                    .map(|joints| joints.into_u16())
+                    .into_iter()
+                    .flatten();
+                let mut weights = reader
+                    .read_weights(0)
+                    .map(|weights| weights.into_f32())
+                    .into_iter()
+                    .flatten();
+                let mut vertices: Vec<Vertex> = positions
+                    .into_iter()
+                    .map(|position| Vertex {
+                        position,
+                        tex_coords: tex_coords.next().unwrap_or_default(),
+                        normal: normals.next().unwrap_or([0.0, 1.0, 0.0]),
+                        tangent: [0.0; 3],
+                        bitangent: [0.0; 3],
+                        joints: vertex_joints.next().unwrap_or_default(),
+                        weights: weights.next().unwrap_or([1.0, 0.0, 0.0, 0.0]),
+                    })
+                    .collect();
+                // Vertex count was checked above, so in range indices fit in u16.
+                let indices: Vec<u16> = match reader.read_indices() {
+                    Some(indices) => indices
+                        .into_u32()
+                        .map(|index| {
+                            u16::try_from(index)
+                                .ok()
+                                .filter(|&index| (index as usize) < vertices.len())
+                                .ok_or_else(|| {
+                                    format!("{path} mesh has an index past its vertices")
+                                })
+                        })
+                        .collect::<Result<_, _>>()?,
+                    None => (0..vertices.len() as u16).collect(),
+                };
+                let name = format!("{base_name} {i}");
+                let built = MapLoader::gen_mesh(&name, &mut vertices, &indices, material, device);
+                if level == 0 {
+                    meshes.push(built);
+                } else {
+                    levels.entry(name).or_default().push(MeshLod {
+                        vertex_buffer: Some(built.vertex_buffer),
+                        index_buffer: built.index_buffer,
+                        num_elements: built.num_elements,
+                        screen_size: MeshLod::default_screen_size(level),
+                    });
+                }
+            }
+        }
+        for mesh in &mut meshes {
+            if let Some(lods) = levels.remove(&mesh.name) {
+                mesh.set_lods(lods);
+            }
+        }
+        for name in levels.keys() {
+            warn!("{path} has LOD levels for {name} but no such mesh");
+        }
+
+        let clips = document
+            .animations()
+            .enumerate()
+            .map(|(i, animation)| {
+                let channels: Vec<Channel> = animation
+                    .channels()
+                    .filter_map(|channel| {
+                        let joint = *joint_index.get(&channel.target().node().index())?;
+                        let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+                        let times: Vec<f32> = reader.read_inputs()?.collect();
+                        let interpolation = channel.sampler().interpolation();
+                        let values = match reader.read_outputs()? {
+                            ReadOutputs::Translations(values) => ChannelValues::Translation(
+                                Self::keyframes(values.map(Vector3::from), interpolation),
+                            ),
+                            ReadOutputs::Rotations(values) => {
+                                ChannelValues::Rotation(Self::keyframes(
+                                    values.into_f32().map(Self::rotation),
+                                    interpolation,
+                                ))
+                            }
+                            ReadOutputs::Scales(values) => ChannelValues::Scale(Self::keyframes(
+                                values.map(Vector3::from),
+                                interpolation,
+                            )),
+                            ReadOutputs::MorphTargetWeights(_) => return None,
+                        };
+                        Some(Channel {
+                            joint,
+                            times,
+                            values,
+                            step: interpolation == Interpolation::Step,
+                        })
+                    })
+                    .collect();
+                let duration = channels
+                    .iter()
+                    .filter_map(|channel| channel.times.last().copied())
+                    .fold(0.0, f32::max);
+                AnimationClip {
+                    name: animation
+                        .name()
+                        .map(String::from)
+                        .unwrap_or_else(|| format!("animation {i}")),
+                    duration,
+                    channels,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            meshes,
+            skeleton: Arc::new(Skeleton::new(joints)),
+            clips: Arc::new(clips),
+        })
+    }
+
+    /// Splits an `_LOD{n}` suffix off a mesh name, the full mesh being level 0.
+    fn lod_name(name: &str) -> (&str, u32) {
+        name.rsplit_once("_LOD")
+            .and_then(|(base, level)| Some((base, level.parse().ok()?)))
+            .unwrap_or((name, 0))
+    }
+
+    fn rotation([x, y, z, w]: [f32; 4]) -> UnitQuaternion<f32> {
+        UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z))
+    }
+
+    /// Cubic spline keys are stored as in tangent, value, out tangent, only the value is kept
+    /// and the curve is approximated linearly.
+    fn keyframes<T>(values: impl Iterator<Item = T>, interpolation: Interpolation) -> Vec<T> {
+        match interpolation {
+            Interpolation::CubicSpline => values.skip(1).step_by(3).collect(),
+            _ => values.collect(),
+        }
+    }
+}
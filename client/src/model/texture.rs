@@ -1,9 +1,9 @@
-use image::GenericImageView;
-use std::fs;
+use image::{Rgba, RgbaImage};
 use wgpu::{BindGroup, BindGroupLayout, Device, Extent3d, Queue};
 
 pub struct TextureBuilder;
 
+#[derive(Clone)]
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
@@ -46,6 +46,16 @@ impl TextureBuilder {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
             label: Some("texture_bind_group_layout"),
         })
@@ -55,6 +65,7 @@ impl TextureBuilder {
         device: &Device,
         diffuse_texture: &Texture,
         normal_texture: &Texture,
+        params_buffer: &wgpu::Buffer,
         texture_bind_group_layout: &BindGroupLayout,
     ) -> BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -76,6 +87,10 @@ impl TextureBuilder {
                     binding: 3,
                     resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: params_buffer.as_entire_binding(),
+                },
             ],
             label: Some("diffuse_bind_group"),
         })
@@ -83,11 +98,23 @@ impl TextureBuilder {
 }
 
 impl Texture {
-    pub fn from_file(filename: &str, device: &Device, queue: &Queue, label: Option<&str>) -> Self {
-        let file_bytes = fs::read(filename).expect("Failed to read image file");
-        let image = image::load_from_memory(&file_bytes).expect("Failed to load image");
-        let rgba = image.to_rgba8();
-        let dimensions = image.dimensions();
+    /// 1x1 texture used while the real image is still loading.
+    pub fn from_color(color: [u8; 4], device: &Device, queue: &Queue, label: Option<&str>) -> Self {
+        Self::from_rgba(
+            &RgbaImage::from_pixel(1, 1, Rgba(color)),
+            device,
+            queue,
+            label,
+        )
+    }
+
+    pub fn from_rgba(
+        rgba: &RgbaImage,
+        device: &Device,
+        queue: &Queue,
+        label: Option<&str>,
+    ) -> Self {
+        let dimensions = rgba.dimensions();
         let size = Extent3d {
             width: dimensions.0,
             height: dimensions.1,
@@ -111,7 +138,7 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -120,8 +147,12 @@ impl Texture {
             size,
         );
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label,
+            ..Default::default()
+        });
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
             address_mode_u: wgpu::AddressMode::Repeat,
             address_mode_v: wgpu::AddressMode::Repeat,
             address_mode_w: wgpu::AddressMode::Repeat,
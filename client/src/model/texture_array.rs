@@ -0,0 +1,239 @@
+use image::{Rgba, RgbaImage, imageops::FilterType};
+use rayon::prelude::*;
+use wgpu::{Device, Extent3d, Queue};
+
+use super::texture::Texture;
+
+/// Collects the files a level packs into its texture array, handing out the layer each one
+/// ends up in before anything is loaded.
+#[derive(Default)]
+pub struct TextureArrayBuilder {
+    files: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct TextureArray {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub num_layers: u32,
+    pub mip_level_count: u32,
+}
+
+impl TextureArrayBuilder {
+    /// The layer `file` is packed into. Files added more than once share a layer.
+    pub fn add(&mut self, file: &str) -> u32 {
+        let layer = match self.files.iter().position(|existing| existing == file) {
+            Some(layer) => layer,
+            None => {
+                self.files.push(file.to_string());
+                self.files.len() - 1
+            }
+        };
+        layer as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The files in layer order, to load as one `TextureKind::Array`.
+    pub fn into_files(self) -> Vec<String> {
+        self.files
+    }
+}
+
+impl TextureArray {
+    /// Packs each image into one layer, in order, with a full mip chain. Layers take the size
+    /// of the first image and any other size is scaled to match.
+    pub fn from_images(
+        images: Vec<RgbaImage>,
+        device: &Device,
+        queue: &Queue,
+        label: Option<&str>,
+    ) -> Result<Self, String> {
+        let Some(first) = images.first() else {
+            return Err("Texture arrays need at least one layer".to_string());
+        };
+        let (w, h) = first.dimensions();
+        let limits = device.limits();
+        if w == 0 || h == 0 {
+            return Err("Texture array layers can't be empty".to_string());
+        }
+        if w.max(h) > limits.max_texture_dimension_2d {
+            return Err(format!(
+                "{w}x{h} layers are larger than the {} the device supports",
+                limits.max_texture_dimension_2d
+            ));
+        }
+        if images.len() > limits.max_texture_array_layers as usize {
+            return Err(format!(
+                "{} layers are more than the {} the device supports",
+                images.len(),
+                limits.max_texture_array_layers
+            ));
+        }
+
+        let mip_level_count = Self::mip_level_count(w, h);
+        let mip_chains: Vec<Vec<RgbaImage>> = images
+            .into_par_iter()
+            .map(|image| {
+                let base = if image.dimensions() == (w, h) {
+                    image
+                } else {
+                    image::imageops::resize(&image, w, h, FilterType::Triangle)
+                };
+                Self::generate_mips(base, mip_level_count)
+            })
+            .collect();
+        Ok(Self::upload(&mip_chains, device, queue, label))
+    }
+
+    /// One 1x1 layer of `color`, bound while the level's textures are still loading.
+    pub fn from_color(color: [u8; 4], device: &Device, queue: &Queue, label: Option<&str>) -> Self {
+        let mip_chains = [vec![RgbaImage::from_pixel(1, 1, Rgba(color))]];
+        Self::upload(&mip_chains, device, queue, label)
+    }
+
+    /// A 2D view of one layer, for passes that bind a material's textures on their own.
+    pub fn layer(&self, layer: u32) -> Texture {
+        let view = self.texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        Texture {
+            texture: self.texture.clone(),
+            view,
+            sampler: self.sampler.clone(),
+        }
+    }
+
+    fn upload(
+        mip_chains: &[Vec<RgbaImage>],
+        device: &Device,
+        queue: &Queue,
+        label: Option<&str>,
+    ) -> Self {
+        let (w, h) = mip_chains[0][0].dimensions();
+        let mip_level_count = mip_chains[0].len() as u32;
+        let num_layers = mip_chains.len() as u32;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: num_layers,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, mips) in mip_chains.iter().enumerate() {
+            for (mip_level, mip) in mips.iter().enumerate() {
+                let (mip_w, mip_h) = mip.dimensions();
+                queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        aspect: wgpu::TextureAspect::All,
+                        texture: &texture,
+                        mip_level: mip_level as u32,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                    },
+                    mip,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * mip_w),
+                        rows_per_image: Some(mip_h),
+                    },
+                    Extent3d {
+                        width: mip_w,
+                        height: mip_h,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label,
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            array_layer_count: Some(num_layers),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            num_layers,
+            mip_level_count,
+        }
+    }
+
+    fn mip_level_count(width: u32, height: u32) -> u32 {
+        32 - width.max(height).max(1).leading_zeros()
+    }
+
+    fn generate_mips(base: RgbaImage, mip_level_count: u32) -> Vec<RgbaImage> {
+        let mut mips = Vec::with_capacity(mip_level_count as usize);
+        mips.push(base);
+        for _ in 1..mip_level_count {
+            let previous = mips.last().unwrap();
+            let (w, h) = previous.dimensions();
+            let next = image::imageops::resize(
+                previous,
+                (w / 2).max(1),
+                (h / 2).max(1),
+                FilterType::Triangle,
+            );
+            mips.push(next);
+        }
+        mips
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_files_share_a_layer() {
+        let mut builder = TextureArrayBuilder::default();
+        assert!(builder.is_empty());
+        assert_eq!(builder.add("bricks.png"), 0);
+        assert_eq!(builder.add("bricks_normal.png"), 1);
+        assert_eq!(builder.add("bricks.png"), 0);
+        assert_eq!(builder.add("sand.png"), 2);
+        assert_eq!(
+            builder.into_files(),
+            ["bricks.png", "bricks_normal.png", "sand.png"]
+        );
+    }
+
+    #[test]
+    fn mips_go_down_to_one_texel() {
+        assert_eq!(TextureArray::mip_level_count(2048, 1024), 12);
+        let mips = TextureArray::generate_mips(RgbaImage::new(4, 2), 3);
+        let sizes: Vec<_> = mips.iter().map(|mip| mip.dimensions()).collect();
+        assert_eq!(sizes, [(4, 2), (2, 1), (1, 1)]);
+    }
+}
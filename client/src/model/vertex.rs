@@ -11,6 +11,9 @@ pub struct Vertex {
     pub normal: [f32; 3],
     pub tangent: [f32; 3],
     pub bitangent: [f32; 3],
+    // Skinning influences, all weights are zero for static meshes.
+    pub joints: [u16; 4],
+    pub weights: [f32; 4],
 }
 
 #[repr(C)]
@@ -63,6 +66,18 @@ impl Vertex {
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                // Instances use locations 5 to 12.
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Uint16x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 14]>() + std::mem::size_of::<[u16; 4]>())
+                        as wgpu::BufferAddress,
+                    shader_location: 14,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
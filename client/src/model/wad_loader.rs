@@ -0,0 +1,957 @@
+use nalgebra::{Point3, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error};
+
+use crate::game::bounding_box::BoundingBox;
+use crate::platform;
+
+use super::vertex::Vertex;
+
+/// Describes which level of a classic Doom WAD to import and how its texture names map onto
+/// the materials declared in the map file.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WadLoader {
+    pub file: String,
+    pub level: String,
+    #[serde(default = "WadLoader::default_scale")]
+    pub scale: f32,
+    #[serde(default = "WadLoader::default_texture_size")]
+    pub texture_size: f32,
+    #[serde(default = "WadLoader::default_collision_cell")]
+    pub collision_cell: f32,
+    #[serde(default)]
+    pub textures: HashMap<String, String>,
+    pub default_material: String,
+}
+
+pub struct WadGeometry {
+    pub meshes: Vec<WadMesh>,
+    pub bounding_boxes: Vec<BoundingBox>,
+    pub spawn_point: Option<Point3<f32>>,
+}
+
+pub struct WadMesh {
+    pub name: String,
+    pub material: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+}
+
+struct Lump {
+    name: String,
+    offset: usize,
+    size: usize,
+}
+
+pub struct WadFile {
+    data: Vec<u8>,
+    lumps: Vec<Lump>,
+}
+
+struct LineDef {
+    start: usize,
+    end: usize,
+    flags: u16,
+    front: Option<usize>,
+    back: Option<usize>,
+}
+
+struct SideDef {
+    x_offset: f32,
+    y_offset: f32,
+    upper: String,
+    lower: String,
+    middle: String,
+    sector: usize,
+}
+
+struct Sector {
+    floor_height: f32,
+    ceiling_height: f32,
+    floor_texture: String,
+    ceiling_texture: String,
+}
+
+struct Thing {
+    position: Vector2<f32>,
+    thing_type: i16,
+}
+
+pub struct WadLevel {
+    vertices: Vec<Vector2<f32>>,
+    linedefs: Vec<LineDef>,
+    sidedefs: Vec<SideDef>,
+    sectors: Vec<Sector>,
+    things: Vec<Thing>,
+}
+
+/// Accumulates triangles per material, splitting into multiple meshes so that u16 indices
+/// never overflow.
+struct MeshBatcher {
+    meshes: Vec<WadMesh>,
+    open_meshes: HashMap<String, usize>,
+}
+
+impl WadLoader {
+    const PLAYER_START: i16 = 1;
+    const SKY_FLAT: &'static str = "F_SKY1";
+    const IMPASSABLE: u16 = 0x0001;
+    const EYE_HEIGHT: f32 = 0.5;
+    const WALL_THICKNESS: f32 = 2.0;
+    const FLOOR_THICKNESS: f32 = 1.0;
+
+    fn default_scale() -> f32 {
+        1.0 / 64.0
+    }
+
+    fn default_texture_size() -> f32 {
+        64.0
+    }
+
+    fn default_collision_cell() -> f32 {
+        16.0
+    }
+
+    pub fn load(&self) -> Result<WadGeometry, Box<dyn Error>> {
+        self.validate()?;
+        let wad = WadFile::from_file(&self.file)?;
+        let level = wad.level(&self.level)?;
+        let mut batcher = MeshBatcher {
+            meshes: vec![],
+            open_meshes: HashMap::new(),
+        };
+        self.build_walls(&level, &mut batcher);
+        self.build_flats(&level, &mut batcher);
+
+        let mut bounding_boxes = self.build_wall_boxes(&level);
+        bounding_boxes.extend(self.build_floor_boxes(&level));
+
+        let spawn_point = level
+            .things
+            .iter()
+            .find(|thing| thing.thing_type == Self::PLAYER_START)
+            .map(|thing| {
+                let floor = level
+                    .sector_at(thing.position)
+                    .map(|sector| level.sectors[sector].floor_height)
+                    .unwrap_or(0.0);
+                let mut point = self.to_world(thing.position, floor);
+                point.y += Self::EYE_HEIGHT;
+                point
+            });
+
+        Ok(WadGeometry {
+            meshes: batcher.meshes,
+            bounding_boxes,
+            spawn_point,
+        })
+    }
+
+    // Each of these divides a length, and collision_cell also sets how many boxes get built.
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        for (name, value) in [
+            ("scale", self.scale),
+            ("texture_size", self.texture_size),
+            ("collision_cell", self.collision_cell),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(format!("WAD {name} must be positive, not {value}").into());
+            }
+        }
+        Ok(())
+    }
+
+    fn material_for(&self, texture: &str) -> String {
+        self.textures
+            .get(texture)
+            .cloned()
+            .unwrap_or_else(|| self.default_material.clone())
+    }
+
+    /// Doom maps live on the x/y plane with z up, the engine is y up.
+    fn to_world(&self, position: Vector2<f32>, height: f32) -> Point3<f32> {
+        Point3::new(
+            position.x * self.scale,
+            height * self.scale,
+            -position.y * self.scale,
+        )
+    }
+
+    fn build_walls(&self, level: &WadLevel, batcher: &mut MeshBatcher) {
+        for linedef in &level.linedefs {
+            let start = level.vertices[linedef.start];
+            let end = level.vertices[linedef.end];
+            let sides = [
+                (linedef.front, linedef.back, start, end),
+                (linedef.back, linedef.front, end, start),
+            ];
+            for (side, opposite, from, to) in sides {
+                let Some(side) = side else {
+                    continue;
+                };
+                let sidedef = &level.sidedefs[side];
+                let sector = &level.sectors[sidedef.sector];
+                match opposite {
+                    None => {
+                        self.push_wall(
+                            batcher,
+                            sidedef,
+                            &sidedef.middle,
+                            (from, to),
+                            sector.floor_height,
+                            sector.ceiling_height,
+                        );
+                    }
+                    Some(opposite) => {
+                        let other = &level.sectors[level.sidedefs[opposite].sector];
+                        let both_sky = sector.ceiling_texture == Self::SKY_FLAT
+                            && other.ceiling_texture == Self::SKY_FLAT;
+                        if other.ceiling_height < sector.ceiling_height && !both_sky {
+                            self.push_wall(
+                                batcher,
+                                sidedef,
+                                &sidedef.upper,
+                                (from, to),
+                                other.ceiling_height,
+                                sector.ceiling_height,
+                            );
+                        }
+                        if other.floor_height > sector.floor_height {
+                            self.push_wall(
+                                batcher,
+                                sidedef,
+                                &sidedef.lower,
+                                (from, to),
+                                sector.floor_height,
+                                other.floor_height,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Walls face the right hand side of the line when walking from `from` to `to`.
+    /// Textures are pegged to the top of the wall.
+    fn push_wall(
+        &self,
+        batcher: &mut MeshBatcher,
+        sidedef: &SideDef,
+        texture: &str,
+        (from, to): (Vector2<f32>, Vector2<f32>),
+        bottom: f32,
+        top: f32,
+    ) {
+        if texture.is_empty() || texture == "-" || top <= bottom {
+            return;
+        }
+        let direction = to - from;
+        let length = direction.norm();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let right = Vector2::new(direction.y, -direction.x) / length;
+        let normal = Vector3::new(right.x, 0.0, -right.y);
+
+        let u0 = sidedef.x_offset / self.texture_size;
+        let u1 = (sidedef.x_offset + length) / self.texture_size;
+        let v_top = sidedef.y_offset / self.texture_size;
+        let v_bottom = (top - bottom + sidedef.y_offset) / self.texture_size;
+
+        let corners = [
+            (self.to_world(from, bottom), [u0, v_bottom]),
+            (self.to_world(to, bottom), [u1, v_bottom]),
+            (self.to_world(to, top), [u1, v_top]),
+            (self.to_world(from, top), [u0, v_top]),
+        ];
+        let material = self.material_for(texture);
+        batcher.push_triangle(&material, [corners[0], corners[1], corners[2]], normal);
+        batcher.push_triangle(&material, [corners[0], corners[2], corners[3]], normal);
+    }
+
+    fn build_flats(&self, level: &WadLevel, batcher: &mut MeshBatcher) {
+        for (sector_index, sector) in level.sectors.iter().enumerate() {
+            for polygon in level.sector_polygons(sector_index) {
+                let triangles = Triangulator::triangulate(&polygon);
+                let flats = [
+                    (
+                        &sector.floor_texture,
+                        sector.floor_height,
+                        Vector3::new(0.0, 1.0, 0.0),
+                    ),
+                    (
+                        &sector.ceiling_texture,
+                        sector.ceiling_height,
+                        Vector3::new(0.0, -1.0, 0.0),
+                    ),
+                ];
+                for (texture, height, normal) in flats {
+                    if texture == Self::SKY_FLAT {
+                        continue;
+                    }
+                    let material = self.material_for(texture);
+                    for triangle in &triangles {
+                        let corners = triangle.map(|index| {
+                            let point = polygon[index];
+                            (
+                                self.to_world(point, height),
+                                [point.x / self.texture_size, -point.y / self.texture_size],
+                            )
+                        });
+                        batcher.push_triangle(&material, corners, normal);
+                    }
+                }
+            }
+        }
+    }
+
+    fn build_wall_boxes(&self, level: &WadLevel) -> Vec<BoundingBox> {
+        let mut boxes = vec![];
+        for linedef in &level.linedefs {
+            let is_solid = linedef.back.is_none() || linedef.flags & Self::IMPASSABLE != 0;
+            if !is_solid {
+                continue;
+            }
+            let sectors: Vec<&Sector> = [linedef.front, linedef.back]
+                .iter()
+                .flatten()
+                .map(|side| &level.sectors[level.sidedefs[*side].sector])
+                .collect();
+            let Some(bottom) = sectors.iter().map(|s| s.floor_height).reduce(f32::min) else {
+                continue;
+            };
+            let top = sectors
+                .iter()
+                .map(|s| s.ceiling_height)
+                .fold(bottom, f32::max);
+
+            let start = level.vertices[linedef.start];
+            let end = level.vertices[linedef.end];
+            let pieces = ((end - start).norm() / self.collision_cell).ceil().max(1.0) as u32;
+            for piece in 0..pieces {
+                let a = start + (end - start) * (piece as f32 / pieces as f32);
+                let b = start + (end - start) * ((piece + 1) as f32 / pieces as f32);
+                let min = a.inf(&b).add_scalar(-Self::WALL_THICKNESS / 2.0);
+                let max = a.sup(&b).add_scalar(Self::WALL_THICKNESS / 2.0);
+                boxes.push(self.world_box(min, max, bottom, top));
+            }
+        }
+        boxes
+    }
+
+    /// Samples floor heights on a grid and merges runs of equal height along x into boxes.
+    fn build_floor_boxes(&self, level: &WadLevel) -> Vec<BoundingBox> {
+        let Some((min, max)) = level.extents() else {
+            return vec![];
+        };
+        let cell = self.collision_cell;
+        let columns = ((max.x - min.x) / cell).ceil() as usize;
+        let rows = ((max.y - min.y) / cell).ceil() as usize;
+        let mut boxes = vec![];
+        for row in 0..rows {
+            let y = min.y + row as f32 * cell;
+            let mut run: Option<(usize, f32)> = None;
+            for column in 0..=columns {
+                let height = (column < columns)
+                    .then(|| {
+                        let center =
+                            Vector2::new(min.x + (column as f32 + 0.5) * cell, y + cell / 2.0);
+                        level
+                            .sector_at(center)
+                            .map(|sector| level.sectors[sector].floor_height)
+                    })
+                    .flatten();
+                if let Some((run_start, run_height)) = run
+                    && height != Some(run_height)
+                {
+                    let run_min = Vector2::new(min.x + run_start as f32 * cell, y);
+                    let run_max = Vector2::new(min.x + column as f32 * cell, y + cell);
+                    let bottom = run_height - Self::FLOOR_THICKNESS / self.scale;
+                    boxes.push(self.world_box(run_min, run_max, bottom, run_height));
+                    run = None;
+                }
+                if run.is_none() {
+                    run = height.map(|height| (column, height));
+                }
+            }
+        }
+        boxes
+    }
+
+    fn world_box(
+        &self,
+        min: Vector2<f32>,
+        max: Vector2<f32>,
+        bottom: f32,
+        top: f32,
+    ) -> BoundingBox {
+        // Flipping y to z swaps which corner is the minimum.
+        let a = self.to_world(Vector2::new(min.x, max.y), top);
+        let b = self.to_world(Vector2::new(max.x, min.y), bottom);
+        BoundingBox {
+            top_left: a,
+            bottom_right: b,
+            collide_on_top: false,
+        }
+    }
+}
+
+impl MeshBatcher {
+    fn push_triangle(
+        &mut self,
+        material: &str,
+        corners: [(Point3<f32>, [f32; 2]); 3],
+        normal: Vector3<f32>,
+    ) {
+        let mesh_index = match self.open_meshes.get(material) {
+            Some(index) if self.meshes[*index].vertices.len() + 3 <= u16::MAX as usize => *index,
+            _ => {
+                self.meshes.push(WadMesh {
+                    name: format!("WAD {material} {}", self.meshes.len()),
+                    material: String::from(material),
+                    vertices: vec![],
+                    indices: vec![],
+                });
+                self.open_meshes
+                    .insert(String::from(material), self.meshes.len() - 1);
+                self.meshes.len() - 1
+            }
+        };
+
+        let (a, b, c) = (corners[0].0, corners[1].0, corners[2].0);
+        let order = if (b - a).cross(&(c - a)).dot(&normal) >= 0.0 {
+            [0, 1, 2]
+        } else {
+            [0, 2, 1]
+        };
+        let mesh = &mut self.meshes[mesh_index];
+        for i in order {
+            mesh.indices.push(mesh.vertices.len() as u16);
+            mesh.vertices.push(Vertex {
+                position: corners[i].0.into(),
+                tex_coords: corners[i].1,
+                normal: normal.into(),
+                tangent: [0.0; 3],
+                bitangent: [0.0; 3],
+                joints: [0; 4],
+                weights: [0.0; 4],
+            });
+        }
+    }
+}
+
+impl WadFile {
+    const HEADER_SIZE: usize = 12;
+    const DIRECTORY_ENTRY_SIZE: usize = 16;
+    const LEVEL_LUMP_COUNT: usize = 10;
+
+    pub fn from_file(filename: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(platform::read(filename)?)
+    }
+
+    fn from_bytes(data: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err("WAD too short".into());
+        }
+        let magic = &data[0..4];
+        if magic != b"IWAD" && magic != b"PWAD" {
+            return Err("Not a WAD file".into());
+        }
+        let num_lumps = read_length(&data, 4)?;
+        let directory_offset = read_length(&data, 8)?;
+        // Every entry has to fit in the file, so a bad count can't ask for a huge directory.
+        let max_lumps = data.len().saturating_sub(directory_offset) / Self::DIRECTORY_ENTRY_SIZE;
+        if num_lumps > max_lumps {
+            return Err(format!("Directory of {num_lumps} lumps runs past the end").into());
+        }
+        let mut lumps = Vec::with_capacity(num_lumps);
+        for i in 0..num_lumps {
+            let entry = directory_offset + i * Self::DIRECTORY_ENTRY_SIZE;
+            let offset = read_length(&data, entry)?;
+            let size = read_length(&data, entry + 4)?;
+            let name = read_name(&data, entry + 8)?;
+            if offset.checked_add(size).is_none_or(|end| end > data.len()) {
+                return Err(format!("Lump {name} is out of bounds").into());
+            }
+            lumps.push(Lump { name, offset, size });
+        }
+        Ok(Self { data, lumps })
+    }
+
+    fn level_lump(&self, marker: usize, name: &str) -> Result<&[u8], Box<dyn Error>> {
+        self.lumps[marker + 1..]
+            .iter()
+            .take(Self::LEVEL_LUMP_COUNT)
+            .find(|lump| lump.name == name)
+            .map(|lump| &self.data[lump.offset..lump.offset + lump.size])
+            .ok_or_else(|| format!("Level is missing its {name} lump").into())
+    }
+
+    pub fn level(&self, name: &str) -> Result<WadLevel, Box<dyn Error>> {
+        let marker = self
+            .lumps
+            .iter()
+            .position(|lump| lump.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Level {name} not found"))?;
+
+        let vertices = self
+            .level_lump(marker, "VERTEXES")?
+            .chunks_exact(4)
+            .map(|v| -> Result<Vector2<f32>, Box<dyn Error>> {
+                Ok(Vector2::new(read_i16(v, 0)? as f32, read_i16(v, 2)? as f32))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let side = |value: u16| (value != u16::MAX).then_some(value as usize);
+        let linedefs = self
+            .level_lump(marker, "LINEDEFS")?
+            .chunks_exact(14)
+            .map(|l| -> Result<LineDef, Box<dyn Error>> {
+                Ok(LineDef {
+                    start: read_u16(l, 0)? as usize,
+                    end: read_u16(l, 2)? as usize,
+                    flags: read_u16(l, 4)?,
+                    front: side(read_u16(l, 10)?),
+                    back: side(read_u16(l, 12)?),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let sidedefs = self
+            .level_lump(marker, "SIDEDEFS")?
+            .chunks_exact(30)
+            .map(|s| -> Result<SideDef, Box<dyn Error>> {
+                Ok(SideDef {
+                    x_offset: read_i16(s, 0)? as f32,
+                    y_offset: read_i16(s, 2)? as f32,
+                    upper: read_name(s, 4)?,
+                    lower: read_name(s, 12)?,
+                    middle: read_name(s, 20)?,
+                    sector: read_u16(s, 28)? as usize,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let sectors = self
+            .level_lump(marker, "SECTORS")?
+            .chunks_exact(26)
+            .map(|s| -> Result<Sector, Box<dyn Error>> {
+                Ok(Sector {
+                    floor_height: read_i16(s, 0)? as f32,
+                    ceiling_height: read_i16(s, 2)? as f32,
+                    floor_texture: read_name(s, 4)?,
+                    ceiling_texture: read_name(s, 12)?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let things = self
+            .level_lump(marker, "THINGS")?
+            .chunks_exact(10)
+            .map(|t| -> Result<Thing, Box<dyn Error>> {
+                Ok(Thing {
+                    position: Vector2::new(read_i16(t, 0)? as f32, read_i16(t, 2)? as f32),
+                    thing_type: read_i16(t, 6)?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let level = WadLevel {
+            vertices,
+            linedefs,
+            sidedefs,
+            sectors,
+            things,
+        };
+        level.validate()?;
+        Ok(level)
+    }
+}
+
+impl WadLevel {
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        for linedef in &self.linedefs {
+            if linedef.start >= self.vertices.len() || linedef.end >= self.vertices.len() {
+                return Err("Linedef references a missing vertex".into());
+            }
+            for side in [linedef.front, linedef.back].iter().flatten() {
+                if *side >= self.sidedefs.len() {
+                    return Err("Linedef references a missing sidedef".into());
+                }
+            }
+        }
+        if self
+            .sidedefs
+            .iter()
+            .any(|sidedef| sidedef.sector >= self.sectors.len())
+        {
+            return Err("Sidedef references a missing sector".into());
+        }
+        Ok(())
+    }
+
+    fn extents(&self) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        let first = *self.vertices.first()?;
+        Some(
+            self.vertices
+                .iter()
+                .fold((first, first), |(min, max), v| (min.inf(v), max.sup(v))),
+        )
+    }
+
+    /// Edges bounding a sector, oriented so the sector is always on the right.
+    fn sector_edges(&self, sector: usize) -> Vec<(usize, usize)> {
+        let mut edges = vec![];
+        for linedef in &self.linedefs {
+            if linedef
+                .front
+                .is_some_and(|side| self.sidedefs[side].sector == sector)
+            {
+                edges.push((linedef.start, linedef.end));
+            }
+            if linedef
+                .back
+                .is_some_and(|side| self.sidedefs[side].sector == sector)
+            {
+                edges.push((linedef.end, linedef.start));
+            }
+        }
+        // Lines with the same sector on both sides don't bound anything.
+        let reversed: Vec<(usize, usize)> = edges.iter().map(|(a, b)| (*b, *a)).collect();
+        edges.retain(|edge| !reversed.contains(edge));
+        edges
+    }
+
+    fn sector_at(&self, point: Vector2<f32>) -> Option<usize> {
+        let mut inside = vec![false; self.sectors.len()];
+        for linedef in &self.linedefs {
+            let a = self.vertices[linedef.start];
+            let b = self.vertices[linedef.end];
+            if (a.y > point.y) == (b.y > point.y) {
+                continue;
+            }
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if x <= point.x {
+                continue;
+            }
+            let sectors: Vec<usize> = [linedef.front, linedef.back]
+                .iter()
+                .flatten()
+                .map(|side| self.sidedefs[*side].sector)
+                .collect();
+            if sectors.len() == 2 && sectors[0] == sectors[1] {
+                continue;
+            }
+            for sector in sectors {
+                inside[sector] = !inside[sector];
+            }
+        }
+        inside.iter().position(|is_inside| *is_inside)
+    }
+
+    /// Closed polygons for a sector's floor, with holes bridged into their outer loops.
+    fn sector_polygons(&self, sector: usize) -> Vec<Vec<Vector2<f32>>> {
+        let mut remaining = self.sector_edges(sector);
+        let mut loops: Vec<Vec<Vector2<f32>>> = vec![];
+        while let Some((loop_start, mut current)) = remaining.pop() {
+            let mut vertex_loop = vec![self.vertices[loop_start]];
+            let mut closed = false;
+            while let Some(next) = remaining.iter().position(|(a, _)| *a == current) {
+                vertex_loop.push(self.vertices[current]);
+                current = remaining.swap_remove(next).1;
+                if current == loop_start {
+                    closed = true;
+                    break;
+                }
+            }
+            if closed && vertex_loop.len() >= 3 {
+                loops.push(vertex_loop);
+            }
+        }
+
+        // The sector sits on the right of every edge, so outer loops wind clockwise.
+        let (mut outers, holes): (Vec<_>, Vec<_>) = loops
+            .into_iter()
+            .partition(|vertex_loop| Triangulator::signed_area(vertex_loop) < 0.0);
+        for outer in &mut outers {
+            outer.reverse();
+        }
+        let mut outer_holes: Vec<Vec<Vec<Vector2<f32>>>> = vec![vec![]; outers.len()];
+        for mut hole in holes {
+            hole.reverse();
+            if let Some(owner) = outers
+                .iter()
+                .position(|outer| Triangulator::contains(outer, hole[0]))
+            {
+                outer_holes[owner].push(hole);
+            }
+        }
+        outers
+            .into_iter()
+            .zip(outer_holes)
+            .map(|(outer, holes)| Triangulator::merge_holes(outer, holes))
+            .collect()
+    }
+}
+
+struct Triangulator;
+
+impl Triangulator {
+    const EPSILON: f32 = 1e-4;
+
+    fn cross(o: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+        (a - o).perp(&(b - o))
+    }
+
+    fn signed_area(polygon: &[Vector2<f32>]) -> f32 {
+        (0..polygon.len())
+            .map(|i| polygon[i].perp(&polygon[(i + 1) % polygon.len()]))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    fn contains(polygon: &[Vector2<f32>], point: Vector2<f32>) -> bool {
+        let mut inside = false;
+        for i in 0..polygon.len() {
+            let a = polygon[i];
+            let b = polygon[(i + 1) % polygon.len()];
+            if (a.y > point.y) != (b.y > point.y)
+                && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+            {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    fn segments_cross(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>, d: Vector2<f32>) -> bool {
+        let d1 = Self::cross(a, b, c);
+        let d2 = Self::cross(a, b, d);
+        let d3 = Self::cross(c, d, a);
+        let d4 = Self::cross(c, d, b);
+        d1 * d2 < 0.0 && d3 * d4 < 0.0
+    }
+
+    /// Connects each (clockwise) hole to the (counter-clockwise) outer loop with a zero-width
+    /// bridge so the result can be ear clipped as one simple polygon.
+    fn merge_holes(
+        mut outer: Vec<Vector2<f32>>,
+        mut holes: Vec<Vec<Vector2<f32>>>,
+    ) -> Vec<Vector2<f32>> {
+        let rightmost = |polygon: &Vec<Vector2<f32>>| {
+            (0..polygon.len())
+                .max_by(|a, b| polygon[*a].x.total_cmp(&polygon[*b].x))
+                .unwrap()
+        };
+        holes.sort_by(|a, b| b[rightmost(b)].x.total_cmp(&a[rightmost(a)].x));
+        for hole in holes {
+            let hole_index = rightmost(&hole);
+            let hole_point = hole[hole_index];
+            let visible = |candidate: &usize| {
+                let target = outer[*candidate];
+                (0..outer.len()).all(|i| {
+                    let a = outer[i];
+                    let b = outer[(i + 1) % outer.len()];
+                    !Self::segments_cross(hole_point, target, a, b)
+                })
+            };
+            let Some(outer_index) = (0..outer.len()).filter(visible).min_by(|a, b| {
+                (outer[*a] - hole_point)
+                    .norm_squared()
+                    .total_cmp(&(outer[*b] - hole_point).norm_squared())
+            }) else {
+                continue;
+            };
+
+            let mut merged = outer[..=outer_index].to_vec();
+            merged.extend((0..=hole.len()).map(|i| hole[(hole_index + i) % hole.len()]));
+            merged.extend_from_slice(&outer[outer_index..]);
+            outer = merged;
+        }
+        outer
+    }
+
+    /// Ear clips a counter-clockwise polygon.
+    fn triangulate(polygon: &[Vector2<f32>]) -> Vec<[usize; 3]> {
+        let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+        let mut triangles = vec![];
+        while remaining.len() > 3 {
+            let len = remaining.len();
+            let mut clipped = false;
+            for i in 0..len {
+                let prev = remaining[(i + len - 1) % len];
+                let cur = remaining[i];
+                let next = remaining[(i + 1) % len];
+                let (a, b, c) = (polygon[prev], polygon[cur], polygon[next]);
+                let turn = Self::cross(a, b, c);
+                if turn.abs() < Self::EPSILON {
+                    // Collinear points add nothing.
+                    remaining.remove(i);
+                    clipped = true;
+                    break;
+                }
+                if turn < 0.0 {
+                    continue;
+                }
+                let blocked = remaining.iter().any(|other| {
+                    let p = polygon[*other];
+                    if p == a || p == b || p == c {
+                        return false;
+                    }
+                    Self::cross(a, b, p) >= 0.0
+                        && Self::cross(b, c, p) >= 0.0
+                        && Self::cross(c, a, p) >= 0.0
+                });
+                if blocked {
+                    continue;
+                }
+                triangles.push([prev, cur, next]);
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+            if !clipped {
+                break;
+            }
+        }
+        if remaining.len() == 3 {
+            triangles.push([remaining[0], remaining[1], remaining[2]]);
+        }
+        triangles
+    }
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], Box<dyn Error>> {
+    Ok(data
+        .get(offset..offset + N)
+        .ok_or("Unexpected end of WAD data")?
+        .try_into()?)
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Result<i16, Box<dyn Error>> {
+    Ok(i16::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Box<dyn Error>> {
+    Ok(u16::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_i32(data: &[u8], offset: usize) -> Result<i32, Box<dyn Error>> {
+    Ok(i32::from_le_bytes(read_bytes(data, offset)?))
+}
+
+/// A size or offset, which can't be negative.
+fn read_length(data: &[u8], offset: usize) -> Result<usize, Box<dyn Error>> {
+    let value = read_i32(data, offset)?;
+    usize::try_from(value).map_err(|_| format!("Negative length {value} at {offset}").into())
+}
+
+fn read_name(data: &[u8], offset: usize) -> Result<String, Box<dyn Error>> {
+    let bytes: [u8; 8] = read_bytes(data, offset)?;
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(8);
+    Ok(String::from_utf8_lossy(&bytes[..end]).to_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wad(num_lumps: i32, directory_offset: i32, entries: &[(i32, i32, &str)]) -> Vec<u8> {
+        let mut data = b"PWAD".to_vec();
+        data.extend(num_lumps.to_le_bytes());
+        data.extend(directory_offset.to_le_bytes());
+        for (offset, size, name) in entries {
+            data.extend(offset.to_le_bytes());
+            data.extend(size.to_le_bytes());
+            let mut padded = [0u8; 8];
+            padded[..name.len()].copy_from_slice(name.as_bytes());
+            data.extend(padded);
+        }
+        data
+    }
+
+    fn loader(collision_cell: f32) -> WadLoader {
+        WadLoader {
+            file: String::new(),
+            level: "E1M1".to_string(),
+            scale: WadLoader::default_scale(),
+            texture_size: WadLoader::default_texture_size(),
+            collision_cell,
+            textures: HashMap::new(),
+            default_material: "wall".to_string(),
+        }
+    }
+
+    #[test]
+    fn reads_directory() {
+        let wad = WadFile::from_bytes(wad(1, 12, &[(0, 4, "e1m1")])).unwrap();
+        assert_eq!(wad.lumps.len(), 1);
+        assert_eq!(wad.lumps[0].name, "E1M1");
+    }
+
+    #[test]
+    fn rejects_directory_out_of_bounds() {
+        assert!(WadFile::from_bytes(wad(2, 12, &[(0, 4, "E1M1")])).is_err());
+        assert!(WadFile::from_bytes(wad(i32::MAX, 12, &[])).is_err());
+        assert!(WadFile::from_bytes(wad(-1, 12, &[])).is_err());
+        assert!(WadFile::from_bytes(wad(1, -12, &[(0, 4, "E1M1")])).is_err());
+        assert!(WadFile::from_bytes(wad(1, 1000, &[(0, 4, "E1M1")])).is_err());
+    }
+
+    #[test]
+    fn rejects_lumps_out_of_bounds() {
+        assert!(WadFile::from_bytes(wad(1, 12, &[(0, 29, "E1M1")])).is_err());
+        assert!(WadFile::from_bytes(wad(1, 12, &[(100, 0, "E1M1")])).is_err());
+        assert!(WadFile::from_bytes(wad(1, 12, &[(i32::MAX, i32::MAX, "E1M1")])).is_err());
+        assert!(WadFile::from_bytes(wad(1, 12, &[(4, -4, "E1M1")])).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_collision_cell() {
+        assert!(loader(16.0).validate().is_ok());
+        for cell in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(loader(cell).validate().is_err());
+        }
+    }
+
+    fn area(polygon: &[Vector2<f32>], triangles: &[[usize; 3]]) -> f32 {
+        triangles
+            .iter()
+            .map(|t| Triangulator::cross(polygon[t[0]], polygon[t[1]], polygon[t[2]]) / 2.0)
+            .sum()
+    }
+
+    #[test]
+    fn triangulates_concave_polygon() {
+        // An L shape, counter-clockwise.
+        let polygon = [
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+        ]
+        .map(|(x, y)| Vector2::new(x, y));
+        let triangles = Triangulator::triangulate(&polygon);
+        assert_eq!(triangles.len(), 4);
+        assert!((area(&polygon, &triangles) - 3.0).abs() < 1e-5);
+        for t in &triangles {
+            assert!(Triangulator::cross(polygon[t[0]], polygon[t[1]], polygon[t[2]]) > 0.0);
+        }
+    }
+
+    #[test]
+    fn triangulates_around_hole() {
+        let outer = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]
+            .map(|(x, y)| Vector2::new(x, y))
+            .to_vec();
+        let hole = [(1.0, 1.0), (1.0, 3.0), (3.0, 3.0), (3.0, 1.0)]
+            .map(|(x, y)| Vector2::new(x, y))
+            .to_vec();
+        let polygon = Triangulator::merge_holes(outer, vec![hole]);
+        let triangles = Triangulator::triangulate(&polygon);
+        assert!((area(&polygon, &triangles) - 12.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,67 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use clap::Parser;
+use log::LevelFilter;
+
+/// What the client was started with. Anything left out falls back to the saved settings or
+/// the built in defaults, which is also all the browser build ever gets.
+#[derive(Parser, Debug, Clone, Default)]
+#[command(version, about)]
+pub struct Options {
+    /// Map file to load instead of the first map.
+    #[arg(long, value_name = "FILE")]
+    pub map: Option<String>,
+
+    /// Window size in physical pixels, e.g. 1280x720.
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    pub size: Option<[u32; 2]>,
+
+    /// Borderless fullscreen on the current monitor.
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Server to join.
+    #[arg(long, value_name = "ADDRESS")]
+    pub connect: Option<SocketAddr>,
+
+    /// Log level for every subsystem RUST_LOG doesn't set one for.
+    #[arg(long, value_name = "LEVEL", value_parser = parse_level)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Keeps the window hidden and the cursor free, for unattended runs such as benchmarks.
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Flies through the benchmark map for the given seconds and writes a report.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "30",
+        value_parser = parse_seconds
+    )]
+    pub benchmark: Option<Duration>,
+}
+
+// LevelFilter's parse error only implements Error when log has std, which the web build lacks.
+fn parse_level(s: &str) -> Result<LevelFilter, String> {
+    s.parse()
+        .map_err(|_| format!("{s} is not one of off, error, warn, info, debug or trace"))
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    match s.trim().parse::<f32>().map(Duration::try_from_secs_f32) {
+        Ok(Ok(duration)) if !duration.is_zero() => Ok(duration),
+        _ => Err(format!("{s} is not a length of time in seconds")),
+    }
+}
+
+fn parse_size(s: &str) -> Result<[u32; 2], String> {
+    let (width, height) = s.split_once(['x', 'X']).ok_or("expected WIDTHxHEIGHT")?;
+    let parse = |side: &str| match side.trim().parse::<u32>() {
+        Ok(side) if side > 0 => Ok(side),
+        _ => Err(format!("{side} is not a size")),
+    };
+    Ok([parse(width)?, parse(height)?])
+}
@@ -0,0 +1,208 @@
+// File access for both the native and the web build. Browsers have no file system, so there
+// paths are fetched over HTTP relative to the page and writes go to local storage.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::{
+        env, fs, io,
+        path::{Path, PathBuf},
+    };
+
+    use log::LevelFilter;
+    use tracing_subscriber::EnvFilter;
+    use tracing_subscriber::filter::{Directive, LevelFilter as TraceLevel};
+
+    // Subsystems that are chatty at info and below, held back to warnings unless RUST_LOG
+    // names them.
+    const QUIET_TARGETS: [&str; 3] = ["wgpu_core", "wgpu_hal", "naga"];
+
+    /// Sends log output through tracing to stderr, so lines carry the spans they were logged
+    /// in. RUST_LOG picks levels per subsystem by module path, e.g.
+    /// `client::renderer=debug,client::game::scripting=trace`, and `level` covers the rest.
+    pub fn init_logging(level: LevelFilter) {
+        let level = match level {
+            LevelFilter::Off => TraceLevel::OFF,
+            LevelFilter::Error => TraceLevel::ERROR,
+            LevelFilter::Warn => TraceLevel::WARN,
+            LevelFilter::Info => TraceLevel::INFO,
+            LevelFilter::Debug => TraceLevel::DEBUG,
+            LevelFilter::Trace => TraceLevel::TRACE,
+        };
+        let mut filter = EnvFilter::default().add_directive(level.into());
+        for target in QUIET_TARGETS {
+            let quiet = format!("{target}={}", level.min(TraceLevel::WARN));
+            filter = filter.add_directive(quiet.parse().expect("valid directive"));
+        }
+        let directives = env::var(EnvFilter::DEFAULT_ENV).unwrap_or_default();
+        for directive in directives.split(',').filter(|d| !d.trim().is_empty()) {
+            match directive.trim().parse::<Directive>() {
+                Ok(directive) => filter = filter.add_directive(directive),
+                Err(e) => eprintln!("Ignoring log filter {directive}: {e}"),
+            }
+        }
+        tracing_subscriber::fmt().with_env_filter(filter).init();
+    }
+
+    /// Per user directory for settings, e.g. `~/.config/mood` on Linux.
+    pub fn config_dir() -> PathBuf {
+        let home = || env::var_os("HOME").map(PathBuf::from);
+        let base = if cfg!(windows) {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            home().map(|home| home.join("Library/Application Support"))
+        } else {
+            env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| home().map(|home| home.join(".config")))
+        };
+        base.unwrap_or_default().join("mood")
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    pub fn write(path: impl AsRef<Path>, contents: &str) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::{
+        io,
+        path::{Path, PathBuf},
+    };
+
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::{Response, Storage, XmlHttpRequest};
+
+    struct ConsoleLogger;
+
+    static LOGGER: ConsoleLogger = ConsoleLogger;
+
+    impl Log for ConsoleLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Info
+        }
+
+        fn log(&self, record: &Record) {
+            if !self.enabled(record.metadata()) {
+                return;
+            }
+            let message = JsValue::from_str(&format!("{}: {}", record.target(), record.args()));
+            match record.level() {
+                Level::Error => web_sys::console::error_1(&message),
+                Level::Warn => web_sys::console::warn_1(&message),
+                _ => web_sys::console::log_1(&message),
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Sends log output and panics to the browser console.
+    pub fn init_logging(level: LevelFilter) {
+        std::panic::set_hook(Box::new(|info| {
+            web_sys::console::error_1(&JsValue::from_str(&info.to_string()));
+        }));
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(level);
+        }
+    }
+
+    /// Only prefixes the local storage keys, there's no directory behind it.
+    pub fn config_dir() -> PathBuf {
+        PathBuf::from("mood")
+    }
+
+    fn js_error(value: JsValue) -> io::Error {
+        io::Error::other(format!("{value:?}"))
+    }
+
+    fn url(path: &Path) -> String {
+        path.to_string_lossy().replace('\\', "/")
+    }
+
+    fn storage() -> io::Result<Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "No local storage"))
+    }
+
+    /// Blocks on a synchronous request. Browsers only hand those back as text, so the body
+    /// comes through as one character per byte.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        let url = url(path.as_ref());
+        let request = XmlHttpRequest::new().map_err(js_error)?;
+        request
+            .open_with_async("GET", &url, false)
+            .map_err(js_error)?;
+        request
+            .override_mime_type("text/plain; charset=x-user-defined")
+            .map_err(js_error)?;
+        request.send().map_err(js_error)?;
+        match request.status().map_err(js_error)? {
+            200 => {}
+            404 => return Err(io::Error::new(io::ErrorKind::NotFound, url)),
+            status => return Err(io::Error::other(format!("{url}: HTTP {status}"))),
+        }
+        let text = request
+            .response_text()
+            .map_err(js_error)?
+            .unwrap_or_default();
+        Ok(text.chars().map(|c| c as u32 as u8).collect())
+    }
+
+    pub async fn fetch(path: &str) -> io::Result<Vec<u8>> {
+        let window = web_sys::window()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "No window"))?;
+        let response: Response = JsFuture::from(window.fetch_with_str(path))
+            .await
+            .and_then(|response| response.dyn_into())
+            .map_err(js_error)?;
+        if !response.ok() {
+            return Err(io::Error::other(format!(
+                "{path}: HTTP {}",
+                response.status()
+            )));
+        }
+        let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+    }
+
+    pub fn write(path: impl AsRef<Path>, contents: &str) -> io::Result<()> {
+        storage()?
+            .set_item(&url(path.as_ref()), contents)
+            .map_err(js_error)
+    }
+
+    /// Reads text, preferring anything written to `path` before.
+    pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+        let path = path.as_ref();
+        let saved = storage()
+            .ok()
+            .and_then(|storage| storage.get_item(&url(path)).ok().flatten());
+        if let Some(contents) = saved {
+            return Ok(contents);
+        }
+        String::from_utf8(read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::{config_dir, init_logging, read, read_to_string, write};
+#[cfg(target_arch = "wasm32")]
+pub use web::{config_dir, fetch, init_logging, read, read_to_string, write};
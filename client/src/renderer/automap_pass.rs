@@ -0,0 +1,248 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::{Matrix4, Point2, Vector2};
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Buffer, CommandEncoder, Device, Queue, RenderPipeline, TextureView};
+
+use crate::game::automap::{Automap, SegmentKind};
+use crate::game::collision_manager::CollisionManager;
+use crate::game::player::Player;
+
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct AutomapVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Draws the explored level from above, centered on the player, over a darkened frame.
+pub struct AutomapPass {
+    pipeline: RenderPipeline,
+    projection_buffer: Buffer,
+    bind_group: BindGroup,
+    vertex_buffer: Buffer,
+    vertex_count: u32,
+}
+
+impl AutomapVertex {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x4,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<AutomapVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+impl AutomapPass {
+    const MAX_LINES: usize = 16384;
+    // Logical pixels per world unit.
+    const ZOOM: f32 = 48.0;
+    const LINE_THICKNESS: f32 = 2.0;
+    const ARROW_LENGTH: f32 = 14.0;
+    const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.85];
+    const WALL_COLOR: [f32; 4] = [0.9, 0.15, 0.1, 1.0];
+    const LEDGE_COLOR: [f32; 4] = [0.55, 0.4, 0.2, 1.0];
+    const PLAYER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    pub fn new(device: &Device, color_format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("automap_bind_group_layout"),
+        });
+        let projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Automap Projection Buffer"),
+            contents: bytemuck::cast_slice(&[[[0.0f32; 4]; 4]]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: projection_buffer.as_entire_binding(),
+            }],
+            label: Some("automap_bind_group"),
+        });
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Automap Vertex Buffer"),
+            size: (Self::MAX_LINES * 6 * std::mem::size_of::<AutomapVertex>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Automap Pipeline Layout",
+            &[&layout],
+        );
+        let pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &pipeline_layout,
+            PipelineDescriptor {
+                vertex_layouts: &[AutomapVertex::desc()],
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("Automap Shader"),
+                        source: wgpu::ShaderSource::Wgsl(
+                            include_str!("shaders/automap.wgsl").into(),
+                        ),
+                    },
+                    Some(color_format),
+                )
+            },
+        );
+
+        Self {
+            pipeline,
+            projection_buffer,
+            bind_group,
+            vertex_buffer,
+            vertex_count: 0,
+        }
+    }
+
+    /// Lays out the automap for a `width` by `height` pixel window. World x runs to the
+    /// right and world z down the screen, with the player in the middle.
+    pub fn update(
+        &mut self,
+        queue: &Queue,
+        automap: &Automap,
+        collision_manager: &CollisionManager,
+        player: &Player,
+        size: (u32, u32),
+        scale_factor: f64,
+    ) {
+        let (width, height) = (size.0 as f32, size.1 as f32);
+        let projection = Matrix4::new_orthographic(0.0, width, height, 0.0, -1.0, 1.0);
+        queue.write_buffer(
+            &self.projection_buffer,
+            0,
+            bytemuck::cast_slice(&[Into::<[[f32; 4]; 4]>::into(projection)]),
+        );
+
+        let scale = (scale_factor as f32).max(1.0);
+        let zoom = Self::ZOOM * scale;
+        let thickness = Self::LINE_THICKNESS * scale;
+        let camera = &player.camera;
+        let center = Point2::new(camera.position.x, camera.position.z);
+        let to_screen =
+            |point: Point2<f32>| Point2::new(width / 2.0, height / 2.0) + (point - center) * zoom;
+
+        let mut vertices = vec![];
+        // A line as thick as the window covers all of it.
+        Self::push_line(
+            &mut vertices,
+            Point2::new(0.0, height / 2.0),
+            Point2::new(width, height / 2.0),
+            height,
+            Self::BACKGROUND,
+        );
+        let hitbox = player.hitbox();
+        let segments =
+            automap.segments(collision_manager, hitbox.bottom_right.y, hitbox.top_left.y);
+        // Walls go last so they stay on top where they share an edge with a floor.
+        for kind in [SegmentKind::Ledge, SegmentKind::Wall] {
+            let color = match kind {
+                SegmentKind::Wall => Self::WALL_COLOR,
+                SegmentKind::Ledge => Self::LEDGE_COLOR,
+            };
+            for segment in segments.iter().filter(|segment| segment.kind == kind) {
+                Self::push_line(
+                    &mut vertices,
+                    to_screen(segment.start),
+                    to_screen(segment.end),
+                    thickness,
+                    color,
+                );
+            }
+        }
+
+        let forward = camera.target - camera.position;
+        let forward = Vector2::new(forward.x, forward.z)
+            .try_normalize(0.0)
+            .unwrap_or(Vector2::y());
+        let side = Vector2::new(-forward.y, forward.x);
+        let length = Self::ARROW_LENGTH * scale;
+        let tip = to_screen(center) + forward * length;
+        let tail = to_screen(center) - forward * length;
+        let head = tip - forward * length * 0.6;
+        for end in [tail, head + side * length * 0.5, head - side * length * 0.5] {
+            Self::push_line(&mut vertices, end, tip, thickness, Self::PLAYER_COLOR);
+        }
+
+        vertices.truncate(Self::MAX_LINES * 6);
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// Quad `thickness` pixels wide from `start` to `end`.
+    fn push_line(
+        vertices: &mut Vec<AutomapVertex>,
+        start: Point2<f32>,
+        end: Point2<f32>,
+        thickness: f32,
+        color: [f32; 4],
+    ) {
+        let Some(direction) = (end - start).try_normalize(0.0) else {
+            return;
+        };
+        let normal = Vector2::new(-direction.y, direction.x) * (thickness / 2.0);
+        let corner = |point: Point2<f32>| AutomapVertex {
+            position: [point.x, point.y],
+            color,
+        };
+        vertices.extend_from_slice(&[
+            corner(start - normal),
+            corner(end - normal),
+            corner(end + normal),
+            corner(start - normal),
+            corner(end + normal),
+            corner(start + normal),
+        ]);
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        color_view: &TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Automap Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use log::warn;
+use nalgebra::{Point3, Vector3};
+
+use crate::game::bounding_box::BoundingBox;
+use crate::game::decals::DecalKind;
+use crate::game::events::GameEvent;
+use crate::game::health::Damage;
+use crate::game::particles::ParticlePreset;
+use crate::game::rng::RngSystem;
+use crate::game::view_model::ViewModel;
+use crate::game::weapons::{Explosion, Impact, WeaponKind};
+
+use super::Renderer;
+
+impl Renderer {
+    // Keeps impact decals from starting exactly on the surface they project onto.
+    const IMPACT_OFFSET: f32 = 0.01;
+    const FLASH_TIME: f32 = 0.2;
+    const FLASH_INTENSITY: f32 = 3.0;
+    const FLASH_COLOR: [f32; 3] = [1.0, 0.6, 0.3];
+
+    /// Where the view model's barrel ends.
+    fn muzzle_position(&self) -> Point3<f32> {
+        let camera = &self.player.camera;
+        let forward = (camera.target - camera.position).normalize();
+        let right = forward.cross(&camera.up).normalize();
+        let up = right.cross(&forward);
+        camera.position + forward * 0.3 + right * 0.18 - up * 0.12
+    }
+
+    /// Every living enemy's index in `characters` and hitbox.
+    fn shootable_targets(&self) -> Vec<(usize, BoundingBox)> {
+        self.characters
+            .iter()
+            .enumerate()
+            .filter(|(_, character)| {
+                character
+                    .enemy
+                    .as_ref()
+                    .is_some_and(|enemy| !enemy.is_dead())
+            })
+            .map(|(index, character)| (index, character.hitbox()))
+            .collect()
+    }
+
+    fn damage_character(&mut self, index: usize, amount: u32) {
+        let damage = Damage {
+            amount,
+            source: self.player.camera.position,
+        };
+        let character = &mut self.characters[index];
+        if let Some(enemy) = &mut character.enemy
+            && enemy.damage(damage)
+        {
+            if let Some(animation) = enemy.animation() {
+                character.animator.play(animation, false, Duration::ZERO);
+            }
+            self.events.publish(GameEvent::EnemyDied {
+                position: character.position,
+            });
+        }
+    }
+
+    /// Fires the current weapon along the view direction.
+    pub(super) fn fire_weapon(&mut self) {
+        let muzzle = self.muzzle_position();
+        let camera = &self.player.camera;
+        let forward = (camera.target - camera.position).normalize();
+        let targets = self.shootable_targets();
+        let weapon = self.weapon_system.current();
+        let kind = weapon.kind;
+        self.events.publish(GameEvent::WeaponFired {
+            weapon: weapon.name,
+            muzzle,
+            direction: forward,
+        });
+        let impact = self.weapon_system.fire(
+            camera.position,
+            forward,
+            &self.collision_manager,
+            &targets,
+            self.rng.stream(RngSystem::Weapons),
+        );
+        if let (Some(impact), WeaponKind::Hitscan { damage, .. }) = (impact, kind) {
+            self.apply_hit(impact, damage);
+        }
+    }
+
+    /// Damages a hit enemy, or marks the level where the shot landed.
+    fn apply_hit(&mut self, impact: Impact, damage: u32) {
+        let point = impact.point + impact.normal * Self::IMPACT_OFFSET;
+        self.events.publish(GameEvent::Hit {
+            point,
+            normal: impact.normal,
+            enemy: impact.target.is_some(),
+        });
+        match impact.target {
+            Some(index) => self.damage_character(index, damage),
+            None => self.decal_system.spawn(
+                point,
+                impact.normal,
+                DecalKind::BulletHole,
+                self.rng.stream(RngSystem::Decals),
+            ),
+        }
+    }
+
+    /// Switches weapons, moves projectiles and sets off the ones that hit something.
+    pub(super) fn update_weapons(&mut self, dt: Duration) {
+        if let Some(slot) = self.player_controller.weapon_slot.take()
+            && let Some(weapon) = self.weapon_system.select(slot)
+        {
+            self.events.publish(GameEvent::WeaponSelected {
+                weapon: weapon.name,
+            });
+        }
+        let trail: Vec<(Point3<f32>, Vector3<f32>)> = self
+            .weapon_system
+            .projectiles()
+            .iter()
+            .map(|projectile| (projectile.position, -projectile.velocity))
+            .collect();
+        for (position, direction) in trail {
+            self.particle_system
+                .add_burst(position, direction, ParticlePreset::Smoke, 1);
+        }
+        let targets = self.shootable_targets();
+        let explosions = self
+            .weapon_system
+            .update(dt, &self.collision_manager, &targets);
+        for explosion in explosions {
+            self.explode(explosion);
+        }
+        self.update_light_flash(dt);
+    }
+
+    fn explode(&mut self, explosion: Explosion) {
+        let point = explosion.point;
+        let normal = explosion
+            .impact
+            .map_or(Vector3::y(), |impact| impact.normal);
+        match explosion.impact {
+            Some(Impact {
+                target: Some(index),
+                ..
+            }) => self.damage_character(index, explosion.settings.direct_damage),
+            Some(impact) => self.decal_system.spawn(
+                point + impact.normal * Self::IMPACT_OFFSET,
+                impact.normal,
+                DecalKind::Scorch,
+                self.rng.stream(RngSystem::Decals),
+            ),
+            None => {}
+        }
+        for (index, hitbox) in self.shootable_targets() {
+            let damage = explosion.splash_damage_at(Self::box_center(&hitbox));
+            if damage > 0 {
+                self.damage_character(index, damage);
+            }
+        }
+        // Standing too close to your own rocket hurts.
+        let damage = explosion.splash_damage_at(Self::box_center(self.player.hitbox()));
+        if damage > 0 {
+            self.damage_player(Damage {
+                amount: damage,
+                source: point,
+            });
+        }
+
+        self.events.publish(GameEvent::Explosion { point, normal });
+        self.flash_light(point + normal * Self::IMPACT_OFFSET);
+    }
+
+    pub(super) fn damage_player(&mut self, damage: Damage) {
+        let amount = self.player.health.damage(damage);
+        if amount > 0 {
+            self.events.publish(GameEvent::PlayerHurt {
+                amount,
+                source: damage.source,
+            });
+        }
+    }
+
+    /// Restarts the level once the player died.
+    pub(super) fn handle_player_death(&mut self) {
+        if !self.player.health.is_dead() {
+            return;
+        }
+        self.rerender();
+        self.player.respawn(self.spawn_point);
+        self.view_model = ViewModel::default();
+        self.events.publish(GameEvent::PlayerDied);
+    }
+
+    fn box_center(hitbox: &BoundingBox) -> Point3<f32> {
+        nalgebra::center(&hitbox.top_left, &hitbox.bottom_right)
+    }
+
+    /// Lights up an explosion. Every explosion shares one light, added the first time.
+    fn flash_light(&mut self, position: Point3<f32>) {
+        let id = match self.flash_light {
+            Some(id) => id,
+            None => match self.add_light(position, 0.0) {
+                Ok(id) => id,
+                Err(e) => {
+                    warn!("No light for the explosion flash {e}");
+                    return;
+                }
+            },
+        };
+        self.flash_light = Some(id);
+        self.flash_remaining = Self::FLASH_TIME;
+        let light = &mut self.lights[id as usize];
+        light.position = position;
+        light.color = Self::FLASH_COLOR;
+        self.shadow_baker.update_light_version_from_id(id);
+    }
+
+    fn update_light_flash(&mut self, dt: Duration) {
+        let Some(id) = self.flash_light else {
+            return;
+        };
+        if self.flash_remaining <= 0.0 {
+            return;
+        }
+        self.flash_remaining = (self.flash_remaining - dt.as_secs_f32()).max(0.0);
+        self.lights[id as usize].intensity =
+            Self::FLASH_INTENSITY * self.flash_remaining / Self::FLASH_TIME;
+        self.upload_lights();
+    }
+}
@@ -0,0 +1,324 @@
+use nalgebra::Point3;
+
+use crate::camera::light::{Light, PcfKernel, ShadowSettings};
+use crate::camera::light_uniform::{LightUniformArray, MAX_LIGHTS};
+use crate::camera::shadow_map_uniform::ShadowMapUniform;
+use crate::game::camera_controller::CameraMode;
+use crate::game::console::ConsoleCommand;
+use crate::game::replay::Replay;
+use crate::game::rng::GameRng;
+use crate::model::map_loader::MapLoader;
+
+use super::Renderer;
+use super::graphics_settings::SsaoQuality;
+use super::shadow_baker::ShadowBaker;
+
+type CommandResult = Result<String, String>;
+
+impl Renderer {
+    const HELP: &str = "noclip, camera fps|fly|orbit, spawn_light x y z [intensity], trigger name, map file, gpu, clear, seed [n], export_cubemap x y z [size] [prefix], \
+        r_shadow_res n, r_shadow_bias id [depth normal], r_shadow_pcf id [1|4|9|16], r_ssao off|low|medium|high, r_render_scale n, r_dynamic_res 0|1, r_depth_prepass 0|1, r_async_compute 0|1, r_lod 0|1, \
+        sensitivity n, fov n, zoom_fov n, invert_y 0|1, m_smoothing n, record, stop_record [file], play_replay [file]";
+    const SPAWNED_LIGHT_INTENSITY: f32 = 5.0;
+    const EXPORTED_CUBEMAP_SIZE: u32 = 512;
+    const EXPORTED_CUBEMAP_PREFIX: &str = "cubemap";
+
+    /// Runs a console line and prints its result to the console. Cvars given without a value
+    /// print their current value.
+    pub fn execute_console_line(&mut self, line: &str) {
+        let Some(command) = ConsoleCommand::parse(line) else {
+            return;
+        };
+        match self.execute_console_command(&command) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => self.console.print(output),
+            Err(e) => self.console.print(e),
+        }
+    }
+
+    fn execute_console_command(&mut self, command: &ConsoleCommand) -> CommandResult {
+        let has_value = !command.args.is_empty();
+        match command.name.as_str() {
+            "help" => Ok(Self::HELP.to_string()),
+            "clear" => {
+                self.console.clear();
+                Ok(String::new())
+            }
+            "noclip" => {
+                let noclip = self.camera_controller.mode() != CameraMode::FreeFly;
+                let mode = if noclip {
+                    CameraMode::FreeFly
+                } else {
+                    CameraMode::Fps
+                };
+                self.set_camera_mode(mode);
+                Ok(format!("noclip {}", Self::on_off(noclip)))
+            }
+            "camera" if has_value => {
+                let mode: CameraMode = command.arg(0)?;
+                self.set_camera_mode(mode);
+                Ok(format!("camera {mode}"))
+            }
+            "camera" => Ok(format!("camera {}", self.camera_controller.mode())),
+            "spawn_light" => self.spawn_light(command),
+            "trigger" => {
+                let name: String = command.arg(0)?;
+                self.fire_trigger(&name);
+                Ok(format!("Triggered {name}"))
+            }
+            "gpu" => Ok(format!(
+                "{} ({:?}, {:?}) {:?}",
+                self.gpu_info.name,
+                self.gpu_info.device_type,
+                self.gpu_info.backend,
+                self.gpu_capabilities
+            )),
+            "map" => {
+                let map_file: String = command.arg(0)?;
+                MapLoader::from_file(&map_file).map_err(|e| format!("Unable to load {e}"))?;
+                self.map_file = map_file;
+                self.rerender();
+                Ok(format!("Loaded {}", self.map_file))
+            }
+            "seed" if has_value => {
+                self.rng = GameRng::new(command.arg(0)?);
+                Ok(format!("seed {}", self.rng.seed()))
+            }
+            "seed" => Ok(format!("seed {}", self.rng.seed())),
+            "export_cubemap" => {
+                let position = Point3::new(command.arg(0)?, command.arg(1)?, command.arg(2)?);
+                let size = command.arg_or(3, Self::EXPORTED_CUBEMAP_SIZE)?;
+                let prefix = command.arg_or(4, Self::EXPORTED_CUBEMAP_PREFIX.to_string())?;
+                let files = self.export_cubemap(position, size, &prefix)?;
+                Ok(format!("Wrote {}", files.join(" ")))
+            }
+            "record" => self.start_recording(),
+            "stop_record" => {
+                self.stop_recording(&command.arg_or(0, Replay::DEFAULT_FILE.to_string())?)
+            }
+            "play_replay" => {
+                self.play_replay(&command.arg_or(0, Replay::DEFAULT_FILE.to_string())?)
+            }
+            "r_shadow_res" if has_value => self.set_shadow_resolution(command.arg(0)?),
+            "r_shadow_res" => Ok(format!("r_shadow_res {}", self.shadow_baker.resolution())),
+            "r_shadow_bias" => self.shadow_bias(command),
+            "r_shadow_pcf" => self.shadow_pcf(command),
+            "r_ssao" if has_value => {
+                let quality: SsaoQuality = command.arg(0)?;
+                self.set_ssao_quality(quality);
+                Ok(format!("r_ssao {quality:?}"))
+            }
+            "r_ssao" => Ok(format!("r_ssao {:?}", self.graphics_settings.ssao)),
+            "r_render_scale" if has_value => {
+                let render_scale = self.set_render_scale(command.arg(0)?);
+                Ok(format!("r_render_scale {render_scale:.2}"))
+            }
+            "r_render_scale" => Ok(format!(
+                "r_render_scale {:.2}",
+                self.graphics_settings.render_scale
+            )),
+            "r_dynamic_res" if has_value => {
+                self.set_dynamic_resolution(command.arg::<u32>(0)? != 0);
+                Ok(format!(
+                    "r_dynamic_res {}",
+                    Self::on_off(self.graphics_settings.dynamic_resolution)
+                ))
+            }
+            "r_dynamic_res" => Ok(format!(
+                "r_dynamic_res {}",
+                Self::on_off(self.graphics_settings.dynamic_resolution)
+            )),
+            "r_depth_prepass" if has_value => {
+                self.graphics_settings.depth_prepass = command.arg::<u32>(0)? != 0;
+                Ok(format!(
+                    "r_depth_prepass {}",
+                    Self::on_off(self.graphics_settings.depth_prepass)
+                ))
+            }
+            "r_depth_prepass" => Ok(format!(
+                "r_depth_prepass {}",
+                Self::on_off(self.graphics_settings.depth_prepass)
+            )),
+            "r_async_compute" if has_value => {
+                self.graphics_settings.async_compute = command.arg::<u32>(0)? != 0;
+                Ok(self.async_compute_status())
+            }
+            "r_async_compute" => Ok(self.async_compute_status()),
+            "r_lod" if has_value => {
+                self.graphics_settings.lod = command.arg::<u32>(0)? != 0;
+                Ok(format!(
+                    "r_lod {}",
+                    Self::on_off(self.graphics_settings.lod)
+                ))
+            }
+            "r_lod" => Ok(format!(
+                "r_lod {}",
+                Self::on_off(self.graphics_settings.lod)
+            )),
+            "sensitivity" if has_value => {
+                let sensitivity = self.player.set_sensitivity(command.arg(0)?);
+                Ok(format!("sensitivity {sensitivity:.2}"))
+            }
+            "sensitivity" => Ok(format!("sensitivity {:.2}", self.player.sensitivity())),
+            "fov" if has_value => {
+                let fov = self.player.camera_settings.set_fov(command.arg(0)?);
+                Ok(format!("fov {fov:.0}"))
+            }
+            "fov" => Ok(format!("fov {:.0}", self.player.camera_settings.fov)),
+            "zoom_fov" if has_value => {
+                let zoom_fov = self.player.camera_settings.set_zoom_fov(command.arg(0)?);
+                Ok(format!("zoom_fov {zoom_fov:.0}"))
+            }
+            "zoom_fov" => Ok(format!(
+                "zoom_fov {:.0}",
+                self.player.camera_settings.zoom_fov
+            )),
+            "invert_y" if has_value => {
+                self.player.camera_settings.invert_y = command.arg::<u32>(0)? != 0;
+                Ok(format!(
+                    "invert_y {}",
+                    Self::on_off(self.player.camera_settings.invert_y)
+                ))
+            }
+            "invert_y" => Ok(format!(
+                "invert_y {}",
+                Self::on_off(self.player.camera_settings.invert_y)
+            )),
+            "m_smoothing" if has_value => {
+                let smoothing = self.player.camera_settings.set_smoothing(command.arg(0)?);
+                Ok(format!("m_smoothing {smoothing:.2}"))
+            }
+            "m_smoothing" => Ok(format!(
+                "m_smoothing {:.2}",
+                self.player.camera_settings.smoothing
+            )),
+            name => Err(format!("Unknown command {name}, try help")),
+        }
+    }
+
+    fn on_off(enabled: bool) -> &'static str {
+        if enabled { "on" } else { "off" }
+    }
+
+    // Says when the setting is on but the backend gains no overlap from it.
+    fn async_compute_status(&self) -> String {
+        let status = format!(
+            "r_async_compute {}",
+            Self::on_off(self.graphics_settings.async_compute)
+        );
+        if self.graphics_settings.async_compute && !self.gpu_capabilities.async_compute {
+            format!("{status} (no effect on {:?})", self.gpu_info.backend)
+        } else {
+            status
+        }
+    }
+
+    fn spawn_light(&mut self, command: &ConsoleCommand) -> CommandResult {
+        let position = Point3::new(command.arg(0)?, command.arg(1)?, command.arg(2)?);
+        let intensity = command.arg_or(3, Self::SPAWNED_LIGHT_INTENSITY)?;
+        let id = self.add_light(position, intensity)?;
+        Ok(format!(
+            "Spawned light {id} at {:.1} {:.1} {:.1}",
+            position.x, position.y, position.z
+        ))
+    }
+
+    fn light_shadow(&mut self, id: u32) -> Result<&mut ShadowSettings, String> {
+        self.lights
+            .get_mut(id as usize)
+            .map(|light| &mut light.shadow)
+            .ok_or_else(|| format!("No light {id}"))
+    }
+
+    fn shadow_bias(&mut self, command: &ConsoleCommand) -> CommandResult {
+        let id = command.arg(0)?;
+        let shadow = self.light_shadow(id)?;
+        if command.args.len() > 1 {
+            shadow.depth_bias = command.arg(1)?;
+            shadow.normal_bias = command.arg_or(2, shadow.normal_bias)?;
+        }
+        let output = format!(
+            "r_shadow_bias {id} {} {}",
+            shadow.depth_bias, shadow.normal_bias
+        );
+        self.upload_lights();
+        Ok(output)
+    }
+
+    fn shadow_pcf(&mut self, command: &ConsoleCommand) -> CommandResult {
+        let id = command.arg(0)?;
+        let shadow = self.light_shadow(id)?;
+        if command.args.len() > 1 {
+            shadow.pcf = command.arg::<PcfKernel>(1)?;
+        }
+        let output = format!("r_shadow_pcf {id} {}", shadow.pcf.taps());
+        self.upload_lights();
+        Ok(output)
+    }
+
+    /// Adds a white light, returning its id.
+    pub(super) fn add_light(
+        &mut self,
+        position: Point3<f32>,
+        intensity: f32,
+    ) -> Result<u32, String> {
+        if self.lights.len() == MAX_LIGHTS {
+            return Err(format!("Already at the {MAX_LIGHTS} light limit"));
+        }
+        // Light ids double as shadow map layers, so they stay dense.
+        let id = self.lights.len() as u32;
+        self.lights.push(Light {
+            id,
+            position,
+            intensity,
+            color: [1.0, 1.0, 1.0],
+            shadow: ShadowSettings::default(),
+            animation: None,
+        });
+        self.upload_lights();
+        self.rebuild_shadow_maps(self.shadow_baker.resolution());
+        Ok(id)
+    }
+
+    pub(super) fn upload_lights(&self) {
+        self.queue.write_buffer(
+            &self.point_light_buffer,
+            0,
+            bytemuck::cast_slice(&[LightUniformArray::new(
+                &self.lights,
+                self.ambient,
+                self.environment,
+                self.light_time,
+            )]),
+        );
+    }
+
+    pub(super) fn set_shadow_resolution(&mut self, resolution: u32) -> CommandResult {
+        // Every face of the most important light still has to fit.
+        let max_resolution = self.shadow_baker.atlas.size() / 4;
+        if !resolution.is_power_of_two()
+            || !(ShadowBaker::MIN_RESOLUTION..=max_resolution).contains(&resolution)
+        {
+            return Err(format!(
+                "r_shadow_res must be a power of two from {} to {max_resolution}",
+                ShadowBaker::MIN_RESOLUTION
+            ));
+        }
+        self.rebuild_shadow_maps(resolution);
+        Ok(format!("r_shadow_res {resolution}"))
+    }
+
+    /// Reallocates every shadow map, for a resolution change or a new light.
+    pub(super) fn rebuild_shadow_maps(&mut self, resolution: u32) {
+        let light_ids: Vec<u32> = self.lights.iter().map(|light| light.id).collect();
+        self.shadow_baker
+            .rebuild(&light_ids, resolution, &self.device);
+        self.shadow_bind_group = ShadowMapUniform::create_shadow_texture_bind_group(
+            &self.device,
+            &self.shadow_baker.atlas.view,
+            &self.shadow_baker.atlas.sampler,
+            &self.shadow_baker.face_buffer,
+            &ShadowMapUniform::create_shadow_texture_layout(&self.device),
+        );
+    }
+}
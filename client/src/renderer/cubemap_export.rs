@@ -0,0 +1,151 @@
+use image::RgbaImage;
+use nalgebra::Point3;
+use wgpu::{Buffer, Device, TextureFormat};
+
+use crate::camera::camera_uniform::CameraUniform;
+use crate::model::cube_texture::{CubeFace, CubeTextureBuilder};
+use crate::model::texture::TextureBuilder;
+
+use super::Renderer;
+use super::pipeline_factory::PipelineFactory;
+use super::reflection_probes::ReflectionProbes;
+
+impl Renderer {
+    // What cube maps load their faces as, so written faces come back looking the same.
+    const EXPORT_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+    const MIN_EXPORT_SIZE: u32 = 16;
+    const MAX_EXPORT_SIZE: u32 = 2048;
+    // Named like map skyboxes list their faces.
+    const FACE_NAMES: [&str; 6] = ["right", "left", "top", "bottom", "front", "back"];
+
+    /// Renders the level seen from `position` into a cube map with `size` texel faces and
+    /// writes them as `{prefix}_{face}.png`. The scene is drawn like a reflection probe, lit
+    /// diffusely without shadows. Returns the files in the order map
+    /// skyboxes take them.
+    pub(super) fn export_cubemap(
+        &self,
+        position: Point3<f32>,
+        size: u32,
+        prefix: &str,
+    ) -> Result<Vec<String>, String> {
+        if !size.is_power_of_two()
+            || !(Self::MIN_EXPORT_SIZE..=Self::MAX_EXPORT_SIZE).contains(&size)
+        {
+            return Err(format!(
+                "Cube map size must be a power of two from {} to {}",
+                Self::MIN_EXPORT_SIZE,
+                Self::MAX_EXPORT_SIZE
+            ));
+        }
+        let camera_layout = CameraUniform::create_bind_group_layout(&self.device);
+        let probe = ReflectionProbes::new(
+            &self.device,
+            &[position],
+            size,
+            Self::EXPORT_FORMAT,
+            &camera_layout,
+            &TextureBuilder::create_bind_group_layout(&self.device),
+        );
+        let skybox_layout = PipelineFactory::create_render_pipeline_layout(
+            &self.device,
+            "Cube Map Export Skybox Pipeline Layout",
+            &[
+                &CubeTextureBuilder::create_bind_group_layout(&self.device),
+                &camera_layout,
+            ],
+        );
+        let skybox_pipeline =
+            Self::create_skybox_pipeline(&self.device, &skybox_layout, Self::EXPORT_FORMAT);
+        let bake = self.prepare_probe_bake(&probe, &skybox_pipeline);
+
+        // Copied rows have to start on 256 byte boundaries.
+        let bytes_per_row = (size * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let face_bytes = bytes_per_row as u64 * size as u64;
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cube Map Export Readback Buffer"),
+            size: face_bytes * CubeFace::ALL.len() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Cube Map Export Encoder"),
+            });
+        probe.draw(&mut encoder, None, &bake, &self.models, &self.materials);
+        for face in CubeFace::ALL {
+            encoder.copy_texture_to_buffer(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &probe.cubes().texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: probe.cubes().layer(0, face)?,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &readback_buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: face as u64 * face_bytes,
+                        bytes_per_row: Some(bytes_per_row),
+                        rows_per_image: Some(size),
+                    },
+                },
+                wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let bytes = Self::read_back(&self.device, &readback_buffer)?;
+        let mut files = vec![];
+        for (face, name) in Self::FACE_NAMES.iter().enumerate() {
+            let start = face * face_bytes as usize;
+            let pixels: Vec<u8> = bytes[start..start + face_bytes as usize]
+                .chunks_exact(bytes_per_row as usize)
+                .flat_map(|row| &row[..size as usize * 4])
+                .copied()
+                .collect();
+            let image = RgbaImage::from_raw(size, size, pixels)
+                .ok_or_else(|| format!("Cube map face {name} came back short"))?;
+            let file = format!("{prefix}_{name}.png");
+            image
+                .save(&file)
+                .map_err(|e| format!("Unable to write {file} {e}"))?;
+            files.push(file);
+        }
+        Ok(files)
+    }
+
+    /// Waits for the GPU to finish with `buffer` and copies it out.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_back(device: &Device, buffer: &Buffer) -> Result<Vec<u8>, String> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        device
+            .poll(wgpu::PollType::Wait)
+            .map_err(|e| format!("Unable to wait for the GPU {e}"))?;
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Unable to read the cube map back {e}"))?;
+        let bytes = buffer.slice(..).get_mapped_range().to_vec();
+        buffer.unmap();
+        Ok(bytes)
+    }
+
+    /// Browsers only map buffers once control returns to them, and can't write files anyway.
+    #[cfg(target_arch = "wasm32")]
+    fn read_back(_device: &Device, _buffer: &Buffer) -> Result<Vec<u8>, String> {
+        Err("Cube maps can only be exported from the native build".to_string())
+    }
+}
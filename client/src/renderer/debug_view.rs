@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroupLayout, Buffer, RenderPipeline, Surface, SurfaceConfiguration};
+use winit::window::Window;
+
+use super::Renderer;
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+/// A second window showing the six faces of one light's shadow map, three by two, each
+/// stretched from its tile of the atlas. It shares the renderer's device and draws straight
+/// from the live shadow atlas.
+pub struct DebugView {
+    window: Arc<Window>,
+    surface: Surface<'static>,
+    config: SurfaceConfiguration,
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    params_buffer: Buffer,
+    light: usize,
+    // Light the params buffer and title were last written for.
+    shown_light: Option<usize>,
+}
+
+impl DebugView {
+    pub fn window(&self) -> &Arc<Window> {
+        &self.window
+    }
+
+    /// Steps through the lights, wrapping around at either end.
+    pub fn select_light(&mut self, step: isize) {
+        self.light = self.light.wrapping_add_signed(step);
+    }
+}
+
+impl Renderer {
+    pub fn open_debug_view(&self, window: Arc<Window>) -> Result<DebugView, String> {
+        let surface = self
+            .instance
+            .create_surface(window.clone())
+            .map_err(|e| format!("Failed to create debug view surface {e}"))?;
+        let size = window.inner_size();
+        let config = surface
+            .get_default_config(&self.adapter, size.width.max(1), size.height.max(1))
+            .ok_or("Debug view surface is incompatible with the adapter")?;
+        surface.configure(&self.device, &config);
+
+        let layout = self
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("debug_view_bind_group_layout"),
+            });
+        let params_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Debug View Params Buffer"),
+                contents: bytemuck::cast_slice(&[0u32; 4]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            &self.device,
+            "Shadow Faces Pipeline Layout",
+            &[&layout],
+        );
+        let pipeline = PipelineFactory::create_render_pipeline(
+            &self.device,
+            &pipeline_layout,
+            PipelineDescriptor::new(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("Shadow Faces Shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        include_str!("shaders/shadow_faces.wgsl").into(),
+                    ),
+                },
+                Some(config.format),
+            ),
+        );
+
+        Ok(DebugView {
+            window,
+            surface,
+            config,
+            pipeline,
+            layout,
+            params_buffer,
+            light: 0,
+            shown_light: None,
+        })
+    }
+
+    pub fn resize_debug_view(&self, view: &mut DebugView, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        view.config.width = width;
+        view.config.height = height;
+        view.surface.configure(&self.device, &view.config);
+    }
+
+    pub fn render_debug_view(&self, view: &mut DebugView) -> Result<(), wgpu::SurfaceError> {
+        if self.lights.is_empty() {
+            return Ok(());
+        }
+        let light = view.light % self.lights.len();
+        if view.shown_light != Some(light) {
+            view.window
+                .set_title(&format!("Mood shadow maps, light {light}"));
+            self.queue.write_buffer(
+                &view.params_buffer,
+                0,
+                bytemuck::cast_slice(&[light as u32, 0, 0, 0]),
+            );
+            view.shown_light = Some(light);
+        }
+        // The face buffer gets reallocated when lights change, so the bind group is made
+        // fresh every frame.
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &view.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.shadow_baker.atlas.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: view.params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.shadow_baker.face_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("debug_view_bind_group"),
+        });
+
+        let output = view.surface.get_current_texture()?;
+        let color_view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Debug View Encoder"),
+            });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Debug View Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&view.pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+        Ok(())
+    }
+}
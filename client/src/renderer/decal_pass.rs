@@ -0,0 +1,278 @@
+use bytemuck::{Pod, Zeroable};
+use image::{Rgba, RgbaImage};
+use nalgebra::{Matrix4, Rotation3, Unit, Vector3};
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Queue, RenderPipeline, TextureView,
+};
+
+use crate::game::decals::{Decal, DecalSystem};
+use crate::model::texture::Texture;
+
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct DecalInstance {
+    // Takes the decal's unit box to world space, the box's y axis is the decal normal.
+    pub model_mat: [[f32; 4]; 4],
+    pub inv_model_mat: [[f32; 4]; 4],
+    pub atlas_index: u32,
+    pub opacity: f32,
+}
+
+/// Projects decals onto whatever geometry is already in the depth buffer, by drawing each
+/// decal's box and rebuilding the world position underneath every covered pixel.
+pub struct DecalPass {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    atlas: Texture,
+    instance_buffer: Buffer,
+    decal_count: u32,
+}
+
+impl DecalInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 10] = wgpu::vertex_attr_array![
+            0 => Float32x4,
+            1 => Float32x4,
+            2 => Float32x4,
+            3 => Float32x4,
+            4 => Float32x4,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Uint32,
+            9 => Float32,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DecalInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+
+    pub fn new(decal: &Decal) -> Self {
+        let size = decal.kind.settings().size;
+        let normal = Unit::new_normalize(decal.normal);
+        let helper = if normal.y.abs() < 0.9 {
+            Vector3::y()
+        } else {
+            Vector3::x()
+        };
+        let tangent =
+            Rotation3::from_axis_angle(&normal, decal.angle) * normal.cross(&helper).normalize();
+        let bitangent = tangent.cross(&normal);
+        let model_mat = Matrix4::new(
+            tangent.x * size,
+            normal.x * size * DecalPass::PROJECTION_DEPTH,
+            bitangent.x * size,
+            decal.position.x,
+            tangent.y * size,
+            normal.y * size * DecalPass::PROJECTION_DEPTH,
+            bitangent.y * size,
+            decal.position.y,
+            tangent.z * size,
+            normal.z * size * DecalPass::PROJECTION_DEPTH,
+            bitangent.z * size,
+            decal.position.z,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        );
+        Self {
+            model_mat: model_mat.into(),
+            inv_model_mat: model_mat.try_inverse().unwrap_or_default().into(),
+            atlas_index: decal.kind.atlas_index(),
+            opacity: decal.opacity(),
+        }
+    }
+}
+
+impl DecalPass {
+    // Depth of the projection box relative to the decal's size.
+    const PROJECTION_DEPTH: f32 = 0.5;
+    const ATLAS_CELL: u32 = 64;
+    const ATLAS_COLUMNS: u32 = 2;
+
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        color_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &BindGroupLayout,
+        depth_view: &TextureView,
+    ) -> Self {
+        let atlas = Texture::from_rgba(&Self::create_atlas(), device, queue, Some("Decal Atlas"));
+        let bind_group_layout = Self::create_bind_group_layout(device);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, depth_view, &atlas);
+        let layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Decal Pipeline Layout",
+            &[camera_bind_group_layout, &bind_group_layout],
+        );
+        // Back faces are drawn so decals still show with the camera inside their box.
+        let pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &layout,
+            PipelineDescriptor {
+                vertex_layouts: &[DecalInstance::desc()],
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                cull_mode: Some(wgpu::Face::Front),
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("Decal Shader"),
+                        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/decal.wgsl").into()),
+                    },
+                    Some(color_format),
+                )
+            },
+        );
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Decal Instance Buffer"),
+            size: (DecalSystem::MAX_DECALS * std::mem::size_of::<DecalInstance>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            atlas,
+            instance_buffer,
+            decal_count: 0,
+        }
+    }
+
+    /// Bullet hole and scorch mark side by side, alpha is the decal's coverage.
+    fn create_atlas() -> RgbaImage {
+        let cell = Self::ATLAS_CELL;
+        RgbaImage::from_fn(cell * Self::ATLAS_COLUMNS, cell, |x, y| {
+            let column = x / cell;
+            let u = (x % cell) as f32 / cell as f32 * 2.0 - 1.0;
+            let v = y as f32 / cell as f32 * 2.0 - 1.0;
+            let r = (u * u + v * v).sqrt();
+            match column {
+                0 => {
+                    // Dark core with a lighter chipped rim.
+                    let core = 1.0 - ((r - 0.35) / 0.1).clamp(0.0, 1.0);
+                    let rim = 1.0 - ((r - 0.6) / 0.2).clamp(0.0, 1.0);
+                    let shade = 10.0 + (1.0 - core) * 50.0;
+                    Rgba([shade as u8, shade as u8, shade as u8, (rim * 255.0) as u8])
+                }
+                _ => {
+                    // Soot falling off towards the edge, broken up by angular streaks.
+                    let angle = v.atan2(u);
+                    let streaks = 0.75 + 0.25 * (angle * 7.0).sin() * (angle * 3.0).cos();
+                    let soot = ((1.0 - r) * streaks).clamp(0.0, 1.0);
+                    Rgba([20, 16, 12, (soot * soot * 230.0) as u8])
+                }
+            }
+        })
+    }
+
+    fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("decal_bind_group_layout"),
+        })
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        depth_view: &TextureView,
+        atlas: &Texture,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&atlas.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&atlas.sampler),
+                },
+            ],
+            label: Some("decal_bind_group"),
+        })
+    }
+
+    /// Must be called whenever the scene depth texture is recreated.
+    pub fn rebind_depth(&mut self, device: &Device, depth_view: &TextureView) {
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, depth_view, &self.atlas);
+    }
+
+    pub fn update(&mut self, queue: &Queue, decal_system: &DecalSystem) {
+        let instances: Vec<DecalInstance> = decal_system.decals().map(DecalInstance::new).collect();
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        self.decal_count = instances.len() as u32;
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        color_view: &TextureView,
+        camera_bind_group: &BindGroup,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        if self.decal_count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Decal Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..36, 0..self.decal_count);
+    }
+}
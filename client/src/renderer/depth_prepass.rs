@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, Device, RenderPass, RenderPipeline, TextureView,
+};
+
+use crate::camera::frustum::Frustum;
+use crate::model::depth_texture::DepthTexture;
+use crate::model::model_instance::RawInstance;
+use crate::model::vertex::Vertex;
+use crate::model::{Material, Model};
+
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+/// Lays down the depth of opaque and masked geometry before the main pass, so the lighting
+/// shader only runs once per pixel however many surfaces overlap it. Like the shadow pass it
+/// has no color targets. SSAO's prepass already fills depth, so this one only runs without it.
+pub struct DepthPrepass {
+    pipeline: RenderPipeline,
+    // Cuts out the holes of masked meshes, with their material at group 1.
+    masked_pipeline: RenderPipeline,
+}
+
+/// What the prepasses draw: every opaque and masked mesh in view. Translucent meshes are left
+/// out since they would cover what shows through them.
+pub struct DepthGeometry<'a> {
+    pub models: &'a [Model],
+    pub materials: &'a HashMap<String, Material>,
+    pub frustum: &'a Frustum,
+}
+
+impl DepthGeometry<'_> {
+    pub const MATERIAL_GROUP: u32 = 1;
+
+    /// Draws opaque meshes with `pipeline` and masked ones with `masked_pipeline`, which
+    /// shares the bind groups already set and takes the material at `MATERIAL_GROUP`.
+    pub fn draw(
+        &self,
+        render_pass: &mut RenderPass,
+        pipeline: &RenderPipeline,
+        masked_pipeline: &RenderPipeline,
+    ) {
+        render_pass.set_pipeline(pipeline);
+        for model in self.models {
+            model.draw_geometry(render_pass, self.frustum);
+        }
+        render_pass.set_pipeline(masked_pipeline);
+        for model in self.models {
+            model.draw_masked_geometry(
+                render_pass,
+                self.frustum,
+                self.materials,
+                Self::MATERIAL_GROUP,
+            );
+        }
+    }
+}
+
+impl DepthPrepass {
+    pub fn new(
+        device: &Device,
+        camera_bind_group_layout: &BindGroupLayout,
+        material_bind_group_layout: &BindGroupLayout,
+    ) -> Self {
+        let layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Depth Prepass Pipeline Layout",
+            &[camera_bind_group_layout],
+        );
+        let masked_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Masked Depth Prepass Pipeline Layout",
+            &[camera_bind_group_layout, material_bind_group_layout],
+        );
+        let create_pipeline = |layout, label, fragment_entry_point| {
+            PipelineFactory::create_render_pipeline(
+                device,
+                layout,
+                PipelineDescriptor {
+                    fragment_entry_point,
+                    vertex_layouts: &[Vertex::desc(), RawInstance::desc()],
+                    cull_mode: Some(wgpu::Face::Back),
+                    depth_format: Some(DepthTexture::DEPTH_FORMAT),
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    ..PipelineDescriptor::new(
+                        wgpu::ShaderModuleDescriptor {
+                            label: Some(label),
+                            source: wgpu::ShaderSource::Wgsl(
+                                include_str!("shaders/depth_prepass.wgsl").into(),
+                            ),
+                        },
+                        None,
+                    )
+                },
+            )
+        };
+        Self {
+            pipeline: create_pipeline(&layout, "Depth Prepass Shader", "fs_main"),
+            masked_pipeline: create_pipeline(
+                &masked_layout,
+                "Masked Depth Prepass Shader",
+                "fs_masked",
+            ),
+        }
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        depth_view: &TextureView,
+        camera_bind_group: &BindGroup,
+        geometry: &DepthGeometry,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Prepass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        geometry.draw(&mut render_pass, &self.pipeline, &self.masked_pipeline);
+    }
+}
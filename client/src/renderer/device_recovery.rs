@@ -0,0 +1,191 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use log::error;
+use nalgebra::Point3;
+use winit::window::Window;
+
+use crate::camera::light::Light;
+use crate::game::automap::Automap;
+use crate::game::camera_controller::CameraController;
+use crate::game::character::Character;
+use crate::game::collision_manager::CollisionManager;
+use crate::game::console::Console;
+use crate::game::decals::DecalSystem;
+use crate::game::hud::Hud;
+use crate::game::movers::Mover;
+use crate::game::navigation::NavGrid;
+use crate::game::particles::ParticleSystem;
+use crate::game::pickups::PickupSystem;
+use crate::game::player::Player;
+use crate::game::replay::ReplayMode;
+use crate::game::script_host::ScriptHost;
+use crate::game::triggers::TriggerSystem;
+use crate::game::view_model::ViewModel;
+use crate::game::weapons::WeaponSystem;
+use crate::settings::Settings;
+
+use super::Renderer;
+
+/// Everything about a play session that lives outside the GPU, carried over to the renderer
+/// built on a new device.
+struct Session {
+    window: Arc<Window>,
+    map_file: String,
+    settings: Settings,
+    player: Player,
+    camera_controller: Box<dyn CameraController>,
+    view_model: ViewModel,
+    lights: Vec<Light>,
+    light_time: f32,
+    flash_light: Option<u32>,
+    flash_remaining: f32,
+    characters: Vec<Character>,
+    particle_system: ParticleSystem,
+    decal_system: DecalSystem,
+    weapon_system: WeaponSystem,
+    pickup_system: PickupSystem,
+    hud: Hud,
+    console: Console,
+    automap: Automap,
+    replay: ReplayMode,
+    script_host: Option<ScriptHost>,
+    trigger_system: TriggerSystem,
+    movers: Vec<Mover>,
+    nav_grid: NavGrid,
+    spawn_point: Point3<f32>,
+    collision_manager: CollisionManager,
+}
+
+impl Renderer {
+    // Timeouts in a row before the surface is given up on along with its device.
+    const MAX_SURFACE_TIMEOUTS: u32 = 3;
+
+    /// Flags `device_lost` once the driver drops `device`. Losing it on purpose doesn't count.
+    pub(super) fn watch_device_loss(device: &wgpu::Device) -> Arc<AtomicBool> {
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let flag = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            if reason == wgpu::DeviceLostReason::Unknown {
+                error!("GPU device lost: {message}");
+                flag.store(true, Ordering::Relaxed);
+            }
+        });
+        device_lost
+    }
+
+    /// True once nothing drawn will reach the screen anymore and `recover` is needed.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+            || self.surface_timeouts >= Self::MAX_SURFACE_TIMEOUTS
+    }
+
+    /// Rebuilds the surface, device and pipelines on whatever adapter is usable now and
+    /// carries the play session over. Textures come back through the asset loader like on a
+    /// fresh start.
+    #[tracing::instrument(skip_all, fields(map = %self.map_file))]
+    pub async fn recover(self) -> Result<Self, String> {
+        let session = self.into_session();
+        let mut renderer = Self::new(
+            session.window.clone(),
+            session.map_file.clone(),
+            &session.settings,
+        )
+        .await?;
+        renderer.restore_session(session);
+        Ok(renderer)
+    }
+
+    /// Keeps the session and drops the lost device, so its surface is gone before the
+    /// window gets a new one.
+    fn into_session(mut self) -> Session {
+        // The new renderer streams the chunks in again, only the map's own lights carry over.
+        self.unload_chunks();
+        let mut settings = Settings::default();
+        self.capture_settings(&mut settings);
+        Session {
+            settings,
+            window: self.window,
+            map_file: self.map_file,
+            player: self.player,
+            camera_controller: self.camera_controller,
+            view_model: self.view_model,
+            lights: self.lights,
+            light_time: self.light_time,
+            flash_light: self.flash_light,
+            flash_remaining: self.flash_remaining,
+            characters: self.characters,
+            particle_system: self.particle_system,
+            decal_system: self.decal_system,
+            weapon_system: self.weapon_system,
+            pickup_system: self.pickup_system,
+            hud: self.hud,
+            console: self.console,
+            automap: self.automap,
+            replay: self.replay,
+            script_host: self.script_host,
+            trigger_system: self.trigger_system,
+            movers: self.movers,
+            nav_grid: self.nav_grid,
+            spawn_point: self.spawn_point,
+            collision_manager: self.collision_manager,
+        }
+    }
+
+    /// Puts the session back and brings the freshly loaded map's models up to date with it.
+    fn restore_session(&mut self, session: Session) {
+        // Collected pickups are the ones the fresh map still has but the session doesn't.
+        let remaining: Vec<(usize, usize)> = session.pickup_system.models().collect();
+        for (model, instance) in self.pickup_system.models() {
+            if !remaining.contains(&(model, instance)) {
+                self.models[model].hide_instance(instance, &self.queue);
+            }
+        }
+        for mover in &session.movers {
+            if let Some(model) = mover.model {
+                self.models[model].translate(mover.travelled(), &self.queue);
+            }
+        }
+        for character in &session.characters {
+            self.models[character.model].set_instance(0, character.instance(), &self.queue);
+        }
+
+        let aspect = self.player.camera.aspect;
+        self.player = session.player;
+        self.player.camera.aspect = aspect;
+        self.camera_controller = session.camera_controller;
+        self.view_model = session.view_model;
+        self.lights = session.lights;
+        self.light_time = session.light_time;
+        self.flash_light = session.flash_light;
+        self.flash_remaining = session.flash_remaining;
+        self.characters = session.characters;
+        self.particle_system = session.particle_system;
+        self.decal_system = session.decal_system;
+        self.weapon_system = session.weapon_system;
+        self.pickup_system = session.pickup_system;
+        self.hud = session.hud;
+        self.console = session.console;
+        self.automap = session.automap;
+        self.replay = session.replay;
+        self.script_host = session.script_host;
+        self.trigger_system = session.trigger_system;
+        self.movers = session.movers;
+        self.nav_grid = session.nav_grid;
+        self.spawn_point = session.spawn_point;
+        self.collision_manager = session.collision_manager;
+
+        self.upload_lights();
+        self.shadow_baker.update_scene_version();
+        self.rebuild_shadow_maps(self.shadow_baker.resolution());
+        self.animate_characters(Duration::ZERO);
+        self.camera_uniform.update_cam(&self.player.camera);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+        self.hud.show_message("Recovered from a GPU reset");
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+/// Picks a render scale that keeps frames within a time budget. Frame times are smoothed and
+/// the scale only moves every so often, since each change recreates the render targets.
+pub struct DynamicResolution {
+    scale: f32,
+    average_frame_time: f32,
+    since_adjust: Duration,
+}
+
+impl Default for DynamicResolution {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            average_frame_time: Self::BUDGET.as_secs_f32(),
+            since_adjust: Duration::ZERO,
+        }
+    }
+}
+
+impl DynamicResolution {
+    const BUDGET: Duration = Duration::from_micros(16_667);
+    const ADJUST_INTERVAL: Duration = Duration::from_millis(500);
+    const SMOOTHING: f32 = 0.1;
+    const STEP: f32 = 0.05;
+    // Only scale back up with some headroom so the scale doesn't flip between two steps.
+    const RAISE_THRESHOLD: f32 = 0.85;
+    const LOWER_THRESHOLD: f32 = 1.05;
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Records a frame and returns true when the scale changed.
+    pub fn update(&mut self, frame_time: Duration, min_scale: f32) -> bool {
+        self.average_frame_time +=
+            (frame_time.as_secs_f32() - self.average_frame_time) * Self::SMOOTHING;
+        self.since_adjust += frame_time;
+        if self.since_adjust < Self::ADJUST_INTERVAL {
+            return false;
+        }
+        self.since_adjust = Duration::ZERO;
+        let load = self.average_frame_time / Self::BUDGET.as_secs_f32();
+        let scale = if load > Self::LOWER_THRESHOLD {
+            self.scale - Self::STEP
+        } else if load < Self::RAISE_THRESHOLD {
+            self.scale + Self::STEP
+        } else {
+            self.scale
+        }
+        .clamp(min_scale, 1.0);
+        let changed = scale != self.scale;
+        self.scale = scale;
+        changed
+    }
+}
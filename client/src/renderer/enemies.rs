@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use crate::game::character::Character;
+use crate::game::collision_manager::CollisionManager;
+use crate::game::enemy::{Enemy, EnemyWorld};
+use crate::game::health::Damage;
+use crate::game::navigation::NavGrid;
+use crate::game::rng::RngSystem;
+
+use super::Renderer;
+
+impl Renderer {
+    const ENEMY_ANIMATION_BLEND: Duration = Duration::from_millis(200);
+
+    /// Navigation grid at the height the enemies stand on.
+    pub(super) fn build_nav_grid(
+        collision_manager: &CollisionManager,
+        characters: &[Character],
+    ) -> NavGrid {
+        let floor = characters
+            .iter()
+            .filter(|character| character.enemy.is_some())
+            .map(|character| character.position.y)
+            .reduce(f32::min)
+            .unwrap_or(0.0);
+        NavGrid::build(collision_manager, floor, Enemy::RADIUS, Enemy::HEIGHT)
+    }
+
+    fn has_enemies(&self) -> bool {
+        self.characters
+            .iter()
+            .any(|character| character.enemy.is_some())
+    }
+
+    /// Rebuilds the navigation grid after level geometry moved, e.g. a door opened.
+    pub(super) fn refresh_nav_grid(&mut self) {
+        if self.has_enemies() {
+            self.nav_grid = Self::build_nav_grid(&self.collision_manager, &self.characters);
+        }
+    }
+
+    /// Runs every enemy's AI, moves their models and applies the damage they dealt.
+    pub(super) fn update_enemies(&mut self, dt: Duration) {
+        let target = self.player.camera.position;
+        let world = EnemyWorld {
+            collision_manager: &self.collision_manager,
+            nav_grid: &self.nav_grid,
+        };
+        let mut hits = vec![];
+        for character in &mut self.characters {
+            let Some(enemy) = &mut character.enemy else {
+                continue;
+            };
+            let (position, yaw, state) = (character.position, character.yaw, enemy.state());
+            let damage = enemy.update(
+                dt,
+                &mut character.position,
+                &mut character.yaw,
+                target,
+                &world,
+                self.rng.stream(RngSystem::Enemies),
+            );
+            if damage > 0 {
+                hits.push(Damage {
+                    amount: damage,
+                    source: character.position,
+                });
+            }
+            if enemy.state() != state
+                && let Some(animation) = enemy.animation()
+            {
+                let looping = !enemy.is_dead();
+                character
+                    .animator
+                    .play(animation, looping, Self::ENEMY_ANIMATION_BLEND);
+            }
+            if character.position != position || character.yaw != yaw {
+                self.models[character.model].set_instance(0, character.instance(), &self.queue);
+            }
+        }
+        for hit in hits {
+            self.damage_player(hit);
+        }
+    }
+}
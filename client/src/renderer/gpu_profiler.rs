@@ -0,0 +1,269 @@
+use log::{debug, warn};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use web_time::Instant;
+use wgpu::{Buffer, CommandEncoder, Device, QuerySet, Queue};
+
+struct ProfilerQueries {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    // Nanoseconds per timestamp tick.
+    period: f64,
+    // Set by the map callback, true when the readback buffer is mapped.
+    mapped: Arc<Mutex<Option<bool>>>,
+    // Labels of the frame currently being read back.
+    pending: Option<(u64, Vec<String>)>,
+}
+
+/// Measures how long each pass takes on the GPU with timestamp queries. Everything is a no-op
+/// when the adapter doesn't support them.
+///
+/// Pass labels may be grouped with a `group:detail` prefix, the periodic report sums each
+/// group while the CSV keeps every label.
+pub struct GpuProfiler {
+    queries: Option<ProfilerQueries>,
+    labels: Vec<String>,
+    recording: bool,
+    frame: u64,
+    csv: Option<BufWriter<File>>,
+    totals: Vec<(String, f64)>,
+    // Per group timings of the last frame read back, until taken.
+    latest: Vec<(String, f64)>,
+    frames_since_report: u32,
+    last_report: Instant,
+}
+
+impl GpuProfiler {
+    const MAX_QUERIES: u32 = 512;
+    const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+    // Set to a file path to dump every pass timing as CSV.
+    const CSV_ENV: &str = "MOOD_GPU_PROFILE";
+
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let queries = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let size = Self::MAX_QUERIES as u64 * std::mem::size_of::<u64>() as u64;
+                ProfilerQueries {
+                    query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("Profiler Query Set"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count: Self::MAX_QUERIES,
+                    }),
+                    resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Profiler Resolve Buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("Profiler Readback Buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    period: queue.get_timestamp_period() as f64,
+                    mapped: Arc::new(Mutex::new(None)),
+                    pending: None,
+                }
+            });
+        if queries.is_none() {
+            warn!("Timestamp queries are unsupported, GPU profiling is disabled");
+        }
+
+        let csv = std::env::var(Self::CSV_ENV).ok().and_then(|path| {
+            let file = File::create(&path)
+                .inspect_err(|e| warn!("Unable to create profile {path}: {e}"))
+                .ok()?;
+            let mut writer = BufWriter::new(file);
+            writeln!(writer, "frame,pass,gpu_ms").ok()?;
+            Some(writer)
+        });
+
+        Self {
+            queries,
+            labels: vec![],
+            recording: false,
+            frame: 0,
+            csv,
+            totals: vec![],
+            latest: vec![],
+            frames_since_report: 0,
+            last_report: Instant::now(),
+        }
+    }
+
+    /// Collects finished timings and decides whether this frame gets measured. Frames are
+    /// skipped while the previous readback is still in flight.
+    pub fn begin_frame(&mut self, device: &Device) {
+        self.labels.clear();
+        let Some(queries) = &mut self.queries else {
+            return;
+        };
+        let mut timings = None;
+        if queries.pending.is_some() {
+            let _ = device.poll(wgpu::PollType::Poll);
+            let mapped = queries.mapped.lock().unwrap().take();
+            // A failed map still finishes the readback, that frame is just dropped.
+            if let Some(mapped) = mapped
+                && let Some((frame, labels)) = queries.pending.take()
+                && mapped
+            {
+                timings = Some((frame, Self::read_timings(queries, &labels)));
+            }
+        }
+        self.recording = queries.pending.is_none();
+        if let Some((frame, timings)) = timings {
+            self.record(frame, timings);
+        }
+    }
+
+    fn read_timings(queries: &ProfilerQueries, labels: &[String]) -> Vec<(String, f64)> {
+        let size = labels.len() as u64 * 2 * std::mem::size_of::<u64>() as u64;
+        let timings = {
+            let data = queries.readback_buffer.slice(..size).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            labels
+                .iter()
+                .zip(timestamps.chunks_exact(2))
+                .map(|(label, pair)| {
+                    let ticks = pair[1].saturating_sub(pair[0]);
+                    (label.clone(), ticks as f64 * queries.period / 1_000_000.0)
+                })
+                .collect()
+        };
+        queries.readback_buffer.unmap();
+        timings
+    }
+
+    fn record(&mut self, frame: u64, timings: Vec<(String, f64)>) {
+        self.latest.clear();
+        for (label, ms) in timings {
+            if let Some(csv) = &mut self.csv
+                && let Err(e) = writeln!(csv, "{frame},{label},{ms:.4}")
+            {
+                warn!("Unable to write profile {e}");
+                self.csv = None;
+            }
+            let group = label.split(':').next().unwrap_or(&label);
+            for totals in [&mut self.totals, &mut self.latest] {
+                match totals.iter_mut().find(|(name, _)| name == group) {
+                    Some((_, total)) => *total += ms,
+                    None => totals.push((group.to_string(), ms)),
+                }
+            }
+        }
+        self.frames_since_report += 1;
+    }
+
+    fn allocate(&mut self, label: &str) -> Option<(&QuerySet, u32)> {
+        let queries = self.queries.as_ref()?;
+        let index = self.labels.len() as u32 * 2;
+        if !self.recording || index + 2 > Self::MAX_QUERIES {
+            return None;
+        }
+        self.labels.push(label.to_string());
+        Some((&queries.query_set, index))
+    }
+
+    pub fn render_pass_writes(
+        &mut self,
+        label: &str,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.allocate(label)
+            .map(|(query_set, index)| wgpu::RenderPassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: Some(index + 1),
+            })
+    }
+
+    pub fn compute_pass_writes(
+        &mut self,
+        label: &str,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.allocate(label)
+            .map(|(query_set, index)| wgpu::ComputePassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: Some(index + 1),
+            })
+    }
+
+    /// Copies this frame's timestamps into the readback buffer. Must be recorded after every
+    /// measured pass has been submitted or encoded.
+    pub fn resolve(&self, encoder: &mut CommandEncoder) {
+        let Some(queries) = &self.queries else {
+            return;
+        };
+        if !self.recording || self.labels.is_empty() {
+            return;
+        }
+        let count = self.labels.len() as u32 * 2;
+        encoder.resolve_query_set(&queries.query_set, 0..count, &queries.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &queries.resolve_buffer,
+            0,
+            &queries.readback_buffer,
+            0,
+            count as u64 * std::mem::size_of::<u64>() as u64,
+        );
+    }
+
+    /// Starts reading back the frame once its command buffers have been submitted.
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+        let Some(queries) = &mut self.queries else {
+            return;
+        };
+        if !self.recording || self.labels.is_empty() {
+            return;
+        }
+        let size = self.labels.len() as u64 * 2 * std::mem::size_of::<u64>() as u64;
+        let mapped = queries.mapped.clone();
+        queries
+            .readback_buffer
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if let Err(e) = &result {
+                    warn!("Unable to read GPU timestamps {e}");
+                }
+                *mapped.lock().unwrap() = Some(result.is_ok());
+            });
+        queries.pending = Some((self.frame, std::mem::take(&mut self.labels)));
+        self.recording = false;
+    }
+
+    /// GPU time per pass group of the newest measured frame, empty when nothing new was read
+    /// back since the last call.
+    pub fn take_frame_timings(&mut self) -> Vec<(String, f64)> {
+        std::mem::take(&mut self.latest)
+    }
+
+    /// Average GPU time per pass group since the last report, at most once per interval.
+    pub fn take_report(&mut self) -> Option<String> {
+        if self.last_report.elapsed() < Self::REPORT_INTERVAL || self.frames_since_report == 0 {
+            return None;
+        }
+        let frames = self.frames_since_report as f64;
+        let report = self
+            .totals
+            .iter()
+            .map(|(group, total)| format!("{group} {:.2}ms", total / frames))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        debug!("GPU {report}");
+        if let Some(csv) = &mut self.csv {
+            let _ = csv.flush();
+        }
+        self.totals.clear();
+        self.frames_since_report = 0;
+        self.last_report = Instant::now();
+        Some(report)
+    }
+}
@@ -0,0 +1,205 @@
+use std::{convert::Infallible, str::FromStr};
+
+use log::{info, warn};
+use wgpu::{Adapter, DeviceDescriptor, Features, Instance, Limits, Surface};
+
+/// Which GPU to run on, set through `MOOD_GPU`. Anything that isn't a device type picks the
+/// first adapter whose name contains it, ignoring case.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum GpuPreference {
+    #[default]
+    Discrete,
+    Integrated,
+    // Software rasterizers such as llvmpipe or WARP.
+    Cpu,
+    Named(String),
+}
+
+/// Optional GPU features the renderer adapts to instead of requiring them.
+#[derive(Debug, Clone, Copy)]
+pub struct GpuCapabilities {
+    // Timestamp queries for the GPU profiler.
+    pub timestamps: bool,
+    // BC compressed textures can be uploaded.
+    pub texture_compression: bool,
+    // Submissions start on the GPU as soon as they are made, so compute submitted ahead of
+    // the frame runs while the CPU is still recording the rest of it. There is only one queue,
+    // so it never runs alongside the render passes. GL replays submissions on the calling
+    // thread so it gains nothing, and browser WebGPU is left out until it is measured.
+    pub async_compute: bool,
+}
+
+/// The adapter picked for rendering along with its device.
+pub struct Gpu {
+    pub adapter: Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub capabilities: GpuCapabilities,
+}
+
+impl GpuPreference {
+    pub const ENV: &str = "MOOD_GPU";
+
+    pub fn from_env() -> Self {
+        std::env::var(Self::ENV)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Position of an adapter in the order they get tried, lowest first.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn rank(&self, info: &wgpu::AdapterInfo) -> usize {
+        use wgpu::DeviceType;
+
+        let order = match self {
+            Self::Integrated => [
+                DeviceType::IntegratedGpu,
+                DeviceType::DiscreteGpu,
+                DeviceType::VirtualGpu,
+                DeviceType::Other,
+                DeviceType::Cpu,
+            ],
+            Self::Cpu => [
+                DeviceType::Cpu,
+                DeviceType::IntegratedGpu,
+                DeviceType::DiscreteGpu,
+                DeviceType::VirtualGpu,
+                DeviceType::Other,
+            ],
+            Self::Discrete | Self::Named(_) => [
+                DeviceType::DiscreteGpu,
+                DeviceType::IntegratedGpu,
+                DeviceType::VirtualGpu,
+                DeviceType::Other,
+                DeviceType::Cpu,
+            ],
+        };
+        let rank = order
+            .iter()
+            .position(|device_type| *device_type == info.device_type)
+            .unwrap_or(order.len());
+        match self {
+            Self::Named(name) if info.name.to_lowercase().contains(&name.to_lowercase()) => 0,
+            Self::Named(_) => rank + 1,
+            _ => rank,
+        }
+    }
+}
+
+impl FromStr for GpuPreference {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "discrete" | "dedicated" => Self::Discrete,
+            "integrated" => Self::Integrated,
+            "cpu" | "software" => Self::Cpu,
+            _ => Self::Named(s.to_string()),
+        })
+    }
+}
+
+impl GpuCapabilities {
+    fn of(adapter: &Adapter) -> Self {
+        let features = adapter.features();
+        Self {
+            timestamps: features.contains(Features::TIMESTAMP_QUERY),
+            texture_compression: features.contains(Features::TEXTURE_COMPRESSION_BC),
+            async_compute: matches!(
+                adapter.get_info().backend,
+                wgpu::Backend::Vulkan | wgpu::Backend::Metal | wgpu::Backend::Dx12
+            ),
+        }
+    }
+
+    fn features(&self) -> Features {
+        let mut features = Features::empty();
+        features.set(Features::TIMESTAMP_QUERY, self.timestamps);
+        features.set(Features::TEXTURE_COMPRESSION_BC, self.texture_compression);
+        features
+    }
+}
+
+impl Gpu {
+    /// Tries every adapter that can present to `surface` in order of preference and keeps the
+    /// first that hands out a device.
+    pub async fn new(
+        instance: &Instance,
+        surface: &Surface<'_>,
+        preference: &GpuPreference,
+    ) -> Result<Self, String> {
+        for adapter in Self::candidates(instance, surface, preference).await {
+            let info = adapter.get_info();
+            let capabilities = GpuCapabilities::of(&adapter);
+            // Anything short of the usual limits still gets to run with what it has.
+            let supported = adapter.limits();
+            let required_limits = if Limits::default().check_limits(&supported) {
+                Limits::default()
+            } else {
+                warn!("{} is below the default limits", info.name);
+                Limits::downlevel_defaults().using_resolution(supported)
+            };
+            let device = adapter
+                .request_device(&DeviceDescriptor {
+                    required_features: capabilities.features(),
+                    required_limits,
+                    ..Default::default()
+                })
+                .await;
+            match device {
+                Ok((device, queue)) => {
+                    info!(
+                        "Using {} ({:?}, {:?}) {capabilities:?}",
+                        info.name, info.device_type, info.backend
+                    );
+                    return Ok(Self {
+                        adapter,
+                        device,
+                        queue,
+                        capabilities,
+                    });
+                }
+                Err(e) => warn!("Skipping {}: {e}", info.name),
+            }
+        }
+        Err("No usable GPU".to_string())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn candidates(
+        instance: &Instance,
+        surface: &Surface<'_>,
+        preference: &GpuPreference,
+    ) -> Vec<Adapter> {
+        let mut adapters: Vec<Adapter> = instance
+            .enumerate_adapters(wgpu::Backends::PRIMARY)
+            .into_iter()
+            .filter(|adapter| adapter.is_surface_supported(surface))
+            .collect();
+        adapters.sort_by_key(|adapter| preference.rank(&adapter.get_info()));
+        adapters
+    }
+
+    /// Browsers only hand out one adapter, chosen by power preference.
+    #[cfg(target_arch = "wasm32")]
+    async fn candidates(
+        instance: &Instance,
+        surface: &Surface<'_>,
+        preference: &GpuPreference,
+    ) -> Vec<Adapter> {
+        let power_preference = match preference {
+            GpuPreference::Integrated | GpuPreference::Cpu => wgpu::PowerPreference::LowPower,
+            _ => wgpu::PowerPreference::HighPerformance,
+        };
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference,
+                compatible_surface: Some(surface),
+                force_fallback_adapter: *preference == GpuPreference::Cpu,
+            })
+            .await
+            .into_iter()
+            .collect()
+    }
+}
@@ -0,0 +1,147 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SsaoQuality {
+    Off,
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+/// Which window resolution the scene is drawn at. On a high DPI display `Logical` draws one
+/// pixel per logical point and upscales, trading sharpness for fill rate.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderResolution {
+    #[default]
+    Physical,
+    Logical,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UpscaleFilter {
+    #[default]
+    Bilinear,
+    // Bilinear followed by contrast adaptive sharpening to win back some lost detail.
+    Sharpen,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub ssao: SsaoQuality,
+    pub render_resolution: RenderResolution,
+    // Fraction of the render resolution the scene is drawn at, the upper bound when dynamic.
+    pub render_scale: f32,
+    pub upscale_filter: UpscaleFilter,
+    // Lowers the render scale while frames take longer than the budget.
+    pub dynamic_resolution: bool,
+    // Draws opaque depth first so hidden surfaces skip lighting, ignored while SSAO is on
+    // since its prepass does the same.
+    pub depth_prepass: bool,
+    // Submits compute passes on their own as soon as they are recorded, so the GPU starts on
+    // them while the CPU records the rest of the frame. Only where that overlaps, see
+    // `GpuCapabilities::async_compute`.
+    pub async_compute: bool,
+    // Draws coarser mesh levels for meshes that are small on screen.
+    pub lod: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            ssao: SsaoQuality::default(),
+            render_resolution: RenderResolution::default(),
+            render_scale: 1.0,
+            upscale_filter: UpscaleFilter::default(),
+            dynamic_resolution: false,
+            depth_prepass: false,
+            async_compute: true,
+            lod: true,
+        }
+    }
+}
+
+impl GraphicsSettings {
+    pub const MIN_RENDER_SCALE: f32 = 0.5;
+    pub const RENDER_SCALE_STEP: f32 = 0.1;
+
+    pub fn set_render_scale(&mut self, render_scale: f32) -> f32 {
+        self.render_scale = render_scale.clamp(Self::MIN_RENDER_SCALE, 1.0);
+        self.render_scale
+    }
+}
+
+impl FromStr for SsaoQuality {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "0" => Ok(Self::Off),
+            "low" | "1" => Ok(Self::Low),
+            "medium" | "2" => Ok(Self::Medium),
+            "high" | "3" => Ok(Self::High),
+            _ => Err(()),
+        }
+    }
+}
+
+impl SsaoQuality {
+    /// Occlusion samples taken per pixel, at most `SsaoPass::MAX_SAMPLES`.
+    pub fn sample_count(self) -> u32 {
+        match self {
+            Self::Off => 0,
+            Self::Low => 8,
+            Self::Medium => 16,
+            Self::High => 32,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Low,
+            Self::Low => Self::Medium,
+            Self::Medium => Self::High,
+            Self::High => Self::Off,
+        }
+    }
+}
+
+impl RenderResolution {
+    /// Fraction of the surface's physical size the scene is drawn at.
+    pub fn render_scale(self, scale_factor: f64) -> f64 {
+        match self {
+            Self::Physical => 1.0,
+            Self::Logical => 1.0 / scale_factor.max(1.0),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Physical => Self::Logical,
+            Self::Logical => Self::Physical,
+        }
+    }
+}
+
+impl UpscaleFilter {
+    /// Sharpening strength handed to the upscale shader.
+    pub fn sharpness(self) -> f32 {
+        match self {
+            Self::Bilinear => 0.0,
+            Self::Sharpen => 0.5,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Bilinear => Self::Sharpen,
+            Self::Sharpen => Self::Bilinear,
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::Matrix4;
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Buffer, CommandEncoder, Device, Queue, RenderPipeline, TextureView};
+
+use crate::game::console::Console;
+use crate::game::hud::Hud;
+use crate::model::texture::Texture;
+
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+use super::sdf_font::{SdfFont, TextBatch};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct HudVertex {
+    pub position: [f32; 2],
+    pub texel: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Draws the crosshair, counters, messages, menus and console over the finished frame in
+/// window pixels.
+pub struct HudPass {
+    pipeline: RenderPipeline,
+    projection_buffer: Buffer,
+    bind_group: BindGroup,
+    vertex_buffer: Buffer,
+    vertex_count: u32,
+}
+
+impl HudVertex {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32x4,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<HudVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+impl HudPass {
+    const MAX_QUADS: usize = 2048;
+    // Glyph heights in logical pixels.
+    const TEXT_SIZE: f32 = 21.0;
+    const MENU_TITLE_SIZE: f32 = 56.0;
+    const CONSOLE_TEXT_SIZE: f32 = 14.0;
+    const MARGIN: f32 = 24.0;
+    const CROSSHAIR_GAP: f32 = 4.0;
+    const CROSSHAIR_LENGTH: f32 = 8.0;
+    const CROSSHAIR_THICKNESS: f32 = 2.0;
+    const LOW_HEALTH: u32 = 25;
+    const TEXT_COLOR: [f32; 4] = [1.0, 0.9, 0.6, 0.9];
+    const LOW_HEALTH_COLOR: [f32; 4] = [1.0, 0.2, 0.1, 0.9];
+    const CROSSHAIR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+    // Fraction of the window the open console covers from the top.
+    const CONSOLE_HEIGHT: f32 = 0.4;
+    const CONSOLE_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+    const CONSOLE_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+    const CONSOLE_INPUT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+    const MENU_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+
+    pub fn new(device: &Device, queue: &Queue, color_format: wgpu::TextureFormat) -> Self {
+        let atlas = Texture::from_rgba(&SdfFont::create_atlas(), device, queue, Some("HUD Font"));
+        // The distance field is filtered both ways, unlike the nearest minification of
+        // material textures.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("HUD Font Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("hud_bind_group_layout"),
+        });
+        let projection_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("HUD Projection Buffer"),
+            contents: bytemuck::cast_slice(&[[[0.0f32; 4]; 4]]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: projection_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&atlas.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("hud_bind_group"),
+        });
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Vertex Buffer"),
+            size: (Self::MAX_QUADS * 6 * std::mem::size_of::<HudVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "HUD Pipeline Layout",
+            &[&layout],
+        );
+        let pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &pipeline_layout,
+            PipelineDescriptor {
+                vertex_layouts: &[HudVertex::desc()],
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("HUD Shader"),
+                        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/hud.wgsl").into()),
+                    },
+                    Some(color_format),
+                )
+            },
+        );
+
+        Self {
+            pipeline,
+            projection_buffer,
+            bind_group,
+            vertex_buffer,
+            vertex_count: 0,
+        }
+    }
+
+    /// Lays out the HUD for a `width` by `height` pixel window.
+    pub fn update(
+        &mut self,
+        queue: &Queue,
+        hud: &Hud,
+        console: &Console,
+        width: u32,
+        height: u32,
+        scale_factor: f64,
+    ) {
+        let (width, height) = (width as f32, height as f32);
+        let projection = Matrix4::new_orthographic(0.0, width, height, 0.0, -1.0, 1.0);
+        queue.write_buffer(
+            &self.projection_buffer,
+            0,
+            bytemuck::cast_slice(&[Into::<[[f32; 4]; 4]>::into(projection)]),
+        );
+
+        let scale = (scale_factor as f32).max(1.0);
+        let mut batch = TextBatch::default();
+
+        // Menus take the screen over from the playing HUD.
+        if hud.menu().is_empty() {
+            Self::push_playing(&mut batch, hud, width, height, scale);
+        } else {
+            Self::push_menu(&mut batch, hud.menu(), width, height, scale);
+        }
+        if console.is_open() {
+            Self::push_console(&mut batch, console, width, height, scale);
+        }
+
+        let vertices = batch.vertices();
+        let vertices = &vertices[..vertices.len().min(Self::MAX_QUADS * 6)];
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// Crosshair, health and ammo counters and messages.
+    fn push_playing(batch: &mut TextBatch, hud: &Hud, width: f32, height: f32, scale: f32) {
+        let text_size = Self::TEXT_SIZE * scale;
+        let margin = Self::MARGIN * scale;
+        if hud.crosshair_visible {
+            let (cx, cy) = ((width / 2.0).round(), (height / 2.0).round());
+            let gap = Self::CROSSHAIR_GAP * scale;
+            let length = Self::CROSSHAIR_LENGTH * scale;
+            let half = (Self::CROSSHAIR_THICKNESS * scale / 2.0).max(1.0);
+            let arms = [
+                [cx - gap - length, cy - half, cx - gap, cy + half],
+                [cx + gap, cy - half, cx + gap + length, cy + half],
+                [cx - half, cy - gap - length, cx + half, cy - gap],
+                [cx - half, cy + gap, cx + half, cy + gap + length],
+            ];
+            for arm in arms {
+                batch.draw_rect(arm, Self::CROSSHAIR_COLOR);
+            }
+        }
+
+        let health_color = if hud.health() <= Self::LOW_HEALTH {
+            Self::LOW_HEALTH_COLOR
+        } else {
+            Self::TEXT_COLOR
+        };
+        let bottom = height - margin - text_size;
+        let health = format!("HEALTH {}", hud.health());
+        batch.draw_text([margin, bottom], text_size, health_color, &health);
+        if let Some((loaded, reserve)) = hud.ammo() {
+            let ammo = format!("AMMO {loaded}/{reserve}");
+            let x = width - margin - TextBatch::text_width(text_size, &ammo);
+            batch.draw_text([x, bottom], text_size, Self::TEXT_COLOR, &ammo);
+        }
+
+        let mut y = (height * 0.25).round();
+        for message in hud.messages() {
+            let x = ((width - TextBatch::text_width(text_size, &message.text)) / 2.0).round();
+            let mut color = Self::TEXT_COLOR;
+            color[3] *= message.opacity();
+            batch.draw_text([x, y], text_size, color, &message.text);
+            y += TextBatch::line_height(text_size);
+        }
+    }
+
+    /// The title with the other lines below it, all centered on the window.
+    fn push_menu(batch: &mut TextBatch, lines: &[&str], width: f32, height: f32, scale: f32) {
+        let Some((title, hints)) = lines.split_first() else {
+            return;
+        };
+        let title_size = Self::MENU_TITLE_SIZE * scale;
+        let text_size = Self::TEXT_SIZE * scale;
+        let total = TextBatch::line_height(title_size)
+            + hints.len() as f32 * TextBatch::line_height(text_size);
+        let mut y = ((height - total) / 2.0).round();
+        for (line, size) in
+            std::iter::once((title, title_size)).chain(hints.iter().map(|hint| (hint, text_size)))
+        {
+            let x = ((width - TextBatch::text_width(size, line)) / 2.0).round();
+            batch.draw_text([x, y], size, Self::MENU_COLOR, line);
+            y += TextBatch::line_height(size);
+        }
+    }
+
+    /// Background over the top of the window with the input line at its bottom and the
+    /// scrollback above it, as much as fits.
+    fn push_console(batch: &mut TextBatch, console: &Console, width: f32, height: f32, scale: f32) {
+        let text_size = Self::CONSOLE_TEXT_SIZE * scale;
+        let line_height = TextBatch::line_height(text_size);
+        let padding = text_size / 2.0;
+        let bottom = (height * Self::CONSOLE_HEIGHT).round();
+        batch.draw_rect([0.0, 0.0, width, bottom], Self::CONSOLE_BACKGROUND);
+
+        let mut y = bottom - padding - line_height;
+        let input = format!("{}{}_", Console::PROMPT, console.input());
+        batch.draw_text([padding, y], text_size, Self::CONSOLE_INPUT_COLOR, &input);
+        for line in console.output() {
+            y -= line_height;
+            if y < padding {
+                break;
+            }
+            batch.draw_text([padding, y], text_size, Self::CONSOLE_COLOR, line);
+        }
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        color_view: &TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("HUD Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
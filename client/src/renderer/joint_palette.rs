@@ -0,0 +1,31 @@
+use nalgebra::Matrix4;
+use wgpu::{Buffer, Device, Queue};
+
+/// Storage buffer holding the joint matrices of every skinned instance back to back, each
+/// instance addresses its joints by `RawInstance::joint_offset`.
+pub struct JointPalette {
+    pub buffer: Buffer,
+}
+
+impl JointPalette {
+    pub const MAX_JOINTS: usize = 1024;
+
+    pub fn new(device: &Device) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Joint Palette Buffer"),
+            size: (Self::MAX_JOINTS * std::mem::size_of::<[[f32; 4]; 4]>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer }
+    }
+
+    pub fn write(&self, queue: &Queue, offset: u32, matrices: &[Matrix4<f32>]) {
+        let raw: Vec<[[f32; 4]; 4]> = matrices.iter().map(|matrix| (*matrix).into()).collect();
+        queue.write_buffer(
+            &self.buffer,
+            offset as u64 * std::mem::size_of::<[[f32; 4]; 4]>() as u64,
+            bytemuck::cast_slice(&raw),
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use log::{error, warn};
+use nalgebra::Point3;
+
+use crate::camera::light_uniform::MAX_LIGHTS;
+use crate::model::asset_loader::ChunkHandle;
+use crate::model::map_loader::{ChunkInfo, MapChunk};
+use crate::model::texture::TextureBuilder;
+
+use super::Renderer;
+
+/// The chunks of a large map and which of them are in memory. A chunk's models and lights are
+/// appended to the renderer's lists while it is resident and taken out again once the player
+/// walks away, so only the part of the map around the player takes up memory.
+pub struct LevelStreamer {
+    chunks: Vec<StreamedChunk>,
+}
+
+struct StreamedChunk {
+    info: ChunkInfo,
+    state: ChunkState,
+}
+
+enum ChunkState {
+    Unloaded,
+    Loading(ChunkHandle),
+    // Where its models and lights sit in the renderer's lists, and the materials it added.
+    Resident {
+        first_model: usize,
+        models: usize,
+        first_light: usize,
+        lights: usize,
+        materials: Vec<String>,
+    },
+    // Its file couldn't be read, so it isn't asked for again until the map reloads.
+    Failed,
+}
+
+impl LevelStreamer {
+    // Chunks start loading once the player is this close to their bounds.
+    const LOAD_DISTANCE: f32 = 16.0;
+    // And are dropped past this, so walking along the edge doesn't load them over and over.
+    const UNLOAD_DISTANCE: f32 = 24.0;
+
+    pub fn new(chunks: Vec<ChunkInfo>) -> Self {
+        Self {
+            chunks: chunks
+                .into_iter()
+                .map(|info| StreamedChunk {
+                    info,
+                    state: ChunkState::Unloaded,
+                })
+                .collect(),
+        }
+    }
+
+    /// Moves the list positions of resident chunks past a removed one back by its length.
+    fn close_gap(&mut self, model: usize, models: usize, light: usize, lights: usize) {
+        for chunk in &mut self.chunks {
+            if let ChunkState::Resident {
+                first_model,
+                first_light,
+                ..
+            } = &mut chunk.state
+            {
+                if *first_model > model {
+                    *first_model -= models;
+                }
+                if *first_light > light {
+                    *first_light -= lights;
+                }
+            }
+        }
+    }
+}
+
+impl Renderer {
+    /// Requests the chunks near `position`, adds the ones that finished loading and drops the
+    /// ones the player left behind. Critical requests keep the loading screen up, which the
+    /// chunks around the spawn use so the level doesn't start out with holes.
+    pub(super) fn stream_chunks(&mut self, position: Point3<f32>, critical: bool) {
+        for chunk in &mut self.level_streamer.chunks {
+            let distance = chunk.info.bounds.distance_to(position);
+            match chunk.state {
+                ChunkState::Unloaded if distance <= LevelStreamer::LOAD_DISTANCE => {
+                    let handle = self.asset_loader.load_chunk(&chunk.info.file, critical);
+                    chunk.state = ChunkState::Loading(handle);
+                }
+                // Still in flight, whatever arrives for it is dropped.
+                ChunkState::Loading(_) if distance > LevelStreamer::UNLOAD_DISTANCE => {
+                    chunk.state = ChunkState::Unloaded;
+                }
+                _ => {}
+            }
+        }
+
+        for loaded in self.asset_loader.poll_chunks() {
+            let Some(index) = self.level_streamer.chunks.iter().position(
+                |chunk| matches!(chunk.state, ChunkState::Loading(handle) if handle == loaded.handle),
+            ) else {
+                continue;
+            };
+            match loaded.chunk {
+                Ok(chunk) => self.add_chunk(index, &chunk, critical),
+                Err(e) => {
+                    error!("Failed to load chunk {e}");
+                    self.level_streamer.chunks[index].state = ChunkState::Failed;
+                }
+            }
+        }
+
+        let far: Vec<usize> = self
+            .level_streamer
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| {
+                matches!(chunk.state, ChunkState::Resident { .. })
+                    && chunk.info.bounds.distance_to(position) > LevelStreamer::UNLOAD_DISTANCE
+            })
+            .map(|(index, _)| index)
+            .collect();
+        for index in far {
+            self.remove_chunk(index);
+        }
+    }
+
+    /// Drops every resident chunk, leaving the level as the map file alone describes it.
+    pub(super) fn unload_chunks(&mut self) {
+        for index in 0..self.level_streamer.chunks.len() {
+            self.remove_chunk(index);
+        }
+    }
+
+    fn add_chunk(&mut self, index: usize, chunk: &MapChunk, critical: bool) {
+        let file = &self.level_streamer.chunks[index].info.file;
+        let first_model = self.models.len();
+        let first_light = self.lights.len();
+        let mut loaded = chunk.load(
+            first_light as u32,
+            critical,
+            &self.device,
+            &self.queue,
+            &TextureBuilder::create_bind_group_layout(&self.device),
+            &mut self.asset_loader,
+        );
+
+        let room = MAX_LIGHTS - first_light;
+        if loaded.lights.len() > room {
+            warn!(
+                "{file} has {} lights, only {room} fit in the level",
+                loaded.lights.len()
+            );
+            loaded.lights.truncate(room);
+        }
+
+        let mut materials = vec![];
+        for (name, material) in loaded.materials {
+            if self.materials.contains_key(&name) {
+                warn!("{file} redefines material {name}, keeping the one loaded first");
+                material.release_textures(&mut self.asset_loader);
+                continue;
+            }
+            materials.push(name.clone());
+            self.materials.insert(name, material);
+        }
+
+        let mut models = loaded.models;
+        models.retain(|model| {
+            let unknown = model
+                .meshes
+                .iter()
+                .find(|mesh| !self.materials.contains_key(&mesh.material));
+            if let Some(mesh) = unknown {
+                error!(
+                    "{file} mesh {} uses unknown material {}",
+                    mesh.name, mesh.material
+                );
+            }
+            unknown.is_none()
+        });
+        for model in &mut models {
+            model.apply_blend_modes(&self.materials);
+        }
+
+        let lights = loaded.lights.len();
+        self.level_streamer.chunks[index].state = ChunkState::Resident {
+            first_model,
+            models: models.len(),
+            first_light,
+            lights,
+            materials,
+        };
+        self.models.extend(models);
+        self.lights.extend(loaded.lights);
+        self.resolve_textures();
+        self.shadow_baker.update_scene_version();
+        if lights > 0 {
+            self.upload_lights();
+            self.rebuild_shadow_maps(self.shadow_baker.resolution());
+        }
+    }
+
+    fn remove_chunk(&mut self, index: usize) {
+        let state = std::mem::replace(
+            &mut self.level_streamer.chunks[index].state,
+            ChunkState::Unloaded,
+        );
+        let ChunkState::Resident {
+            first_model,
+            models,
+            first_light,
+            lights,
+            materials,
+        } = state
+        else {
+            self.level_streamer.chunks[index].state = state;
+            return;
+        };
+
+        self.models.drain(first_model..first_model + models);
+        self.lights.drain(first_light..first_light + lights);
+        // Light ids double as shadow map layers, so the ones after the gap move down.
+        for (id, light) in self.lights.iter_mut().enumerate().skip(first_light) {
+            light.id = id as u32;
+        }
+        if let Some(flash_light) = &mut self.flash_light
+            && *flash_light as usize >= first_light + lights
+        {
+            *flash_light -= lights as u32;
+        }
+        self.level_streamer
+            .close_gap(first_model, models, first_light, lights);
+
+        for name in materials {
+            if let Some(material) = self.materials.remove(&name) {
+                material.release_textures(&mut self.asset_loader);
+            }
+        }
+        for handle in self.asset_loader.evict_unused() {
+            self.textures.remove(&handle);
+            self.cube_textures.remove(&handle);
+            self.texture_arrays.remove(&handle);
+        }
+
+        self.shadow_baker.update_scene_version();
+        if lights > 0 {
+            self.upload_lights();
+            self.rebuild_shadow_maps(self.shadow_baker.resolution());
+        }
+    }
+}
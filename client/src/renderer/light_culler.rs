@@ -0,0 +1,198 @@
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Buffer, CommandEncoder, ComputePipeline, Device, Queue};
+
+use crate::camera::Camera;
+use crate::camera::light_uniform::MAX_LIGHTS;
+
+use super::pipeline_factory::PipelineFactory;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ClusterUniform {
+    screen_size: [f32; 2],
+    near: f32,
+    far: f32,
+    grid: [u32; 4],
+}
+
+/// Bins lights into view space froxels so the lighting shader only has to loop over the lights
+/// that can reach each fragment.
+pub struct LightCuller {
+    pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    pub cluster_buffer: Buffer,
+    pub light_grid_buffer: Buffer,
+    pub light_index_buffer: Buffer,
+}
+
+impl ClusterUniform {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            screen_size: [width.max(1) as f32, height.max(1) as f32],
+            near: Camera::NEAR_PLANE,
+            far: Camera::FAR_PLANE,
+            grid: [
+                LightCuller::GRID_X,
+                LightCuller::GRID_Y,
+                LightCuller::GRID_Z,
+                MAX_LIGHTS as u32,
+            ],
+        }
+    }
+}
+
+impl LightCuller {
+    pub const GRID_X: u32 = 16;
+    pub const GRID_Y: u32 = 9;
+    pub const GRID_Z: u32 = 24;
+    const NUM_CLUSTERS: u32 = Self::GRID_X * Self::GRID_Y * Self::GRID_Z;
+
+    pub fn new(
+        device: &Device,
+        camera_bind_group_layout: &BindGroupLayout,
+        point_light_buffer: &Buffer,
+    ) -> Self {
+        let cluster_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cluster Buffer"),
+            contents: bytemuck::cast_slice(&[ClusterUniform::new(1, 1)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let light_grid_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Grid Buffer"),
+            size: (Self::NUM_CLUSTERS as usize * std::mem::size_of::<[u32; 2]>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let light_index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Index Buffer"),
+            size: (Self::NUM_CLUSTERS as usize * MAX_LIGHTS * std::mem::size_of::<u32>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = Self::create_bind_group_layout(device);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            point_light_buffer,
+            &cluster_buffer,
+            &light_grid_buffer,
+            &light_index_buffer,
+        );
+        let layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Light Cluster Pipeline Layout",
+            &[camera_bind_group_layout, &bind_group_layout],
+        );
+        let pipeline = PipelineFactory::create_compute_pipeline(
+            device,
+            &layout,
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Light Cluster Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/cluster.wgsl").into()),
+            },
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            cluster_buffer,
+            light_grid_buffer,
+            light_index_buffer,
+        }
+    }
+
+    fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
+        let entry = |binding: u32, ty: wgpu::BufferBindingType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Uniform),
+                entry(1, wgpu::BufferBindingType::Uniform),
+                entry(2, wgpu::BufferBindingType::Storage { read_only: false }),
+                entry(3, wgpu::BufferBindingType::Storage { read_only: false }),
+            ],
+            label: Some("light_cull_bind_group_layout"),
+        })
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        point_light_buffer: &Buffer,
+        cluster_buffer: &Buffer,
+        light_grid_buffer: &Buffer,
+        light_index_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: point_light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cluster_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: light_grid_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: light_index_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("light_cull_bind_group"),
+        })
+    }
+
+    /// Must be called whenever the point light buffer is recreated.
+    pub fn rebind_lights(&mut self, device: &Device, point_light_buffer: &Buffer) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            point_light_buffer,
+            &self.cluster_buffer,
+            &self.light_grid_buffer,
+            &self.light_index_buffer,
+        );
+    }
+
+    pub fn update(&self, queue: &Queue, width: u32, height: u32) {
+        queue.write_buffer(
+            &self.cluster_buffer,
+            0,
+            bytemuck::cast_slice(&[ClusterUniform::new(width, height)]),
+        );
+    }
+
+    pub fn dispatch(
+        &self,
+        encoder: &mut CommandEncoder,
+        camera_bind_group: &BindGroup,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Light Cull Pass"),
+            timestamp_writes,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, camera_bind_group, &[]);
+        compute_pass.set_bind_group(1, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(1, 1, Self::GRID_Z);
+    }
+}
@@ -1,72 +1,226 @@
+use automap_pass::AutomapPass;
+use decal_pass::DecalPass;
+use depth_prepass::{DepthGeometry, DepthPrepass};
+use dynamic_resolution::DynamicResolution;
+use gpu_profiler::GpuProfiler;
+use gpu_selection::{Gpu, GpuCapabilities, GpuPreference};
+use graphics_settings::{GraphicsSettings, RenderResolution, SsaoQuality, UpscaleFilter};
+use hud_pass::HudPass;
+use joint_palette::JointPalette;
+use level_streaming::LevelStreamer;
+use light_culler::LightCuller;
+use log::{error, warn};
 use nalgebra::{Point3, Vector3};
-use pipeline_factory::PipelineFactory;
+use overlay_pass::OverlayPass;
+use particle_pass::ParticlePass;
+use pipeline_factory::{PipelineDescriptor, PipelineFactory};
+use reflection_probes::ReflectionProbes;
+use render_graph::{PassContext, RenderGraph, Resource};
+use shadow_atlas::ShadowAtlas;
 use shadow_baker::ShadowBaker;
+use ssao_pass::SsaoPass;
+use std::collections::HashMap;
+use std::error::Error;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use std::time::Duration;
+use upscale_pass::UpscalePass;
+use view_model_pass::ViewModelPass;
 use wgpu::util::DeviceExt;
 
 use wgpu::{
-    BindGroup, BindGroupLayout, Buffer, Device, DeviceDescriptor, Queue, RenderPipeline, Surface,
-    SurfaceConfiguration,
+    AdapterInfo, BindGroup, Buffer, Device, Queue, RenderPipeline, Surface, SurfaceConfiguration,
+    TextureView,
 };
 use winit::window::Window;
 
 use crate::camera::Camera;
 use crate::camera::camera_uniform::CameraUniform;
+use crate::camera::frustum::Frustum;
 use crate::camera::light::Light;
-use crate::camera::light_uniform::LightUniformArray;
+use crate::camera::light_uniform::{LightResources, LightUniformArray};
 use crate::camera::shadow_map_uniform::ShadowMapUniform;
+use crate::game::automap::Automap;
+use crate::game::camera_controller::{CameraController, CameraMode, FpsController};
+use crate::game::character::Character;
 use crate::game::collision_manager::CollisionManager;
+use crate::game::console::Console;
+use crate::game::decals::DecalSystem;
+use crate::game::events::{EventBus, EventSubscriber, GameEvent};
+use crate::game::hud::Hud;
+use crate::game::movers::Mover;
+use crate::game::navigation::NavGrid;
+use crate::game::particles::ParticleSystem;
+use crate::game::physics::Physics;
+use crate::game::pickups::PickupSystem;
 use crate::game::player::Player;
 use crate::game::player_controller::PlayerController;
-use crate::model::Model;
-use crate::model::cube_texture::{CubeTexture, CubeTextureBuilder};
+use crate::game::replay::ReplayMode;
+use crate::game::rng::{GameRng, RngSystem};
+use crate::game::save_game::SaveGame;
+use crate::game::script_host::{ScriptHook, ScriptHost};
+use crate::game::triggers::TriggerSystem;
+use crate::game::view_model::ViewModel;
+use crate::game::weapons::WeaponSystem;
+use crate::model::asset_cache::TextureKind;
+use crate::model::asset_loader::{AssetLoader, TextureHandle};
+use crate::model::cube_texture::{CubeTexture, CubeTextureBuilder, Ibl};
 use crate::model::depth_texture::DepthTexture;
 use crate::model::map_loader::MapLoader;
 use crate::model::model_instance::RawInstance;
-use crate::model::texture::TextureBuilder;
+use crate::model::texture::{Texture, TextureBuilder};
+use crate::model::texture_array::TextureArray;
 use crate::model::vertex::{LineVertex, Vertex};
+use crate::model::{BlendMode, Material, Model};
+use crate::settings::Settings;
 
-mod pipeline_factory;
+mod automap_pass;
+mod combat;
+mod console_commands;
+mod cubemap_export;
+pub mod debug_view;
+mod decal_pass;
+mod depth_prepass;
+mod device_recovery;
+mod dynamic_resolution;
+mod enemies;
+mod gpu_profiler;
+mod gpu_selection;
+pub mod graphics_settings;
+mod hud_font;
+mod hud_pass;
+pub(crate) mod joint_palette;
+mod level_streaming;
+pub(crate) mod light_culler;
+mod overlay_pass;
+mod particle_pass;
+mod pickups;
+pub(crate) mod pipeline_factory;
+pub(crate) mod reflection_probes;
+mod render_graph;
+mod replay;
+mod scripting;
+mod sdf_font;
+pub(crate) mod shadow_atlas;
 mod shadow_baker;
+mod ssao_pass;
+mod upscale_pass;
+mod view_model_pass;
 
 pub struct Renderer {
     window: Arc<Window>,
     surface: Surface<'static>,
     device: Device,
     queue: Queue,
+    // Kept for the surfaces of extra windows.
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    gpu_info: AdapterInfo,
+    gpu_capabilities: GpuCapabilities,
+    // Set by the driver, see `watch_device_loss`.
+    device_lost: Arc<AtomicBool>,
+    surface_timeouts: u32,
     config: SurfaceConfiguration,
+    // Surface configuration scaled to the resolution the scene is drawn at.
+    render_config: SurfaceConfiguration,
+    scale_factor: f64,
     models: Vec<Model>,
+    materials: HashMap<String, Material>,
+    asset_loader: AssetLoader,
+    skybox_handle: TextureHandle,
+    skybox_pending: bool,
+    // The map materials' textures as one array, bound with the lights once it loads.
+    level_textures: TextureArray,
+    level_textures_handle: Option<TextureHandle>,
+    level_textures_pending: bool,
+    // Uploaded textures shared by everything holding the same handle.
+    textures: HashMap<TextureHandle, Texture>,
+    cube_textures: HashMap<TextureHandle, CubeTexture>,
+    texture_arrays: HashMap<TextureHandle, TextureArray>,
     lights: Vec<Light>,
+    ambient: [f32; 3],
+    environment: f32,
+    // Lighting taken from the skybox, regenerated once the real one loads.
+    ibl: Ibl,
+    reflection_probes: ReflectionProbes,
+    // Seconds of simulation the light animations have run for.
+    light_time: f32,
+    characters: Vec<Character>,
     player: Player,
+    camera_controller: Box<dyn CameraController>,
     is_surface_configured: bool,
     debug_lines_len: u32,
     player_controller: PlayerController,
+    view_model: ViewModel,
+    particle_system: ParticleSystem,
+    decal_system: DecalSystem,
+    // What gameplay did this frame, for the HUD, particles and scripts to react to.
+    events: EventBus,
+    weapon_system: WeaponSystem,
+    pickup_system: PickupSystem,
+    // Light shared by explosion flashes, added by the first explosion.
+    flash_light: Option<u32>,
+    flash_remaining: f32,
+    hud: Hud,
+    console: Console,
+    automap: Automap,
+    level_streamer: LevelStreamer,
+    replay: ReplayMode,
+    // Seeded again from the replay when one is recorded or played back.
+    rng: GameRng,
+    script_host: Option<ScriptHost>,
+    trigger_system: TriggerSystem,
+    movers: Vec<Mover>,
+    nav_grid: NavGrid,
+    // Where the player comes back after dying.
+    spawn_point: Point3<f32>,
+    tick_accumulator: Duration,
     map_file: String,
     depth_texture: DepthTexture,
     collision_manager: CollisionManager,
     shadow_baker: ShadowBaker,
+    light_culler: LightCuller,
+    joint_palette: JointPalette,
+    view_model_pass: ViewModelPass,
+    particle_pass: ParticlePass,
+    decal_pass: DecalPass,
+    overlay_pass: OverlayPass,
+    hud_pass: HudPass,
+    automap_pass: AutomapPass,
+    ssao_pass: SsaoPass,
+    depth_prepass: DepthPrepass,
+    // Only present while the scene renders below the surface resolution.
+    upscale_pass: Option<UpscalePass>,
+    graphics_settings: GraphicsSettings,
+    dynamic_resolution: DynamicResolution,
+    gpu_profiler: GpuProfiler,
     camera_uniform: CameraUniform,
     camera_buffer: Buffer,
     debug_buffer: Buffer,
     camera_bind_group: BindGroup,
+    point_light_buffer: Buffer,
     point_light_bind_group: BindGroup,
     skybox_bind_group: BindGroup,
     shadow_bind_group: BindGroup,
-    shadow_bind_group_layout: BindGroupLayout,
     skybox_render_pipeline: RenderPipeline,
     debug_render_pipeline: RenderPipeline,
-    shadow_render_pipeline: RenderPipeline,
     render_pipeline: RenderPipeline,
+    masked_render_pipeline: RenderPipeline,
+    translucent_render_pipeline: RenderPipeline,
 }
 
 impl Renderer {
     const MOVE_SPEED: f32 = 2.0;
     const SENSITIVITY: f32 = 0.3;
     const JUMP_STRENGTH: f32 = 1.6;
-    pub const FAR_PLANE: f32 = 200.0;
-    pub const NEAR_PLANE: f32 = 0.01;
-    pub async fn new(window: Arc<Window>, map_file: String) -> Result<Self, String> {
+    const PLACEHOLDER_SKY: [u8; 4] = [0, 0, 0, 255];
+    const DEFAULT_SPAWN: [f32; 3] = [1.0, 0.5, 1.0];
+    #[tracing::instrument(name = "renderer", skip_all, fields(map = %map_file))]
+    pub async fn new(
+        window: Arc<Window>,
+        map_file: String,
+        settings: &Settings,
+    ) -> Result<Self, String> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             ..Default::default()
@@ -78,21 +232,16 @@ impl Renderer {
             .create_surface(window.clone())
             .map_err(|_| "Failed to create surface")?;
 
-        let adaptor = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .map_err(|_| "Failed to request Adapter")?;
-
-        let (device, queue) = adaptor
-            .request_device(&DeviceDescriptor::default())
-            .await
-            .map_err(|_| "Failed to request device")?;
+        let Gpu {
+            adapter,
+            device,
+            queue,
+            capabilities: gpu_capabilities,
+        } = Gpu::new(&instance, &surface, &GpuPreference::from_env()).await?;
+        let gpu_info = adapter.get_info();
+        let device_lost = Self::watch_device_loss(&device);
 
-        let surface_caps = surface.get_capabilities(&adaptor);
+        let surface_caps = surface.get_capabilities(&adapter);
 
         let surface_format = surface_caps
             .formats
@@ -113,6 +262,9 @@ impl Renderer {
         };
 
         surface.configure(&device, &config);
+        let graphics_settings = settings.graphics;
+        let scale_factor = window.scale_factor();
+        let render_config = Self::scaled_config(&config, &graphics_settings, 1.0, scale_factor);
 
         // layouts
         let camera_bind_group_layout = CameraUniform::create_bind_group_layout(&device);
@@ -123,6 +275,7 @@ impl Renderer {
         let shadow_texture_layout = ShadowMapUniform::create_shadow_texture_layout(&device);
         let render_pipeline_layout = PipelineFactory::create_render_pipeline_layout(
             &device,
+            "Scene Pipeline Layout",
             &[
                 &camera_bind_group_layout,
                 &point_light_bind_group_layout,
@@ -132,31 +285,71 @@ impl Renderer {
         );
         let skybox_pipeline_layout = PipelineFactory::create_render_pipeline_layout(
             &device,
+            "Skybox Pipeline Layout",
             &[&skybox_bind_group_layout, &camera_bind_group_layout],
         );
-        let shadow_pipeline_layout =
-            PipelineFactory::create_render_pipeline_layout(&device, &[&shadow_bind_group_layout]);
-        let debug_pipeline_layout =
-            PipelineFactory::create_render_pipeline_layout(&device, &[&camera_bind_group_layout]);
+        let shadow_pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            &device,
+            "Shadow Mapping Pipeline Layout",
+            &[&shadow_bind_group_layout],
+        );
+        let masked_shadow_pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            &device,
+            "Masked Shadow Mapping Pipeline Layout",
+            &[&shadow_bind_group_layout, &diffuse_texture_layout],
+        );
+        let debug_pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            &device,
+            "Debug Pipeline Layout",
+            &[&camera_bind_group_layout],
+        );
 
-        let map_loader = MapLoader::from_file(&map_file).unwrap();
-        let map = map_loader.load(&device, &queue, &diffuse_texture_layout);
+        let map_loader = MapLoader::from_file(&map_file)
+            .map_err(|e| format!("Unable to load {map_file}: {e}"))?;
+        let mut asset_loader = AssetLoader::default();
+        let map = map_loader.load(&device, &queue, &diffuse_texture_layout, &mut asset_loader);
         let models = map.models;
+        let materials = map.materials;
+        let level_textures_handle = map.level_textures;
         let skybox_files = map.skybox_textures;
         let lights = map.lights;
+        let ambient = map.ambient;
+        let environment = map.environment;
+        let characters = map.characters;
         let collision_manager = map.collision_manager;
         let debug_lines = map.debug_lines;
         let debug_lines_len = debug_lines.len() as u32;
+        let mut particle_system = ParticleSystem::default();
+        for emitter in map.emitters {
+            particle_system.add_emitter(emitter);
+        }
+        let mut decal_system = DecalSystem::default();
+        for decal in map.decals {
+            decal_system.add(decal);
+        }
+        let script_host = Self::load_script(map.script.as_deref());
+        let mut trigger_system = TriggerSystem::default();
+        for trigger in map.triggers {
+            trigger_system.add(trigger);
+        }
+        let movers = map.movers;
+        let nav_grid = Self::build_nav_grid(&collision_manager, &characters);
+        let automap = Automap::new(&collision_manager);
+        let mut pickup_system = PickupSystem::default();
+        for pickup in map.pickups {
+            pickup_system.add(pickup);
+        }
+        let spawn_point = map.spawn_point.unwrap_or(Point3::from(Self::DEFAULT_SPAWN));
         let camera = Camera {
-            position: Point3::new(1.0, 0.5, 1.0),
-            target: Point3::new(0.0, 0.5, 0.0),
+            position: spawn_point,
+            target: spawn_point - Vector3::new(1.0, 0.0, 1.0),
             up: Vector3::new(0.0, 1.0, 0.0),
-            aspect: size.width as f32 / size.height as f32,
+            aspect: size.width as f32 / size.height.max(1) as f32,
             fovy: 1.0,
-            near: Self::NEAR_PLANE,
-            far: Self::FAR_PLANE,
+            near: Camera::NEAR_PLANE,
+            far: Camera::FAR_PLANE,
         };
-        let player = Player::new(
+        let mut player = Player::new(
             Self::SENSITIVITY,
             Self::MOVE_SPEED,
             Self::JUMP_STRENGTH,
@@ -164,14 +357,14 @@ impl Renderer {
             0.5,
             camera,
         );
+        player.camera_settings = settings.camera;
         let player_controller = PlayerController::default();
-        let light_ids: Vec<u32> = lights.iter().map(|light| light.id).collect();
-        let shadow_baker = ShadowBaker::new(&light_ids, &device);
 
         // uniforms
         let mut camera_uniform = CameraUniform::new(player.camera.position);
-        let point_light_uniform = LightUniformArray::new(&lights);
+        let point_light_uniform = LightUniformArray::new(&lights, ambient, environment, 0.0);
         camera_uniform.update_cam(&player.camera);
+        camera_uniform.set_fog(&map.fog);
 
         // buffers
         let point_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -191,246 +384,1051 @@ impl Renderer {
         });
 
         // textures
-        let skybox_texture =
-            CubeTexture::from_files(&skybox_files, &device, &queue, Some("Galaxy Texture"));
-        let depth_texture = DepthTexture::create_depth_texture(&device, &config, "depth_texture");
+        let skybox_texture = CubeTexture::from_color(
+            Self::PLACEHOLDER_SKY,
+            &device,
+            &queue,
+            Some("Galaxy Texture"),
+        );
+        let ibl = skybox_texture.generate_ibl(&device, &queue);
+        let skybox_handle = asset_loader.load_images(skybox_files, TextureKind::Cube, true);
+        // Never sampled, materials only point at layers once the real array is in.
+        let level_textures =
+            TextureArray::from_color([0; 4], &device, &queue, Some("Level Textures"));
+        let depth_texture =
+            DepthTexture::create_depth_texture(&device, &render_config, "depth_texture");
 
         //bind groups
-        let camera_bind_group =
-            CameraUniform::create_bind_group(&device, &camera_bind_group_layout, &camera_buffer);
-        let point_light_bind_group = LightUniformArray::create_bind_group(
+        let joint_palette = JointPalette::new(&device);
+        let camera_bind_group = CameraUniform::create_bind_group(
+            &device,
+            &camera_bind_group_layout,
+            &camera_buffer,
+            &joint_palette.buffer,
+        );
+        let light_culler =
+            LightCuller::new(&device, &camera_bind_group_layout, &point_light_buffer);
+        let ssao_pass = SsaoPass::new(
+            &device,
+            &render_config,
+            &camera_bind_group_layout,
+            &diffuse_texture_layout,
+            &depth_texture.view,
+            graphics_settings.ssao,
+        );
+        let depth_prepass =
+            DepthPrepass::new(&device, &camera_bind_group_layout, &diffuse_texture_layout);
+        let reflection_probes = ReflectionProbes::new(
+            &device,
+            &map.reflection_probes,
+            ReflectionProbes::RESOLUTION,
+            config.format,
+            &camera_bind_group_layout,
+            &diffuse_texture_layout,
+        );
+        let point_light_bind_group = Self::create_point_light_bind_group(
             &device,
-            &point_light_bind_group_layout,
             &point_light_buffer,
+            &light_culler,
+            &ssao_pass.ambient_occlusion_view,
+            &ibl,
+            &reflection_probes,
+            &level_textures,
         );
         let skybox_bind_group = CubeTextureBuilder::create_bind_group(
             &device,
             &skybox_texture,
             &skybox_bind_group_layout,
         );
-        let shadow_bind_group = ShadowMapUniform::create_shadow_texture_bind_group(
-            &device,
-            &shadow_baker.shadow_map_texture,
-            &shadow_texture_layout,
-        );
-
         // pipelines
-        let render_pipeline = PipelineFactory::create_render_pipeline(
+        let [
+            render_pipeline,
+            masked_render_pipeline,
+            translucent_render_pipeline,
+        ] = [BlendMode::Opaque, BlendMode::Masked, BlendMode::Translucent].map(|blend_mode| {
+            PipelineFactory::create_material_pipeline(
+                &device,
+                &render_pipeline_layout,
+                config.format,
+                blend_mode,
+            )
+        });
+
+        let skybox_render_pipeline =
+            Self::create_skybox_pipeline(&device, &skybox_pipeline_layout, config.format);
+
+        let debug_render_pipeline = PipelineFactory::create_render_pipeline(
             &device,
-            &render_pipeline_layout,
-            config.format,
-            Some(DepthTexture::DEPTH_FORMAT),
-            &[Vertex::desc(), RawInstance::desc()],
-            wgpu::PrimitiveTopology::TriangleList,
-            wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shader.wgsl").into()),
+            &debug_pipeline_layout,
+            PipelineDescriptor {
+                vertex_layouts: &[LineVertex::desc()],
+                topology: wgpu::PrimitiveTopology::LineList,
+                depth_format: Some(DepthTexture::DEPTH_FORMAT),
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("Debug Shader"),
+                        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/debug.wgsl").into()),
+                    },
+                    Some(config.format),
+                )
             },
-            Some(wgpu::Face::Back),
-            true,
-            wgpu::CompareFunction::LessEqual,
         );
 
-        let skybox_render_pipeline = PipelineFactory::create_render_pipeline(
+        let create_shadow_pipeline = |layout, label, fragment_entry_point| {
+            PipelineFactory::create_render_pipeline(
+                &device,
+                layout,
+                PipelineDescriptor {
+                    fragment_entry_point,
+                    vertex_layouts: &[Vertex::desc(), RawInstance::desc()],
+                    cull_mode: Some(wgpu::Face::Back),
+                    depth_format: Some(ShadowAtlas::FORMAT),
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    ..PipelineDescriptor::new(
+                        wgpu::ShaderModuleDescriptor {
+                            label: Some(label),
+                            source: wgpu::ShaderSource::Wgsl(
+                                include_str!("shaders/shadow.wgsl").into(),
+                            ),
+                        },
+                        None,
+                    )
+                },
+            )
+        };
+        let light_ids: Vec<u32> = lights.iter().map(|light| light.id).collect();
+        let shadow_baker = ShadowBaker::new(
+            &light_ids,
             &device,
-            &skybox_pipeline_layout,
-            config.format,
-            Some(DepthTexture::DEPTH_FORMAT),
-            &[],
-            wgpu::PrimitiveTopology::TriangleList,
-            wgpu::ShaderModuleDescriptor {
-                label: Some("Skybox Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/skybox.wgsl").into()),
-            },
-            Some(wgpu::Face::Back),
-            true,
-            wgpu::CompareFunction::LessEqual,
+            create_shadow_pipeline(&shadow_pipeline_layout, "Shadow Mapping Shader", "fs_main"),
+            create_shadow_pipeline(
+                &masked_shadow_pipeline_layout,
+                "Masked Shadow Mapping Shader",
+                "fs_masked",
+            ),
+            shadow_bind_group_layout,
+        );
+        let shadow_bind_group = ShadowMapUniform::create_shadow_texture_bind_group(
+            &device,
+            &shadow_baker.atlas.view,
+            &shadow_baker.atlas.sampler,
+            &shadow_baker.face_buffer,
+            &shadow_texture_layout,
         );
 
-        let debug_render_pipeline = PipelineFactory::create_render_pipeline(
+        let view_model_pass =
+            ViewModelPass::new(&device, &queue, config.format, &diffuse_texture_layout);
+        let overlay_pass = OverlayPass::new(&device, config.format);
+        let hud_pass = HudPass::new(&device, &queue, config.format);
+        let automap_pass = AutomapPass::new(&device, config.format);
+        let upscale_pass = (render_config.width != config.width
+            || render_config.height != config.height)
+            .then(|| UpscalePass::new(&device, &render_config, graphics_settings.upscale_filter));
+        let gpu_profiler = GpuProfiler::new(&device, &queue);
+        let particle_pass = ParticlePass::new(
             &device,
-            &debug_pipeline_layout,
             config.format,
-            Some(DepthTexture::DEPTH_FORMAT),
-            &[LineVertex::desc()],
-            wgpu::PrimitiveTopology::LineList,
-            wgpu::ShaderModuleDescriptor {
-                label: Some("Debug Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/debug.wgsl").into()),
-            },
-            None,
-            false,
-            wgpu::CompareFunction::Always,
+            &camera_bind_group_layout,
+            &depth_texture.view,
         );
-
-        let shadow_render_pipeline = PipelineFactory::create_shadow_render_pipeline(
+        let decal_pass = DecalPass::new(
             &device,
-            &shadow_pipeline_layout,
-            Some(CubeTexture::DEPTH_FORMAT),
-            &[Vertex::desc(), RawInstance::desc()],
-            wgpu::PrimitiveTopology::TriangleList,
-            wgpu::ShaderModuleDescriptor {
-                label: Some("Shadow Mapping Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shadow.wgsl").into()),
-            },
-            Some(wgpu::Face::Back),
-            true,
-            wgpu::CompareFunction::Less,
+            &queue,
+            config.format,
+            &camera_bind_group_layout,
+            &depth_texture.view,
         );
 
-        Ok(Self {
+        let mut renderer = Self {
             window,
             surface,
             device,
             queue,
             config,
+            render_config,
+            scale_factor,
             is_surface_configured: true,
             models,
+            materials,
+            asset_loader,
+            skybox_handle,
+            skybox_pending: true,
+            level_textures,
+            level_textures_handle,
+            level_textures_pending: true,
+            textures: HashMap::new(),
+            cube_textures: HashMap::new(),
+            texture_arrays: HashMap::new(),
             lights,
+            ambient,
+            environment,
+            ibl,
+            reflection_probes,
+            light_time: 0.0,
+            characters,
             player,
+            camera_controller: Box::new(FpsController),
             collision_manager,
             map_file,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
+            point_light_buffer,
             point_light_bind_group,
             depth_texture,
             render_pipeline,
+            masked_render_pipeline,
+            translucent_render_pipeline,
             skybox_bind_group,
             skybox_render_pipeline,
             player_controller,
+            view_model: ViewModel::default(),
+            particle_system,
+            decal_system,
+            events: EventBus::default(),
+            weapon_system: WeaponSystem::default(),
+            pickup_system,
+            flash_light: None,
+            flash_remaining: 0.0,
+            hud: Hud::default(),
+            console: Console::default(),
+            automap,
+            level_streamer: LevelStreamer::new(map.chunks),
+            replay: ReplayMode::Off,
+            rng: GameRng::new(rand::random()),
+            script_host,
+            trigger_system,
+            movers,
+            nav_grid,
+            spawn_point,
+            tick_accumulator: Duration::ZERO,
             debug_render_pipeline,
             debug_lines_len,
             debug_buffer,
-            shadow_render_pipeline,
-            shadow_bind_group_layout,
             shadow_bind_group,
             shadow_baker,
-        })
+            light_culler,
+            joint_palette,
+            view_model_pass,
+            particle_pass,
+            decal_pass,
+            overlay_pass,
+            hud_pass,
+            automap_pass,
+            ssao_pass,
+            depth_prepass,
+            upscale_pass,
+            graphics_settings,
+            dynamic_resolution: DynamicResolution::default(),
+            gpu_profiler,
+            instance,
+            adapter,
+            gpu_info,
+            gpu_capabilities,
+            device_lost,
+            surface_timeouts: 0,
+        };
+        if let Some(resolution) = settings.shadow_resolution
+            && let Err(e) = renderer.set_shadow_resolution(resolution)
+        {
+            warn!("{e}");
+        }
+        renderer.stream_chunks(renderer.spawn_point, true);
+        renderer.animate_characters(Duration::ZERO);
+        renderer.run_script(ScriptHook::Start);
+        Ok(renderer)
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+    /// Draws the scene, with `overlay` blended over it when a menu is open.
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub fn render(&mut self, overlay: Option<[f32; 4]>) -> Result<(), wgpu::SurfaceError> {
         self.window.request_redraw();
+        // Menus dim the screen, otherwise a hit or pickup may be flashing.
+        let overlay = overlay.or_else(|| self.hud.flash_color());
 
         if !self.is_surface_configured {
             return Ok(());
         }
 
-        // Shadow render pass
-        for light in &self.lights {
-            self.shadow_baker.update_light_shadow_map(
-                light,
-                &self.device,
-                &self.queue,
-                &self.models,
-                &self.shadow_render_pipeline,
-                &self.shadow_bind_group_layout,
-            );
-        }
+        self.gpu_profiler.begin_frame(&self.device);
+        let camera = &self.player.camera;
+        let frustum = Frustum::from_view_proj(&(camera.get_proj_mat() * camera.get_view_mat()));
 
-        let output = self.surface.get_current_texture()?;
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(e) => {
+                if e == wgpu::SurfaceError::Timeout {
+                    self.surface_timeouts += 1;
+                }
+                return Err(e);
+            }
+        };
+        self.surface_timeouts = 0;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        let scene_view = self
+            .upscale_pass
+            .as_ref()
+            .map_or(&view, |upscale_pass| &upscale_pass.color_view);
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 1.0,
-                            g: 1.0,
-                            b: 1.0,
-                            a: 1.0,
+
+        self.light_culler.update(
+            &self.queue,
+            self.render_config.width,
+            self.render_config.height,
+        );
+        self.decal_pass.update(&self.queue, &self.decal_system);
+        self.particle_pass.update(
+            &self.queue,
+            &self.particle_system,
+            self.player.camera.position,
+        );
+        self.view_model_pass.update(
+            &self.queue,
+            self.player.camera.aspect,
+            self.view_model.transform(),
+        );
+        self.hud_pass.update(
+            &self.queue,
+            &self.hud,
+            &self.console,
+            self.config.width,
+            self.config.height,
+            self.scale_factor,
+        );
+        if let Some(color) = overlay {
+            self.overlay_pass.update(&self.queue, color);
+        }
+        if self.automap.is_open() {
+            self.automap_pass.update(
+                &self.queue,
+                &self.automap,
+                &self.collision_manager,
+                &self.player,
+                (self.config.width, self.config.height),
+                self.scale_factor,
+            );
+        }
+
+        // Probes wait for the real textures and sky, then stay as they are for the whole map.
+        let probe_bake =
+            (self.reflection_probes.needs_bake() && !self.is_loading() && !self.skybox_pending)
+                .then(|| {
+                    self.prepare_probe_bake(&self.reflection_probes, &self.skybox_render_pipeline)
+                });
+        self.shadow_baker
+            .allocate(&self.lights, self.player.camera.position, &self.queue);
+        let mut graph = RenderGraph::default();
+        // First, so with async compute it is already running while the rest is recorded.
+        graph.add_compute_pass(
+            "light cull",
+            &[],
+            &[Resource::LightClusters],
+            |ctx: &mut PassContext| {
+                let (encoder, timestamp_writes) = ctx.encoder_with_compute_writes();
+                self.light_culler
+                    .dispatch(encoder, &self.camera_bind_group, timestamp_writes);
+            },
+        );
+        // Lights that can't reach anything on screen keep their stale shadow maps until they
+        // come into view.
+        graph.add_pass(
+            "shadows",
+            &[],
+            &[Resource::ShadowMaps],
+            |ctx: &mut PassContext| {
+                for light in self
+                    .lights
+                    .iter()
+                    .filter(|light| frustum.intersects_sphere(&light.position, light.radius()))
+                {
+                    self.shadow_baker.update_light_shadow_map(
+                        light,
+                        &self.device,
+                        ctx.encoder,
+                        &self.models,
+                        &self.materials,
+                        ctx.profiler,
+                    );
+                }
+            },
+        );
+        if let Some(probe_bake) = &probe_bake {
+            graph.add_pass(
+                "reflection probes",
+                &[],
+                &[Resource::ReflectionProbes],
+                |ctx: &mut PassContext| {
+                    self.reflection_probes.draw(
+                        ctx.encoder,
+                        Some(ctx.profiler),
+                        probe_bake,
+                        &self.models,
+                        &self.materials,
+                    );
+                },
+            );
+        }
+        let ssao_prepass = self.ssao_pass.is_enabled();
+        let depth_prepass = !ssao_prepass && self.graphics_settings.depth_prepass;
+        let prepass = ssao_prepass || depth_prepass;
+        let depth_geometry = DepthGeometry {
+            models: &self.models,
+            materials: &self.materials,
+            frustum: &frustum,
+        };
+        if depth_prepass {
+            graph.add_pass(
+                "depth prepass",
+                &[],
+                &[Resource::SceneDepth],
+                |ctx: &mut PassContext| {
+                    let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                    self.depth_prepass.draw(
+                        encoder,
+                        &self.depth_texture.view,
+                        &self.camera_bind_group,
+                        &depth_geometry,
+                        timestamp_writes,
+                    );
+                },
+            );
+        }
+        if ssao_prepass {
+            graph.add_pass(
+                "depth prepass",
+                &[],
+                &[Resource::SceneDepth, Resource::SceneNormals],
+                |ctx: &mut PassContext| {
+                    let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                    self.ssao_pass.draw_prepass(
+                        encoder,
+                        &self.depth_texture.view,
+                        &self.camera_bind_group,
+                        &depth_geometry,
+                        timestamp_writes,
+                    );
+                },
+            );
+        }
+        graph.add_pass(
+            "ssao",
+            &[Resource::SceneDepth, Resource::SceneNormals],
+            &[Resource::AmbientOcclusion],
+            |ctx: &mut PassContext| {
+                self.ssao_pass
+                    .draw(ctx.encoder, &self.camera_bind_group, ctx.profiler);
+            },
+        );
+        graph.add_pass(
+            "main",
+            &[
+                Resource::ShadowMaps,
+                Resource::LightClusters,
+                Resource::AmbientOcclusion,
+                Resource::ReflectionProbes,
+            ],
+            &[Resource::SceneColor, Resource::SceneDepth],
+            |ctx: &mut PassContext| {
+                let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Main Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: scene_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color {
+                                r: 1.0,
+                                g: 1.0,
+                                b: 1.0,
+                                a: 1.0,
+                            }),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &self.depth_texture.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: if prepass {
+                                wgpu::LoadOp::Load
+                            } else {
+                                wgpu::LoadOp::Clear(1.0)
+                            },
+                            store: wgpu::StoreOp::Store,
                         }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
+                        stencil_ops: None,
                     }),
-                    stencil_ops: None,
-                }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+                    occlusion_query_set: None,
+                    timestamp_writes,
+                });
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.point_light_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.shadow_bind_group, &[]);
-            for model in &self.models {
-                model.draw(&mut render_pass);
-            }
+                render_pass.push_debug_group("opaque");
+                render_pass.set_pipeline(&self.render_pipeline);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_bind_group(1, &self.point_light_bind_group, &[]);
+                render_pass.set_bind_group(2, &self.shadow_bind_group, &[]);
+                for model in &self.models {
+                    model.draw(
+                        &mut render_pass,
+                        &frustum,
+                        &self.materials,
+                        BlendMode::Opaque,
+                    );
+                }
+                render_pass.pop_debug_group();
+                render_pass.push_debug_group("masked");
+                render_pass.set_pipeline(&self.masked_render_pipeline);
+                for model in &self.models {
+                    model.draw(
+                        &mut render_pass,
+                        &frustum,
+                        &self.materials,
+                        BlendMode::Masked,
+                    );
+                }
+                render_pass.pop_debug_group();
 
-            render_pass.set_pipeline(&self.skybox_render_pipeline);
-            render_pass.set_bind_group(0, &self.skybox_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.draw(0..3, 0..1);
+                render_pass.push_debug_group("skybox");
+                render_pass.set_pipeline(&self.skybox_render_pipeline);
+                render_pass.set_bind_group(0, &self.skybox_bind_group, &[]);
+                render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+                render_pass.pop_debug_group();
 
-            if self.player_controller.debug_enabled {
-                render_pass.set_pipeline(&self.debug_render_pipeline);
+                // Translucent surfaces go over the sky, farthest first so nearer ones blend
+                // over them.
+                let eye = self.player.camera.position;
+                let mut translucent: Vec<_> = self
+                    .models
+                    .iter()
+                    .flat_map(|model| {
+                        model
+                            .translucent_meshes(&frustum, eye)
+                            .map(move |(distance, mesh)| (distance, model, mesh))
+                    })
+                    .collect();
+                translucent.sort_by(|a, b| b.0.total_cmp(&a.0));
+                render_pass.push_debug_group("translucent");
+                render_pass.set_pipeline(&self.translucent_render_pipeline);
                 render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-                render_pass.set_vertex_buffer(0, self.debug_buffer.slice(..));
-                render_pass.draw(0..self.debug_lines_len, 0..1);
-            }
+                render_pass.set_bind_group(1, &self.point_light_bind_group, &[]);
+                for (_, model, mesh) in translucent {
+                    model.draw_single(&mut render_pass, mesh, &self.materials);
+                }
+                render_pass.pop_debug_group();
+
+                if self.player_controller.debug_enabled {
+                    render_pass.push_debug_group("debug lines");
+                    render_pass.set_pipeline(&self.debug_render_pipeline);
+                    render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                    render_pass.set_vertex_buffer(0, self.debug_buffer.slice(..));
+                    render_pass.draw(0..self.debug_lines_len, 0..1);
+                    render_pass.pop_debug_group();
+                }
+            },
+        );
+        graph.add_pass(
+            "decals",
+            &[Resource::SceneDepth],
+            &[Resource::SceneColor],
+            |ctx: &mut PassContext| {
+                let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                self.decal_pass.draw(
+                    encoder,
+                    scene_view,
+                    &self.camera_bind_group,
+                    timestamp_writes,
+                );
+            },
+        );
+        graph.add_pass(
+            "particles",
+            &[Resource::SceneDepth],
+            &[Resource::SceneColor],
+            |ctx: &mut PassContext| {
+                let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                self.particle_pass.draw(
+                    encoder,
+                    scene_view,
+                    &self.camera_bind_group,
+                    timestamp_writes,
+                );
+            },
+        );
+        graph.add_pass(
+            "view model",
+            &[],
+            &[Resource::SceneColor, Resource::SceneDepth],
+            |ctx: &mut PassContext| {
+                let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                self.view_model_pass.draw(
+                    encoder,
+                    scene_view,
+                    &self.depth_texture.view,
+                    timestamp_writes,
+                );
+            },
+        );
+        if let Some(upscale_pass) = &self.upscale_pass {
+            graph.add_pass(
+                "upscale",
+                &[Resource::SceneColor],
+                &[Resource::SceneColor],
+                |ctx: &mut PassContext| {
+                    let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                    upscale_pass.draw(encoder, &view, timestamp_writes);
+                },
+            );
         }
+        if self.automap.is_open() {
+            graph.add_pass(
+                "automap",
+                &[],
+                &[Resource::SceneColor],
+                |ctx: &mut PassContext| {
+                    let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                    self.automap_pass.draw(encoder, &view, timestamp_writes);
+                },
+            );
+        }
+        // Menu text goes over the dimmed scene.
+        if overlay.is_some() {
+            graph.add_pass(
+                "overlay",
+                &[],
+                &[Resource::SceneColor],
+                |ctx: &mut PassContext| {
+                    let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                    self.overlay_pass.draw(encoder, &view, timestamp_writes);
+                },
+            );
+        }
+        graph.add_pass(
+            "hud",
+            &[],
+            &[Resource::SceneColor],
+            |ctx: &mut PassContext| {
+                let (encoder, timestamp_writes) = ctx.encoder_with_render_writes();
+                self.hud_pass.draw(encoder, &view, timestamp_writes);
+            },
+        );
+        let submit_compute = (self.graphics_settings.async_compute
+            && self.gpu_capabilities.async_compute)
+            .then_some((&self.device, &self.queue));
+        graph.execute(&mut encoder, submit_compute, &mut self.gpu_profiler);
+        if probe_bake.is_some() {
+            self.reflection_probes.mark_baked();
+        }
+        self.gpu_profiler.resolve(&mut encoder);
 
         // submit will accept anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
+        self.gpu_profiler.end_frame();
 
         Ok(())
     }
 
+    #[tracing::instrument(level = "trace", skip_all)]
     pub fn update(&mut self, dt: Duration) {
-        self.player
-            .update(dt, &mut self.collision_manager, &mut self.player_controller);
-        self.camera_uniform.update_cam(&self.player.camera);
+        let dt = self.replay_frame(dt);
+        self.camera_controller
+            .look(&mut self.player, dt, &mut self.player_controller);
+        if self.view_model.update(dt, &self.player_controller) {
+            self.fire_weapon();
+        }
+        self.update_weapons(dt);
+        self.particle_system
+            .update(dt, self.rng.stream(RngSystem::Particles));
+        self.decal_system.update(dt);
+        self.hud.update(dt);
+        self.run_script(ScriptHook::Update(dt));
+        self.update_enemies(dt);
+        self.animate_characters(dt);
+        self.update_dynamic_resolution(dt);
+        self.tick_accumulator = (self.tick_accumulator + dt).min(Physics::MAX_FRAME_TIME);
+        let mut movers_moved = false;
+        while self.tick_accumulator >= Physics::TICK {
+            self.player.begin_tick();
+            movers_moved |= self.tick_movers(Physics::TICK);
+            self.camera_controller.tick(
+                &mut self.player,
+                Physics::TICK,
+                &mut self.collision_manager,
+                &self.player_controller,
+            );
+            self.light_time += Physics::TICK.as_secs_f32();
+            self.tick_accumulator -= Physics::TICK;
+        }
+        // How far along the next tick this frame is, for drawing between the last two.
+        let alpha = self.tick_accumulator.as_secs_f32() / Physics::TICK.as_secs_f32();
+        self.draw_movers(alpha);
+        if self.lights.iter().any(|light| light.animation.is_some()) {
+            self.upload_lights();
+        }
+        // Moving geometry invalidates the cached shadow maps.
+        if movers_moved {
+            self.shadow_baker.update_scene_version();
+            self.refresh_nav_grid();
+        }
+        for name in self.trigger_system.update(self.player.hitbox()) {
+            self.fire_trigger(&name);
+        }
+        self.automap
+            .explore(self.player.camera.position, &self.collision_manager);
+        self.collect_pickups();
+        self.handle_player_death();
+        self.dispatch_events();
+        self.hud.set_health(self.player.health.current());
+        self.hud
+            .set_ammo(self.view_model.ammo(), self.view_model.reserve_ammo());
+        self.camera_uniform
+            .update_cam(&self.player.interpolated_camera(alpha));
         self.queue.write_buffer(
             &self.camera_buffer,
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
+        self.stream_chunks(self.player.camera.position, false);
+        self.select_lods();
+    }
+
+    /// Picks the mesh levels for this frame's camera, or the full meshes with LOD turned off.
+    fn select_lods(&mut self) {
+        let camera = &self.player.camera;
+        let focal_length = if self.graphics_settings.lod {
+            1.0 / (camera.fovy / 2.0).tan()
+        } else {
+            f32::INFINITY
+        };
+        for model in &mut self.models {
+            model.select_lods(camera.position, focal_length);
+        }
+    }
+
+    /// Moves every mover's collision boxes by one tick, carrying the player along. Returns
+    /// true when anything moved.
+    fn tick_movers(&mut self, dt: Duration) -> bool {
+        let mut moved = false;
+        for mover in &mut self.movers {
+            mover.begin_tick();
+            let Some(delta) = mover.update(dt) else {
+                continue;
+            };
+            moved = true;
+            let mut carried = false;
+            for &index in &mover.bounding_boxes {
+                carried |= self
+                    .collision_manager
+                    .move_box(index, delta, self.player.hitbox());
+            }
+            if carried {
+                self.player.carry(delta);
+            }
+        }
+        moved
+    }
+
+    /// Moves the movers' models to `alpha` of the way into the next tick.
+    fn draw_movers(&mut self, alpha: f32) {
+        for mover in &mut self.movers {
+            let Some(model) = mover.model else {
+                continue;
+            };
+            if let Some(delta) = mover.draw_delta(alpha) {
+                self.models[model].translate(delta, &self.queue);
+            }
+        }
+    }
+
+    /// Activates the movers waiting on `name` and lets the level script react to it.
+    fn fire_trigger(&mut self, name: &str) {
+        let mut activated = false;
+        for mover in &mut self.movers {
+            if mover.trigger.as_deref() != Some(name) {
+                continue;
+            }
+            match &mover.key {
+                Some(key) if !self.player.has_key(key) => {
+                    self.events
+                        .publish(GameEvent::MoverLocked { key: key.clone() });
+                }
+                _ => {
+                    mover.activate();
+                    activated = true;
+                }
+            }
+        }
+        if activated {
+            self.events.publish(GameEvent::MoverActivated {
+                trigger: name.to_string(),
+            });
+        }
+        self.run_script(ScriptHook::Trigger(name));
+    }
+
+    /// Hands this frame's events to everything reacting to them, in the order they happened.
+    fn dispatch_events(&mut self) {
+        for event in self.events.take() {
+            self.hud.on_event(&event);
+            self.particle_system.on_event(&event);
+            self.run_script(ScriptHook::Event(&event));
+        }
     }
 
+    /// Advances every character's animation and uploads the resulting joint matrices.
+    fn animate_characters(&mut self, dt: Duration) {
+        for character in &mut self.characters {
+            character.animator.update(dt);
+            self.joint_palette.write(
+                &self.queue,
+                character.joint_offset,
+                &character.animator.joint_matrices(),
+            );
+        }
+    }
+
+    /// Reconfigures the surface for a new physical window size. A zero sized (minimized)
+    /// window stops rendering until it is resized again.
     pub fn resize(&mut self, width: u32, height: u32) {
-        if width > 0 && height > 0 {
-            self.config.width = width;
-            self.config.height = height;
-            self.surface.configure(&self.device, &self.config);
-            self.is_surface_configured = true;
-            self.depth_texture =
-                DepthTexture::create_depth_texture(&self.device, &self.config, "depth_texture");
+        if width == 0 || height == 0 {
+            self.is_surface_configured = false;
+            return;
+        }
+        let max_size = self.device.limits().max_texture_dimension_2d;
+        self.config.width = width.min(max_size);
+        self.config.height = height.min(max_size);
+        self.surface.configure(&self.device, &self.config);
+        self.is_surface_configured = true;
+        self.player.camera.aspect = self.config.width as f32 / self.config.height as f32;
+        self.camera_uniform.update_cam(&self.player.camera);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+        self.recreate_render_targets();
+    }
+
+    /// Called when the window moves to a display with a different DPI. The window is resized
+    /// separately, this only matters for logical render resolution.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        if self.is_surface_configured {
+            self.recreate_render_targets();
+        }
+    }
+
+    fn scaled_config(
+        config: &SurfaceConfiguration,
+        graphics_settings: &GraphicsSettings,
+        dynamic_scale: f32,
+        scale_factor: f64,
+    ) -> SurfaceConfiguration {
+        let scale = graphics_settings
+            .render_resolution
+            .render_scale(scale_factor)
+            * (graphics_settings.render_scale * dynamic_scale) as f64;
+        SurfaceConfiguration {
+            width: ((config.width as f64 * scale).round() as u32).max(1),
+            height: ((config.height as f64 * scale).round() as u32).max(1),
+            ..config.clone()
+        }
+    }
+
+    /// Rebuilds everything sized to the render resolution.
+    fn recreate_render_targets(&mut self) {
+        self.render_config = Self::scaled_config(
+            &self.config,
+            &self.graphics_settings,
+            self.dynamic_resolution.scale(),
+            self.scale_factor,
+        );
+        self.depth_texture =
+            DepthTexture::create_depth_texture(&self.device, &self.render_config, "depth_texture");
+        self.particle_pass
+            .rebind_depth(&self.device, &self.depth_texture.view);
+        self.decal_pass
+            .rebind_depth(&self.device, &self.depth_texture.view);
+        self.ssao_pass
+            .resize(&self.device, &self.render_config, &self.depth_texture.view);
+        self.point_light_bind_group = Self::create_point_light_bind_group(
+            &self.device,
+            &self.point_light_buffer,
+            &self.light_culler,
+            &self.ssao_pass.ambient_occlusion_view,
+            &self.ibl,
+            &self.reflection_probes,
+            &self.level_textures,
+        );
+        let scaled = self.render_config.width != self.config.width
+            || self.render_config.height != self.config.height;
+        match (&mut self.upscale_pass, scaled) {
+            (Some(upscale_pass), true) => upscale_pass.resize(&self.device, &self.render_config),
+            (None, true) => {
+                self.upscale_pass = Some(UpscalePass::new(
+                    &self.device,
+                    &self.render_config,
+                    self.graphics_settings.upscale_filter,
+                ))
+            }
+            (_, false) => self.upscale_pass = None,
         }
     }
 
+    /// Switches between drawing at the physical and the logical window resolution.
+    pub fn cycle_render_resolution(&mut self) -> RenderResolution {
+        self.graphics_settings.render_resolution = self.graphics_settings.render_resolution.next();
+        if self.is_surface_configured {
+            self.recreate_render_targets();
+        }
+        self.graphics_settings.render_resolution
+    }
+
+    pub fn adjust_render_scale(&mut self, delta: f32) -> f32 {
+        self.set_render_scale(self.graphics_settings.render_scale + delta)
+    }
+
+    /// Clamped to `GraphicsSettings::MIN_RENDER_SCALE` and one, returns the scale used.
+    pub fn set_render_scale(&mut self, render_scale: f32) -> f32 {
+        let render_scale = self.graphics_settings.set_render_scale(render_scale);
+        self.dynamic_resolution.reset();
+        if self.is_surface_configured {
+            self.recreate_render_targets();
+        }
+        render_scale
+    }
+
+    pub fn cycle_upscale_filter(&mut self) -> UpscaleFilter {
+        self.graphics_settings.upscale_filter = self.graphics_settings.upscale_filter.next();
+        if let Some(upscale_pass) = &self.upscale_pass {
+            upscale_pass.set_filter(&self.queue, self.graphics_settings.upscale_filter);
+        }
+        self.graphics_settings.upscale_filter
+    }
+
+    pub fn toggle_dynamic_resolution(&mut self) -> bool {
+        self.set_dynamic_resolution(!self.graphics_settings.dynamic_resolution);
+        self.graphics_settings.dynamic_resolution
+    }
+
+    pub fn set_dynamic_resolution(&mut self, enabled: bool) {
+        self.graphics_settings.dynamic_resolution = enabled;
+        self.dynamic_resolution.reset();
+        if self.is_surface_configured {
+            self.recreate_render_targets();
+        }
+    }
+
+    /// Feeds the last frame time to dynamic resolution, rebuilding targets when it rescales.
+    fn update_dynamic_resolution(&mut self, dt: Duration) {
+        if !self.graphics_settings.dynamic_resolution || !self.is_surface_configured {
+            return;
+        }
+        let min_scale = GraphicsSettings::MIN_RENDER_SCALE / self.graphics_settings.render_scale;
+        if self.dynamic_resolution.update(dt, min_scale) {
+            self.recreate_render_targets();
+        }
+    }
+
+    /// Steps to the next SSAO quality, wrapping around to off.
+    pub fn cycle_ssao_quality(&mut self) -> SsaoQuality {
+        self.set_ssao_quality(self.graphics_settings.ssao.next());
+        self.graphics_settings.ssao
+    }
+
+    pub fn set_ssao_quality(&mut self, quality: SsaoQuality) {
+        self.graphics_settings.ssao = quality;
+        self.ssao_pass.set_quality(&self.queue, quality);
+    }
+
+    pub fn toggle_depth_prepass(&mut self) -> bool {
+        self.graphics_settings.depth_prepass = !self.graphics_settings.depth_prepass;
+        self.graphics_settings.depth_prepass
+    }
+
+    fn create_skybox_pipeline(
+        device: &Device,
+        layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+    ) -> RenderPipeline {
+        PipelineFactory::create_render_pipeline(
+            device,
+            layout,
+            PipelineDescriptor {
+                cull_mode: Some(wgpu::Face::Back),
+                depth_format: Some(DepthTexture::DEPTH_FORMAT),
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("Skybox Shader"),
+                        source: wgpu::ShaderSource::Wgsl(
+                            include_str!("shaders/skybox.wgsl").into(),
+                        ),
+                    },
+                    Some(format),
+                )
+            },
+        )
+    }
+
+    fn create_point_light_bind_group(
+        device: &Device,
+        light_buffer: &Buffer,
+        light_culler: &LightCuller,
+        ambient_occlusion: &TextureView,
+        ibl: &Ibl,
+        reflection_probes: &ReflectionProbes,
+        level_textures: &TextureArray,
+    ) -> BindGroup {
+        LightUniformArray::create_bind_group(
+            device,
+            &LightUniformArray::create_bind_group_layout(device),
+            &LightResources {
+                lights: light_buffer,
+                clusters: &light_culler.cluster_buffer,
+                light_grid: &light_culler.light_grid_buffer,
+                light_indices: &light_culler.light_index_buffer,
+                ambient_occlusion,
+                ibl,
+                probe_faces: &reflection_probes.faces_view,
+                probes: &reflection_probes.buffer,
+                level_textures,
+            },
+        )
+    }
+
+    #[tracing::instrument(skip_all, fields(map = %self.map_file))]
     pub fn rerender(&mut self) {
         let diffuse_texture_layout = TextureBuilder::create_bind_group_layout(&self.device);
         let skybox_bind_group_layout = CubeTextureBuilder::create_bind_group_layout(&self.device);
-        let point_light_bind_group_layout =
-            LightUniformArray::create_bind_group_layout(&self.device);
 
         let map_loader = MapLoader::from_file(&self.map_file).unwrap();
-        let map = map_loader.load(&self.device, &self.queue, &diffuse_texture_layout);
+        let map = map_loader.load(
+            &self.device,
+            &self.queue,
+            &diffuse_texture_layout,
+            &mut self.asset_loader,
+        );
         let models = map.models;
+        let materials = map.materials;
+        let level_textures_handle = map.level_textures;
         let skybox_files = map.skybox_textures;
         let lights = map.lights;
+        let characters = map.characters;
         let collision_manager = map.collision_manager;
         let debug_lines = map.debug_lines;
         let debug_lines_len = debug_lines.len() as u32;
 
-        let point_light_uniform = LightUniformArray::new(&lights);
+        let point_light_uniform =
+            LightUniformArray::new(&lights, map.ambient, map.environment, self.light_time);
 
         let point_light_buffer =
             self.device
@@ -447,38 +1445,332 @@ impl Renderer {
                 usage: wgpu::BufferUsages::VERTEX,
             });
 
-        let point_light_bind_group = LightUniformArray::create_bind_group(
-            &self.device,
-            &point_light_bind_group_layout,
-            &point_light_buffer,
-        );
-        let skybox_texture = CubeTexture::from_files(
-            &skybox_files,
+        self.light_culler
+            .rebind_lights(&self.device, &point_light_buffer);
+        let skybox_texture = CubeTexture::from_color(
+            Self::PLACEHOLDER_SKY,
             &self.device,
             &self.queue,
             Some("Skybox Texture"),
         );
+        let ibl = skybox_texture.generate_ibl(&self.device, &self.queue);
+        let reflection_probes = ReflectionProbes::new(
+            &self.device,
+            &map.reflection_probes,
+            ReflectionProbes::RESOLUTION,
+            self.config.format,
+            &CameraUniform::create_bind_group_layout(&self.device),
+            &diffuse_texture_layout,
+        );
+        // The old map's array stays out of the new map's bind group while its own loads.
+        self.level_textures =
+            TextureArray::from_color([0; 4], &self.device, &self.queue, Some("Level Textures"));
+        let point_light_bind_group = Self::create_point_light_bind_group(
+            &self.device,
+            &point_light_buffer,
+            &self.light_culler,
+            &self.ssao_pass.ambient_occlusion_view,
+            &ibl,
+            &reflection_probes,
+            &self.level_textures,
+        );
+        let skybox_handle = self
+            .asset_loader
+            .load_images(skybox_files, TextureKind::Cube, true);
         let skybox_bind_group = CubeTextureBuilder::create_bind_group(
             &self.device,
             &skybox_texture,
             &skybox_bind_group_layout,
         );
+
+        // The new map already holds references to anything it shares with the old one, so
+        // only textures unique to the old map get evicted.
+        for material in self.materials.values() {
+            material.release_textures(&mut self.asset_loader);
+        }
+        self.asset_loader.release(self.skybox_handle);
+        if let Some(handle) = self.level_textures_handle {
+            self.asset_loader.release(handle);
+        }
+        for handle in self.asset_loader.evict_unused() {
+            self.textures.remove(&handle);
+            self.cube_textures.remove(&handle);
+            self.texture_arrays.remove(&handle);
+        }
+
         self.skybox_bind_group = skybox_bind_group;
-        self.models = models;
+        self.skybox_handle = skybox_handle;
+        self.skybox_pending = true;
+        self.level_textures_handle = level_textures_handle;
+        self.level_textures_pending = true;
+        self.ibl = ibl;
+        self.reflection_probes = reflection_probes;
+        self.point_light_buffer = point_light_buffer;
         self.point_light_bind_group = point_light_bind_group;
+        self.models = models;
+        self.materials = materials;
+        self.resolve_textures();
+        self.lights = lights;
+        self.flash_light = None;
+        self.weapon_system.clear();
+        self.ambient = map.ambient;
+        self.environment = map.environment;
+        self.characters = characters;
+        self.animate_characters(Duration::ZERO);
+        self.player.health.reset();
+        self.pickup_system.clear();
+        for pickup in map.pickups {
+            self.pickup_system.add(pickup);
+        }
+        self.spawn_point = map.spawn_point.unwrap_or(Point3::from(Self::DEFAULT_SPAWN));
+        self.events.clear();
+        self.decal_system.clear();
+        for decal in map.decals {
+            self.decal_system.add(decal);
+        }
+        self.camera_uniform.set_fog(&map.fog);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
         self.debug_buffer = debug_buffer;
         self.debug_lines_len = debug_lines_len;
         self.collision_manager = collision_manager;
         self.shadow_baker.update_scene_version();
-        for light in &self.lights {
-            self.shadow_baker.update_light_version_from_id(light.id);
+        self.rebuild_shadow_maps(self.shadow_baker.resolution());
+        self.trigger_system.clear();
+        for trigger in map.triggers {
+            self.trigger_system.add(trigger);
+        }
+        self.movers = map.movers;
+        self.nav_grid = Self::build_nav_grid(&self.collision_manager, &self.characters);
+        self.automap = Automap::new(&self.collision_manager);
+        self.level_streamer = LevelStreamer::new(map.chunks);
+        self.stream_chunks(self.spawn_point, true);
+        self.script_host = Self::load_script(map.script.as_deref());
+        self.run_script(ScriptHook::Start);
+    }
+
+    /// Uploads textures that finished decoding. Returns true if anything was uploaded.
+    pub fn poll_assets(&mut self) -> bool {
+        let loaded = self.asset_loader.poll();
+        if loaded.is_empty() {
+            return false;
+        }
+        for asset in loaded {
+            let images = match asset.images {
+                Ok(images) => images,
+                Err(e) => {
+                    error!("Failed to load {e}");
+                    continue;
+                }
+            };
+            match asset.kind {
+                TextureKind::D2 => {
+                    let texture = Texture::from_rgba(
+                        &images[0],
+                        &self.device,
+                        &self.queue,
+                        Some(&asset.label),
+                    );
+                    self.textures.insert(asset.handle, texture);
+                }
+                TextureKind::Cube => {
+                    let texture = CubeTexture::from_images(
+                        &images,
+                        &self.device,
+                        &self.queue,
+                        Some(&asset.label),
+                    );
+                    self.cube_textures.insert(asset.handle, texture);
+                }
+                TextureKind::Array => {
+                    match TextureArray::from_images(
+                        images,
+                        &self.device,
+                        &self.queue,
+                        Some(&asset.label),
+                    ) {
+                        Ok(texture_array) => {
+                            self.texture_arrays.insert(asset.handle, texture_array);
+                        }
+                        Err(e) => error!("Unable to pack {} {e}", asset.label),
+                    }
+                }
+            }
+        }
+        self.resolve_textures();
+        true
+    }
+
+    /// Replaces placeholders whose textures have been uploaded.
+    fn resolve_textures(&mut self) {
+        let material_layout = TextureBuilder::create_bind_group_layout(&self.device);
+        let level_textures = self
+            .level_textures_handle
+            .and_then(|handle| self.texture_arrays.get(&handle));
+        for material in self.materials.values_mut() {
+            // Masked materials cut their holes into shadows, which have to be baked again.
+            if material.resolve(
+                &self.textures,
+                level_textures,
+                &self.device,
+                &self.queue,
+                &material_layout,
+            ) && material.blend_mode == BlendMode::Masked
+            {
+                self.shadow_baker.update_scene_version();
+            }
+        }
+        if self.level_textures_pending
+            && let Some(level_textures) = level_textures
+        {
+            self.level_textures = level_textures.clone();
+            self.level_textures_pending = false;
+            self.point_light_bind_group = Self::create_point_light_bind_group(
+                &self.device,
+                &self.point_light_buffer,
+                &self.light_culler,
+                &self.ssao_pass.ambient_occlusion_view,
+                &self.ibl,
+                &self.reflection_probes,
+                &self.level_textures,
+            );
         }
+        if self.skybox_pending
+            && let Some(skybox_texture) = self.cube_textures.get(&self.skybox_handle)
+        {
+            self.skybox_bind_group = CubeTextureBuilder::create_bind_group(
+                &self.device,
+                skybox_texture,
+                &CubeTextureBuilder::create_bind_group_layout(&self.device),
+            );
+            self.ibl = skybox_texture.generate_ibl(&self.device, &self.queue);
+            self.point_light_bind_group = Self::create_point_light_bind_group(
+                &self.device,
+                &self.point_light_buffer,
+                &self.light_culler,
+                &self.ssao_pass.ambient_occlusion_view,
+                &self.ibl,
+                &self.reflection_probes,
+                &self.level_textures,
+            );
+            self.skybox_pending = false;
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.asset_loader.is_loading_critical()
+    }
+
+    pub fn loading_progress(&self) -> (usize, usize) {
+        self.asset_loader.progress()
+    }
+
+    /// Copies the settings the player can change while playing into `settings`.
+    pub fn capture_settings(&self, settings: &mut Settings) {
+        settings.graphics = self.graphics_settings;
+        settings.camera = self.player.camera_settings;
+        settings.shadow_resolution = Some(self.shadow_baker.resolution());
+    }
+
+    pub fn save_game(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        SaveGame {
+            map_file: self.map_file.clone(),
+            player: self.player.save_state(),
+            lights: self.lights.clone(),
+        }
+        .write_to_file(path)
+    }
+
+    /// Restores a save, switching maps first if it was made on a different one.
+    #[tracing::instrument(skip(self))]
+    pub fn load_game(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let save = SaveGame::from_file(path)?;
+        if save.map_file != self.map_file {
+            // Checked before anything changes, so a save of a missing map leaves the game as is.
+            MapLoader::from_file(&save.map_file).map_err(|e| format!("{} {e}", save.map_file))?;
+            self.map_file = save.map_file;
+            self.rerender();
+        }
+        self.player.restore_state(&save.player);
+        self.player_controller.release_all();
+        self.tick_accumulator = Duration::ZERO;
+
+        // Saved lights are edits of the map's lights, anything else no longer exists.
+        for saved in save.lights {
+            match self.lights.iter_mut().find(|light| light.id == saved.id) {
+                Some(light) => {
+                    *light = saved;
+                    self.shadow_baker.update_light_version_from_id(light.id);
+                }
+                None => warn!("Save references unknown light {}", saved.id),
+            }
+        }
+        self.upload_lights();
+        self.camera_uniform.update_cam(&self.player.camera);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+        Ok(())
     }
 
     pub fn get_mut_player_controller(&mut self) -> &mut PlayerController {
         &mut self.player_controller
     }
 
+    pub fn get_mut_hud(&mut self) -> &mut Hud {
+        &mut self.hud
+    }
+
+    pub fn get_mut_console(&mut self) -> &mut Console {
+        &mut self.console
+    }
+
+    /// Hands movement over to a controller for `mode`.
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        self.camera_controller.exit(&mut self.player);
+        self.camera_controller = mode.controller(&self.player);
+    }
+
+    pub fn get_mut_automap(&mut self) -> &mut Automap {
+        &mut self.automap
+    }
+
+    pub fn get_mut_player(&mut self) -> &mut Player {
+        &mut self.player
+    }
+
+    /// Periodic GPU pass timings, see `GpuProfiler::take_report`.
+    pub fn take_gpu_report(&mut self) -> Option<String> {
+        self.gpu_profiler.take_report()
+    }
+
+    pub fn take_gpu_frame_timings(&mut self) -> Vec<(String, f64)> {
+        self.gpu_profiler.take_frame_timings()
+    }
+
+    pub fn gpu_name(&self) -> &str {
+        &self.gpu_info.name
+    }
+
+    /// Puts the camera at `position` looking at `target` without running any gameplay, for
+    /// the benchmark flythrough.
+    pub fn fly_camera(&mut self, position: Point3<f32>, target: Point3<f32>) {
+        let camera = &mut self.player.camera;
+        camera.position = position;
+        camera.target = target;
+        self.camera_uniform.update_cam(camera);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform]),
+        );
+    }
+
     pub fn get_window(&self) -> &Arc<Window> {
         &self.window
     }
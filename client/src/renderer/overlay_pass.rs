@@ -0,0 +1,99 @@
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, Buffer, CommandEncoder, Device, Queue, RenderPipeline, TextureView};
+
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+/// Blends a flat colour over the finished frame, used to dim the scene behind menus.
+pub struct OverlayPass {
+    pipeline: RenderPipeline,
+    color_buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl OverlayPass {
+    pub fn new(device: &Device, color_format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("overlay_bind_group_layout"),
+        });
+        let color_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Overlay Color Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32; 4]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: color_buffer.as_entire_binding(),
+            }],
+            label: Some("overlay_bind_group"),
+        });
+
+        let pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Overlay Pipeline Layout",
+            &[&layout],
+        );
+        let pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &pipeline_layout,
+            PipelineDescriptor {
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("Overlay Shader"),
+                        source: wgpu::ShaderSource::Wgsl(
+                            include_str!("shaders/overlay.wgsl").into(),
+                        ),
+                    },
+                    Some(color_format),
+                )
+            },
+        );
+
+        Self {
+            pipeline,
+            color_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn update(&self, queue: &Queue, color: [f32; 4]) {
+        queue.write_buffer(&self.color_buffer, 0, bytemuck::cast_slice(&color));
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        color_view: &TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
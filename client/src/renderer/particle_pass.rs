@@ -0,0 +1,217 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::Point3;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Queue, RenderPipeline, TextureView,
+};
+
+use crate::game::particles::ParticleSystem;
+
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ParticleInstance {
+    pub position: [f32; 3],
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+/// Draws camera facing particle quads, faded where they intersect opaque geometry.
+pub struct ParticlePass {
+    additive_pipeline: RenderPipeline,
+    alpha_pipeline: RenderPipeline,
+    depth_bind_group_layout: BindGroupLayout,
+    depth_bind_group: BindGroup,
+    instance_buffer: Buffer,
+    additive_count: u32,
+    alpha_count: u32,
+}
+
+impl ParticleInstance {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ParticleInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+impl ParticlePass {
+    const ADDITIVE_BLEND: wgpu::BlendState = wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent::OVER,
+    };
+
+    pub fn new(
+        device: &Device,
+        color_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &BindGroupLayout,
+        depth_view: &TextureView,
+    ) -> Self {
+        let depth_bind_group_layout = Self::create_depth_bind_group_layout(device);
+        let depth_bind_group =
+            Self::create_depth_bind_group(device, &depth_bind_group_layout, depth_view);
+        let layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Particle Pipeline Layout",
+            &[camera_bind_group_layout, &depth_bind_group_layout],
+        );
+        let create_pipeline = |blend: wgpu::BlendState| {
+            PipelineFactory::create_render_pipeline(
+                device,
+                &layout,
+                PipelineDescriptor {
+                    vertex_layouts: &[ParticleInstance::desc()],
+                    blend: Some(blend),
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..PipelineDescriptor::new(
+                        wgpu::ShaderModuleDescriptor {
+                            label: Some("Particle Shader"),
+                            source: wgpu::ShaderSource::Wgsl(
+                                include_str!("shaders/particle.wgsl").into(),
+                            ),
+                        },
+                        Some(color_format),
+                    )
+                },
+            )
+        };
+        let additive_pipeline = create_pipeline(Self::ADDITIVE_BLEND);
+        let alpha_pipeline = create_pipeline(wgpu::BlendState::ALPHA_BLENDING);
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Instance Buffer"),
+            size: (ParticleSystem::MAX_PARTICLES * std::mem::size_of::<ParticleInstance>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            additive_pipeline,
+            alpha_pipeline,
+            depth_bind_group_layout,
+            depth_bind_group,
+            instance_buffer,
+            additive_count: 0,
+            alpha_count: 0,
+        }
+    }
+
+    fn create_depth_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            }],
+            label: Some("particle_depth_bind_group_layout"),
+        })
+    }
+
+    fn create_depth_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        depth_view: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(depth_view),
+            }],
+            label: Some("particle_depth_bind_group"),
+        })
+    }
+
+    /// Must be called whenever the scene depth texture is recreated.
+    pub fn rebind_depth(&mut self, device: &Device, depth_view: &TextureView) {
+        self.depth_bind_group =
+            Self::create_depth_bind_group(device, &self.depth_bind_group_layout, depth_view);
+    }
+
+    pub fn update(
+        &mut self,
+        queue: &Queue,
+        particle_system: &ParticleSystem,
+        camera_position: Point3<f32>,
+    ) {
+        let (particles, additive_count) = particle_system.sorted_particles(camera_position);
+        let instances: Vec<ParticleInstance> = particles
+            .iter()
+            .map(|particle| ParticleInstance {
+                position: particle.position.into(),
+                size: particle.size(),
+                color: particle.color(),
+            })
+            .collect();
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        self.additive_count = additive_count as u32;
+        self.alpha_count = (instances.len() - additive_count) as u32;
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        color_view: &TextureView,
+        camera_bind_group: &BindGroup,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        if self.additive_count + self.alpha_count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Particle Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.depth_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        let additive_end = self.additive_count;
+        let alpha_end = additive_end + self.alpha_count;
+        if self.additive_count > 0 {
+            render_pass.set_pipeline(&self.additive_pipeline);
+            render_pass.draw(0..4, 0..additive_end);
+        }
+        if self.alpha_count > 0 {
+            render_pass.set_pipeline(&self.alpha_pipeline);
+            render_pass.draw(0..4, additive_end..alpha_end);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use crate::game::events::GameEvent;
+use crate::game::pickups::PickupKind;
+
+use super::Renderer;
+
+impl Renderer {
+    /// Gives the player whatever they walked over and takes it out of the level.
+    pub(super) fn collect_pickups(&mut self) {
+        let player_box = self.player.hitbox().clone();
+        let player = &mut self.player;
+        let view_model = &mut self.view_model;
+        let collected = self.pickup_system.collect(&player_box, |kind| match kind {
+            PickupKind::Medkit { health } => player.health.heal(*health) > 0,
+            PickupKind::Ammo { rounds } => view_model.add_ammo(*rounds) > 0,
+            PickupKind::Key { name } => player.give_key(name),
+        });
+        for pickup in collected {
+            if let Some((model, instance)) = pickup.model {
+                self.models[model].hide_instance(instance, &self.queue);
+                self.shadow_baker.update_scene_version();
+            }
+            self.events
+                .publish(GameEvent::PickupCollected { kind: pickup.kind });
+        }
+    }
+}
@@ -1,53 +1,108 @@
-use wgpu::{BindGroupLayout, Device, PipelineLayout};
+use wgpu::{BindGroupLayout, Device, PipelineLayout, TextureFormat};
+
+use crate::model::BlendMode;
+use crate::model::depth_texture::DepthTexture;
+use crate::model::model_instance::RawInstance;
+use crate::model::vertex::Vertex;
 
 pub struct PipelineFactory;
 
+/// How a pipeline from `PipelineFactory::create_render_pipeline` is set up. `new` gives a
+/// fullscreen or 2D pass: a triangle list without vertex buffers, culling, depth or blending,
+/// so callers only spell out what they change.
+pub struct PipelineDescriptor<'a> {
+    pub shader: wgpu::ShaderModuleDescriptor<'a>,
+    // Lets one shader hold variants of its fragment stage, like depth passes cutting out masked
+    // surfaces.
+    pub fragment_entry_point: &'a str,
+    pub vertex_layouts: &'a [wgpu::VertexBufferLayout<'a>],
+    // None for depth only pipelines, like shadow maps.
+    pub color_format: Option<TextureFormat>,
+    pub blend: Option<wgpu::BlendState>,
+    pub topology: wgpu::PrimitiveTopology,
+    pub cull_mode: Option<wgpu::Face>,
+    pub depth_format: Option<TextureFormat>,
+    pub depth_write_enabled: bool,
+    pub depth_compare: wgpu::CompareFunction,
+}
+
+impl<'a> PipelineDescriptor<'a> {
+    pub fn new(
+        shader: wgpu::ShaderModuleDescriptor<'a>,
+        color_format: Option<TextureFormat>,
+    ) -> Self {
+        Self {
+            shader,
+            fragment_entry_point: "fs_main",
+            vertex_layouts: &[],
+            color_format,
+            blend: None,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: None,
+            depth_format: None,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+        }
+    }
+}
+
 impl PipelineFactory {
+    /// Names a pipeline after its shader, so "Decal Shader" builds the "Decal Pipeline".
+    fn pipeline_label(shader: &wgpu::ShaderModuleDescriptor) -> String {
+        let name = shader.label.unwrap_or("Unnamed");
+        format!("{} Pipeline", name.strip_suffix(" Shader").unwrap_or(name))
+    }
+
     pub fn create_render_pipeline_layout(
         device: &Device,
+        label: &str,
         layouts: &[&BindGroupLayout],
     ) -> PipelineLayout {
         device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
+            label: Some(label),
             bind_group_layouts: layouts,
             push_constant_ranges: &[],
         })
     }
 
-    pub fn create_shadow_render_pipeline(
-        device: &wgpu::Device,
-        layout: &wgpu::PipelineLayout,
-        depth_format: Option<wgpu::TextureFormat>,
-        vertex_layouts: &[wgpu::VertexBufferLayout],
-        topology: wgpu::PrimitiveTopology,
-        shader: wgpu::ShaderModuleDescriptor,
-        cull_mode: Option<wgpu::Face>,
-        depth_write_enabled: bool,
-        depth_compare: wgpu::CompareFunction,
+    pub fn create_render_pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
+        descriptor: PipelineDescriptor,
     ) -> wgpu::RenderPipeline {
-        let shader = device.create_shader_module(shader);
+        let label = Self::pipeline_label(&descriptor.shader);
+        let shader = device.create_shader_module(descriptor.shader);
+        let targets: Vec<Option<wgpu::ColorTargetState>> = descriptor
+            .color_format
+            .map(|format| wgpu::ColorTargetState {
+                format,
+                blend: descriptor.blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .into_iter()
+            .map(Some)
+            .collect();
 
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some(&format!("{shader:?}")),
+            label: Some(&label),
             layout: Some(layout),
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: Some("vs_main"),
-                buffers: vertex_layouts,
+                buffers: descriptor.vertex_layouts,
                 compilation_options: Default::default(),
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[],
+                entry_point: Some(descriptor.fragment_entry_point),
+                targets: &targets,
                 compilation_options: Default::default(),
             }),
-
             primitive: wgpu::PrimitiveState {
-                topology,
+                topology: descriptor.topology,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode,
+                cull_mode: descriptor.cull_mode,
                 // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
                 polygon_mode: wgpu::PolygonMode::Fill,
                 // Requires Features::DEPTH_CLIP_CONTROL
@@ -55,13 +110,15 @@ impl PipelineFactory {
                 // Requires Features::CONSERVATIVE_RASTERIZATION
                 conservative: false,
             },
-            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
-                format,
-                depth_write_enabled,
-                depth_compare,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
+            depth_stencil: descriptor
+                .depth_format
+                .map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: descriptor.depth_write_enabled,
+                    depth_compare: descriptor.depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
             multisample: wgpu::MultisampleState {
                 count: 1,
                 mask: !0,
@@ -72,27 +129,52 @@ impl PipelineFactory {
         })
     }
 
-    pub fn create_render_pipeline(
-        device: &wgpu::Device,
-        layout: &wgpu::PipelineLayout,
-        color_format: wgpu::TextureFormat,
-        depth_format: Option<wgpu::TextureFormat>,
-        vertex_layouts: &[wgpu::VertexBufferLayout],
-        topology: wgpu::PrimitiveTopology,
+    pub fn create_compute_pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
         shader: wgpu::ShaderModuleDescriptor,
-        cull_mode: Option<wgpu::Face>,
-        depth_write_enabled: bool,
-        depth_compare: wgpu::CompareFunction,
-    ) -> wgpu::RenderPipeline {
+    ) -> wgpu::ComputePipeline {
+        let label = Self::pipeline_label(&shader);
         let shader = device.create_shader_module(shader);
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&label),
+            layout: Some(layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        })
+    }
+
+    /// The main scene shader specialised for one blend mode. Translucent surfaces blend over
+    /// what is behind them and leave depth alone so they don't hide each other.
+    pub fn create_material_pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
+        color_format: wgpu::TextureFormat,
+        blend_mode: BlendMode,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Scene Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shader.wgsl").into()),
+        });
+        let (blend, depth_write_enabled) = match blend_mode {
+            BlendMode::Opaque | BlendMode::Masked => (None, true),
+            BlendMode::Translucent => (Some(wgpu::BlendState::ALPHA_BLENDING), false),
+        };
+        let constants = [("BLEND_MODE", blend_mode as u32 as f64)];
+        let compilation_options = wgpu::PipelineCompilationOptions {
+            constants: &constants,
+            ..Default::default()
+        };
 
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some(&format!("{shader:?}")),
+            label: Some(&format!("{blend_mode:?} Scene Pipeline")),
             layout: Some(layout),
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: Some("vs_main"),
-                buffers: vertex_layouts,
+                buffers: &[Vertex::desc(), RawInstance::desc()],
                 compilation_options: Default::default(),
             },
             fragment: Some(wgpu::FragmentState {
@@ -100,27 +182,24 @@ impl PipelineFactory {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: color_format,
-                    blend: None,
+                    blend,
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
-                compilation_options: Default::default(),
+                compilation_options,
             }),
             primitive: wgpu::PrimitiveState {
-                topology,
+                topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode,
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                cull_mode: Some(wgpu::Face::Back),
                 polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
                 unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
                 conservative: false,
             },
-            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
-                format,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DepthTexture::DEPTH_FORMAT,
                 depth_write_enabled,
-                depth_compare,
+                depth_compare: wgpu::CompareFunction::LessEqual,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
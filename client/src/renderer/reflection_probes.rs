@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use log::warn;
+use nalgebra::Point3;
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, RenderPipeline, TextureFormat,
+    TextureView,
+};
+
+use crate::camera::camera_uniform::CameraUniform;
+use crate::camera::frustum::Frustum;
+use crate::camera::shadow_map_uniform::ShadowMapUniform;
+use crate::model::cube_texture::{CubeFace, CubeTexture};
+use crate::model::depth_texture::DepthTexture;
+use crate::model::model_instance::RawInstance;
+use crate::model::vertex::Vertex;
+use crate::model::{BlendMode, Material, Model};
+
+use super::Renderer;
+use super::gpu_profiler::GpuProfiler;
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProbeUniform {
+    positions: [[f32; 4]; ReflectionProbes::MAX_PROBES],
+    count: u32,
+    _padding: [u32; 3],
+}
+
+/// Cube maps of the level seen from the map's probe positions, drawn once its textures are in
+/// and reflected by shiny materials from whichever probe is nearest. Faces are rendered with
+/// the same views as point shadows and kept in one cube array, which the scene shader samples
+/// as plain layers.
+pub struct ReflectionProbes {
+    positions: Vec<Point3<f32>>,
+    cubes: CubeTexture,
+    pub faces_view: TextureView,
+    pub buffer: Buffer,
+    depth_view: TextureView,
+    light_layout: BindGroupLayout,
+    // Stands in for the shadow group, so materials stay at group 3 as Model::draw binds them.
+    empty_bind_group: BindGroup,
+    pipeline: RenderPipeline,
+    baked: bool,
+}
+
+/// Bind groups for one bake: the lights, the sky drawn behind everything, and a camera looking
+/// out of each face of each probe.
+pub struct ProbeBake {
+    light_bind_group: BindGroup,
+    skybox_pipeline: RenderPipeline,
+    skybox_bind_group: BindGroup,
+    faces: Vec<(Frustum, BindGroup)>,
+}
+
+impl ReflectionProbes {
+    pub const MAX_PROBES: usize = 16;
+    pub const RESOLUTION: u32 = 128;
+
+    /// Cubes of `resolution` texels a side, which can be copied out as well as sampled.
+    pub fn new(
+        device: &Device,
+        positions: &[Point3<f32>],
+        resolution: u32,
+        color_format: TextureFormat,
+        camera_bind_group_layout: &BindGroupLayout,
+        material_layout: &BindGroupLayout,
+    ) -> Self {
+        if positions.len() > Self::MAX_PROBES {
+            warn!(
+                "Map has {} reflection probes, only the first {} are used",
+                positions.len(),
+                Self::MAX_PROBES
+            );
+        }
+        let positions: Vec<Point3<f32>> =
+            positions.iter().take(Self::MAX_PROBES).copied().collect();
+
+        let cubes = CubeTexture::new(
+            device,
+            Some("Reflection Probes"),
+            resolution,
+            positions.len() as u32,
+            1,
+            color_format,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC,
+        );
+        let faces_view = cubes.layers_view(0).expect("probes have a single mip");
+
+        let mut uniform = ProbeUniform {
+            positions: [[0.0; 4]; Self::MAX_PROBES],
+            count: positions.len() as u32,
+            _padding: [0; 3],
+        };
+        for (slot, position) in uniform.positions.iter_mut().zip(&positions) {
+            *slot = position.to_homogeneous().into();
+        }
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reflection Probe Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reflection Probe Depth"),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DepthTexture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Reflection Probe Depth"),
+            ..Default::default()
+        });
+
+        let light_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("reflection_probe_light_bind_group_layout"),
+        });
+        let empty_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[],
+            label: Some("reflection_probe_empty_bind_group_layout"),
+        });
+        let empty_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &empty_layout,
+            entries: &[],
+            label: Some("reflection_probe_empty_bind_group"),
+        });
+
+        let pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Reflection Probe Pipeline Layout",
+            &[
+                camera_bind_group_layout,
+                &light_layout,
+                &empty_layout,
+                material_layout,
+            ],
+        );
+        let pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &pipeline_layout,
+            PipelineDescriptor {
+                vertex_layouts: &[Vertex::desc(), RawInstance::desc()],
+                cull_mode: Some(wgpu::Face::Back),
+                depth_format: Some(DepthTexture::DEPTH_FORMAT),
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("Reflection Probe Shader"),
+                        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/probe.wgsl").into()),
+                    },
+                    Some(color_format),
+                )
+            },
+        );
+
+        Self {
+            positions,
+            cubes,
+            faces_view,
+            buffer,
+            depth_view,
+            light_layout,
+            empty_bind_group,
+            pipeline,
+            baked: false,
+        }
+    }
+
+    pub fn needs_bake(&self) -> bool {
+        !self.baked && !self.positions.is_empty()
+    }
+
+    pub fn mark_baked(&mut self) {
+        self.baked = true;
+    }
+
+    pub fn cubes(&self) -> &CubeTexture {
+        &self.cubes
+    }
+
+    /// Draws all six faces of every probe: static opaque and masked geometry lit by every
+    /// light without shadows, then the skybox.
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        mut profiler: Option<&mut GpuProfiler>,
+        bake: &ProbeBake,
+        models: &[Model],
+        materials: &HashMap<String, Material>,
+    ) {
+        let mut faces = bake.faces.iter();
+        for index in 0..self.positions.len() {
+            encoder.push_debug_group(&format!("probe {index}"));
+            for (face, (frustum, camera_bind_group)) in CubeFace::ALL.into_iter().zip(&mut faces) {
+                let view = self
+                    .cubes
+                    .face_view(index as u32, face, 0)
+                    .expect("every probe has its own cube");
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(&format!("Reflection Probe {face:?} Pass")),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &self.depth_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: profiler.as_deref_mut().and_then(|profiler| {
+                        profiler.render_pass_writes(&format!("probes:probe {index} {face:?}"))
+                    }),
+                    occlusion_query_set: None,
+                });
+
+                render_pass.set_pipeline(&self.pipeline);
+                render_pass.set_bind_group(0, camera_bind_group, &[]);
+                render_pass.set_bind_group(1, &bake.light_bind_group, &[]);
+                render_pass.set_bind_group(2, &self.empty_bind_group, &[]);
+                for blend_mode in [BlendMode::Opaque, BlendMode::Masked] {
+                    for model in models.iter().filter(|model| !model.skinned) {
+                        model.draw(&mut render_pass, frustum, materials, blend_mode);
+                    }
+                }
+
+                render_pass.set_pipeline(&bake.skybox_pipeline);
+                render_pass.set_bind_group(0, &bake.skybox_bind_group, &[]);
+                render_pass.set_bind_group(1, camera_bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+            encoder.pop_debug_group();
+        }
+    }
+}
+
+impl Renderer {
+    /// Uniforms for baking `probes` with the current lights, sky and fog. The sky is drawn
+    /// with `skybox_pipeline`, which has to match the probes' format.
+    pub(super) fn prepare_probe_bake(
+        &self,
+        probes: &ReflectionProbes,
+        skybox_pipeline: &RenderPipeline,
+    ) -> ProbeBake {
+        let light_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &probes.light_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.point_light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&self.ibl.irradiance.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.ibl.irradiance.sampler),
+                },
+            ],
+            label: Some("reflection_probe_light_bind_group"),
+        });
+
+        let camera_bind_group_layout = CameraUniform::create_bind_group_layout(&self.device);
+        let mut faces = Vec::new();
+        for position in &probes.positions {
+            for face in CubeFace::ALL {
+                let mut camera_uniform = self.camera_uniform;
+                camera_uniform.update_cube_face(*position, face as u32);
+                let camera_buffer =
+                    self.device
+                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Reflection Probe Camera Buffer"),
+                            contents: bytemuck::cast_slice(&[camera_uniform]),
+                            usage: wgpu::BufferUsages::UNIFORM,
+                        });
+                let frustum = Frustum::from_view_proj(&ShadowMapUniform::get_view_proj_for_face(
+                    *position,
+                    face as u32,
+                ));
+                faces.push((
+                    frustum,
+                    CameraUniform::create_bind_group(
+                        &self.device,
+                        &camera_bind_group_layout,
+                        &camera_buffer,
+                        &self.joint_palette.buffer,
+                    ),
+                ));
+            }
+        }
+        ProbeBake {
+            light_bind_group,
+            skybox_pipeline: skybox_pipeline.clone(),
+            skybox_bind_group: self.skybox_bind_group.clone(),
+            faces,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use log::debug;
+use tracing::debug_span;
+use wgpu::{CommandEncoder, Device, Queue};
+
+use super::gpu_profiler::GpuProfiler;
+
+/// Something passes read from or write to. Writing includes loading and blending into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    ShadowMaps,
+    LightClusters,
+    SceneDepth,
+    SceneNormals,
+    AmbientOcclusion,
+    ReflectionProbes,
+    // The surface texture being presented, the graph's output.
+    SceneColor,
+}
+
+pub struct PassContext<'a> {
+    pub encoder: &'a mut CommandEncoder,
+    pub profiler: &'a mut GpuProfiler,
+    name: &'a str,
+}
+
+type RecordFn<'a> = Box<dyn FnOnce(&mut PassContext) + 'a>;
+
+struct GraphPass<'a> {
+    name: String,
+    reads: Vec<Resource>,
+    writes: Vec<Resource>,
+    // Only records compute passes, so it may be submitted on its own ahead of the frame.
+    compute: bool,
+    record: RecordFn<'a>,
+}
+
+/// Passes of a single frame and the resources each one touches.
+///
+/// Passes run in the order they were added, which is also how hazards are resolved: a read
+/// sees every write added before it. Passes that nothing downstream of them reads are culled
+/// before recording. wgpu tracks the barriers between passes itself, so everything is recorded
+/// into one encoder, except that compute passes may be submitted on their own as soon as they
+/// are recorded, so the GPU works on them while the CPU records the rest of the frame.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    passes: Vec<GraphPass<'a>>,
+}
+
+impl PassContext<'_> {
+    /// The encoder along with timestamp writes labeled with the pass name, for passes that
+    /// record a single render pass.
+    pub fn encoder_with_render_writes(
+        &mut self,
+    ) -> (
+        &mut CommandEncoder,
+        Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        (self.encoder, self.profiler.render_pass_writes(self.name))
+    }
+
+    pub fn encoder_with_compute_writes(
+        &mut self,
+    ) -> (
+        &mut CommandEncoder,
+        Option<wgpu::ComputePassTimestampWrites<'_>>,
+    ) {
+        (self.encoder, self.profiler.compute_pass_writes(self.name))
+    }
+}
+
+impl<'a> RenderGraph<'a> {
+    const OUTPUT: Resource = Resource::SceneColor;
+
+    pub fn add_pass(
+        &mut self,
+        name: &str,
+        reads: &[Resource],
+        writes: &[Resource],
+        record: impl FnOnce(&mut PassContext) + 'a,
+    ) {
+        self.passes.push(GraphPass {
+            name: name.to_string(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            compute: false,
+            record: Box::new(record),
+        });
+    }
+
+    /// Adds a pass that only records compute passes. Given a queue, `execute` submits it on its
+    /// own unless it reads something an earlier pass in the frame encoder writes. Add these
+    /// before the passes they don't depend on, so they are submitted before those are recorded.
+    pub fn add_compute_pass(
+        &mut self,
+        name: &str,
+        reads: &[Resource],
+        writes: &[Resource],
+        record: impl FnOnce(&mut PassContext) + 'a,
+    ) {
+        self.add_pass(name, reads, writes, record);
+        if let Some(pass) = self.passes.last_mut() {
+            pass.compute = true;
+        }
+    }
+
+    /// Which passes contribute to the output, walking back from the end. A pass is live when
+    /// it writes the output or something a later live pass reads.
+    fn live_passes(&self) -> Vec<bool> {
+        let mut live = vec![false; self.passes.len()];
+        let mut needed = vec![Self::OUTPUT];
+        for (index, pass) in self.passes.iter().enumerate().rev() {
+            if pass.writes.iter().any(|resource| needed.contains(resource)) {
+                live[index] = true;
+                needed.extend(pass.reads.iter().copied());
+            }
+        }
+        live
+    }
+
+    /// Records every live pass into `encoder`. With `submit_compute`, compute passes are
+    /// recorded into their own encoders and submitted right away instead, so they run before
+    /// `encoder`, which the caller submits last.
+    pub fn execute(
+        self,
+        encoder: &mut CommandEncoder,
+        submit_compute: Option<(&Device, &Queue)>,
+        profiler: &mut GpuProfiler,
+    ) {
+        let live = self.live_passes();
+        // Written by passes already in `encoder`, which runs after the compute submissions.
+        let mut written = Vec::new();
+        for (pass, live) in self.passes.into_iter().zip(live) {
+            if !live {
+                debug!("Culled render pass {}", pass.name);
+                continue;
+            }
+            let _span = debug_span!("pass", name = %pass.name).entered();
+            match submit_compute {
+                Some((device, queue))
+                    if pass.compute && !pass.reads.iter().any(|read| written.contains(read)) =>
+                {
+                    let mut compute_encoder =
+                        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some(&format!("{} Encoder", pass.name)),
+                        });
+                    Self::record(pass, &mut compute_encoder, profiler);
+                    queue.submit(std::iter::once(compute_encoder.finish()));
+                }
+                _ => {
+                    written.extend(pass.writes.iter().copied());
+                    Self::record(pass, encoder, profiler);
+                }
+            }
+        }
+    }
+
+    fn record(pass: GraphPass, encoder: &mut CommandEncoder, profiler: &mut GpuProfiler) {
+        // Groups show up as the pass tree in GPU captures such as RenderDoc.
+        encoder.push_debug_group(&pass.name);
+        (pass.record)(&mut PassContext {
+            encoder,
+            profiler,
+            name: &pass.name,
+        });
+        encoder.pop_debug_group();
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use crate::game::camera_controller::CameraMode;
+use crate::game::replay::{InputFrame, Replay, ReplayMode};
+use crate::game::rng::GameRng;
+use crate::game::view_model::ViewModel;
+use crate::game::weapons::WeaponSystem;
+use crate::model::map_loader::MapLoader;
+
+use super::Renderer;
+
+impl Renderer {
+    /// Reloads the level and puts the player at the spawn, the state every replay starts in.
+    fn restart_level(&mut self) {
+        self.rerender();
+        self.set_camera_mode(CameraMode::Fps);
+        self.player.respawn(self.spawn_point);
+        self.view_model = ViewModel::default();
+        self.weapon_system = WeaponSystem::default();
+        self.player_controller.release_all();
+        self.tick_accumulator = Duration::ZERO;
+    }
+
+    pub(super) fn start_recording(&mut self) -> Result<String, String> {
+        if matches!(self.replay, ReplayMode::Playing { .. }) {
+            return Err("Can't record while a replay is playing".to_string());
+        }
+        self.restart_level();
+        self.rng = GameRng::new(rand::random());
+        let replay = Replay::new(
+            self.map_file.clone(),
+            self.player.save_state(),
+            self.rng.seed(),
+        );
+        self.replay = ReplayMode::Recording(replay);
+        Ok(format!("Recording {}", self.map_file))
+    }
+
+    pub(super) fn stop_recording(&mut self, path: &str) -> Result<String, String> {
+        let ReplayMode::Recording(replay) = std::mem::take(&mut self.replay) else {
+            return Err("Not recording".to_string());
+        };
+        replay
+            .write_to_file(path)
+            .map_err(|e| format!("Unable to write {path} {e}"))?;
+        Ok(format!(
+            "Wrote {} frames, {:.1}s to {path}",
+            replay.frames.len(),
+            replay.duration().as_secs_f32()
+        ))
+    }
+
+    pub(super) fn play_replay(&mut self, path: &str) -> Result<String, String> {
+        let replay = Replay::from_file(path).map_err(|e| format!("Unable to load {path} {e}"))?;
+        // Checked before anything changes, so a replay of a missing map leaves the game as is.
+        MapLoader::from_file(&replay.map_file)
+            .map_err(|e| format!("Unable to load {} {e}", replay.map_file))?;
+        self.map_file = replay.map_file.clone();
+        self.restart_level();
+        self.rng = GameRng::new(replay.seed);
+        self.player.restore_state(&replay.player);
+        let message = format!("Playing {path}, {:.1}s", replay.duration().as_secs_f32());
+        self.replay = ReplayMode::Playing { replay, frame: 0 };
+        Ok(message)
+    }
+
+    /// Records this frame's inputs, or swaps in the recorded ones during playback. Returns
+    /// the frame time the simulation should use, which is the recorded one in both cases
+    /// so recording and playback step through exactly the same times.
+    pub(super) fn replay_frame(&mut self, dt: Duration) -> Duration {
+        match &mut self.replay {
+            ReplayMode::Off => dt,
+            ReplayMode::Recording(replay) => {
+                let input = InputFrame::capture(dt, &self.player_controller);
+                replay.frames.push(input);
+                input.duration()
+            }
+            ReplayMode::Playing { replay, frame } => {
+                let Some(input) = replay.frames.get(*frame).copied() else {
+                    self.replay = ReplayMode::Off;
+                    self.player_controller.release_all();
+                    self.hud.show_message("Replay finished");
+                    return dt;
+                };
+                *frame += 1;
+                input.apply(&mut self.player_controller);
+                input.duration()
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use log::{error, info, warn};
+
+use crate::game::script_host::{ScriptCommand, ScriptHook, ScriptHost};
+
+use super::Renderer;
+
+impl Renderer {
+    const SCRIPT_PARTICLE_COUNT: u32 = 16;
+
+    pub(super) fn load_script(path: Option<&str>) -> Option<ScriptHost> {
+        let path = path?;
+        ScriptHost::load(path)
+            .inspect_err(|e| error!("Unable to load script {path}: {e}"))
+            .ok()
+    }
+
+    /// Calls a hook of the current level's script and applies whatever it asked for.
+    pub(super) fn run_script(&mut self, hook: ScriptHook) {
+        let Some(script_host) = &mut self.script_host else {
+            return;
+        };
+        let commands = script_host.call(hook, self.player.camera.position, self.lights.len());
+        for command in commands {
+            self.apply_script_command(command);
+        }
+    }
+
+    fn apply_script_command(&mut self, command: ScriptCommand) {
+        match command {
+            ScriptCommand::SpawnLight {
+                position,
+                intensity,
+            } => {
+                if let Err(e) = self.add_light(position, intensity) {
+                    warn!("Script spawn_light failed {e}");
+                }
+            }
+            ScriptCommand::MoveLight { id, position } => {
+                let Some(light) = self.lights.iter_mut().find(|light| light.id == id) else {
+                    warn!("Script moved unknown light {id}");
+                    return;
+                };
+                light.position = position;
+                self.shadow_baker.update_light_version_from_id(id);
+                self.upload_lights();
+            }
+            ScriptCommand::SetLightIntensity { id, intensity } => {
+                let Some(light) = self.lights.iter_mut().find(|light| light.id == id) else {
+                    warn!("Script changed unknown light {id}");
+                    return;
+                };
+                light.intensity = intensity;
+                self.upload_lights();
+            }
+            ScriptCommand::SpawnParticles { preset, position } => self.particle_system.add_burst(
+                position,
+                nalgebra::Vector3::y(),
+                preset,
+                Self::SCRIPT_PARTICLE_COUNT,
+            ),
+            // There's no audio output yet, so sounds only show up in the log.
+            ScriptCommand::PlaySound(name) => info!("Sound {name}"),
+            ScriptCommand::ShowMessage(text) => self.hud.show_message(text),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use image::{Rgba, RgbaImage};
+
+use super::hud_font::HudFont;
+use super::hud_pass::HudVertex;
+
+/// Signed distance field of the HUD font, baked at startup from its bitmap glyphs. Each texel
+/// holds how far it is from the nearest glyph edge, so the shader can cut a sharp antialiased
+/// edge at any text size instead of scaling pixels up.
+pub struct SdfFont;
+
+/// Text and solid rectangles in window pixels, collected for the HUD pass to draw.
+#[derive(Default)]
+pub struct TextBatch {
+    vertices: Vec<HudVertex>,
+}
+
+impl SdfFont {
+    // Atlas texels per font pixel.
+    const TEXELS_PER_PIXEL: u32 = 8;
+    // Clear font pixels around each glyph, so the field has room to fall off.
+    const PADDING: u32 = 1;
+    // Font pixels from the edge at which the field is fully inside or outside.
+    const SPREAD: f32 = 1.0;
+    const CELL_WIDTH: u32 = (HudFont::GLYPH_WIDTH + 2 * Self::PADDING) * Self::TEXELS_PER_PIXEL;
+    const CELL_HEIGHT: u32 = (HudFont::GLYPH_HEIGHT + 2 * Self::PADDING) * Self::TEXELS_PER_PIXEL;
+    const COLUMNS: u32 = 16;
+
+    /// White texels with the distance field in alpha, 0.5 on the glyph edge. The solid cell
+    /// is inside everywhere.
+    pub fn create_atlas() -> RgbaImage {
+        let rows = (HudFont::SOLID as u32 + 1).div_ceil(Self::COLUMNS);
+        RgbaImage::from_fn(
+            Self::COLUMNS * Self::CELL_WIDTH,
+            rows * Self::CELL_HEIGHT,
+            |x, y| {
+                let glyph = (y / Self::CELL_HEIGHT * Self::COLUMNS + x / Self::CELL_WIDTH) as usize;
+                let field = if glyph == HudFont::SOLID {
+                    1.0
+                } else {
+                    let position = [x % Self::CELL_WIDTH, y % Self::CELL_HEIGHT].map(|texel| {
+                        (texel as f32 + 0.5) / Self::TEXELS_PER_PIXEL as f32 - Self::PADDING as f32
+                    });
+                    0.5 + Self::signed_distance(glyph, position) / (2.0 * Self::SPREAD)
+                };
+                Rgba([255, 255, 255, (field.clamp(0.0, 1.0) * 255.0).round() as u8])
+            },
+        )
+    }
+
+    /// Distance in font pixels from `position` to the nearest edge of `glyph`, positive
+    /// inside it. Only pixels within the spread are searched.
+    fn signed_distance(glyph: usize, position: [f32; 2]) -> f32 {
+        let [px, py] = position;
+        let inside = HudFont::covered(glyph, px.floor() as i32, py.floor() as i32);
+        let reach = Self::SPREAD.ceil() as i32 + 1;
+        let mut nearest = Self::SPREAD;
+        for y in py.floor() as i32 - reach..=py.floor() as i32 + reach {
+            for x in px.floor() as i32 - reach..=px.floor() as i32 + reach {
+                if HudFont::covered(glyph, x, y) == inside {
+                    continue;
+                }
+                // Distance to the pixel's square, zero inside it.
+                let dx = ((px - x as f32 - 0.5).abs() - 0.5).max(0.0);
+                let dy = ((py - y as f32 - 0.5).abs() - 0.5).max(0.0);
+                nearest = nearest.min((dx * dx + dy * dy).sqrt());
+            }
+        }
+        if inside { nearest } else { -nearest }
+    }
+
+    /// Top left texel of an atlas cell.
+    fn cell_origin(glyph: usize) -> [f32; 2] {
+        let glyph = glyph as u32;
+        [
+            (glyph % Self::COLUMNS * Self::CELL_WIDTH) as f32,
+            (glyph / Self::COLUMNS * Self::CELL_HEIGHT) as f32,
+        ]
+    }
+
+    /// Window pixels per font pixel for glyphs `size` pixels tall.
+    fn pixel_size(size: f32) -> f32 {
+        size / HudFont::GLYPH_HEIGHT as f32
+    }
+}
+
+impl TextBatch {
+    pub fn vertices(&self) -> &[HudVertex] {
+        &self.vertices
+    }
+
+    /// Width of `text` with glyphs `size` pixels tall, from the left of the first glyph to the
+    /// right of the last.
+    pub fn text_width(size: f32, text: &str) -> f32 {
+        let count = text.chars().count() as u32;
+        (count * HudFont::ADVANCE).saturating_sub(HudFont::ADVANCE - HudFont::GLYPH_WIDTH) as f32
+            * SdfFont::pixel_size(size)
+    }
+
+    /// Distance between the tops of two lines of `size` pixel text.
+    pub fn line_height(size: f32) -> f32 {
+        (HudFont::GLYPH_HEIGHT + 2) as f32 * SdfFont::pixel_size(size)
+    }
+
+    /// Draws `text` with glyphs `size` pixels tall and the top left of the first at `position`.
+    pub fn draw_text(&mut self, position: [f32; 2], size: f32, color: [f32; 4], text: &str) {
+        let pixel = SdfFont::pixel_size(size);
+        let padding = SdfFont::PADDING as f32 * pixel;
+        let glyph_size = [
+            HudFont::GLYPH_WIDTH as f32 * pixel,
+            HudFont::GLYPH_HEIGHT as f32 * pixel,
+        ];
+        for (i, c) in text.chars().enumerate() {
+            let Some(glyph) = HudFont::glyph(c) else {
+                continue;
+            };
+            // The quad takes in the cell's padding, where the edge fades out.
+            let x = position[0] + (i as u32 * HudFont::ADVANCE) as f32 * pixel;
+            let [u, v] = SdfFont::cell_origin(glyph);
+            self.push_quad(
+                [
+                    x - padding,
+                    position[1] - padding,
+                    x + glyph_size[0] + padding,
+                    position[1] + glyph_size[1] + padding,
+                ],
+                [
+                    u,
+                    v,
+                    u + SdfFont::CELL_WIDTH as f32,
+                    v + SdfFont::CELL_HEIGHT as f32,
+                ],
+                color,
+            );
+        }
+    }
+
+    pub fn draw_rect(&mut self, rect: [f32; 4], color: [f32; 4]) {
+        // The middle of the solid cell, clear of its neighbours under filtering.
+        let [u, v] = SdfFont::cell_origin(HudFont::SOLID);
+        let [cu, cv] = [
+            u + SdfFont::CELL_WIDTH as f32 / 2.0,
+            v + SdfFont::CELL_HEIGHT as f32 / 2.0,
+        ];
+        self.push_quad(rect, [cu - 1.0, cv - 1.0, cu + 1.0, cv + 1.0], color);
+    }
+
+    /// `rect` and `texels` are min x, min y, max x, max y.
+    fn push_quad(&mut self, rect: [f32; 4], texels: [f32; 4], color: [f32; 4]) {
+        let [x0, y0, x1, y1] = rect;
+        let [u0, v0, u1, v1] = texels;
+        let corner = |x, y, u, v| HudVertex {
+            position: [x, y],
+            texel: [u, v],
+            color,
+        };
+        self.vertices.extend_from_slice(&[
+            corner(x0, y0, u0, v0),
+            corner(x0, y1, u0, v1),
+            corner(x1, y1, u1, v1),
+            corner(x0, y0, u0, v0),
+            corner(x1, y1, u1, v1),
+            corner(x1, y0, u1, v0),
+        ]);
+    }
+}
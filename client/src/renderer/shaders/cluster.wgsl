@@ -0,0 +1,96 @@
+struct CameraUniform {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+
+struct LightUniform {
+    position: vec3<f32>,
+    radius: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    depth_bias: f32,
+    normal_bias: f32,
+    pcf_taps: u32,
+}
+
+struct Lights {
+    lights: array<LightUniform, 32>,
+    count: u32,
+}
+
+struct ClusterParams {
+    screen_size: vec2<f32>,
+    near: f32,
+    far: f32,
+    // xyz is the grid size, w is the maximum number of lights per cluster.
+    grid: vec4<u32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<uniform> point_lights: Lights;
+@group(1) @binding(1)
+var<uniform> cluster: ClusterParams;
+@group(1) @binding(2)
+var<storage, read_write> light_grid: array<vec2<u32>>;
+@group(1) @binding(3)
+var<storage, read_write> light_indices: array<u32>;
+
+// View space direction through an NDC position, scaled so that z = -1.
+fn view_ray(ndc: vec2<f32>) -> vec3<f32> {
+    let p = camera.inv_proj * vec4<f32>(ndc, 1.0, 1.0);
+    let v = p.xyz / p.w;
+    return v / -v.z;
+}
+
+fn slice_depth(slice: u32) -> f32 {
+    return cluster.near * pow(cluster.far / cluster.near, f32(slice) / f32(cluster.grid.z));
+}
+
+@compute @workgroup_size(16, 9, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= cluster.grid.xyz)) {
+        return;
+    }
+    let grid = vec2<f32>(cluster.grid.xy);
+    let cluster_index = id.x + id.y * cluster.grid.x + id.z * cluster.grid.x * cluster.grid.y;
+
+    // Tiles are numbered from the top left of the screen like fragment coordinates.
+    let ndc_min = vec2<f32>(f32(id.x) / grid.x * 2.0 - 1.0, 1.0 - f32(id.y + 1u) / grid.y * 2.0);
+    let ndc_max = vec2<f32>(f32(id.x + 1u) / grid.x * 2.0 - 1.0, 1.0 - f32(id.y) / grid.y * 2.0);
+    let rays = array<vec3<f32>, 4>(
+        view_ray(ndc_min),
+        view_ray(vec2<f32>(ndc_max.x, ndc_min.y)),
+        view_ray(vec2<f32>(ndc_min.x, ndc_max.y)),
+        view_ray(ndc_max),
+    );
+    let near_depth = slice_depth(id.z);
+    let far_depth = slice_depth(id.z + 1u);
+    var aabb_min = rays[0] * near_depth;
+    var aabb_max = aabb_min;
+    for (var i = 0u; i < 4u; i++) {
+        let near_point = rays[i] * near_depth;
+        let far_point = rays[i] * far_depth;
+        aabb_min = min(aabb_min, min(near_point, far_point));
+        aabb_max = max(aabb_max, max(near_point, far_point));
+    }
+
+    let offset = cluster_index * cluster.grid.w;
+    var count = 0u;
+    for (var i = 0u; i < point_lights.count; i++) {
+        let light = point_lights.lights[i];
+        let center = (camera.view * vec4<f32>(light.position, 1.0)).xyz;
+        let closest = clamp(center, aabb_min, aabb_max);
+        let delta = center - closest;
+        if (dot(delta, delta) <= light.radius * light.radius && count < cluster.grid.w) {
+            light_indices[offset + count] = i;
+            count++;
+        }
+    }
+    light_grid[cluster_index] = vec2<u32>(offset, count);
+}
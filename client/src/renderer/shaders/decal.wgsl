@@ -0,0 +1,111 @@
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var scene_depth: texture_depth_2d;
+@group(1) @binding(1)
+var t_atlas: texture_2d<f32>;
+@group(1) @binding(2)
+var s_atlas: sampler;
+
+struct InstanceInput {
+    @location(0) model_matrix_0: vec4<f32>,
+    @location(1) model_matrix_1: vec4<f32>,
+    @location(2) model_matrix_2: vec4<f32>,
+    @location(3) model_matrix_3: vec4<f32>,
+    @location(4) inv_model_matrix_0: vec4<f32>,
+    @location(5) inv_model_matrix_1: vec4<f32>,
+    @location(6) inv_model_matrix_2: vec4<f32>,
+    @location(7) inv_model_matrix_3: vec4<f32>,
+    @location(8) atlas_index: u32,
+    @location(9) opacity: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) inv_model_0: vec4<f32>,
+    @location(1) inv_model_1: vec4<f32>,
+    @location(2) inv_model_2: vec4<f32>,
+    @location(3) inv_model_3: vec4<f32>,
+    @location(4) normal: vec3<f32>,
+    @location(5) @interpolate(flat) atlas_index: u32,
+    @location(6) opacity: f32,
+}
+
+const ATLAS_COLUMNS: f32 = 2.0;
+// Surfaces turned further than this from the decal normal don't receive it.
+const MIN_FACING: f32 = 0.3;
+
+// Corners of a unit cube centered on the origin, indexed by the bits of the corner id.
+fn cube_corner(id: u32) -> vec3<f32> {
+    return vec3<f32>(f32(id & 1u), f32((id >> 1u) & 1u), f32((id >> 2u) & 1u)) - 0.5;
+}
+
+const CUBE_INDICES = array<u32, 36>(
+    0u, 2u, 1u, 1u, 2u, 3u,
+    4u, 5u, 6u, 5u, 7u, 6u,
+    0u, 1u, 4u, 1u, 5u, 4u,
+    2u, 6u, 3u, 3u, 6u, 7u,
+    0u, 4u, 2u, 2u, 4u, 6u,
+    1u, 3u, 5u, 3u, 7u, 5u,
+);
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) id: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_mat = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_mat * vec4<f32>(cube_corner(CUBE_INDICES[id]), 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.inv_model_0 = instance.inv_model_matrix_0;
+    out.inv_model_1 = instance.inv_model_matrix_1;
+    out.inv_model_2 = instance.inv_model_matrix_2;
+    out.inv_model_3 = instance.inv_model_matrix_3;
+    out.normal = normalize(instance.model_matrix_1.xyz);
+    out.atlas_index = instance.atlas_index;
+    out.opacity = instance.opacity;
+    return out;
+}
+
+fn world_from_depth(frag_coord: vec2<f32>, depth: f32) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(scene_depth));
+    let ndc = vec2<f32>(frag_coord.x / size.x * 2.0 - 1.0, 1.0 - frag_coord.y / size.y * 2.0);
+    let view_position = camera.inv_proj * vec4<f32>(ndc, depth, 1.0);
+    return (camera.inv_view * vec4<f32>(view_position.xyz / view_position.w, 1.0)).xyz;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let depth = textureLoad(scene_depth, vec2<i32>(in.clip_position.xy), 0);
+    let world_position = world_from_depth(in.clip_position.xy, depth);
+    // Derivatives must be taken before anything is discarded.
+    let surface_normal = normalize(cross(dpdx(world_position), dpdy(world_position)));
+
+    let inv_model_mat = mat4x4<f32>(in.inv_model_0, in.inv_model_1, in.inv_model_2, in.inv_model_3);
+    let local = (inv_model_mat * vec4<f32>(world_position, 1.0)).xyz;
+    if (any(abs(local) > vec3<f32>(0.5)) || abs(dot(surface_normal, in.normal)) < MIN_FACING) {
+        discard;
+    }
+
+    let uv = vec2<f32>((local.x + 0.5 + f32(in.atlas_index)) / ATLAS_COLUMNS, local.z + 0.5);
+    let color = textureSampleLevel(t_atlas, s_atlas, uv, 0.0);
+    // Fade towards the ends of the projection so it doesn't cut off sharply on curved surfaces.
+    let edge = 1.0 - smoothstep(0.35, 0.5, abs(local.y));
+    return vec4<f32>(color.rgb, color.a * in.opacity * edge);
+}
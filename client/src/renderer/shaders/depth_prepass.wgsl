@@ -0,0 +1,92 @@
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(0) @binding(1)
+var<storage, read> joint_matrices: array<mat4x4<f32>>;
+
+const NO_SKIN: u32 = 0xffffffffu;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+    @location(13) joints: vec4<u32>,
+    @location(14) weights: vec4<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) joint_offset: u32,
+    @location(15) tint: vec4<f32>,
+}
+
+struct VertexOutput {
+    // Must match shader.wgsl exactly, the main pass tests against this depth with LessEqual.
+    @builtin(position) @invariant clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput
+) -> VertexOutput {
+    var model_mat = mat4x4<f32> (
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3
+    );
+    if instance.joint_offset != NO_SKIN {
+        let joints = model.joints + vec4<u32>(instance.joint_offset);
+        let skin_mat = joint_matrices[joints.x] * model.weights.x
+            + joint_matrices[joints.y] * model.weights.y
+            + joint_matrices[joints.z] * model.weights.z
+            + joint_matrices[joints.w] * model.weights.w;
+        model_mat = model_mat * skin_mat;
+    }
+
+    let world_position = model_mat * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.tex_coords = model.tex_coords;
+    out.tint = instance.tint;
+    return out;
+}
+
+// Only depth is written, there are no color targets.
+@fragment
+fn fs_main(in: VertexOutput) {}
+
+// Masked materials, bound only for the pipeline that draws them.
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+// Matches shader.wgsl, so both passes cut out the same holes.
+const ALPHA_CUTOFF: f32 = 0.5;
+
+@fragment
+fn fs_masked(in: VertexOutput) {
+    if (textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint).a < ALPHA_CUTOFF {
+        discard;
+    }
+}
@@ -0,0 +1,39 @@
+@group(0) @binding(0)
+var<uniform> projection: mat4x4<f32>;
+@group(0) @binding(1)
+var font_atlas: texture_2d<f32>;
+@group(0) @binding(2)
+var font_sampler: sampler;
+
+struct VertexInput {
+    // Pixels from the top left of the window.
+    @location(0) position: vec2<f32>,
+    // Atlas texels, the distance field is sampled between them.
+    @location(1) texel: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) texel: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.texel = in.texel;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.texel / vec2<f32>(textureDimensions(font_atlas));
+    // 0.5 is the glyph edge. Smoothing over about a window pixel keeps it sharp at any size.
+    let distance = textureSample(font_atlas, font_sampler, uv).a;
+    let smoothing = max(fwidth(distance) * 0.5, 0.001);
+    let coverage = smoothstep(0.5 - smoothing, 0.5 + smoothing, distance);
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,120 @@
+struct IblParams {
+    // 0 convolves irradiance, 1 prefilters specular reflections.
+    mode: u32,
+    roughness: f32,
+}
+
+@group(0) @binding(0)
+var environment: texture_cube<f32>;
+@group(0) @binding(1)
+var environment_sampler: sampler;
+@group(0) @binding(2)
+var output: texture_storage_2d_array<rgba16float, write>;
+@group(0) @binding(3)
+var<uniform> params: IblParams;
+
+const PI: f32 = 3.14159265;
+const MODE_IRRADIANCE: u32 = 0u;
+// Steps around and up the hemisphere when convolving irradiance.
+const IRRADIANCE_STEPS: u32 = 32u;
+const SPECULAR_SAMPLES: u32 = 64u;
+
+// World direction through texel `uv` of cube face `face`, in the usual cube map layout.
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let st = uv * 2.0 - 1.0;
+    switch face {
+        case 0u: { return normalize(vec3<f32>(1.0, -st.y, -st.x)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -st.y, st.x)); }
+        case 2u: { return normalize(vec3<f32>(st.x, 1.0, st.y)); }
+        case 3u: { return normalize(vec3<f32>(st.x, -1.0, -st.y)); }
+        case 4u: { return normalize(vec3<f32>(st.x, -st.y, 1.0)); }
+        default: { return normalize(vec3<f32>(-st.x, -st.y, -1.0)); }
+    }
+}
+
+// Tangent space around `normal`, as columns.
+fn tangent_frame(normal: vec3<f32>) -> mat3x3<f32> {
+    var helper = vec3<f32>(0.0, 1.0, 0.0);
+    if abs(normal.y) > 0.999 {
+        helper = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(helper, normal));
+    let bitangent = cross(normal, tangent);
+    return mat3x3<f32>(tangent, bitangent, normal);
+}
+
+// Cosine weighted average of the light arriving over the hemisphere around `normal`.
+fn irradiance(normal: vec3<f32>) -> vec3<f32> {
+    let frame = tangent_frame(normal);
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < IRRADIANCE_STEPS; i++) {
+        let phi = 2.0 * PI * (f32(i) + 0.5) / f32(IRRADIANCE_STEPS);
+        for (var j = 0u; j < IRRADIANCE_STEPS / 4u; j++) {
+            let theta = 0.5 * PI * (f32(j) + 0.5) / f32(IRRADIANCE_STEPS / 4u);
+            let local = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let sample_weight = cos(theta) * sin(theta);
+            sum += textureSampleLevel(environment, environment_sampler, frame * local, 0.0).rgb * sample_weight;
+            weight += sample_weight;
+        }
+    }
+    return sum / weight;
+}
+
+fn radical_inverse(bits_in: u32) -> f32 {
+    var bits = bits_in;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+// GGX distributed half vector around +z for the `i`th Hammersley point.
+fn importance_sample_ggx(i: u32, roughness: f32) -> vec3<f32> {
+    let xi = vec2<f32>(f32(i) / f32(SPECULAR_SAMPLES), radical_inverse(i));
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+}
+
+// Reflection seen along `normal` off a surface of the given roughness, assuming the view
+// direction matches the normal.
+fn prefilter(normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    if roughness <= 0.0 {
+        return textureSampleLevel(environment, environment_sampler, normal, 0.0).rgb;
+    }
+    let frame = tangent_frame(normal);
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < SPECULAR_SAMPLES; i++) {
+        let half_dir = frame * importance_sample_ggx(i, roughness);
+        let light_dir = normalize(2.0 * dot(normal, half_dir) * half_dir - normal);
+        let n_dot_l = dot(normal, light_dir);
+        if n_dot_l > 0.0 {
+            sum += textureSampleLevel(environment, environment_sampler, light_dir, 0.0).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    return sum / max(weight, 0.0001);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    let uv = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(size);
+    let direction = face_direction(id.z, uv);
+    var color: vec3<f32>;
+    if params.mode == MODE_IRRADIANCE {
+        color = irradiance(direction);
+    } else {
+        color = prefilter(direction, params.roughness);
+    }
+    textureStore(output, vec2<u32>(id.xy), id.z, vec4<f32>(color, 1.0));
+}
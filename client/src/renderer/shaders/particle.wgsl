@@ -0,0 +1,70 @@
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var scene_depth: texture_depth_2d;
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) size: f32,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) view_distance: f32,
+}
+
+// How far in front of opaque geometry a particle starts fading out.
+const SOFTNESS: f32 = 0.2;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) id: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let uv = vec2<f32>(f32(id & 1u), f32((id >> 1u) & 1u)) * 2.0 - 1.0;
+    // Billboard against the camera using the rows of the view matrix.
+    let right = vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]);
+    let up = vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]);
+    let world_position = instance.position + (right * uv.x + up * uv.y) * instance.size;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.color = instance.color;
+    out.uv = uv;
+    out.view_distance = -(camera.view * vec4<f32>(world_position, 1.0)).z;
+    return out;
+}
+
+fn view_distance_from_depth(frag_coord: vec2<f32>, depth: f32) -> f32 {
+    let size = vec2<f32>(textureDimensions(scene_depth));
+    let ndc = vec2<f32>(frag_coord.x / size.x * 2.0 - 1.0, 1.0 - frag_coord.y / size.y * 2.0);
+    let view_position = camera.inv_proj * vec4<f32>(ndc, depth, 1.0);
+    return -view_position.z / view_position.w;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let falloff = 1.0 - dot(in.uv, in.uv);
+    if (falloff <= 0.0) {
+        discard;
+    }
+    let depth = textureLoad(scene_depth, vec2<i32>(in.clip_position.xy), 0);
+    let scene_distance = view_distance_from_depth(in.clip_position.xy, depth);
+    let soft = clamp((scene_distance - in.view_distance) / SOFTNESS, 0.0, 1.0);
+    if (soft <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * falloff * soft);
+}
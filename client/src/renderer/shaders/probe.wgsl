@@ -0,0 +1,141 @@
+struct CameraUniform {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+    fog_color: vec3<f32>,
+    fog_density: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_mode: u32,
+}
+
+struct LightUniform {
+    position: vec3<f32>,
+    radius: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    depth_bias: f32,
+    normal_bias: f32,
+    pcf_taps: u32,
+}
+
+struct Lights {
+    lights: array<LightUniform, 32>,
+    count: u32,
+    ambient: vec3<f32>,
+    environment: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<uniform> point_lights: Lights;
+@group(1) @binding(1)
+var irradiance_map: texture_cube<f32>;
+@group(1) @binding(2)
+var ibl_sampler: sampler;
+
+// Group 2 is left empty so materials stay at group 3 like in shader.wgsl.
+@group(3) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(3) @binding(1)
+var s_diffuse: sampler;
+
+const ALPHA_CUTOFF: f32 = 0.5;
+
+const FOG_LINEAR: u32 = 1u;
+const FOG_EXPONENTIAL: u32 = 2u;
+
+// How much of the fog color covers something `distance` away from the camera.
+fn fog_amount(distance: f32) -> f32 {
+    switch camera.fog_mode {
+        case FOG_LINEAR: {
+            return clamp((distance - camera.fog_start) / (camera.fog_end - camera.fog_start), 0.0, 1.0);
+        }
+        case FOG_EXPONENTIAL: {
+            return 1.0 - exp(-camera.fog_density * distance);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+// Probes only see static geometry, so there is no skinning.
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+    @location(15) tint: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tint: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput
+) -> VertexOutput {
+    let model_mat = mat4x4<f32> (
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3
+    );
+    let normal_mat = mat3x3<f32> (
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2
+    );
+    let world_position = model_mat * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.tex_coords = model.tex_coords;
+    out.world_position = world_position.xyz;
+    out.normal = normalize(normal_mat * model.normal);
+    out.tint = instance.tint;
+    return out;
+}
+
+// Diffuse only and without shadows, a reflection is too blurry and small to tell.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let albedo = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+    let normal = normalize(in.normal);
+    let environment = textureSample(irradiance_map, ibl_sampler, normal).rgb * point_lights.environment;
+    if albedo.a < ALPHA_CUTOFF {
+        discard;
+    }
+
+    var color = point_lights.ambient + environment;
+    for (var i = 0u; i < point_lights.count; i++) {
+        let light = point_lights.lights[i];
+        let to_light = light.position - in.world_position;
+        let light_dist = length(to_light);
+        let diffuse = max(dot(normal, to_light / light_dist), 0.0);
+        color += light.color * diffuse * light.intensity / (light_dist * light_dist);
+    }
+
+    let fog = fog_amount(distance(in.world_position, camera.view_pos.xyz));
+    return vec4<f32>(mix(albedo.rgb * color, camera.fog_color, fog), 1.0);
+}
@@ -4,32 +4,138 @@ struct CameraUniform {
     view_proj: mat4x4<f32>,
     inv_proj: mat4x4<f32>,
     inv_view: mat4x4<f32>,
+    fog_color: vec3<f32>,
+    fog_density: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_mode: u32,
 }
 
 struct LightUniform {
     position: vec3<f32>,
-    _padding: f32,
+    radius: f32,
     color: vec3<f32>,
     intensity: f32,
+    depth_bias: f32,
+    normal_bias: f32,
+    pcf_taps: u32,
 }
 
 struct Lights {
     lights: array<LightUniform, 32>,
     count: u32,
+    ambient: vec3<f32>,
+    environment: f32,
 }
 
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
+@group(0) @binding(1)
+var<storage, read> joint_matrices: array<mat4x4<f32>>;
+
+const NO_SKIN: u32 = 0xffffffffu;
+
+// Set per pipeline, matching BlendMode in model/mod.rs.
+override BLEND_MODE: u32 = 0u;
+const BLEND_MASKED: u32 = 1u;
+const BLEND_TRANSLUCENT: u32 = 2u;
+const ALPHA_CUTOFF: f32 = 0.5;
+
+const FOG_LINEAR: u32 = 1u;
+const FOG_EXPONENTIAL: u32 = 2u;
+
+// How much of the fog color covers something `distance` away from the camera.
+fn fog_amount(distance: f32) -> f32 {
+    switch camera.fog_mode {
+        case FOG_LINEAR: {
+            return clamp((distance - camera.fog_start) / (camera.fog_end - camera.fog_start), 0.0, 1.0);
+        }
+        case FOG_EXPONENTIAL: {
+            return 1.0 - exp(-camera.fog_density * distance);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+struct ClusterParams {
+    screen_size: vec2<f32>,
+    near: f32,
+    far: f32,
+    grid: vec4<u32>,
+}
 
 @group(1) @binding(0)
 var<uniform> point_lights: Lights;
+@group(1) @binding(1)
+var<uniform> cluster: ClusterParams;
+@group(1) @binding(2)
+var<storage, read> light_grid: array<vec2<u32>>;
+@group(1) @binding(3)
+var<storage, read> light_indices: array<u32>;
+@group(1) @binding(4)
+var ambient_occlusion: texture_2d<f32>;
+@group(1) @binding(5)
+var irradiance_map: texture_cube<f32>;
+@group(1) @binding(6)
+var prefiltered_map: texture_cube<f32>;
+@group(1) @binding(7)
+var ibl_sampler: sampler;
+// Six layers per probe in the usual cube face order, see ReflectionProbes.
+@group(1) @binding(8)
+var probe_faces: texture_2d_array<f32>;
+
+// Matches ProbeUniform in renderer/reflection_probes.rs.
+struct ReflectionProbes {
+    positions: array<vec4<f32>, 16>,
+    count: u32,
+}
+
+@group(1) @binding(9)
+var<uniform> reflection_probes: ReflectionProbes;
+// Every map material texture, one per layer, see TextureArray.
+@group(1) @binding(10)
+var level_textures: texture_2d_array<f32>;
+@group(1) @binding(11)
+var level_sampler: sampler;
+
+// Matches the roughness of the prefiltered mip the main pass reflects, see Ibl in cube_texture.rs.
+const SURFACE_ROUGHNESS: f32 = 0.5;
+// Reflectance of a dielectric seen head on.
+const BASE_REFLECTANCE: f32 = 0.04;
+
+// Light from the skybox reaching a surface with `normal` seen along `view_dir`, both in world space.
+fn environment_light(normal: vec3<f32>, view_dir: vec3<f32>) -> vec3<f32> {
+    let diffuse = textureSample(irradiance_map, ibl_sampler, normal).rgb;
+    let max_mip = f32(textureNumLevels(prefiltered_map) - 1u);
+    let reflected = reflect(-view_dir, normal);
+    let specular = textureSampleLevel(prefiltered_map, ibl_sampler, reflected, SURFACE_ROUGHNESS * max_mip).rgb;
+    let fresnel = BASE_REFLECTANCE + (1.0 - BASE_REFLECTANCE) * pow(1.0 - max(dot(normal, view_dir), 0.0), 5.0);
+    return (diffuse + specular * fresnel) * point_lights.environment;
+}
+
+// Finds the froxel a fragment belongs to, matching the slicing in cluster.wgsl.
+fn cluster_index(frag_coord: vec2<f32>, view_depth: f32) -> u32 {
+    let tile = vec2<u32>(clamp(
+        frag_coord / cluster.screen_size * vec2<f32>(cluster.grid.xy),
+        vec2<f32>(0.0),
+        vec2<f32>(cluster.grid.xy - 1u),
+    ));
+    let slice_f = log(max(view_depth, cluster.near) / cluster.near)
+        / log(cluster.far / cluster.near) * f32(cluster.grid.z);
+    let slice = min(u32(slice_f), cluster.grid.z - 1u);
+    return tile.x + tile.y * cluster.grid.x + slice * cluster.grid.x * cluster.grid.y;
+}
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
     @location(2) normal: vec3<f32>,
     @location(3) tangent: vec3<f32>,
-    @location(4) bitangent: vec3<f32>
+    @location(4) bitangent: vec3<f32>,
+    @location(13) joints: vec4<u32>,
+    @location(14) weights: vec4<f32>,
 };
 
 
@@ -41,10 +147,13 @@ struct InstanceInput {
     @location(9) normal_matrix_0: vec3<f32>,
     @location(10) normal_matrix_1: vec3<f32>,
     @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) joint_offset: u32,
+    @location(15) tint: vec4<f32>,
 }
 
 struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
+    // Must match prepass.wgsl exactly, see there.
+    @builtin(position) @invariant clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) tangent_position: vec3<f32>,
     @location(2) tangent_view_position: vec3<f32>,
@@ -52,6 +161,7 @@ struct VertexOutput {
     @location(4) B: vec3<f32>,
     @location(5) N: vec3<f32>,
     @location(6) world_position: vec4<f32>,
+    @location(7) tint: vec4<f32>,
 };
 
 @vertex
@@ -59,17 +169,26 @@ fn vs_main(
     model: VertexInput,
     instance: InstanceInput
 ) -> VertexOutput {
-    let model_mat = mat4x4<f32> (
+    var model_mat = mat4x4<f32> (
         instance.model_matrix_0,
         instance.model_matrix_1,
         instance.model_matrix_2,
         instance.model_matrix_3
     );
-    let normal_mat = mat3x3<f32> (
+    var normal_mat = mat3x3<f32> (
         instance.normal_matrix_0,
         instance.normal_matrix_1,
         instance.normal_matrix_2
     );
+    if instance.joint_offset != NO_SKIN {
+        let joints = model.joints + vec4<u32>(instance.joint_offset);
+        let skin_mat = joint_matrices[joints.x] * model.weights.x
+            + joint_matrices[joints.y] * model.weights.y
+            + joint_matrices[joints.z] * model.weights.z
+            + joint_matrices[joints.w] * model.weights.w;
+        model_mat = model_mat * skin_mat;
+        normal_mat = normal_mat * mat3x3<f32>(skin_mat[0].xyz, skin_mat[1].xyz, skin_mat[2].xyz);
+    }
     let world_normal = normalize(normal_mat * model.normal);
     let world_tangent = normalize(normal_mat * model.tangent);
     let world_bitangent = normalize(normal_mat * model.bitangent);
@@ -90,13 +209,75 @@ fn vs_main(
     out.tangent_position = tangent_matrix * world_position.xyz;
     out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
     out.world_position = world_position;
+    out.tint = instance.tint;
     return out;
 }
 
+// Matches ShadowFace in renderer/shadow_atlas.rs.
+struct ShadowFace {
+    offset: vec2<f32>,
+    scale: f32,
+    resolution: f32,
+}
+
 @group(2) @binding(0)
-var shadow_maps: texture_depth_cube_array;
+var shadow_atlas: texture_depth_2d;
 @group(2) @binding(1)
 var shadow_sampler: sampler_comparison;
+// Six faces per light in the usual cube face order.
+@group(2) @binding(2)
+var<storage, read> shadow_faces: array<ShadowFace>;
+
+// Which cube face `direction` points at in z and where on it in xy, from 0 to 1.
+fn cube_face_coords(direction: vec3<f32>) -> vec3<f32> {
+    let a = abs(direction);
+    var face = 0u;
+    var uv = vec2<f32>(0.0);
+    var major = 1.0;
+    if a.x >= a.y && a.x >= a.z {
+        major = a.x;
+        if direction.x > 0.0 {
+            face = 0u;
+            uv = vec2<f32>(-direction.z, -direction.y);
+        } else {
+            face = 1u;
+            uv = vec2<f32>(direction.z, -direction.y);
+        }
+    } else if a.y >= a.z {
+        major = a.y;
+        if direction.y > 0.0 {
+            face = 2u;
+            uv = vec2<f32>(direction.x, direction.z);
+        } else {
+            face = 3u;
+            uv = vec2<f32>(direction.x, -direction.z);
+        }
+    } else {
+        major = a.z;
+        if direction.z > 0.0 {
+            face = 4u;
+            uv = vec2<f32>(direction.x, -direction.y);
+        } else {
+            face = 5u;
+            uv = vec2<f32>(-direction.x, -direction.y);
+        }
+    }
+    return vec3<f32>(uv / major * 0.5 + 0.5, f32(face));
+}
+
+fn sample_shadow(direction: vec3<f32>, light: u32, depth: f32) -> f32 {
+    let face_coords = cube_face_coords(direction);
+    let tile = shadow_faces[light * 6u + u32(face_coords.z)];
+    // Filtering stays inside the tile rather than reading its neighbours.
+    let border = 0.5 / tile.resolution;
+    let coords = clamp(face_coords.xy, vec2<f32>(border), vec2<f32>(1.0 - border));
+    return textureSampleCompareLevel(
+        shadow_atlas,
+        shadow_sampler,
+        tile.offset + coords * tile.scale,
+        depth
+    );
+}
 
 @group(3) @binding(0)
 var t_diffuse: texture_2d<f32>;
@@ -107,10 +288,93 @@ var t_normal: texture_2d<f32>;
 @group(3) @binding(3)
 var s_normal: sampler;
 
+// Matches MaterialUniform in model/mod.rs.
+struct MaterialParams {
+    reflectivity: f32,
+    diffuse_layer: u32,
+    normal_layer: u32,
+}
+
+@group(3) @binding(4)
+var<uniform> material: MaterialParams;
+
+const NO_LAYER: u32 = 0xffffffffu;
+
+// Map materials read their layer of the level textures, anything else its own texture.
+fn material_sample(own: texture_2d<f32>, own_sampler: sampler, layer: u32, uv: vec2<f32>) -> vec4<f32> {
+    if layer == NO_LAYER {
+        return textureSample(own, own_sampler, uv);
+    }
+    return textureSample(level_textures, level_sampler, uv, layer);
+}
+
+// What the probe nearest to `position` sees along `direction`, or the sky when the map has none.
+fn probe_reflection(position: vec3<f32>, direction: vec3<f32>) -> vec3<f32> {
+    if reflection_probes.count == 0u {
+        return textureSampleLevel(prefiltered_map, ibl_sampler, direction, 0.0).rgb * point_lights.environment;
+    }
+    var nearest = 0u;
+    var nearest_distance = distance(position, reflection_probes.positions[0].xyz);
+    for (var i = 1u; i < reflection_probes.count; i++) {
+        let probe_distance = distance(position, reflection_probes.positions[i].xyz);
+        if probe_distance < nearest_distance {
+            nearest = i;
+            nearest_distance = probe_distance;
+        }
+    }
+    let face_coords = cube_face_coords(direction);
+    // Filtering stays on the face rather than bleeding into the next layer's edge.
+    let border = 0.5 / f32(textureDimensions(probe_faces).x);
+    let coords = clamp(face_coords.xy, vec2<f32>(border), vec2<f32>(1.0 - border));
+    return textureSampleLevel(probe_faces, ibl_sampler, coords, nearest * 6u + u32(face_coords.z), 0.0).rgb;
+}
+
+// Shadow map depths are distances from the light divided by the far plane.
+const SHADOW_FAR: f32 = 200.0;
+// PCF tap spacing in shadow map texels.
+const PCF_SPREAD: f32 = 1.5;
+
+// Share of light `i` reaching `position`, averaged over the light's PCF kernel.
+fn shadow_factor(i: u32, position: vec3<f32>, surface_normal: vec3<f32>) -> f32 {
+    let light = point_lights.lights[i];
+    let to_surface = position - light.position;
+    let cos_angle = max(dot(surface_normal, -normalize(to_surface)), 0.0);
+    // Looking up from slightly off the surface keeps it from shadowing itself.
+    let from_light = position + surface_normal * light.normal_bias * (1.0 - cos_angle)
+        - light.position;
+    let light_distance = length(from_light);
+    let direction = from_light / light_distance;
+    let bias = light.depth_bias * mix(4.0, 1.0, cos_angle) * light_distance;
+    let depth = (light_distance - bias) / SHADOW_FAR;
+
+    // Taps spread over a grid on the plane facing the light.
+    let side = u32(round(sqrt(f32(max(light.pcf_taps, 1u)))));
+    let helper = select(
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(1.0, 0.0, 0.0),
+        abs(direction.y) > 0.99
+    );
+    let tangent = normalize(cross(helper, direction));
+    let bitangent = cross(direction, tangent);
+    let texel = 2.0 / shadow_faces[i * 6u].resolution;
+    let spread = texel * PCF_SPREAD;
+    let center = (f32(side) - 1.0) / 2.0;
+    var lit = 0.0;
+    for (var y = 0u; y < side; y++) {
+        for (var x = 0u; x < side; x++) {
+            let offset = (vec2<f32>(f32(x), f32(y)) - center) * spread;
+            let sample_dir = direction + tangent * offset.x + bitangent * offset.y;
+            lit += sample_shadow(sample_dir, i, depth);
+        }
+    }
+    return lit / f32(side * side);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var color = vec3<f32>(0.0);
-    let normal = textureSample(t_normal, s_normal, in.tex_coords);
+    let texture_color = material_sample(t_diffuse, s_diffuse, material.diffuse_layer, in.tex_coords) * in.tint;
+    let normal = material_sample(t_normal, s_normal, material.normal_layer, in.tex_coords);
     let tangent_normal = normal.xyz * 2.0 - 1.0;
     let view_dir = normalize(in.tangent_view_position - in.tangent_position);
     let tangent_matrix = transpose(mat3x3<f32>(
@@ -118,8 +382,11 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         in.B,
         in.N
     ));
-    
-    for (var i = 0u; i < point_lights.count; i++) {
+
+    let view_depth = -(camera.view * in.world_position).z;
+    let cell = light_grid[cluster_index(in.clip_position.xy, view_depth)];
+    for (var j = 0u; j < cell.y; j++) {
+        let i = light_indices[cell.x + j];
         let light_pos = tangent_matrix * point_lights.lights[i].position;
         let light_color = point_lights.lights[i].color;
         let light_intensity = point_lights.lights[i].intensity;
@@ -134,29 +401,31 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
             let spec_angle = max(dot(tangent_normal, half_dir), 0.0);
             specular = pow(spec_angle, 32.0);
         }
-        let world_light_dir = in.world_position.xyz - point_lights.lights[i].position;
-        let light_distance = length(world_light_dir);
-        let light_dir_norm = world_light_dir / light_distance;
-
-        let surface_normal = normalize(in.N);
-        let cos_angle = max(dot(surface_normal, -light_dir_norm), 0.0);
-
-        // Shadow map "touch up" parameters.
-        let world_bias = mix(0.2, 0.05, cos_angle) * (light_distance / 50.0);
-        let shadow_dist = (length(world_light_dir) - world_bias ) / 200.0;
-        let shadow = textureSampleCompare(
-            shadow_maps,
-            shadow_sampler,
-            normalize(world_light_dir),
-            i,
-            shadow_dist
-        );
-
-        
+        let shadow = shadow_factor(i, in.world_position.xyz, normalize(in.N));
         color += light_color * (specular + diffuse) * attenuation * light_intensity * shadow;
     }
     
-    let texture_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    let frag_color = texture_color.xyz * color;
-    return vec4<f32>(frag_color, 1.0);
+    let occlusion = textureLoad(ambient_occlusion, vec2<i32>(in.clip_position.xy), 0).r;
+    let world_normal = normalize(mat3x3<f32>(in.T, in.B, in.N) * tangent_normal);
+    let world_view_dir = normalize(camera.view_pos.xyz - in.world_position.xyz);
+    let environment = environment_light(world_normal, world_view_dir);
+    color += (point_lights.ambient + environment) * occlusion;
+
+    if BLEND_MODE == BLEND_MASKED && texture_color.a < ALPHA_CUTOFF {
+        discard;
+    }
+    var alpha = 1.0;
+    if BLEND_MODE == BLEND_TRANSLUCENT {
+        alpha = texture_color.a;
+    }
+
+    var frag_color = texture_color.xyz * color;
+    if material.reflectivity > 0.0 {
+        let reflection = probe_reflection(in.world_position.xyz, reflect(-world_view_dir, world_normal));
+        let fresnel = material.reflectivity
+            + (1.0 - material.reflectivity) * pow(1.0 - max(dot(world_normal, world_view_dir), 0.0), 5.0);
+        frag_color = mix(frag_color, reflection, fresnel);
+    }
+    let fog = fog_amount(distance(in.world_position.xyz, camera.view_pos.xyz));
+    return vec4<f32>(mix(frag_color, camera.fog_color, fog), alpha);
 }
@@ -16,7 +16,9 @@ struct VertexInput {
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) world_pos: vec3<f32>
+    @location(0) world_pos: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) tint: vec4<f32>,
 }
 
 struct FragmentOutput {
@@ -31,6 +33,7 @@ struct InstanceInput {
     @location(9) normal_matrix_0: vec3<f32>,
     @location(10) normal_matrix_1: vec3<f32>,
     @location(11) normal_matrix_2: vec3<f32>,
+    @location(15) tint: vec4<f32>,
 }
 
 @vertex
@@ -48,15 +51,40 @@ fn vs_main(
     let world_pos = model_mat * vec4<f32>(in.position, 1.0);
     out.world_pos = world_pos.xyz;
     out.clip_position = light.view_proj * world_pos;
+    out.tex_coords = in.tex_coords;
+    out.tint = instance.tint;
     return out;
 }
 
-@fragment
-fn fs_main(
-    in: VertexOutput
-) -> FragmentOutput {
+fn light_depth(in: VertexOutput) -> FragmentOutput {
     let light_distance = distance(in.world_pos, light.position);
     var out: FragmentOutput;
     out.depth = light_distance / 200.0;
     return out;
 }
+
+@fragment
+fn fs_main(
+    in: VertexOutput
+) -> FragmentOutput {
+    return light_depth(in);
+}
+
+// Masked materials, bound only for the pipeline that draws them.
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+// Matches shader.wgsl, so light shows through the same holes the surface has.
+const ALPHA_CUTOFF: f32 = 0.5;
+
+@fragment
+fn fs_masked(
+    in: VertexOutput
+) -> FragmentOutput {
+    if (textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint).a < ALPHA_CUTOFF {
+        discard;
+    }
+    return light_depth(in);
+}
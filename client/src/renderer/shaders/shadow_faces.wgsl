@@ -0,0 +1,49 @@
+struct Params {
+    light: u32,
+    _padding: vec3<u32>,
+}
+
+// Matches ShadowFace in renderer/shadow_atlas.rs.
+struct ShadowFace {
+    offset: vec2<f32>,
+    scale: f32,
+    resolution: f32,
+}
+
+@group(0) @binding(0)
+var shadow_atlas: texture_depth_2d;
+@group(0) @binding(1)
+var<uniform> params: Params;
+@group(0) @binding(2)
+var<storage, read> shadow_faces: array<ShadowFace>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One triangle that covers the whole screen.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// The faces in layer order, +X -X +Y on the top row and -Y +Z -Z below.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let grid = in.uv * vec2<f32>(3.0, 2.0);
+    let cell = min(vec2<u32>(grid), vec2<u32>(2u, 1u));
+    let face = cell.y * 3u + cell.x;
+    let tile = shadow_faces[params.light * 6u + face];
+    let size = u32(tile.resolution);
+    let corner = vec2<u32>(tile.offset * vec2<f32>(textureDimensions(shadow_atlas)));
+    let texel = corner + min(vec2<u32>(fract(grid) * tile.resolution), vec2<u32>(size - 1u));
+    let depth = textureLoad(shadow_atlas, texel, 0);
+    // Depth is distance over the far plane, so anything close would be near black.
+    let shade = 1.0 - sqrt(depth);
+    return vec4<f32>(vec3<f32>(shade), 1.0);
+}
@@ -4,6 +4,11 @@ struct Camera {
     view_proj: mat4x4<f32>,
     inv_proj: mat4x4<f32>,
     inv_view: mat4x4<f32>,
+    fog_color: vec3<f32>,
+    fog_density: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_mode: u32,
 }
 
 @group(0) @binding(0)
@@ -13,6 +18,27 @@ var env_sampler: sampler;
 @group(1) @binding(0)
 var<uniform> camera: Camera;
 
+const FOG_LINEAR: u32 = 1u;
+const FOG_EXPONENTIAL: u32 = 2u;
+
+// How much of the fog color covers something `distance` away from the camera.
+fn fog_amount(distance: f32) -> f32 {
+    switch camera.fog_mode {
+        case FOG_LINEAR: {
+            return clamp((distance - camera.fog_start) / (camera.fog_end - camera.fog_start), 0.0, 1.0);
+        }
+        case FOG_EXPONENTIAL: {
+            return 1.0 - exp(-camera.fog_density * distance);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+// Height of the view ray above which the sky is clear of fog.
+const FOG_HORIZON: f32 = 0.4;
+
 
 struct VertexOutput {
     @builtin(position) frag_position: vec4<f32>,
@@ -40,5 +66,9 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var ray_direction = normalize((camera.inv_view * vec4<f32>(view_ray_direction, 0.0)).xyz);
 
     let sample = textureSample(env_map, env_sampler, ray_direction);
-    return sample;
+    // The sky sits at the far plane, fogged like geometry there and thinning out above the
+    // horizon so distant walls blend into it.
+    let horizon = 1.0 - smoothstep(0.0, FOG_HORIZON, ray_direction.y);
+    let fog = fog_amount(length(view_ray_direction)) * horizon;
+    return vec4<f32>(mix(sample.rgb, camera.fog_color, fog), sample.a);
 }
@@ -0,0 +1,86 @@
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+
+struct SsaoParams {
+    kernel: array<vec4<f32>, 32>,
+    sample_count: u32,
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var scene_depth: texture_depth_2d;
+@group(1) @binding(1)
+var scene_normals: texture_2d<f32>;
+@group(1) @binding(2)
+var<uniform> params: SsaoParams;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle that covers the whole screen.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn view_position(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(scene_depth));
+    let depth = textureLoad(scene_depth, pixel, 0);
+    let frag_coord = vec2<f32>(pixel) + 0.5;
+    let ndc = vec2<f32>(frag_coord.x / size.x * 2.0 - 1.0, 1.0 - frag_coord.y / size.y * 2.0);
+    let position = camera.inv_proj * vec4<f32>(ndc, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+// Cheap per pixel angle to rotate the kernel by, blurred away afterwards.
+fn noise(pixel: vec2<i32>) -> f32 {
+    let p = vec2<f32>(pixel % 4);
+    return fract(sin(dot(p, vec2<f32>(12.9898, 78.233))) * 43758.5453) * 6.2831853;
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(frag_coord.xy);
+    let normal_sample = textureLoad(scene_normals, pixel, 0);
+    // Nothing was drawn here in the prepass, so it's sky.
+    if (normal_sample.w == 0.0) {
+        return vec4<f32>(1.0);
+    }
+    let origin = view_position(pixel);
+    let normal = normalize(normal_sample.xyz);
+
+    let angle = noise(pixel);
+    let random = vec3<f32>(cos(angle), sin(angle), 0.0);
+    let tangent = normalize(random - normal * dot(random, normal));
+    let bitangent = cross(normal, tangent);
+    let tbn = mat3x3<f32>(tangent, bitangent, normal);
+
+    let proj = camera.view_proj * camera.inv_view;
+    let size = vec2<f32>(textureDimensions(scene_depth));
+    var occlusion = 0.0;
+    for (var i = 0u; i < params.sample_count; i++) {
+        let sample_position = origin + tbn * params.kernel[i].xyz * params.radius;
+        let clip = proj * vec4<f32>(sample_position, 1.0);
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if (any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0))) {
+            continue;
+        }
+        let scene_z = view_position(vec2<i32>(uv * size)).z;
+        // Geometry much closer to the camera than the sample shouldn't darken it.
+        let range = smoothstep(0.0, 1.0, params.radius / abs(origin.z - scene_z));
+        if (scene_z >= sample_position.z + params.bias) {
+            occlusion += range;
+        }
+    }
+    let ao = 1.0 - occlusion / f32(max(params.sample_count, 1u));
+    return vec4<f32>(pow(ao, params.intensity));
+}
@@ -0,0 +1,27 @@
+@group(0) @binding(0)
+var occlusion: texture_2d<f32>;
+
+// Matches the 4x4 tile the occlusion noise repeats over.
+const BLUR_SIZE: i32 = 4;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle that covers the whole screen.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(occlusion));
+    let pixel = vec2<i32>(frag_coord.xy);
+    var total = 0.0;
+    for (var x = 0; x < BLUR_SIZE; x++) {
+        for (var y = 0; y < BLUR_SIZE; y++) {
+            let offset = vec2<i32>(x, y) - BLUR_SIZE / 2;
+            let sample_pixel = clamp(pixel + offset, vec2<i32>(0), size - 1);
+            total += textureLoad(occlusion, sample_pixel, 0).r;
+        }
+    }
+    return vec4<f32>(total / f32(BLUR_SIZE * BLUR_SIZE));
+}
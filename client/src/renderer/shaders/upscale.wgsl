@@ -0,0 +1,45 @@
+@group(0) @binding(0)
+var scene_color: texture_2d<f32>;
+@group(0) @binding(1)
+var scene_sampler: sampler;
+// x is the sharpening strength, zero for plain bilinear.
+@group(0) @binding(2)
+var<uniform> upscale: vec4<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // One triangle that covers the whole screen.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let center = textureSample(scene_color, scene_sampler, in.uv);
+    let sharpness = upscale.x;
+    if sharpness <= 0.0 {
+        return center;
+    }
+
+    // Contrast adaptive sharpening over the plus shaped neighbourhood in source texels.
+    // Flat areas and strong edges get less sharpening so neither noise nor halos show up.
+    let texel = 1.0 / vec2<f32>(textureDimensions(scene_color));
+    let north = textureSample(scene_color, scene_sampler, in.uv - vec2<f32>(0.0, texel.y)).rgb;
+    let south = textureSample(scene_color, scene_sampler, in.uv + vec2<f32>(0.0, texel.y)).rgb;
+    let west = textureSample(scene_color, scene_sampler, in.uv - vec2<f32>(texel.x, 0.0)).rgb;
+    let east = textureSample(scene_color, scene_sampler, in.uv + vec2<f32>(texel.x, 0.0)).rgb;
+    let lowest = min(center.rgb, min(min(north, south), min(west, east)));
+    let highest = max(center.rgb, max(max(north, south), max(west, east)));
+    let amount = sqrt(clamp(min(lowest, 1.0 - highest) / max(highest, vec3<f32>(0.0001)), vec3<f32>(0.0), vec3<f32>(1.0)));
+    let weight = -amount * mix(0.125, 0.2, sharpness);
+    let sharpened = (center.rgb + (north + south + west + east) * weight) / (1.0 + 4.0 * weight);
+    return vec4<f32>(clamp(sharpened, vec3<f32>(0.0), vec3<f32>(1.0)), center.a);
+}
@@ -0,0 +1,46 @@
+struct ViewModel {
+    view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> view_model: ViewModel;
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    in: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view_model.view_proj * vec4<f32>(in.position, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.normal = (view_model.model * vec4<f32>(in.normal, 0.0)).xyz;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Lit by a fixed view space light so the weapon reads clearly in dark areas.
+    let light_dir = normalize(vec3<f32>(-0.3, 0.8, 0.5));
+    let diffuse = max(dot(normalize(in.normal), light_dir), 0.0);
+    let texture_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(texture_color.xyz * (0.25 + 0.75 * diffuse), 1.0);
+}
@@ -0,0 +1,157 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{Device, Sampler, TextureView};
+
+/// Where one cube face of a light's shadow map sits in the atlas, as the scene shader reads
+/// it from the face buffer.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Pod, Zeroable)]
+pub struct ShadowFace {
+    // Top left corner in atlas UVs.
+    pub offset: [f32; 2],
+    // Side length in atlas UVs.
+    pub scale: f32,
+    // Side length in texels.
+    pub resolution: f32,
+}
+
+/// A square region of the atlas in texels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AtlasTile {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+}
+
+/// One depth texture holding every light's six shadow faces, each light at its own
+/// resolution. Sides are powers of two and get split into quarters on demand, so packing the
+/// largest faces first leaves no gaps.
+pub struct ShadowAtlas {
+    pub view: TextureView,
+    pub sampler: Sampler,
+    size: u32,
+}
+
+impl AtlasTile {
+    pub fn face(&self, atlas_size: u32) -> ShadowFace {
+        let atlas_size = atlas_size as f32;
+        ShadowFace {
+            offset: [self.x as f32 / atlas_size, self.y as f32 / atlas_size],
+            scale: self.size as f32 / atlas_size,
+            resolution: self.size as f32,
+        }
+    }
+
+    fn quarters(&self) -> [Self; 4] {
+        let size = self.size / 2;
+        [(0, 0), (size, 0), (0, size), (size, size)].map(|(x, y)| Self {
+            x: self.x + x,
+            y: self.y + y,
+            size,
+        })
+    }
+}
+
+impl ShadowAtlas {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    const SIZE: u32 = 4096;
+
+    pub fn new(device: &Device) -> Self {
+        let size = Self::SIZE.min(device.limits().max_texture_dimension_2d);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Atlas"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Atlas"),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Atlas Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Self {
+            view,
+            sampler,
+            size,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Six tiles for each of `resolutions`, in the same order. Everything is halved until it
+    /// fits, but never below `min_resolution`.
+    pub fn pack(&self, resolutions: &[u32], min_resolution: u32) -> Vec<[AtlasTile; 6]> {
+        let mut resolutions = resolutions.to_vec();
+        loop {
+            if let Some(tiles) = self.try_pack(&resolutions) {
+                return tiles;
+            }
+            if resolutions
+                .iter()
+                .all(|resolution| *resolution <= min_resolution)
+            {
+                // Only reachable with far more lights than the light buffer holds.
+                return vec![[self.whole(); 6]; resolutions.len()];
+            }
+            for resolution in &mut resolutions {
+                *resolution = (*resolution / 2).max(min_resolution);
+            }
+        }
+    }
+
+    fn try_pack(&self, resolutions: &[u32]) -> Option<Vec<[AtlasTile; 6]>> {
+        let mut order: Vec<usize> = (0..resolutions.len()).collect();
+        order.sort_by_key(|index| std::cmp::Reverse(resolutions[*index]));
+        let mut free = vec![self.whole()];
+        let mut tiles = vec![[self.whole(); 6]; resolutions.len()];
+        for index in order {
+            for tile in &mut tiles[index] {
+                *tile = Self::allocate(&mut free, resolutions[index])?;
+            }
+        }
+        Some(tiles)
+    }
+
+    /// Takes the smallest free tile that is big enough, splitting it down to `size`.
+    fn allocate(free: &mut Vec<AtlasTile>, size: u32) -> Option<AtlasTile> {
+        let (index, _) = free
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.size >= size)
+            .min_by_key(|(_, tile)| tile.size)?;
+        let mut tile = free.swap_remove(index);
+        while tile.size > size {
+            let [first, rest @ ..] = tile.quarters();
+            free.extend(rest);
+            tile = first;
+        }
+        Some(tile)
+    }
+
+    fn whole(&self) -> AtlasTile {
+        AtlasTile {
+            x: 0,
+            y: 0,
+            size: self.size,
+        }
+    }
+}
@@ -1,19 +1,37 @@
+use nalgebra::Point3;
 use rand::random;
 use std::collections::HashMap;
 use wgpu::util::DeviceExt;
-use wgpu::{BindGroupLayout, Device, Queue, RenderPipeline};
+use wgpu::{BindGroupLayout, Buffer, CommandEncoder, Device, Queue, RenderPipeline};
 
+use super::depth_prepass::DepthGeometry;
+use super::gpu_profiler::GpuProfiler;
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+use super::shadow_atlas::{AtlasTile, ShadowAtlas, ShadowFace};
+use crate::camera::frustum::Frustum;
+use crate::camera::light::Light;
 use crate::camera::shadow_map_uniform::ShadowMapUniform;
-use crate::{
-    camera::light::Light,
-    model::{Model, cube_texture::CubeTexture},
-};
+use crate::model::{Material, Model};
 
+/// Bakes point light shadows into the faces of a shared atlas. Lights get less of the atlas
+/// the farther away they are, see `face_resolution`.
 pub struct ShadowBaker {
-    pub shadow_map_texture: CubeTexture,
+    pub atlas: ShadowAtlas,
+    // Where every face of every light sits in the atlas, indexed by light id then face.
+    pub face_buffer: Buffer,
     cached_shadow_maps: HashMap<u32, CachedShadowMap>,
     scene_version: u64,
     light_versions: HashMap<u32, u64>,
+    shadow_pipeline: RenderPipeline,
+    // Cuts out the holes of masked meshes, with their material at group 1.
+    masked_shadow_pipeline: RenderPipeline,
+    // Resets depth inside one tile, clearing the attachment would wipe every light.
+    clear_pipeline: RenderPipeline,
+    shadow_bind_group_layout: BindGroupLayout,
+    // Face resolution of the most important lights.
+    resolution: u32,
+    // Requested face resolution and allocated tiles of each light, by light id.
+    tiles: HashMap<u32, (u32, [AtlasTile; 6])>,
 }
 
 pub struct CachedShadowMap {
@@ -24,20 +42,70 @@ pub struct CachedShadowMap {
 }
 
 impl ShadowBaker {
-    const RESOLUTION: u32 = 1024;
+    pub const DEFAULT_RESOLUTION: u32 = 1024;
+    pub const MIN_RESOLUTION: u32 = 64;
     const INIT_VERSION: u64 = 0;
-    pub fn new(light_ids: &[u32], device: &Device) -> Self {
+    pub fn new(
+        light_ids: &[u32],
+        device: &Device,
+        shadow_pipeline: RenderPipeline,
+        masked_shadow_pipeline: RenderPipeline,
+        shadow_bind_group_layout: BindGroupLayout,
+    ) -> Self {
+        let (face_buffer, cached_shadow_maps, light_versions) =
+            Self::create_shadow_maps(light_ids, device);
+        let clear_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Shadow Clear Pipeline Layout",
+            &[],
+        );
+        let clear_pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &clear_layout,
+            PipelineDescriptor {
+                depth_format: Some(ShadowAtlas::FORMAT),
+                depth_write_enabled: true,
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("Shadow Clear Shader"),
+                        source: wgpu::ShaderSource::Wgsl(
+                            include_str!("shaders/shadow_clear.wgsl").into(),
+                        ),
+                    },
+                    None,
+                )
+            },
+        );
+        Self {
+            atlas: ShadowAtlas::new(device),
+            face_buffer,
+            cached_shadow_maps,
+            scene_version: Self::INIT_VERSION,
+            light_versions,
+            shadow_pipeline,
+            masked_shadow_pipeline,
+            clear_pipeline,
+            shadow_bind_group_layout,
+            resolution: Self::DEFAULT_RESOLUTION,
+            tiles: HashMap::new(),
+        }
+    }
+
+    fn create_shadow_maps(
+        light_ids: &[u32],
+        device: &Device,
+    ) -> (Buffer, HashMap<u32, CachedShadowMap>, HashMap<u32, u64>) {
         let light_versions = light_ids
             .iter()
             .map(|id| (*id, Self::INIT_VERSION))
             .collect();
-        let num_lights = light_ids.len();
-        let shadow_map_texture = CubeTexture::new_shadow_map(
-            device,
-            Self::RESOLUTION,
-            num_lights as u32,
-            Some("Shadow Map"),
-        );
+        // Light ids index the face buffer, and bindings can't be empty.
+        let face_count = 6 * light_ids.iter().max().map_or(1, |id| *id as usize + 1);
+        let face_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Face Buffer"),
+            contents: bytemuck::cast_slice(&vec![ShadowFace::default(); face_count]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
         let cached_shadow_maps = light_ids
             .iter()
             .map(|id| {
@@ -51,22 +119,75 @@ impl ShadowBaker {
                 )
             })
             .collect();
-        Self {
-            cached_shadow_maps,
-            shadow_map_texture,
-            scene_version: Self::INIT_VERSION,
-            light_versions,
+        (face_buffer, cached_shadow_maps, light_versions)
+    }
+
+    /// Starts over for a new set of lights or resolution, every map gets repacked and
+    /// rebaked. The shadow texture bind group has to be recreated afterwards.
+    pub fn rebuild(&mut self, light_ids: &[u32], resolution: u32, device: &Device) {
+        let (face_buffer, cached_shadow_maps, light_versions) =
+            Self::create_shadow_maps(light_ids, device);
+        self.face_buffer = face_buffer;
+        self.cached_shadow_maps = cached_shadow_maps;
+        self.light_versions = light_versions;
+        self.resolution = resolution;
+        self.tiles.clear();
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// Face resolution for `light` seen from `eye`. Lights around the eye get the full
+    /// resolution, then it halves every time the distance doubles past the light's radius.
+    fn face_resolution(&self, light: &Light, eye: Point3<f32>) -> u32 {
+        let radius = light.radius().max(f32::EPSILON);
+        let distance = (light.position - eye).norm();
+        let halvings = (distance / radius).max(1.0).log2() as u32;
+        self.resolution
+            .checked_shr(halvings)
+            .unwrap_or(0)
+            .max(Self::MIN_RESOLUTION)
+    }
+
+    /// Repacks the atlas when a light's share of it changes. Lights moved to another tile
+    /// have to be rebaked before they are sampled again.
+    pub fn allocate(&mut self, lights: &[Light], eye: Point3<f32>, queue: &Queue) {
+        let resolutions: Vec<u32> = lights
+            .iter()
+            .map(|light| self.face_resolution(light, eye))
+            .collect();
+        let unchanged = lights.len() == self.tiles.len()
+            && lights.iter().zip(&resolutions).all(|(light, resolution)| {
+                self.tiles
+                    .get(&light.id)
+                    .is_some_and(|(current, _)| current == resolution)
+            });
+        if unchanged {
+            return;
+        }
+        let packed = self.atlas.pack(&resolutions, Self::MIN_RESOLUTION);
+        self.tiles.clear();
+        for ((light, resolution), tiles) in lights.iter().zip(resolutions).zip(packed) {
+            let faces = tiles.map(|tile| tile.face(self.atlas.size()));
+            queue.write_buffer(
+                &self.face_buffer,
+                light.id as u64 * std::mem::size_of::<[ShadowFace; 6]>() as u64,
+                bytemuck::cast_slice(&faces),
+            );
+            self.tiles.insert(light.id, (resolution, tiles));
         }
+        self.update_scene_version();
     }
 
     pub fn update_light_shadow_map(
         &mut self,
         light: &Light,
         device: &Device,
-        queue: &Queue,
+        encoder: &mut CommandEncoder,
         models: &[Model],
-        shadow_pipeline: &RenderPipeline,
-        shadow_bind_group_layout: &BindGroupLayout,
+        materials: &HashMap<String, Material>,
+        profiler: &mut GpuProfiler,
     ) {
         let current_scene_version = self.scene_version;
         let current_light_version = *self.light_versions.get(&light.id).unwrap();
@@ -82,14 +203,7 @@ impl ShadowBaker {
             });
 
         if needs_rebake {
-            self.bake_shadows(
-                device,
-                queue,
-                models,
-                light,
-                shadow_pipeline,
-                shadow_bind_group_layout,
-            );
+            self.bake_shadows(device, encoder, models, materials, light, profiler);
             let cached_shadow_map = self.cached_shadow_maps.get_mut(&light.id).unwrap();
             cached_shadow_map.scene_version = current_scene_version;
             cached_shadow_map.light_version = current_light_version;
@@ -99,15 +213,23 @@ impl ShadowBaker {
     fn bake_shadows(
         &self,
         device: &Device,
-        queue: &Queue,
+        encoder: &mut CommandEncoder,
         models: &[Model],
+        materials: &HashMap<String, Material>,
         light: &Light,
-        shadow_pipeline: &RenderPipeline,
-        shadow_bind_group_layout: &BindGroupLayout,
+        profiler: &mut GpuProfiler,
     ) {
+        let Some((_, tiles)) = self.tiles.get(&light.id) else {
+            return;
+        };
+        encoder.push_debug_group(&format!("light {}", light.id));
         for face_index in 0..6 {
             let shadow_map_uniform =
                 ShadowMapUniform::get_uniform_map_for_face(light.position, face_index);
+            let face_frustum = Frustum::from_view_proj(&ShadowMapUniform::get_view_proj_for_face(
+                light.position,
+                face_index,
+            ));
             let light_camera_uniform_buffer =
                 device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("Shadow ViewProj Buffer"),
@@ -116,48 +238,60 @@ impl ShadowBaker {
                 });
 
             let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: shadow_bind_group_layout,
+                layout: &self.shadow_bind_group_layout,
                 entries: &[wgpu::BindGroupEntry {
                     binding: 0,
                     resource: light_camera_uniform_buffer.as_entire_binding(),
                 }],
-                label: Some("Shadow Bind Group"),
+                label: Some("shadow_view_proj_bind_group"),
             });
 
-            let face_view = self.shadow_map_texture.create_view_from_face(
-                light.id,
-                face_index,
-                Some("shadow map face view"),
-            );
+            let tile = tiles[face_index as usize];
 
-            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Shadow Encoder"),
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&format!("Shadow Face {face_index} Pass")),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.atlas.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: profiler
+                    .render_pass_writes(&format!("shadow:light {} face {face_index}", light.id)),
+                ..Default::default()
             });
 
-            {
-                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Shadow Render Pass"),
-                    color_attachments: &[],
-                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &face_view,
-                        depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0),
-                            store: wgpu::StoreOp::Store,
-                        }),
-                        stencil_ops: None,
-                    }),
-                    ..Default::default()
-                });
+            render_pass.set_viewport(
+                tile.x as f32,
+                tile.y as f32,
+                tile.size as f32,
+                tile.size as f32,
+                0.0,
+                1.0,
+            );
+            render_pass.set_scissor_rect(tile.x, tile.y, tile.size, tile.size);
+            render_pass.set_pipeline(&self.clear_pipeline);
+            render_pass.draw(0..3, 0..1);
 
-                render_pass.set_pipeline(shadow_pipeline);
-                render_pass.set_bind_group(0, &light_bind_group, &[]);
-                for model in models {
-                    model.draw_shadow(&mut render_pass);
-                }
+            render_pass.set_pipeline(&self.shadow_pipeline);
+            render_pass.set_bind_group(0, &light_bind_group, &[]);
+            for model in models.iter().filter(|model| !model.skinned) {
+                model.draw_geometry(&mut render_pass, &face_frustum);
+            }
+            render_pass.set_pipeline(&self.masked_shadow_pipeline);
+            for model in models.iter().filter(|model| !model.skinned) {
+                model.draw_masked_geometry(
+                    &mut render_pass,
+                    &face_frustum,
+                    materials,
+                    DepthGeometry::MATERIAL_GROUP,
+                );
             }
-
-            queue.submit(Some(encoder.finish()));
         }
+        encoder.pop_debug_group();
     }
 
     pub fn update_scene_version(&mut self) {
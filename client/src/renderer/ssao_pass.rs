@@ -0,0 +1,454 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::Vector3;
+use rand::random;
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Queue, RenderPipeline,
+    SurfaceConfiguration, TextureView,
+};
+
+use crate::model::depth_texture::DepthTexture;
+use crate::model::model_instance::RawInstance;
+use crate::model::vertex::Vertex;
+
+use super::depth_prepass::DepthGeometry;
+use super::gpu_profiler::GpuProfiler;
+use super::graphics_settings::SsaoQuality;
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct SsaoUniform {
+    kernel: [[f32; 4]; SsaoPass::MAX_SAMPLES],
+    sample_count: u32,
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+}
+
+/// Screen space ambient occlusion. A prepass writes scene depth and view space normals, the
+/// occlusion pass samples a hemisphere kernel around every pixel and a blur removes the noise
+/// from rotating the kernel. The result scales ambient light in the main pass.
+pub struct SsaoPass {
+    prepass_pipeline: RenderPipeline,
+    masked_prepass_pipeline: RenderPipeline,
+    occlusion_pipeline: RenderPipeline,
+    blur_pipeline: RenderPipeline,
+    input_bind_group_layout: BindGroupLayout,
+    input_bind_group: BindGroup,
+    blur_bind_group_layout: BindGroupLayout,
+    blur_bind_group: BindGroup,
+    params_buffer: Buffer,
+    uniform: SsaoUniform,
+    normal_view: TextureView,
+    occlusion_view: TextureView,
+    pub ambient_occlusion_view: TextureView,
+}
+
+impl SsaoPass {
+    pub const MAX_SAMPLES: usize = 32;
+    const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+    const RADIUS: f32 = 0.5;
+    const BIAS: f32 = 0.025;
+    const INTENSITY: f32 = 1.5;
+
+    pub fn new(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        camera_bind_group_layout: &BindGroupLayout,
+        material_bind_group_layout: &BindGroupLayout,
+        depth_view: &TextureView,
+        quality: SsaoQuality,
+    ) -> Self {
+        let uniform = SsaoUniform {
+            kernel: Self::create_kernel(),
+            sample_count: quality.sample_count(),
+            radius: Self::RADIUS,
+            bias: Self::BIAS,
+            intensity: Self::INTENSITY,
+        };
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SSAO Params Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let input_bind_group_layout = Self::create_input_bind_group_layout(device);
+        let blur_bind_group_layout = Self::create_blur_bind_group_layout(device);
+        let normal_view = Self::create_target(device, config, Self::NORMAL_FORMAT, "SSAO Normals");
+        let occlusion_view =
+            Self::create_target(device, config, Self::OCCLUSION_FORMAT, "SSAO Occlusion");
+        let ambient_occlusion_view =
+            Self::create_target(device, config, Self::OCCLUSION_FORMAT, "Ambient Occlusion");
+        let input_bind_group = Self::create_input_bind_group(
+            device,
+            &input_bind_group_layout,
+            depth_view,
+            &normal_view,
+            &params_buffer,
+        );
+        let blur_bind_group =
+            Self::create_blur_bind_group(device, &blur_bind_group_layout, &occlusion_view);
+
+        let prepass_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "SSAO Prepass Pipeline Layout",
+            &[camera_bind_group_layout],
+        );
+        let masked_prepass_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Masked SSAO Prepass Pipeline Layout",
+            &[camera_bind_group_layout, material_bind_group_layout],
+        );
+        let create_prepass_pipeline = |layout, label, fragment_entry_point| {
+            PipelineFactory::create_render_pipeline(
+                device,
+                layout,
+                PipelineDescriptor {
+                    fragment_entry_point,
+                    vertex_layouts: &[Vertex::desc(), RawInstance::desc()],
+                    cull_mode: Some(wgpu::Face::Back),
+                    depth_format: Some(DepthTexture::DEPTH_FORMAT),
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    ..PipelineDescriptor::new(
+                        wgpu::ShaderModuleDescriptor {
+                            label: Some(label),
+                            source: wgpu::ShaderSource::Wgsl(
+                                include_str!("shaders/prepass.wgsl").into(),
+                            ),
+                        },
+                        Some(Self::NORMAL_FORMAT),
+                    )
+                },
+            )
+        };
+        let prepass_pipeline =
+            create_prepass_pipeline(&prepass_layout, "SSAO Prepass Shader", "fs_main");
+        let masked_prepass_pipeline = create_prepass_pipeline(
+            &masked_prepass_layout,
+            "Masked SSAO Prepass Shader",
+            "fs_masked",
+        );
+        let occlusion_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "SSAO Pipeline Layout",
+            &[camera_bind_group_layout, &input_bind_group_layout],
+        );
+        let occlusion_pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &occlusion_layout,
+            PipelineDescriptor::new(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("SSAO Shader"),
+                    source: wgpu::ShaderSource::Wgsl(include_str!("shaders/ssao.wgsl").into()),
+                },
+                Some(Self::OCCLUSION_FORMAT),
+            ),
+        );
+        let blur_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "SSAO Blur Pipeline Layout",
+            &[&blur_bind_group_layout],
+        );
+        let blur_pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &blur_layout,
+            PipelineDescriptor::new(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("SSAO Blur Shader"),
+                    source: wgpu::ShaderSource::Wgsl(include_str!("shaders/ssao_blur.wgsl").into()),
+                },
+                Some(Self::OCCLUSION_FORMAT),
+            ),
+        );
+
+        Self {
+            prepass_pipeline,
+            masked_prepass_pipeline,
+            occlusion_pipeline,
+            blur_pipeline,
+            input_bind_group_layout,
+            input_bind_group,
+            blur_bind_group_layout,
+            blur_bind_group,
+            params_buffer,
+            uniform,
+            normal_view,
+            occlusion_view,
+            ambient_occlusion_view,
+        }
+    }
+
+    /// Samples in the +z hemisphere, packed closer to the origin so nearby occluders count most.
+    fn create_kernel() -> [[f32; 4]; Self::MAX_SAMPLES] {
+        std::array::from_fn(|i| {
+            let direction = Vector3::new(
+                random::<f32>() * 2.0 - 1.0,
+                random::<f32>() * 2.0 - 1.0,
+                random::<f32>(),
+            )
+            .try_normalize(0.0)
+            .unwrap_or(Vector3::z());
+            let t = i as f32 / Self::MAX_SAMPLES as f32;
+            let scale = 0.1 + 0.9 * t * t;
+            let sample = direction * random::<f32>() * scale;
+            [sample.x, sample.y, sample.z, 0.0]
+        })
+    }
+
+    fn create_target(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor {
+                label: Some(label),
+                ..Default::default()
+            })
+    }
+
+    fn create_input_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("ssao_input_bind_group_layout"),
+        })
+    }
+
+    fn create_input_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        depth_view: &TextureView,
+        normal_view: &TextureView,
+        params_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(normal_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("ssao_input_bind_group"),
+        })
+    }
+
+    fn create_blur_bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+            label: Some("ssao_blur_bind_group_layout"),
+        })
+    }
+
+    fn create_blur_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        occlusion_view: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(occlusion_view),
+            }],
+            label: Some("ssao_blur_bind_group"),
+        })
+    }
+
+    /// Recreates the screen sized targets. The caller must rebind `ambient_occlusion_view`.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        config: &SurfaceConfiguration,
+        depth_view: &TextureView,
+    ) {
+        self.normal_view = Self::create_target(device, config, Self::NORMAL_FORMAT, "SSAO Normals");
+        self.occlusion_view =
+            Self::create_target(device, config, Self::OCCLUSION_FORMAT, "SSAO Occlusion");
+        self.ambient_occlusion_view =
+            Self::create_target(device, config, Self::OCCLUSION_FORMAT, "Ambient Occlusion");
+        self.input_bind_group = Self::create_input_bind_group(
+            device,
+            &self.input_bind_group_layout,
+            depth_view,
+            &self.normal_view,
+            &self.params_buffer,
+        );
+        self.blur_bind_group = Self::create_blur_bind_group(
+            device,
+            &self.blur_bind_group_layout,
+            &self.occlusion_view,
+        );
+    }
+
+    pub fn set_quality(&mut self, queue: &Queue, quality: SsaoQuality) {
+        self.uniform.sample_count = quality.sample_count();
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.uniform.sample_count > 0
+    }
+
+    /// Fills the scene depth along with normals for the occlusion pass.
+    pub fn draw_prepass(
+        &self,
+        encoder: &mut CommandEncoder,
+        depth_view: &TextureView,
+        camera_bind_group: &BindGroup,
+        geometry: &DepthGeometry,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Normal Prepass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.normal_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        geometry.draw(
+            &mut render_pass,
+            &self.prepass_pipeline,
+            &self.masked_prepass_pipeline,
+        );
+    }
+
+    fn begin_fullscreen_pass<'e>(
+        encoder: &'e mut CommandEncoder,
+        view: &TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) -> wgpu::RenderPass<'e> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("SSAO Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+        })
+    }
+
+    /// Writes `ambient_occlusion_view`, plain white when disabled.
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        camera_bind_group: &BindGroup,
+        profiler: &mut GpuProfiler,
+    ) {
+        if !self.is_enabled() {
+            Self::begin_fullscreen_pass(encoder, &self.ambient_occlusion_view, None);
+            return;
+        }
+        {
+            let mut render_pass = Self::begin_fullscreen_pass(
+                encoder,
+                &self.occlusion_view,
+                profiler.render_pass_writes("ssao:occlusion"),
+            );
+            render_pass.set_pipeline(&self.occlusion_pipeline);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.input_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        let mut render_pass = Self::begin_fullscreen_pass(
+            encoder,
+            &self.ambient_occlusion_view,
+            profiler.render_pass_writes("ssao:blur"),
+        );
+        render_pass.set_pipeline(&self.blur_pipeline);
+        render_pass.set_bind_group(0, &self.blur_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,199 @@
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Queue, RenderPipeline, Sampler,
+    SurfaceConfiguration, TextureView,
+};
+
+use super::graphics_settings::UpscaleFilter;
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+/// Offscreen target the scene is drawn into when it renders below the surface resolution,
+/// stretched over the surface with bilinear filtering and optionally sharpened.
+pub struct UpscalePass {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    sampler: Sampler,
+    params_buffer: Buffer,
+    pub color_view: TextureView,
+}
+
+impl UpscalePass {
+    /// `config` holds the render resolution, not the surface's.
+    pub fn new(device: &Device, config: &SurfaceConfiguration, filter: UpscaleFilter) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("upscale_bind_group_layout"),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Upscale Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Upscale Params Buffer"),
+            contents: bytemuck::cast_slice(&[filter.sharpness(), 0.0, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let color_view = Self::create_color_view(device, config);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &color_view,
+            &sampler,
+            &params_buffer,
+        );
+
+        let pipeline_layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "Upscale Pipeline Layout",
+            &[&bind_group_layout],
+        );
+        let pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &pipeline_layout,
+            PipelineDescriptor::new(
+                wgpu::ShaderModuleDescriptor {
+                    label: Some("Upscale Shader"),
+                    source: wgpu::ShaderSource::Wgsl(include_str!("shaders/upscale.wgsl").into()),
+                },
+                Some(config.format),
+            ),
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            sampler,
+            params_buffer,
+            color_view,
+        }
+    }
+
+    fn create_color_view(device: &Device, config: &SurfaceConfiguration) -> TextureView {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Scene Color"),
+                size: wgpu::Extent3d {
+                    width: config.width.max(1),
+                    height: config.height.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: config.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Scene Color"),
+                ..Default::default()
+            })
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        color_view: &TextureView,
+        sampler: &Sampler,
+        params_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("upscale_bind_group"),
+        })
+    }
+
+    pub fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        self.color_view = Self::create_color_view(device, config);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.color_view,
+            &self.sampler,
+            &self.params_buffer,
+        );
+    }
+
+    pub fn set_filter(&self, queue: &Queue, filter: UpscaleFilter) {
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[filter.sharpness(), 0.0, 0.0, 0.0]),
+        );
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        surface_view: &TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Upscale Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,195 @@
+use image::{Rgba, RgbaImage};
+use nalgebra::{Matrix4, Point3};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, Device, Queue, RenderPipeline, TextureView,
+};
+
+use crate::camera::view_model_uniform::ViewModelUniform;
+use crate::model::MaterialUniform;
+use crate::model::depth_texture::DepthTexture;
+use crate::model::primitives::Primitives;
+use crate::model::texture::{Texture, TextureBuilder};
+use crate::model::vertex::Vertex;
+
+use super::pipeline_factory::{PipelineDescriptor, PipelineFactory};
+
+/// Draws the first person weapon on top of the scene.
+pub struct ViewModelPass {
+    pipeline: RenderPipeline,
+    uniform_buffer: Buffer,
+    bind_group: BindGroup,
+    // The weapon's own material, so it looks the same whatever the level has loaded.
+    material_bind_group: BindGroup,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    num_indices: u32,
+}
+
+impl ViewModelPass {
+    const METAL: [u8; 4] = [58, 60, 66, 255];
+    // Lighter rim around every face, so the blocks read apart from each other.
+    const EDGE: [u8; 4] = [92, 95, 102, 255];
+    const TEXTURE_SIZE: u32 = 16;
+    const FLAT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        color_format: wgpu::TextureFormat,
+        material_layout: &BindGroupLayout,
+    ) -> Self {
+        let uniform_layout = ViewModelUniform::create_bind_group_layout(device);
+        let uniform = ViewModelUniform::new(1.0, Matrix4::identity());
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("View Model Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group =
+            ViewModelUniform::create_bind_group(device, &uniform_layout, &uniform_buffer);
+
+        let material_bind_group = Self::create_material(device, queue, material_layout);
+
+        let (vertices, indices) = Self::weapon_mesh();
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("View Model Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("View Model Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let layout = PipelineFactory::create_render_pipeline_layout(
+            device,
+            "View Model Pipeline Layout",
+            &[&uniform_layout, material_layout],
+        );
+        let pipeline = PipelineFactory::create_render_pipeline(
+            device,
+            &layout,
+            PipelineDescriptor {
+                vertex_layouts: &[Vertex::desc()],
+                cull_mode: Some(wgpu::Face::Back),
+                depth_format: Some(DepthTexture::DEPTH_FORMAT),
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                ..PipelineDescriptor::new(
+                    wgpu::ShaderModuleDescriptor {
+                        label: Some("View Model Shader"),
+                        source: wgpu::ShaderSource::Wgsl(
+                            include_str!("shaders/view_model.wgsl").into(),
+                        ),
+                    },
+                    Some(color_format),
+                )
+            },
+        );
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            material_bind_group,
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+        }
+    }
+
+    /// Gunmetal with a bevel drawn into the texture, which spans each face of the mesh.
+    fn create_material(device: &Device, queue: &Queue, layout: &BindGroupLayout) -> BindGroup {
+        let last = Self::TEXTURE_SIZE - 1;
+        let diffuse = RgbaImage::from_fn(Self::TEXTURE_SIZE, Self::TEXTURE_SIZE, |x, y| {
+            let edge = x == 0 || y == 0 || x == last || y == last;
+            Rgba(if edge { Self::EDGE } else { Self::METAL })
+        });
+        let diffuse_texture =
+            Texture::from_rgba(&diffuse, device, queue, Some("View Model Diffuse"));
+        let normal_texture =
+            Texture::from_color(Self::FLAT_NORMAL, device, queue, Some("View Model Normal"));
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("View Model Material Params"),
+            contents: bytemuck::cast_slice(&[MaterialUniform::new(0.0)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        TextureBuilder::create_bind_group(
+            device,
+            &diffuse_texture,
+            &normal_texture,
+            &params_buffer,
+            layout,
+        )
+    }
+
+    /// A blocky pistol made of a slide, barrel and grip, pointing down -z.
+    fn weapon_mesh() -> (Vec<Vertex>, Vec<u16>) {
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for (min, max) in [
+            (
+                Point3::new(-0.03, -0.02, -0.22),
+                Point3::new(0.03, 0.04, 0.0),
+            ),
+            (
+                Point3::new(-0.012, 0.0, -0.28),
+                Point3::new(0.012, 0.024, -0.22),
+            ),
+            (
+                Point3::new(-0.025, -0.13, -0.04),
+                Point3::new(0.025, -0.02, 0.02),
+            ),
+        ] {
+            Primitives::append_cuboid(min, max, &mut vertices, &mut indices);
+        }
+        (vertices, indices)
+    }
+
+    pub fn update(&self, queue: &Queue, aspect: f32, transform: Matrix4<f32>) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[ViewModelUniform::new(aspect, transform)]),
+        );
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        color_view: &TextureView,
+        depth_view: &TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("View Model Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            // Clearing depth gives the weapon its own depth range on top of the world.
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.material_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+}
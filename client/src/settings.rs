@@ -0,0 +1,95 @@
+use std::{error::Error, io, path::PathBuf};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+use crate::game::camera_settings::CameraSettings;
+use crate::platform;
+use crate::renderer::graphics_settings::GraphicsSettings;
+
+/// Keys behind the held inputs. Menus, the console and weapon slots keep their fixed keys.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub jump: KeyCode,
+    pub reload: KeyCode,
+    // Held to show the debug view.
+    pub debug: KeyCode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct AudioSettings {
+    // From 0 to 1.
+    pub volume: f32,
+}
+
+/// Everything the player can tune, read when the game starts and written when it exits.
+/// Missing entries keep their defaults, so older files still load.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    // Window size in physical pixels, left to the platform when None.
+    pub resolution: Option<[u32; 2]>,
+    // Shadow map size per cube face, the renderer's default when None.
+    pub shadow_resolution: Option<u32>,
+    pub graphics: GraphicsSettings,
+    pub camera: CameraSettings,
+    pub keys: KeyBindings,
+    pub audio: AudioSettings,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            back: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            jump: KeyCode::Space,
+            reload: KeyCode::KeyR,
+            debug: KeyCode::KeyG,
+        }
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self { volume: 1.0 }
+    }
+}
+
+impl Settings {
+    const FILE: &str = "settings.json";
+
+    pub fn path() -> PathBuf {
+        platform::config_dir().join(Self::FILE)
+    }
+
+    /// The saved settings, or the defaults when there are none or they can't be read.
+    pub fn load() -> Self {
+        let path = Self::path();
+        let json = match platform::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("Unable to read {}: {e}", path.display());
+                return Self::default();
+            }
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            warn!("Ignoring {}: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        platform::write(Self::path(), &serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}